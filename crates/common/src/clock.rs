@@ -0,0 +1,200 @@
+//! Time and handle sources for RoyaOS subsystems
+//!
+//! Subsystems read the time from the kernel's `Clock` and draw new handles from its
+//! `HandleGenerator` instead of calling `Instant::now` or `Uuid::new_v4` themselves.
+//! A normal run uses the system clock and random handles. A simulated run uses a
+//! virtual clock that only moves when it is advanced and a generator seeded with a
+//! fixed value, so the same calls produce the same handles, timestamps and decisions
+//! in every run.
+
+use chrono::{DateTime, Utc};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Source of the current time
+///
+/// The clock is a cheap handle; clones of a simulated clock share its virtual time.
+#[derive(Debug, Clone, Default)]
+pub struct Clock {
+    simulated: Option<Arc<SimulatedTime>>,
+}
+
+/// Virtual time of a simulated clock
+#[derive(Debug)]
+struct SimulatedTime {
+    /// Process instant the virtual time started at
+    origin: Instant,
+    /// Wall-clock time the virtual time started at
+    start: DateTime<Utc>,
+    /// Virtual time elapsed since the start
+    elapsed: Mutex<Duration>,
+}
+
+impl SimulatedTime {
+    /// Get the virtual time elapsed since the start
+    fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Clock {
+    /// Create a clock reading the system time
+    ///
+    /// # Returns
+    ///
+    /// A new Clock following the system clocks
+    pub fn system() -> Self {
+        Self::default()
+    }
+    
+    /// Create a simulated clock that only moves when it is advanced
+    ///
+    /// Every simulated clock starts at the Unix epoch, so wall-clock times are the
+    /// same in every run.
+    ///
+    /// # Returns
+    ///
+    /// A new Clock standing still at the Unix epoch
+    pub fn simulated() -> Self {
+        Self {
+            simulated: Some(Arc::new(SimulatedTime {
+                origin: Instant::now(),
+                start: DateTime::UNIX_EPOCH,
+                elapsed: Mutex::new(Duration::ZERO),
+            })),
+        }
+    }
+    
+    /// Check whether the clock is simulated
+    ///
+    /// # Returns
+    ///
+    /// `true` if the clock only moves when it is advanced, `false` otherwise
+    pub fn is_simulated(&self) -> bool {
+        self.simulated.is_some()
+    }
+    
+    /// Get the current monotonic time
+    ///
+    /// # Returns
+    ///
+    /// The current instant
+    pub fn now(&self) -> Instant {
+        match &self.simulated {
+            Some(time) => time.origin + time.elapsed(),
+            None => Instant::now(),
+        }
+    }
+    
+    /// Get the current wall-clock time
+    ///
+    /// # Returns
+    ///
+    /// The current time in UTC
+    pub fn utc_now(&self) -> DateTime<Utc> {
+        match &self.simulated {
+            Some(time) => time.start + time.elapsed(),
+            None => Utc::now(),
+        }
+    }
+    
+    /// Get the time elapsed since an instant
+    ///
+    /// # Arguments
+    ///
+    /// * `since` - An instant read from this clock
+    ///
+    /// # Returns
+    ///
+    /// The time elapsed since `since`, or zero if it lies in the future
+    pub fn elapsed(&self, since: Instant) -> Duration {
+        self.now().saturating_duration_since(since)
+    }
+    
+    /// Move a simulated clock forward
+    ///
+    /// The system clock cannot be moved, so this has no effect on it.
+    ///
+    /// # Arguments
+    ///
+    /// * `duration` - The virtual time to pass
+    pub fn advance(&self, duration: Duration) {
+        if let Some(time) = &self.simulated {
+            *time.elapsed.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) += duration;
+        }
+    }
+}
+
+/// Source of new handles for allocations, tools, sessions, tasks and jobs
+///
+/// The generator is a cheap handle; clones of a seeded generator share its sequence.
+#[derive(Debug, Clone, Default)]
+pub struct HandleGenerator {
+    seeded: Option<Arc<Mutex<u64>>>,
+}
+
+impl HandleGenerator {
+    /// Create a generator of random handles
+    ///
+    /// # Returns
+    ///
+    /// A new HandleGenerator returning random version 4 UUIDs
+    pub fn random() -> Self {
+        Self::default()
+    }
+    
+    /// Create a generator returning the same sequence of handles for the same seed
+    ///
+    /// # Arguments
+    ///
+    /// * `seed` - Seed of the sequence
+    ///
+    /// # Returns
+    ///
+    /// A new HandleGenerator returning pseudo-random version 4 UUIDs
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            seeded: Some(Arc::new(Mutex::new(seed))),
+        }
+    }
+    
+    /// Check whether the generator is seeded
+    ///
+    /// # Returns
+    ///
+    /// `true` if the generator returns a fixed sequence, `false` otherwise
+    pub fn is_seeded(&self) -> bool {
+        self.seeded.is_some()
+    }
+    
+    /// Draw a new handle
+    ///
+    /// # Returns
+    ///
+    /// The next handle
+    pub fn next_handle(&self) -> Uuid {
+        match &self.seeded {
+            Some(state) => {
+                let mut state = state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                let mut bytes = [0; 16];
+                bytes[..8].copy_from_slice(&splitmix64(&mut state).to_le_bytes());
+                bytes[8..].copy_from_slice(&splitmix64(&mut state).to_le_bytes());
+                uuid::Builder::from_random_bytes(bytes).into_uuid()
+            },
+            None => Uuid::new_v4(),
+        }
+    }
+}
+
+/// Advance a SplitMix64 state and return its next output
+///
+/// SplitMix64 is small, fast and gives the same sequence on every platform, which
+/// is all the handle generator needs; it is not suitable for secrets.
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,97 @@
+//! Error reporting shared by the RoyaOS crates
+//!
+//! Every crate defines its own error enum. This module adds the pieces they share: the
+//! `ErrorCode` trait giving each failure a stable machine-readable code, and the error
+//! type returned by subsystem lifecycle hooks.
+
+use std::error::Error;
+use std::fmt;
+
+/// Stable machine-readable code for an error
+///
+/// Codes are upper snake case, prefixed with the area they belong to, for example
+/// `MEMORY_LIMIT_EXCEEDED`. A code never changes once published, so clients can
+/// match on it instead of parsing messages.
+pub trait ErrorCode {
+    /// Get the code of the error
+    fn code(&self) -> &'static str;
+}
+
+/// Error returned by a subsystem lifecycle hook
+///
+/// Wraps the typed error of the crate implementing the subsystem together with its
+/// code; the original error can be recovered with `downcast_ref`.
+#[derive(Debug)]
+pub struct SubsystemError {
+    /// Code of the wrapped error
+    code: &'static str,
+    /// The wrapped error
+    inner: Box<dyn Error + Send + Sync>,
+}
+
+impl SubsystemError {
+    /// Code of failures reported with a message only
+    pub const CODE: &'static str = "SUBSYSTEM_FAILED";
+    
+    /// Wrap a typed error
+    ///
+    /// # Arguments
+    ///
+    /// * `error` - The error reported by the subsystem
+    ///
+    /// # Returns
+    ///
+    /// A new SubsystemError carrying the code of `error`
+    pub fn new<E>(error: E) -> Self
+    where
+        E: Error + ErrorCode + Send + Sync + 'static,
+    {
+        Self {
+            code: error.code(),
+            inner: Box::new(error),
+        }
+    }
+    
+    /// Report a failure that has no typed error
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - Description of the failure
+    ///
+    /// # Returns
+    ///
+    /// A new SubsystemError with the code `SUBSYSTEM_FAILED`
+    pub fn msg(message: impl Into<String>) -> Self {
+        Self {
+            code: Self::CODE,
+            inner: message.into().into(),
+        }
+    }
+    
+    /// Get the wrapped error as a concrete type
+    ///
+    /// # Returns
+    ///
+    /// The wrapped error, or `None` if it is not of type `E`
+    pub fn downcast_ref<E: Error + 'static>(&self) -> Option<&E> {
+        self.inner.downcast_ref::<E>()
+    }
+}
+
+impl fmt::Display for SubsystemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.inner, f)
+    }
+}
+
+impl Error for SubsystemError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.inner.source()
+    }
+}
+
+impl ErrorCode for SubsystemError {
+    fn code(&self) -> &'static str {
+        self.code
+    }
+}
@@ -0,0 +1,291 @@
+//! Kernel event bus
+//!
+//! This module implements the publish/subscribe event bus that the kernel uses to carry
+//! notifications between subsystems and to embedding code. The kernel owns a single bus
+//! and hands a clone to every subsystem it registers.
+//!
+//! Every subscriber has a bounded buffer. A subscriber that falls behind loses the oldest
+//! events in its buffer and is told how many it missed the next time it receives.
+
+use crate::{ErrorCode, SubsystemHealth};
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Notification published on the kernel event bus
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum KernelEvent {
+    /// Memory usage crossed the pressure threshold or an allocation was refused
+    MemoryPressure {
+        /// Current memory usage in bytes
+        current_usage: usize,
+        /// Maximum memory allocation in bytes
+        max_allocation: usize,
+        /// Memory usage as a percentage of maximum allocation
+        usage_percentage: f64,
+    },
+    /// A memory allocation was promoted to another category by consolidation
+    MemoryPromoted {
+        /// Handle of the allocation, which is unchanged
+        handle: Uuid,
+        /// Memory category before the promotion
+        from: String,
+        /// Memory category after the promotion
+        to: String,
+    },
+    /// A tool capability finished executing
+    ToolExecuted {
+        /// Handle of the executed tool
+        tool: Uuid,
+        /// Name of the executed capability
+        capability: String,
+        /// Whether the execution succeeded
+        success: bool,
+        /// Execution time in milliseconds
+        execution_time_ms: u64,
+    },
+    /// The security subsystem denied an operation
+    PermissionDenied {
+        /// Type of resource being accessed
+        resource_type: String,
+        /// Operation being performed
+        operation: String,
+        /// Resource being accessed
+        resource: String,
+    },
+    /// The security level was changed
+    SecurityLevelChanged {
+        /// Security level before the change
+        previous: String,
+        /// Security level after the change
+        level: String,
+    },
+    /// An interface session was closed
+    SessionClosed {
+        /// ID of the closed session
+        session: Uuid,
+    },
+    /// The kernel watchdog observed a change in a subsystem's health
+    SubsystemHealthChanged {
+        /// Name of the subsystem
+        subsystem: String,
+        /// Health reported by the subsystem
+        health: SubsystemHealth,
+    },
+    /// The kernel watchdog restarted a subsystem
+    SubsystemRestarted {
+        /// Name of the subsystem
+        subsystem: String,
+        /// Consecutive restart attempt number
+        attempt: u32,
+        /// Whether the subsystem initialized successfully
+        success: bool,
+    },
+}
+
+/// Kind of a kernel event, used to filter subscriptions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// `KernelEvent::MemoryPressure`
+    MemoryPressure,
+    /// `KernelEvent::MemoryPromoted`
+    MemoryPromoted,
+    /// `KernelEvent::ToolExecuted`
+    ToolExecuted,
+    /// `KernelEvent::PermissionDenied`
+    PermissionDenied,
+    /// `KernelEvent::SecurityLevelChanged`
+    SecurityLevelChanged,
+    /// `KernelEvent::SessionClosed`
+    SessionClosed,
+    /// `KernelEvent::SubsystemHealthChanged`
+    SubsystemHealthChanged,
+    /// `KernelEvent::SubsystemRestarted`
+    SubsystemRestarted,
+}
+
+impl KernelEvent {
+    /// Get the kind of the event
+    ///
+    /// # Returns
+    ///
+    /// The event kind
+    pub fn kind(&self) -> EventKind {
+        match self {
+            KernelEvent::MemoryPressure { .. } => EventKind::MemoryPressure,
+            KernelEvent::MemoryPromoted { .. } => EventKind::MemoryPromoted,
+            KernelEvent::ToolExecuted { .. } => EventKind::ToolExecuted,
+            KernelEvent::PermissionDenied { .. } => EventKind::PermissionDenied,
+            KernelEvent::SecurityLevelChanged { .. } => EventKind::SecurityLevelChanged,
+            KernelEvent::SessionClosed { .. } => EventKind::SessionClosed,
+            KernelEvent::SubsystemHealthChanged { .. } => EventKind::SubsystemHealthChanged,
+            KernelEvent::SubsystemRestarted { .. } => EventKind::SubsystemRestarted,
+        }
+    }
+}
+
+/// Error returned when receiving from the event bus
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventError {
+    /// The subscriber fell behind and the given number of events were dropped
+    #[error("Subscriber lagged behind and missed {0} events")]
+    Lagged(u64),
+    
+    /// The event bus was dropped and no more events will arrive
+    #[error("Event bus is closed")]
+    Closed,
+}
+
+impl ErrorCode for EventError {
+    fn code(&self) -> &'static str {
+        match self {
+            EventError::Lagged(_) => "EVENT_LAGGED",
+            EventError::Closed => "EVENT_BUS_CLOSED",
+        }
+    }
+}
+
+/// Publish/subscribe bus carrying kernel events
+///
+/// The bus is a cheap handle; clones publish to the same subscribers.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<KernelEvent>,
+    capacity: usize,
+}
+
+impl EventBus {
+    /// Create a new event bus
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - Number of events buffered per subscriber
+    ///
+    /// # Returns
+    ///
+    /// A new EventBus instance
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender, capacity }
+    }
+    
+    /// Get the number of events buffered per subscriber
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    
+    /// Publish an event to all subscribers
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The event to publish
+    ///
+    /// # Returns
+    ///
+    /// The number of subscribers the event was delivered to
+    pub fn publish(&self, event: KernelEvent) -> usize {
+        self.sender.send(event).unwrap_or(0)
+    }
+    
+    /// Subscribe to all events
+    ///
+    /// # Returns
+    ///
+    /// A subscriber receiving every event published from now on
+    pub fn subscribe(&self) -> EventSubscriber {
+        EventSubscriber {
+            receiver: self.sender.subscribe(),
+            kinds: None,
+            missed: 0,
+        }
+    }
+    
+    /// Subscribe to events of specific kinds
+    ///
+    /// # Arguments
+    ///
+    /// * `kinds` - The event kinds to receive
+    ///
+    /// # Returns
+    ///
+    /// A subscriber receiving the matching events published from now on
+    pub fn subscribe_to(&self, kinds: &[EventKind]) -> EventSubscriber {
+        EventSubscriber {
+            receiver: self.sender.subscribe(),
+            kinds: Some(kinds.to_vec()),
+            missed: 0,
+        }
+    }
+    
+    /// Get the number of active subscribers
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+}
+
+/// Receiving end of an event bus subscription
+#[derive(Debug)]
+pub struct EventSubscriber {
+    receiver: broadcast::Receiver<KernelEvent>,
+    kinds: Option<Vec<EventKind>>,
+    missed: u64,
+}
+
+impl EventSubscriber {
+    /// Wait for the next event
+    ///
+    /// # Returns
+    ///
+    /// The next matching event, `EventError::Lagged` once after events were
+    /// dropped because the subscriber fell behind, or `EventError::Closed`
+    pub async fn recv(&mut self) -> Result<KernelEvent, EventError> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) if self.matches(&event) => return Ok(event),
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(count)) => return Err(self.lagged(count)),
+                Err(broadcast::error::RecvError::Closed) => return Err(EventError::Closed),
+            }
+        }
+    }
+    
+    /// Take the next event without waiting
+    ///
+    /// # Returns
+    ///
+    /// The next matching event if one is buffered, `None` if there is none,
+    /// or the same errors as `recv`
+    pub fn try_recv(&mut self) -> Result<Option<KernelEvent>, EventError> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(event) if self.matches(&event) => return Ok(Some(event)),
+                Ok(_) => continue,
+                Err(broadcast::error::TryRecvError::Empty) => return Ok(None),
+                Err(broadcast::error::TryRecvError::Lagged(count)) => return Err(self.lagged(count)),
+                Err(broadcast::error::TryRecvError::Closed) => return Err(EventError::Closed),
+            }
+        }
+    }
+    
+    /// Get the total number of events this subscriber has missed
+    ///
+    /// # Returns
+    ///
+    /// The number of events dropped because the subscriber fell behind
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+    
+    fn matches(&self, event: &KernelEvent) -> bool {
+        self.kinds.as_ref().is_none_or(|kinds| kinds.contains(&event.kind()))
+    }
+    
+    fn lagged(&mut self, count: u64) -> EventError {
+        self.missed += count;
+        EventError::Lagged(count)
+    }
+}
@@ -0,0 +1,334 @@
+//! Common types for RoyaOS
+//!
+//! This module contains the types shared between the RoyaOS kernel and the modules it
+//! manages. Keeping them in a separate crate allows the memory, tools, security and
+//! interface crates to plug into the kernel without depending on the kernel itself.
+//!
+//! The common module provides:
+//! - The `Subsystem` trait implemented by every kernel-managed module
+//! - Stable error codes and the error type of subsystem lifecycle hooks
+//! - Health reporting types for subsystems
+//! - The kernel event bus that subsystems publish notifications on
+//! - System load figures shared by the kernel
+//! - Conversions for the subsystem state saved in kernel snapshots
+//! - Schedules of the jobs run by the kernel timer service
+//! - The clock and handle generator subsystems read time and new handles from
+
+use serde::{Serialize, Deserialize};
+use std::any::Any;
+
+mod clock;
+mod error;
+mod events;
+mod load;
+mod state;
+mod timer;
+
+pub use clock::{Clock, HandleGenerator};
+pub use error::{ErrorCode, SubsystemError};
+pub use events::{EventBus, EventError, EventKind, EventSubscriber, KernelEvent};
+pub use load::{LoadMonitor, LoadSignals, SystemLoad};
+pub use state::{age_millis, instant_from_age, load_state, save_state, StateError};
+pub use timer::{format_duration, parse_duration, JobSpec, Schedule, ScheduleError};
+
+/// Health of a subsystem as reported by its health probe
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SubsystemHealth {
+    /// The subsystem is operating normally
+    Healthy,
+    /// The subsystem is operating with reduced capacity
+    Degraded(String),
+    /// The subsystem is not able to operate
+    Failed(String),
+}
+
+impl SubsystemHealth {
+    /// Check whether the subsystem is fully healthy
+    ///
+    /// # Returns
+    ///
+    /// `true` if the subsystem is healthy, `false` otherwise
+    pub fn is_healthy(&self) -> bool {
+        matches!(self, SubsystemHealth::Healthy)
+    }
+}
+
+/// Kernel services handed to every registered subsystem
+#[derive(Debug, Clone)]
+pub struct KernelContext {
+    /// Kernel event bus for publishing notifications
+    pub events: EventBus,
+    /// Latest system load computed by the kernel
+    pub load: LoadMonitor,
+    /// Clock to read the time from
+    pub clock: Clock,
+    /// Generator to draw new handles from
+    pub handles: HandleGenerator,
+}
+
+impl KernelContext {
+    /// Create a new kernel context
+    ///
+    /// # Arguments
+    ///
+    /// * `events` - The kernel event bus
+    ///
+    /// # Returns
+    ///
+    /// A new KernelContext with a fresh load monitor, the system clock and random handles
+    pub fn new(events: EventBus) -> Self {
+        Self::with_clock(events, Clock::system(), HandleGenerator::random())
+    }
+    
+    /// Create a new kernel context with a specific clock and handle generator
+    ///
+    /// # Arguments
+    ///
+    /// * `events` - The kernel event bus
+    /// * `clock` - Clock to read the time from
+    /// * `handles` - Generator to draw new handles from
+    ///
+    /// # Returns
+    ///
+    /// A new KernelContext with a fresh load monitor
+    pub fn with_clock(events: EventBus, clock: Clock, handles: HandleGenerator) -> Self {
+        Self {
+            events,
+            load: LoadMonitor::new(),
+            clock,
+            handles,
+        }
+    }
+}
+
+/// Subsystem managed by the RoyaOS kernel
+///
+/// Every module that the kernel drives through its lifecycle implements this trait.
+/// The kernel owns boxed subsystem instances and calls `initialize` on startup and
+/// `shutdown` when the system stops.
+pub trait Subsystem: Any + Send {
+    /// Get the unique name of the subsystem
+    ///
+    /// # Returns
+    ///
+    /// The name the kernel registers the subsystem under
+    fn name(&self) -> &str;
+    
+    /// Get the names of the subsystems this subsystem depends on
+    ///
+    /// # Returns
+    ///
+    /// Names of the subsystems that must be running before this one starts
+    fn dependencies(&self) -> Vec<String> {
+        Vec::new()
+    }
+    
+    /// Initialize the subsystem
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if initialization is successful, or the error that stopped it
+    fn initialize(&mut self) -> Result<(), SubsystemError>;
+    
+    /// Shutdown the subsystem
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if shutdown is successful, or the error that stopped it
+    fn shutdown(&mut self) -> Result<(), SubsystemError>;
+    
+    /// Probe the health of the subsystem
+    ///
+    /// # Returns
+    ///
+    /// The current health of the subsystem
+    fn health(&self) -> SubsystemHealth;
+    
+    /// Attach the kernel services
+    ///
+    /// Called by the kernel when the subsystem is registered. Subsystems keep
+    /// the services they use; the default implementation ignores them.
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - The kernel services
+    fn attach(&mut self, _context: &KernelContext) {}
+    
+    /// Save the state of the subsystem for a kernel snapshot
+    ///
+    /// Only state that should survive a restart is saved; configuration is
+    /// read again on startup. The default implementation saves nothing.
+    ///
+    /// # Returns
+    ///
+    /// The saved state, `None` if the subsystem has no state to save, or the
+    /// error that stopped it
+    fn snapshot(&self) -> Result<Option<serde_json::Value>, SubsystemError> {
+        Ok(None)
+    }
+    
+    /// Restore the state saved by `snapshot`
+    ///
+    /// Called on a running subsystem. If the state cannot be restored the
+    /// subsystem must be left as it was before the call.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The saved state
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the state was restored, or the error that stopped it
+    fn restore(&mut self, _state: serde_json::Value) -> Result<(), SubsystemError> {
+        Ok(())
+    }
+    
+    /// Get the jobs the subsystem wants the kernel timer service to run
+    ///
+    /// Called by the kernel every time the subsystem is initialized. A job
+    /// keeps its ID when the subsystem declares it again after a restart.
+    /// The default implementation declares no jobs.
+    ///
+    /// # Returns
+    ///
+    /// The jobs of the subsystem
+    fn jobs(&self) -> Vec<JobSpec> {
+        Vec::new()
+    }
+    
+    /// Run one of the jobs declared by `jobs`
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - Name of the job
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the job ran, or the error that stopped it
+    fn run_job(&mut self, job: &str) -> Result<(), SubsystemError> {
+        Err(SubsystemError::msg(format!("Unknown job {}", job)))
+    }
+    
+    /// Get the subsystem as `Any` for downcasting to its concrete type
+    fn as_any(&self) -> &dyn Any;
+    
+    /// Get the subsystem as mutable `Any` for downcasting to its concrete type
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    
+    #[test]
+    fn test_subsystem_health() {
+        assert!(SubsystemHealth::Healthy.is_healthy());
+        assert!(!SubsystemHealth::Degraded("slow".to_string()).is_healthy());
+        assert!(!SubsystemHealth::Failed("down".to_string()).is_healthy());
+    }
+    
+    #[test]
+    fn test_subsystem_error_keeps_typed_error() {
+        let error = SubsystemError::new(EventError::Closed);
+        assert_eq!(error.code(), "EVENT_BUS_CLOSED");
+        assert_eq!(error.to_string(), "Event bus is closed");
+        assert_eq!(error.downcast_ref::<EventError>(), Some(&EventError::Closed));
+        
+        let error = SubsystemError::msg("disk full");
+        assert_eq!(error.code(), SubsystemError::CODE);
+        assert!(error.downcast_ref::<EventError>().is_none());
+    }
+    
+    #[test]
+    fn test_schedule_parsing() {
+        assert_eq!("once 30s".parse(), Ok(Schedule::Once(Duration::from_secs(30))));
+        assert_eq!("every 5m".parse(), Ok(Schedule::Interval(Duration::from_secs(300))));
+        assert_eq!("every 250ms".parse(), Ok(Schedule::Interval(Duration::from_millis(250))));
+        assert_eq!("cron 0 */5 * * * *".parse(), Ok(Schedule::Cron("0 */5 * * * *".to_string())));
+        
+        for schedule in ["once 90s", "every 2h", "every 1500ms", "cron 0 0 * * * *"] {
+            assert_eq!(schedule.parse::<Schedule>().unwrap().to_string(), schedule);
+        }
+        assert_eq!("every 120".parse::<Schedule>().unwrap().to_string(), "every 2m");
+        
+        for invalid in ["every", "every 0s", "every 5 days", "hourly 1h"] {
+            assert_eq!(invalid.parse::<Schedule>().unwrap_err().code(), "TIMER_INVALID_SCHEDULE");
+        }
+    }
+    
+    #[test]
+    fn test_simulated_clock() {
+        let start = chrono::DateTime::UNIX_EPOCH;
+        let clock = Clock::simulated();
+        let shared = clock.clone();
+        let before = clock.now();
+        assert_eq!(clock.utc_now(), start);
+        
+        shared.advance(Duration::from_secs(90));
+        assert_eq!(clock.now() - before, Duration::from_secs(90));
+        assert_eq!(clock.elapsed(before), Duration::from_secs(90));
+        assert_eq!(clock.utc_now(), start + Duration::from_secs(90));
+        assert_eq!(age_millis(&clock, before), 90_000);
+        assert_eq!(instant_from_age(&clock, 30_000), before + Duration::from_secs(60));
+        
+        // The system clock moves on its own and cannot be advanced
+        let system = Clock::system();
+        let before = system.now();
+        system.advance(Duration::from_secs(3600));
+        assert!(system.elapsed(before) < Duration::from_secs(3600));
+    }
+    
+    #[test]
+    fn test_seeded_handles() {
+        let first = HandleGenerator::seeded(7);
+        let second = HandleGenerator::seeded(7);
+        let handles: Vec<_> = (0..3).map(|_| first.next_handle()).collect();
+        assert_eq!(handles, (0..3).map(|_| second.next_handle()).collect::<Vec<_>>());
+        assert_ne!(handles[0], handles[1]);
+        assert_eq!(handles[0].get_version_num(), 4);
+        
+        assert_ne!(HandleGenerator::seeded(8).next_handle(), handles[0]);
+        assert_ne!(HandleGenerator::random().next_handle(), HandleGenerator::random().next_handle());
+    }
+    
+    #[tokio::test]
+    async fn test_event_bus_delivery() {
+        let bus = EventBus::new(8);
+        let mut all = bus.subscribe();
+        let mut sessions = bus.subscribe_to(&[EventKind::SessionClosed]);
+        assert_eq!(bus.subscriber_count(), 2);
+        
+        let session = uuid::Uuid::new_v4();
+        bus.publish(KernelEvent::PermissionDenied {
+            resource_type: "tool".to_string(),
+            operation: "execute".to_string(),
+            resource: "calculator".to_string(),
+        });
+        assert_eq!(bus.publish(KernelEvent::SessionClosed { session }), 2);
+        
+        assert_eq!(all.recv().await.unwrap().kind(), EventKind::PermissionDenied);
+        assert_eq!(all.recv().await.unwrap(), KernelEvent::SessionClosed { session });
+        assert_eq!(sessions.recv().await.unwrap(), KernelEvent::SessionClosed { session });
+        assert_eq!(sessions.try_recv(), Ok(None));
+    }
+    
+    #[test]
+    fn test_event_bus_lag_reporting() {
+        let bus = EventBus::new(2);
+        let mut subscriber = bus.subscribe();
+        
+        for _ in 0..5 {
+            bus.publish(KernelEvent::SessionClosed { session: uuid::Uuid::new_v4() });
+        }
+        
+        assert_eq!(subscriber.try_recv(), Err(EventError::Lagged(3)));
+        assert_eq!(subscriber.missed(), 3);
+        assert!(subscriber.try_recv().unwrap().is_some());
+        assert!(subscriber.try_recv().unwrap().is_some());
+        assert_eq!(subscriber.try_recv(), Ok(None));
+        
+        drop(bus);
+        assert_eq!(subscriber.try_recv(), Err(EventError::Closed));
+    }
+}
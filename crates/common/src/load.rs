@@ -0,0 +1,73 @@
+//! System load reporting
+//!
+//! This module defines the load figures the kernel computes from live subsystem signals
+//! and the shared monitor through which other subsystems read them.
+
+use serde::{Serialize, Deserialize};
+use std::sync::{Arc, RwLock};
+
+/// Live signals the system load is computed from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct LoadSignals {
+    /// Memory usage as a percentage of maximum allocation
+    pub memory_usage_percentage: f64,
+    /// Number of tool executions in progress
+    pub running_tool_executions: usize,
+    /// Number of cognitive tasks waiting for a scheduler slot
+    pub scheduler_queue_depth: usize,
+    /// Process CPU usage as a fraction of all available cores (0.0-1.0)
+    pub cpu_usage: f64,
+}
+
+/// System load with its smoothed averages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct SystemLoad {
+    /// Most recent load sample (0.0-1.0)
+    pub current: f64,
+    /// Load averaged over the last minute
+    pub one_minute: f64,
+    /// Load averaged over the last five minutes
+    pub five_minutes: f64,
+    /// Load averaged over the last fifteen minutes
+    pub fifteen_minutes: f64,
+    /// Signals behind the most recent sample
+    pub signals: LoadSignals,
+}
+
+/// Shared view of the latest system load
+///
+/// The monitor is a cheap handle; the kernel updates it and clones read the
+/// same value.
+#[derive(Debug, Clone, Default)]
+pub struct LoadMonitor {
+    load: Arc<RwLock<SystemLoad>>,
+}
+
+impl LoadMonitor {
+    /// Create a new monitor reporting zero load
+    ///
+    /// # Returns
+    ///
+    /// A new LoadMonitor instance
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Get the latest system load
+    ///
+    /// # Returns
+    ///
+    /// The most recently published load figures
+    pub fn get(&self) -> SystemLoad {
+        *self.load.read().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+    
+    /// Publish new system load figures
+    ///
+    /// # Arguments
+    ///
+    /// * `load` - The new load figures
+    pub fn set(&self, load: SystemLoad) {
+        *self.load.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = load;
+    }
+}
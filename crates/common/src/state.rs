@@ -0,0 +1,96 @@
+//! Subsystem state for kernel snapshots
+//!
+//! Subsystems save their state as a JSON value when the kernel takes a snapshot and
+//! load it back when a snapshot is restored. This module provides the conversions
+//! between a subsystem's own state type and that value, and between the `Instant`s
+//! subsystems keep and the ages stored in a snapshot, since an `Instant` has no
+//! meaning in another process.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+use crate::{Clock, ErrorCode, SubsystemError};
+
+/// Error converting subsystem state to or from a snapshot
+#[derive(Error, Debug)]
+pub enum StateError {
+    /// The state could not be encoded
+    #[error("Failed to encode subsystem state: {0}")]
+    Encode(serde_json::Error),
+    
+    /// The saved state does not match the subsystem's state format
+    #[error("Invalid subsystem state: {0}")]
+    Decode(serde_json::Error),
+}
+
+impl ErrorCode for StateError {
+    fn code(&self) -> &'static str {
+        match self {
+            StateError::Encode(_) => "STATE_ENCODE_FAILED",
+            StateError::Decode(_) => "STATE_INVALID",
+        }
+    }
+}
+
+impl From<StateError> for SubsystemError {
+    fn from(error: StateError) -> Self {
+        SubsystemError::new(error)
+    }
+}
+
+/// Encode subsystem state for a snapshot
+///
+/// # Arguments
+///
+/// * `state` - The state to save
+///
+/// # Returns
+///
+/// The state as a JSON value, or `StateError::Encode`
+pub fn save_state<T: Serialize>(state: &T) -> Result<serde_json::Value, StateError> {
+    serde_json::to_value(state).map_err(StateError::Encode)
+}
+
+/// Decode subsystem state from a snapshot
+///
+/// # Arguments
+///
+/// * `state` - The saved state
+///
+/// # Returns
+///
+/// The decoded state, or `StateError::Decode` if it does not match `T`
+pub fn load_state<T: DeserializeOwned>(state: serde_json::Value) -> Result<T, StateError> {
+    serde_json::from_value(state).map_err(StateError::Decode)
+}
+
+/// Get the age of an instant in milliseconds
+///
+/// # Arguments
+///
+/// * `clock` - The clock the instant was read from
+/// * `instant` - The instant
+///
+/// # Returns
+///
+/// Milliseconds elapsed since `instant`
+pub fn age_millis(clock: &Clock, instant: Instant) -> u64 {
+    clock.elapsed(instant).as_millis() as u64
+}
+
+/// Get the instant that lies a number of milliseconds in the past
+///
+/// # Arguments
+///
+/// * `clock` - The clock to read the current time from
+/// * `age_millis` - Age of the instant in milliseconds
+///
+/// # Returns
+///
+/// The instant, or now if the age reaches back before the process clock started
+pub fn instant_from_age(clock: &Clock, age_millis: u64) -> Instant {
+    let now = clock.now();
+    now.checked_sub(Duration::from_millis(age_millis)).unwrap_or(now)
+}
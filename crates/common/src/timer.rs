@@ -0,0 +1,173 @@
+//! Timer schedules for kernel jobs
+//!
+//! The kernel timer service runs jobs on a schedule: once after a delay, at a fixed
+//! interval, or at the times matched by a cron expression. Subsystems declare the
+//! jobs they want run through `Subsystem::jobs`, and the AGI schedules system calls
+//! through the `job_schedule` system call.
+//!
+//! Schedules have a string form used in system call arguments and logs:
+//! `once <duration>`, `every <duration>` or `cron <expression>`, where a duration is
+//! a number followed by `ms`, `s`, `m` or `h` (seconds if no unit is given). Cron
+//! expressions are checked by the kernel when a job is scheduled.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::ErrorCode;
+
+/// When a job runs
+///
+/// Schedules are serialized in their string form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schedule {
+    /// Run once after a delay
+    Once(Duration),
+    /// Run repeatedly with a fixed delay between two runs
+    Interval(Duration),
+    /// Run at the times matched by a cron expression with a seconds field
+    Cron(String),
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Schedule::Once(delay) => write!(f, "once {}", format_duration(*delay)),
+            Schedule::Interval(interval) => write!(f, "every {}", format_duration(*interval)),
+            Schedule::Cron(expression) => write!(f, "cron {}", expression),
+        }
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = ScheduleError;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| ScheduleError {
+            schedule: s.to_string(),
+            reason: reason.to_string(),
+        };
+        
+        let (kind, rest) = s.trim().split_once(char::is_whitespace)
+            .ok_or_else(|| invalid("expected 'once <duration>', 'every <duration>' or 'cron <expression>'"))?;
+        let rest = rest.trim();
+        
+        match kind {
+            "once" => parse_duration(rest).map(Schedule::Once).ok_or_else(|| invalid("invalid duration")),
+            "every" => match parse_duration(rest) {
+                Some(interval) if !interval.is_zero() => Ok(Schedule::Interval(interval)),
+                Some(_) => Err(invalid("the interval must not be zero")),
+                None => Err(invalid("invalid duration")),
+            },
+            "cron" => Ok(Schedule::Cron(rest.to_string())),
+            _ => Err(invalid("expected 'once <duration>', 'every <duration>' or 'cron <expression>'")),
+        }
+    }
+}
+
+impl Serialize for Schedule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Schedule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let schedule = String::deserialize(deserializer)?;
+        schedule.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Error returned when a schedule is not valid
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Invalid schedule '{schedule}': {reason}")]
+pub struct ScheduleError {
+    /// The rejected schedule
+    pub schedule: String,
+    /// Why the schedule was rejected
+    pub reason: String,
+}
+
+impl ErrorCode for ScheduleError {
+    fn code(&self) -> &'static str {
+        "TIMER_INVALID_SCHEDULE"
+    }
+}
+
+/// Job a subsystem asks the kernel timer service to run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobSpec {
+    /// Name of the job, unique within the subsystem
+    pub name: String,
+    /// When the job runs
+    pub schedule: Schedule,
+}
+
+impl JobSpec {
+    /// Create a new job specification
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the job
+    /// * `schedule` - When the job runs
+    ///
+    /// # Returns
+    ///
+    /// A new JobSpec
+    pub fn new(name: &str, schedule: Schedule) -> Self {
+        Self {
+            name: name.to_string(),
+            schedule,
+        }
+    }
+}
+
+/// Parse a duration such as `500ms`, `30s`, `5m` or `2h`
+///
+/// # Arguments
+///
+/// * `s` - The duration; a plain number is taken as seconds
+///
+/// # Returns
+///
+/// The duration, or `None` if it is not valid
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value: u64 = value.parse().ok()?;
+    
+    match unit.trim() {
+        "ms" => Some(Duration::from_millis(value)),
+        "" | "s" => Some(Duration::from_secs(value)),
+        "m" => Some(Duration::from_secs(value.checked_mul(60)?)),
+        "h" => Some(Duration::from_secs(value.checked_mul(3600)?)),
+        _ => None,
+    }
+}
+
+/// Format a duration in the largest unit that represents it exactly
+///
+/// # Arguments
+///
+/// * `duration` - The duration
+///
+/// # Returns
+///
+/// The duration in the form accepted by `parse_duration`
+pub fn format_duration(duration: Duration) -> String {
+    let millis = duration.as_millis();
+    if !millis.is_multiple_of(1000) {
+        return format!("{}ms", millis);
+    }
+    
+    let secs = duration.as_secs();
+    if secs != 0 && secs.is_multiple_of(3600) {
+        format!("{}h", secs / 3600)
+    } else if secs != 0 && secs.is_multiple_of(60) {
+        format!("{}m", secs / 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
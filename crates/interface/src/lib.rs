@@ -11,15 +11,63 @@
 //! - Session management
 //! - Interface versioning and compatibility
 
-use log::{info, error, debug, warn};
+use log::{info, error, debug};
+use royaos_common::{
+    age_millis, instant_from_age, load_state, save_state, Clock, ErrorCode, EventBus, HandleGenerator, JobSpec,
+    KernelContext, KernelEvent, LoadMonitor, Schedule, Subsystem, SubsystemError, SubsystemHealth,
+};
+use std::any::Any;
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::fmt;
+use std::time::{Duration, Instant};
 use serde::{Serialize, Deserialize};
+use thiserror::Error;
 use uuid::Uuid;
 
 /// Session handle type used to reference AGI sessions
 pub type SessionHandle = Uuid;
 
+/// Name of the job that closes idle sessions on the kernel timer service
+pub const REAP_IDLE_SESSIONS_JOB: &str = "reap_idle_sessions";
+
+/// Longest time between two runs of the idle session job; shorter idle timeouts run it more often
+pub const REAP_IDLE_SESSIONS_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Request handler function type
+type RequestHandler = Box<dyn Fn(&Request) -> Response + Send + Sync>;
+
+/// Error returned by the interface manager
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum InterfaceError {
+    /// No session is open with the ID
+    #[error("Session {0} not found")]
+    SessionNotFound(SessionHandle),
+    
+    /// No handler is registered for the request type
+    #[error("No handler found for request type {0}")]
+    UnknownRequestType(String),
+    
+    /// The request could not be decoded or is missing parameters
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+}
+
+impl ErrorCode for InterfaceError {
+    fn code(&self) -> &'static str {
+        match self {
+            InterfaceError::SessionNotFound(_) => "INTERFACE_SESSION_NOT_FOUND",
+            InterfaceError::UnknownRequestType(_) => "INTERFACE_UNKNOWN_REQUEST_TYPE",
+            InterfaceError::InvalidRequest(_) => "INTERFACE_INVALID_REQUEST",
+        }
+    }
+}
+
+impl From<InterfaceError> for SubsystemError {
+    fn from(error: InterfaceError) -> Self {
+        SubsystemError::new(error)
+    }
+}
+
 /// Request from Roya AGI to the RoyaOS kernel
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Request {
@@ -44,32 +92,132 @@ pub struct Response {
     pub data: Option<serde_json::Value>,
     /// Error message (if unsuccessful)
     pub error: Option<String>,
+    /// Stable error code identifying the failure (if unsuccessful)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
     /// Response timestamp
     pub timestamp: u64,
 }
 
+impl Response {
+    /// Build a successful response
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - ID of the request
+    /// * `data` - Response data
+    ///
+    /// # Returns
+    ///
+    /// A response carrying the data, stamped with the current time
+    pub fn success(id: String, data: serde_json::Value) -> Self {
+        Self {
+            id,
+            success: true,
+            data: Some(data),
+            error: None,
+            error_code: None,
+            timestamp: timestamp(),
+        }
+    }
+    
+    /// Build a failed response
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - ID of the request
+    /// * `error` - The error that made the request fail
+    ///
+    /// # Returns
+    ///
+    /// A response carrying the error message and its code, stamped with the current time
+    pub fn failure<E: fmt::Display + ErrorCode + ?Sized>(id: String, error: &E) -> Self {
+        Self {
+            id,
+            success: false,
+            data: None,
+            error: Some(error.to_string()),
+            error_code: Some(error.code().to_string()),
+            timestamp: timestamp(),
+        }
+    }
+}
+
+/// Get the current time in seconds since the Unix epoch
+fn timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
 /// Session representing an active connection from Roya AGI
 #[derive(Debug)]
+#[allow(dead_code)]
 struct Session {
     /// Session ID
     id: SessionHandle,
     /// Session creation time
-    created_at: std::time::Instant,
+    created_at: Instant,
     /// Last activity time
-    last_activity: std::time::Instant,
+    last_activity: Instant,
+    /// Session metadata
+    metadata: HashMap<String, String>,
+}
+
+/// Saved state of the interface manager in a kernel snapshot
+#[derive(Debug, Serialize, Deserialize)]
+struct InterfaceState {
+    /// The sessions that were open when the snapshot was taken
+    sessions: Vec<SessionState>,
+}
+
+/// Saved state of one session
+#[derive(Debug, Serialize, Deserialize)]
+struct SessionState {
+    /// Session ID
+    id: SessionHandle,
+    /// Milliseconds between the session creation and the snapshot
+    age_ms: u64,
+    /// Milliseconds between the last activity and the snapshot
+    idle_ms: u64,
     /// Session metadata
     metadata: HashMap<String, String>,
 }
 
 /// Interface manager responsible for handling AGI-OS communication
-#[derive(Debug)]
 pub struct InterfaceManager {
     /// Active sessions
     sessions: HashMap<SessionHandle, Session>,
     /// API version
     api_version: String,
     /// Request handlers
-    request_handlers: HashMap<String, Box<dyn Fn(&Request) -> Response + Send + Sync>>,
+    request_handlers: HashMap<String, RequestHandler>,
+    /// Event bus for session notifications
+    event_bus: Option<EventBus>,
+    /// System load reported in system info responses
+    load: LoadMonitor,
+    /// Time without activity after which a session is closed, if sessions expire
+    idle_timeout: Option<Duration>,
+    /// Clock for session activity times
+    clock: Clock,
+    /// Generator of session IDs
+    handles: HandleGenerator,
+}
+
+impl std::fmt::Debug for InterfaceManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InterfaceManager")
+            .field("sessions", &self.sessions)
+            .field("api_version", &self.api_version)
+            .field("request_handlers", &self.request_handlers.keys().collect::<Vec<_>>())
+            .field("event_bus", &self.event_bus)
+            .field("load", &self.load)
+            .field("idle_timeout", &self.idle_timeout)
+            .field("clock", &self.clock)
+            .field("handles", &self.handles)
+            .finish()
+    }
 }
 
 impl InterfaceManager {
@@ -89,6 +237,11 @@ impl InterfaceManager {
             sessions: HashMap::new(),
             api_version: api_version.to_string(),
             request_handlers: HashMap::new(),
+            event_bus: None,
+            load: LoadMonitor::new(),
+            idle_timeout: None,
+            clock: Clock::system(),
+            handles: HandleGenerator::random(),
         }
     }
     
@@ -99,7 +252,7 @@ impl InterfaceManager {
     /// # Returns
     ///
     /// `Ok(())` if initialization is successful, or an error message
-    pub fn initialize(&mut self) -> Result<(), String> {
+    pub fn initialize(&mut self) -> Result<(), InterfaceError> {
         info!("Initializing interface manager");
         
         // Register default request handlers
@@ -119,8 +272,8 @@ impl InterfaceManager {
     ///
     /// Handle to the new session
     pub fn create_session(&mut self, metadata: HashMap<String, String>) -> SessionHandle {
-        let session_id = Uuid::new_v4();
-        let now = std::time::Instant::now();
+        let session_id = self.handles.next_handle();
+        let now = self.clock.now();
         
         let session = Session {
             id: session_id,
@@ -143,16 +296,83 @@ impl InterfaceManager {
     ///
     /// # Returns
     ///
-    /// `Ok(())` if successful, or an error message
-    pub fn close_session(&mut self, session_id: SessionHandle) -> Result<(), String> {
+    /// `Ok(())` if successful, or `InterfaceError::SessionNotFound`
+    pub fn close_session(&mut self, session_id: SessionHandle) -> Result<(), InterfaceError> {
         if self.sessions.remove(&session_id).is_some() {
             info!("Closed session with ID {}", session_id);
+            self.publish_session_closed(session_id);
             Ok(())
         } else {
-            let error_msg = format!("Session {} not found", session_id);
-            error!("{}", error_msg);
-            Err(error_msg)
+            let error = InterfaceError::SessionNotFound(session_id);
+            error!("{}", error);
+            Err(error)
+        }
+    }
+    
+    /// Record activity on a session
+    ///
+    /// Requests handled by `process_request` record their activity themselves;
+    /// callers serving requests in another way call this for each request so
+    /// the session is not closed as idle.
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - ID of the session
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the session is open, or `InterfaceError::SessionNotFound`
+    pub fn touch_session(&mut self, session_id: SessionHandle) -> Result<(), InterfaceError> {
+        match self.sessions.get_mut(&session_id) {
+            Some(session) => {
+                session.last_activity = self.clock.now();
+                Ok(())
+            },
+            None => Err(InterfaceError::SessionNotFound(session_id)),
+        }
+    }
+    
+    /// Set how long a session may stay idle before `reap_idle_sessions` closes it
+    ///
+    /// # Arguments
+    ///
+    /// * `idle_timeout` - The idle timeout, or `None` to keep idle sessions open
+    pub fn set_idle_timeout(&mut self, idle_timeout: Option<Duration>) {
+        self.idle_timeout = idle_timeout;
+    }
+    
+    /// Get the idle timeout of sessions
+    ///
+    /// # Returns
+    ///
+    /// The idle timeout, or `None` if idle sessions are kept open
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout
+    }
+    
+    /// Close the sessions that have been idle for longer than the idle timeout
+    ///
+    /// # Returns
+    ///
+    /// IDs of the closed sessions; none if no idle timeout is set
+    pub fn reap_idle_sessions(&mut self) -> Vec<SessionHandle> {
+        let Some(idle_timeout) = self.idle_timeout else {
+            return Vec::new();
+        };
+        
+        let mut idle: Vec<SessionHandle> = self.sessions.values()
+            .filter(|session| self.clock.elapsed(session.last_activity) > idle_timeout)
+            .map(|session| session.id)
+            .collect();
+        idle.sort();
+        
+        for session_id in &idle {
+            self.sessions.remove(session_id);
+            info!("Closed session {} after {:?} without activity", session_id, idle_timeout);
+            self.publish_session_closed(*session_id);
         }
+        
+        idle
     }
     
     /// Process a request from Roya AGI
@@ -164,18 +384,16 @@ impl InterfaceManager {
     ///
     /// # Returns
     ///
-    /// Response to the request, or an error message
-    pub fn process_request(&mut self, session_id: SessionHandle, request: Request) -> Result<Response, String> {
+    /// Response to the request, or `InterfaceError::SessionNotFound`; a request of
+    /// an unknown type is answered with a failed response
+    pub fn process_request(&mut self, session_id: SessionHandle, request: Request) -> Result<Response, InterfaceError> {
         debug!("Processing request {} of type {} for session {}", 
                request.id, request.request_type, session_id);
         
         // Update session activity
-        if let Some(session) = self.sessions.get_mut(&session_id) {
-            session.last_activity = std::time::Instant::now();
-        } else {
-            let error_msg = format!("Session {} not found", session_id);
-            error!("{}", error_msg);
-            return Err(error_msg);
+        if let Err(error) = self.touch_session(session_id) {
+            error!("{}", error);
+            return Err(error);
         }
         
         // Find handler for request type
@@ -183,21 +401,10 @@ impl InterfaceManager {
             let response = handler(&request);
             Ok(response)
         } else {
-            let error_msg = format!("No handler found for request type {}", request.request_type);
-            error!("{}", error_msg);
-            
-            let response = Response {
-                id: request.id,
-                success: false,
-                data: None,
-                error: Some(error_msg),
-                timestamp: std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_secs(),
-            };
+            let error = InterfaceError::UnknownRequestType(request.request_type);
+            error!("{}", error);
             
-            Ok(response)
+            Ok(Response::failure(request.id, &error))
         }
     }
     
@@ -211,7 +418,7 @@ impl InterfaceManager {
     /// # Returns
     ///
     /// `Ok(())` if registration is successful, or an error message
-    pub fn register_handler<F>(&mut self, request_type: &str, handler: F) -> Result<(), String>
+    pub fn register_handler<F>(&mut self, request_type: &str, handler: F) -> Result<(), InterfaceError>
     where
         F: Fn(&Request) -> Response + Send + Sync + 'static,
     {
@@ -240,48 +447,153 @@ impl InterfaceManager {
         self.sessions.keys().cloned().collect()
     }
     
-    /// Register default request handlers
-    fn register_default_handlers(&mut self) {
-        // Register system info handler
-        self.register_handler("system_info", |request| {
+    /// Announce a closed session on the kernel event bus if one is attached
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - ID of the closed session
+    fn publish_session_closed(&self, session_id: SessionHandle) {
+        if let Some(bus) = &self.event_bus {
+            bus.publish(KernelEvent::SessionClosed { session: session_id });
+        }
+    }
+    
+    /// Register the system info handler reporting the current system load
+    fn register_system_info_handler(&mut self) {
+        let load = self.load.clone();
+        self.register_handler("system_info", move |request| {
             let system_info = serde_json::json!({
                 "name": "RoyaOS",
                 "version": "0.1.0",
                 "api_version": "1.0",
                 "uptime_seconds": 0, // In a real implementation, this would be the actual uptime
+                "load": load.get(),
             });
             
-            Response {
-                id: request.id.clone(),
-                success: true,
-                data: Some(system_info),
-                error: None,
-                timestamp: std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_secs(),
-            }
+            Response::success(request.id.clone(), system_info)
         }).unwrap();
+    }
+    
+    /// Register default request handlers
+    fn register_default_handlers(&mut self) {
+        // Register system info handler
+        self.register_system_info_handler();
         
         // Register echo handler (for testing)
         self.register_handler("echo", |request| {
-            Response {
-                id: request.id.clone(),
-                success: true,
-                data: Some(request.parameters.clone()),
-                error: None,
-                timestamp: std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_secs(),
-            }
+            Response::success(request.id.clone(), request.parameters.clone())
         }).unwrap();
     }
 }
 
+impl Subsystem for InterfaceManager {
+    fn name(&self) -> &str {
+        "interface"
+    }
+    
+    fn dependencies(&self) -> Vec<String> {
+        vec!["security".to_string(), "tools".to_string()]
+    }
+    
+    fn initialize(&mut self) -> Result<(), SubsystemError> {
+        Ok(InterfaceManager::initialize(self)?)
+    }
+    
+    fn shutdown(&mut self) -> Result<(), SubsystemError> {
+        info!("Shutting down interface manager, closing {} sessions", self.sessions.len());
+        for (session_id, _) in std::mem::take(&mut self.sessions) {
+            self.publish_session_closed(session_id);
+        }
+        Ok(())
+    }
+    
+    fn health(&self) -> SubsystemHealth {
+        if self.request_handlers.is_empty() {
+            SubsystemHealth::Degraded("No request handlers registered".to_string())
+        } else {
+            SubsystemHealth::Healthy
+        }
+    }
+    
+    fn attach(&mut self, context: &KernelContext) {
+        self.event_bus = Some(context.events.clone());
+        self.clock = context.clock.clone();
+        self.handles = context.handles.clone();
+        self.load = context.load.clone();
+        
+        // Point an already registered system info handler at the kernel's load
+        if self.request_handlers.contains_key("system_info") {
+            self.register_system_info_handler();
+        }
+    }
+    
+    fn snapshot(&self) -> Result<Option<serde_json::Value>, SubsystemError> {
+        let sessions = self.sessions.values()
+            .map(|session| SessionState {
+                id: session.id,
+                age_ms: age_millis(&self.clock, session.created_at),
+                idle_ms: age_millis(&self.clock, session.last_activity),
+                metadata: session.metadata.clone(),
+            })
+            .collect();
+        
+        Ok(Some(save_state(&InterfaceState { sessions })?))
+    }
+    
+    fn restore(&mut self, state: serde_json::Value) -> Result<(), SubsystemError> {
+        let state: InterfaceState = load_state(state)?;
+        
+        // Sessions opened since the snapshot are kept
+        let mut restored = 0;
+        for saved in state.sessions {
+            self.sessions.entry(saved.id).or_insert_with(|| {
+                restored += 1;
+                Session {
+                    id: saved.id,
+                    created_at: instant_from_age(&self.clock, saved.age_ms),
+                    last_activity: instant_from_age(&self.clock, saved.idle_ms),
+                    metadata: saved.metadata,
+                }
+            });
+        }
+        
+        info!("Restored {} sessions", restored);
+        Ok(())
+    }
+    
+    fn jobs(&self) -> Vec<JobSpec> {
+        match self.idle_timeout {
+            Some(idle_timeout) => {
+                let interval = idle_timeout.min(REAP_IDLE_SESSIONS_INTERVAL);
+                vec![JobSpec::new(REAP_IDLE_SESSIONS_JOB, Schedule::Interval(interval))]
+            },
+            None => Vec::new(),
+        }
+    }
+    
+    fn run_job(&mut self, job: &str) -> Result<(), SubsystemError> {
+        match job {
+            REAP_IDLE_SESSIONS_JOB => {
+                self.reap_idle_sessions();
+                Ok(())
+            },
+            _ => Err(SubsystemError::msg(format!("Unknown interface job {}", job))),
+        }
+    }
+    
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use royaos_common::SystemLoad;
     
     #[test]
     fn test_session_management() {
@@ -303,6 +615,79 @@ mod tests {
         // Check active sessions again
         let sessions = manager.get_active_sessions();
         assert_eq!(sessions.len(), 0);
+        
+        // Closing it again fails
+        assert_eq!(manager.close_session(session_id), Err(InterfaceError::SessionNotFound(session_id)));
+    }
+    
+    #[test]
+    fn test_session_closed_events() {
+        let context = KernelContext::new(EventBus::new(4));
+        let mut events = context.events.subscribe();
+        
+        let mut manager = InterfaceManager::new("1.0");
+        manager.attach(&context);
+        
+        let closed = manager.create_session(HashMap::new());
+        let open = manager.create_session(HashMap::new());
+        manager.close_session(closed).unwrap();
+        assert_eq!(events.try_recv(), Ok(Some(KernelEvent::SessionClosed { session: closed })));
+        
+        // Sessions still open at shutdown are reported as closed too
+        Subsystem::shutdown(&mut manager).unwrap();
+        assert_eq!(events.try_recv(), Ok(Some(KernelEvent::SessionClosed { session: open })));
+        assert_eq!(events.try_recv(), Ok(None));
+    }
+    
+    #[test]
+    fn test_idle_session_reaping() {
+        let context = KernelContext::with_clock(EventBus::new(4), Clock::simulated(), HandleGenerator::seeded(1));
+        let mut events = context.events.subscribe();
+        
+        let mut manager = InterfaceManager::new("1.0");
+        manager.attach(&context);
+        assert!(manager.jobs().is_empty());
+        
+        let idle = manager.create_session(HashMap::new());
+        let active = manager.create_session(HashMap::new());
+        context.clock.advance(Duration::from_secs(120));
+        assert!(manager.reap_idle_sessions().is_empty());
+        
+        manager.set_idle_timeout(Some(Duration::from_secs(60)));
+        assert_eq!(manager.jobs()[0].name, REAP_IDLE_SESSIONS_JOB);
+        manager.touch_session(active).unwrap();
+        manager.run_job(REAP_IDLE_SESSIONS_JOB).unwrap();
+        
+        assert_eq!(manager.get_active_sessions(), vec![active]);
+        assert_eq!(events.try_recv(), Ok(Some(KernelEvent::SessionClosed { session: idle })));
+        assert_eq!(manager.touch_session(idle), Err(InterfaceError::SessionNotFound(idle)));
+    }
+    
+    #[test]
+    fn test_system_info_reports_load() {
+        let context = KernelContext::new(EventBus::new(4));
+        let mut manager = InterfaceManager::new("1.0");
+        manager.attach(&context);
+        manager.initialize().unwrap();
+        
+        context.load.set(SystemLoad {
+            current: 0.25,
+            one_minute: 0.5,
+            ..SystemLoad::default()
+        });
+        
+        let session_id = manager.create_session(HashMap::new());
+        let request = Request {
+            id: "info".to_string(),
+            request_type: "system_info".to_string(),
+            parameters: serde_json::json!({}),
+            timestamp: 0,
+        };
+        let data = manager.process_request(session_id, request).unwrap().data.unwrap();
+        
+        assert_eq!(data["load"]["current"], 0.25);
+        assert_eq!(data["load"]["one_minute"], 0.5);
+        assert_eq!(data["load"]["signals"]["scheduler_queue_depth"], 0);
     }
     
     #[test]
@@ -335,6 +720,18 @@ mod tests {
         // Check response data
         let data = response.data.unwrap();
         assert_eq!(data["message"], "Hello, RoyaOS!");
+        
+        // Unknown request types are answered with a stable error code
+        let request = Request {
+            id: "unknown-request".to_string(),
+            request_type: "teleport".to_string(),
+            parameters: serde_json::json!({}),
+            timestamp: 0,
+        };
+        let response = manager.process_request(session_id, request).unwrap();
+        assert!(!response.success);
+        assert_eq!(response.error_code.as_deref(), Some("INTERFACE_UNKNOWN_REQUEST_TYPE"));
+        assert_eq!(response.error.as_deref(), Some("No handler found for request type teleport"));
     }
     
     #[test]
@@ -350,6 +747,7 @@ mod tests {
                     "result": "Custom handler executed",
                 })),
                 error: None,
+                error_code: None,
                 timestamp: 0,
             }
         }).unwrap();
@@ -0,0 +1,240 @@
+//! System call batches for the RoyaOS kernel
+//!
+//! A batch runs an ordered list of system calls as a unit through `Kernel::process_batch`.
+//! The steps run one after the other until one fails; the steps after it are skipped,
+//! and the steps that already ran are undone in reverse order by compensating system
+//! calls: memory they allocated is freed, permissions they granted are revoked,
+//! permissions they revoked are granted again and jobs they scheduled are cancelled.
+//! A tool execution that reports failure counts as a failed step.
+//!
+//! Some effects cannot be undone: tool executions, freed or overwritten memory,
+//! replaced embeddings and times to live, consolidated memory, cancelled tasks and
+//! cancelled jobs. Such steps are reported
+//! as not rolled back, so a batch should put them after the steps that may still fail.
+//!
+//! An argument of the form `$<n>` is replaced by the result of step `n`, counting from
+//! 0, before the step runs. This lets a step use the handle an earlier step allocated.
+//! Batches are not isolated: calls from other sessions may run between two steps.
+
+use royaos_common::ErrorCode;
+use royaos_security::Permission;
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+
+use crate::syscall::{Syscall, SyscallError, SyscallResult};
+
+/// One system call of a batch in its string form
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchStep {
+    /// Name of the system call
+    pub syscall: String,
+    /// Arguments of the system call, which may refer to the results of earlier steps
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+impl BatchStep {
+    /// Create a new batch step
+    ///
+    /// # Arguments
+    ///
+    /// * `syscall` - Name of the system call
+    /// * `args` - Arguments of the system call
+    ///
+    /// # Returns
+    ///
+    /// A new BatchStep
+    pub fn new(syscall: &str, args: &[&str]) -> Self {
+        Self {
+            syscall: syscall.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        }
+    }
+}
+
+/// What happened to one step of a batch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum StepOutcome {
+    /// The step succeeded and its effect was kept
+    Committed {
+        /// Result of the step
+        result: SyscallResult,
+    },
+    /// The step succeeded and its effect was undone after a later step failed
+    RolledBack {
+        /// Result of the step
+        result: SyscallResult,
+    },
+    /// The step succeeded but its effect could not be undone after a later step failed
+    NotRolledBack {
+        /// Result of the step
+        result: SyscallResult,
+        /// Why the effect could not be undone
+        reason: String,
+    },
+    /// The step failed
+    Failed {
+        /// Stable code of the error
+        error_code: String,
+        /// Error message
+        error: String,
+    },
+    /// The step did not run because an earlier step failed
+    Skipped,
+}
+
+/// Result of a batch of system calls
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchResult {
+    /// Whether every step succeeded and its effect was kept
+    pub committed: bool,
+    /// Outcome of every step in order
+    pub steps: Vec<StepOutcome>,
+}
+
+/// Error returned when a batch or one of its steps is rejected
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum BatchError {
+    /// A step refers to the result of a step that does not run before it
+    #[error("Step {step} refers to the result of step {reference}, which does not run before it")]
+    InvalidReference {
+        /// Position of the referring step
+        step: usize,
+        /// Position of the referenced step
+        reference: usize,
+    },
+    
+    /// A tool execution reported failure
+    #[error("Tool execution failed: {0}")]
+    ToolFailed(String),
+}
+
+impl ErrorCode for BatchError {
+    fn code(&self) -> &'static str {
+        match self {
+            BatchError::InvalidReference { .. } => "BATCH_INVALID_REFERENCE",
+            BatchError::ToolFailed(_) => "BATCH_TOOL_FAILED",
+        }
+    }
+}
+
+/// How the effect of a successful step is undone
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Compensation {
+    /// The step had no effect
+    Nothing,
+    /// The system call undoing the effect
+    Run(Syscall),
+    /// The effect cannot be undone, for this reason
+    Impossible(&'static str),
+}
+
+impl Compensation {
+    /// Find how to undo the effect of a successful step
+    ///
+    /// # Arguments
+    ///
+    /// * `result` - The result of the step
+    ///
+    /// # Returns
+    ///
+    /// The compensation for the step
+    pub(crate) fn of(result: &SyscallResult) -> Self {
+        match result {
+            SyscallResult::MemoryAllocated { handle } => Compensation::Run(Syscall::MemoryFree { handle: *handle }),
+            SyscallResult::PermissionGranted { permission, changed: true } => {
+                let Permission { resource_type, operation, resource } = permission.clone();
+                Compensation::Run(Syscall::SecurityRevoke { resource_type, operation, resource })
+            },
+            SyscallResult::PermissionRevoked { permission, changed: true } => {
+                let Permission { resource_type, operation, resource } = permission.clone();
+                Compensation::Run(Syscall::SecurityGrant { resource_type, operation, resource })
+            },
+            SyscallResult::JobScheduled { job } => Compensation::Run(Syscall::JobCancel { job: *job }),
+            SyscallResult::MemoryFreed { .. } => Compensation::Impossible("freed memory cannot be restored"),
+            SyscallResult::MemoryStored { .. } => Compensation::Impossible("overwritten memory cannot be restored"),
+            SyscallResult::MemoryEmbedded { .. } => Compensation::Impossible("replaced embeddings cannot be restored"),
+            SyscallResult::MemoryConsolidated { .. } => Compensation::Impossible("consolidated memory is not demoted"),
+            SyscallResult::MemoryExpirySet { .. } => Compensation::Impossible("replaced times to live cannot be restored"),
+            SyscallResult::ToolExecuted { .. } => Compensation::Impossible("tool executions cannot be undone"),
+            SyscallResult::TaskCancelled { .. } => Compensation::Impossible("cancelled tasks cannot be resumed"),
+            SyscallResult::JobCancelled { .. } => Compensation::Impossible("cancelled jobs cannot be restored"),
+            SyscallResult::PermissionGranted { .. }
+            | SyscallResult::PermissionRevoked { .. }
+            | SyscallResult::MemoryLoaded { .. }
+            | SyscallResult::MemoryFound { .. }
+            | SyscallResult::MemoryStrength { .. }
+            | SyscallResult::PermissionChecked { .. }
+            | SyscallResult::JobsListed { .. } => Compensation::Nothing,
+        }
+    }
+}
+
+/// Check that every step refers only to the results of earlier steps
+///
+/// # Arguments
+///
+/// * `steps` - The steps of the batch
+///
+/// # Returns
+///
+/// `Ok(())` if every reference is valid, or `BatchError::InvalidReference`
+pub(crate) fn check_references(steps: &[BatchStep]) -> Result<(), BatchError> {
+    for (step, batch_step) in steps.iter().enumerate() {
+        for reference in batch_step.args.iter().filter_map(|arg| reference(arg)) {
+            if reference >= step {
+                return Err(BatchError::InvalidReference { step, reference });
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+/// Replace the references in the arguments of a step by the results they refer to
+///
+/// # Arguments
+///
+/// * `args` - The arguments of the step
+/// * `results` - The results of the earlier steps in their string form
+///
+/// # Returns
+///
+/// The arguments to run the step with
+pub(crate) fn resolve_args(args: &[String], results: &[String]) -> Vec<String> {
+    args.iter()
+        .map(|arg| match reference(arg).and_then(|reference| results.get(reference)) {
+            Some(result) => result.clone(),
+            None => arg.clone(),
+        })
+        .collect()
+}
+
+/// Turn a tool execution that reported failure into a failed step
+///
+/// # Arguments
+///
+/// * `result` - The result of the step
+///
+/// # Returns
+///
+/// The result if the step succeeded, or `BatchError::ToolFailed`
+pub(crate) fn check_step(result: SyscallResult) -> Result<SyscallResult, SyscallError> {
+    match result {
+        SyscallResult::ToolExecuted { result } if !result.success => {
+            let reason = result.error.unwrap_or_else(|| "no error reported".to_string());
+            Err(BatchError::ToolFailed(reason).into())
+        },
+        result => Ok(result),
+    }
+}
+
+/// Parse an argument of the form `$<n>` referring to the result of step `n`
+fn reference(arg: &str) -> Option<usize> {
+    let digits = arg.strip_prefix('$')?;
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
@@ -0,0 +1,179 @@
+//! Kernel errors for RoyaOS
+//!
+//! This module defines the error returned by the kernel's own operations: subsystem
+//! registration and lifecycle, dependency resolution, task scheduling, agent quotas,
+//! timers, snapshots and syscall traces. Failures reported by a subsystem itself are
+//! carried along with the stable code of the subsystem's error.
+
+use royaos_common::{ErrorCode, SubsystemError};
+use std::io;
+use std::path::PathBuf;
+use thiserror::Error;
+
+use crate::quota::QuotaError;
+use crate::scheduler::SchedulerError;
+use crate::timer::TimerError;
+
+/// Error returned by the kernel
+#[derive(Error, Debug)]
+pub enum KernelError {
+    /// The kernel was initialized while already running
+    #[error("Kernel is already running")]
+    AlreadyRunning,
+    
+    /// The operation needs a running kernel
+    #[error("Kernel is not running")]
+    NotRunning,
+    
+    /// No subsystem is registered under the name
+    #[error("Subsystem {0} is not registered")]
+    SubsystemNotRegistered(String),
+    
+    /// A subsystem is already registered under the name
+    #[error("Subsystem {0} is already registered")]
+    SubsystemAlreadyRegistered(String),
+    
+    /// The subsystem has not been started
+    #[error("Subsystem {0} is not running")]
+    SubsystemNotRunning(String),
+    
+    /// The subsystem is not of the requested type
+    #[error("Subsystem {0} is not of the requested type")]
+    SubsystemTypeMismatch(String),
+    
+    /// A thread panicked while holding the subsystem's lock
+    #[error("Subsystem {0} lock is poisoned")]
+    SubsystemPoisoned(String),
+    
+    /// A subsystem depends on a subsystem that is not registered
+    #[error("Subsystem {subsystem} depends on unregistered subsystem {dependency}")]
+    MissingDependency {
+        /// Name of the dependent subsystem
+        subsystem: String,
+        /// Name of the missing dependency
+        dependency: String,
+    },
+    
+    /// The subsystem dependencies form a cycle
+    #[error("Dependency cycle detected among subsystems: {}", .0.join(", "))]
+    DependencyCycle(Vec<String>),
+    
+    /// A subsystem failed to initialize, shut down, save or restore its state, or run a job
+    #[error("Subsystem {subsystem} failed to {action}: {source}")]
+    SubsystemFailed {
+        /// Name of the subsystem
+        subsystem: String,
+        /// The action that failed: `initialize`, `shutdown`, `snapshot`, `restore` or `run job`
+        action: &'static str,
+        /// The error reported by the subsystem
+        source: SubsystemError,
+    },
+    
+    /// One or more subsystems failed to shut down
+    #[error("{}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    ShutdownFailed(Vec<KernelError>),
+    
+    /// The restart policy name is not known
+    #[error("Invalid restart policy: {0}")]
+    InvalidRestartPolicy(String),
+    
+    /// The scheduler rejected the operation
+    #[error(transparent)]
+    Scheduler(#[from] SchedulerError),
+    
+    /// The principal is unknown
+    #[error(transparent)]
+    Quota(#[from] QuotaError),
+    
+    /// The job is unknown or its schedule is not valid
+    #[error(transparent)]
+    Timer(#[from] TimerError),
+    
+    /// A snapshot file or directory could not be read or written
+    #[error("Snapshot I/O error at {}: {source}", .path.display())]
+    SnapshotIo {
+        /// The file or directory
+        path: PathBuf,
+        /// The I/O error
+        source: io::Error,
+    },
+    
+    /// A snapshot file is not a valid snapshot
+    #[error("Invalid snapshot {}: {source}", .path.display())]
+    SnapshotFormat {
+        /// The snapshot file
+        path: PathBuf,
+        /// The encoding error
+        source: serde_json::Error,
+    },
+    
+    /// A snapshot was written in a format version this kernel cannot read
+    #[error("Snapshot {} has format version {found}, supported version is {supported}", .path.display())]
+    SnapshotVersion {
+        /// The snapshot file
+        path: PathBuf,
+        /// Format version of the snapshot
+        found: u32,
+        /// Format version this kernel reads
+        supported: u32,
+    },
+    
+    /// A trace file could not be read or written
+    #[error("Trace I/O error at {}: {source}", .path.display())]
+    TraceIo {
+        /// The trace file
+        path: PathBuf,
+        /// The I/O error
+        source: io::Error,
+    },
+    
+    /// A line of a trace file is not a valid trace entry
+    #[error("Invalid trace {} at line {line}: {source}", .path.display())]
+    TraceFormat {
+        /// The trace file
+        path: PathBuf,
+        /// Number of the invalid line, starting at 1
+        line: usize,
+        /// The decoding error
+        source: serde_json::Error,
+    },
+    
+    /// A trace was written in a format version this kernel cannot read
+    #[error("Trace {} has format version {found}, supported version is {supported}", .path.display())]
+    TraceVersion {
+        /// The trace file
+        path: PathBuf,
+        /// Format version of the trace
+        found: u32,
+        /// Format version this kernel reads
+        supported: u32,
+    },
+}
+
+impl ErrorCode for KernelError {
+    fn code(&self) -> &'static str {
+        match self {
+            KernelError::AlreadyRunning => "KERNEL_ALREADY_RUNNING",
+            KernelError::NotRunning => "KERNEL_NOT_RUNNING",
+            KernelError::SubsystemNotRegistered(_) => "SUBSYSTEM_NOT_REGISTERED",
+            KernelError::SubsystemAlreadyRegistered(_) => "SUBSYSTEM_ALREADY_REGISTERED",
+            KernelError::SubsystemNotRunning(_) => "SUBSYSTEM_NOT_RUNNING",
+            KernelError::SubsystemTypeMismatch(_) => "SUBSYSTEM_TYPE_MISMATCH",
+            KernelError::SubsystemPoisoned(_) => "SUBSYSTEM_POISONED",
+            KernelError::MissingDependency { .. } => "SUBSYSTEM_MISSING_DEPENDENCY",
+            KernelError::DependencyCycle(_) => "SUBSYSTEM_DEPENDENCY_CYCLE",
+            KernelError::SubsystemFailed { source, .. } => source.code(),
+            KernelError::ShutdownFailed(_) => "KERNEL_SHUTDOWN_FAILED",
+            KernelError::InvalidRestartPolicy(_) => "KERNEL_INVALID_RESTART_POLICY",
+            KernelError::Scheduler(error) => error.code(),
+            KernelError::Quota(error) => error.code(),
+            KernelError::Timer(error) => error.code(),
+            KernelError::SnapshotIo { .. } => "SNAPSHOT_IO",
+            KernelError::SnapshotFormat { .. } => "SNAPSHOT_INVALID",
+            KernelError::SnapshotVersion { .. } => "SNAPSHOT_UNSUPPORTED_VERSION",
+            KernelError::TraceIo { .. } => "TRACE_IO",
+            KernelError::TraceFormat { .. } => "TRACE_INVALID",
+            KernelError::TraceVersion { .. } => "TRACE_UNSUPPORTED_VERSION",
+        }
+    }
+}
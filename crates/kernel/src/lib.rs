@@ -4,6 +4,18 @@
 //! component of the operating system. The kernel is responsible for managing system resources,
 //! handling process scheduling, and providing essential services to the Roya AGI.
 //!
+//! Cognitive tasks run on the kernel's scheduler, which is registered as a built-in
+//! subsystem of every kernel instance. Subsystems notify each other and embedding code
+//! through the kernel's event bus, and the kernel derives the system load from their live
+//! signals. A watchdog polls the subsystem health probes and restarts failed subsystems
+//! according to their restart policy. When several agents share the kernel, each is
+//! registered as a principal and its system calls are charged against a quota. The timer
+//! service runs the periodic jobs subsystems declare and the system calls the AGI
+//! schedules. System calls can be grouped into batches that are rolled back as a unit
+//! when a step fails. The kernel and its subsystems read the time and new handles from
+//! the kernel context, so a kernel on a simulated clock with a seeded handle generator
+//! repeats a run exactly.
+//!
 //! The kernel design is specifically optimized for AGI workloads, with a focus on:
 //! - Efficient resource allocation
 //! - Real-time processing capabilities
@@ -11,25 +23,158 @@
 //! - Cognitive process prioritization
 //! - Advanced memory management integration
 
-use log::{info, error, debug};
-use std::sync::{Arc, Mutex};
+use log::{info, error, debug, warn};
+use royaos_memory::{MemoryError, MemoryManager, CONSOLIDATE_JOB};
+use royaos_security::{Permission, SecurityManager};
+use royaos_tools::{ToolError, ToolManager, ToolResult};
+use royaos_memory::MemoryCategory;
+use std::sync::{Arc, Mutex, TryLockError};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+mod batch;
+mod error;
+mod load;
+mod quota;
+mod scheduler;
+mod snapshot;
+mod syscall;
+mod timer;
+mod trace;
+mod watchdog;
+
+use batch::Compensation;
+use load::LoadTracker;
+use quota::{Charge, Principals};
+use timer::Timers;
+use trace::{HandleMap, SyscallTracer};
+use watchdog::RestartTracker;
+
+pub use batch::{BatchError, BatchResult, BatchStep, StepOutcome};
+pub use error::KernelError;
+pub use load::LOAD_SAMPLE_INTERVAL;
+pub use royaos_common::{
+    Clock, ErrorCode, EventBus, EventError, EventKind, EventSubscriber, HandleGenerator, JobSpec, KernelContext,
+    KernelEvent, LoadSignals, Schedule, ScheduleError, Subsystem, SubsystemError, SubsystemHealth, SystemLoad,
+};
+pub use quota::{PrincipalUsage, Quota, QuotaError, AUDIT_VOLUME_WINDOW, SYSCALL_RATE_WINDOW};
+pub use snapshot::{Snapshot, SNAPSHOT_DIR, SNAPSHOT_FORMAT_VERSION, SNAPSHOT_RETENTION};
+pub use scheduler::{priority_share, Scheduler, SchedulerError, TaskCancelled, TaskContext, TaskId, TaskInfo, TaskPriority, TaskState};
+pub use syscall::{Syscall, SyscallError, SyscallResult, ToolRef};
+pub use timer::{JobAction, JobId, JobInfo, TimerError, KERNEL_JOB_OWNER, TIMER_TICK};
+pub use trace::{Divergence, ReplayReport, Trace, TraceHeader, TraceOutcome, TraceRecord, TRACE_DIR, TRACE_FORMAT_VERSION};
+pub use watchdog::{RestartPolicy, DEFAULT_WATCHDOG_INTERVAL, MAX_RESTART_BACKOFF, WEDGED_CHECK_LIMIT};
+
+/// Name of the memory subsystem
+pub const MEMORY_SUBSYSTEM: &str = "memory";
+/// Name of the tools subsystem
+pub const TOOLS_SUBSYSTEM: &str = "tools";
+/// Name of the security subsystem
+pub const SECURITY_SUBSYSTEM: &str = "security";
+/// Name of the interface subsystem
+pub const INTERFACE_SUBSYSTEM: &str = "interface";
+/// Name of the built-in scheduler subsystem
+pub const SCHEDULER_SUBSYSTEM: &str = "scheduler";
+
+/// Number of cognitive tasks the scheduler runs at the same time by default
+pub const DEFAULT_TASK_SLOTS: usize = 4;
+
+/// Number of events buffered for each event bus subscriber
+pub const EVENT_BUS_CAPACITY: usize = 1024;
+
+/// Interval at which `drain` checks for in-flight work
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Lifecycle state of a subsystem registered with the kernel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubsystemState {
+    /// Registered but not yet initialized
+    Registered,
+    /// Initialized and running
+    Running,
+    /// Running with reduced capacity according to its health probe
+    Degraded,
+    /// Shut down
+    Stopped,
+    /// Initialization or shutdown failed, or the health probe reports a failure
+    Failed,
+}
+
+/// Lifecycle and health bookkeeping for a registered subsystem
+struct SubsystemStatus {
+    /// Current lifecycle state
+    state: SubsystemState,
+    /// Health observed by the most recent health check
+    health: SubsystemHealth,
+    /// Restart policy and attempts
+    restart: RestartTracker,
+}
+
+/// Subsystem instance owned by the kernel together with its lifecycle state
+struct RegisteredSubsystem {
+    /// The subsystem instance
+    instance: Mutex<Box<dyn Subsystem>>,
+    /// Lifecycle and health bookkeeping
+    status: Mutex<SubsystemStatus>,
+}
+
+impl RegisteredSubsystem {
+    /// Lock the lifecycle and health bookkeeping
+    fn status(&self) -> std::sync::MutexGuard<'_, SubsystemStatus> {
+        self.status.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
 
 /// Kernel state representing the core of the RoyaOS system
 /// 
 /// The Kernel maintains the overall system state and coordinates all subsystems.
 /// It serves as the primary interface between the Roya AGI and the underlying
 /// hardware and software resources.
-#[derive(Debug)]
 pub struct Kernel {
     /// Indicates whether the kernel is currently running
     running: bool,
     /// The version of the kernel
     version: String,
     /// Registered subsystems that the kernel manages
-    subsystems: HashMap<String, bool>,
-    /// Current system load (0.0-1.0)
-    system_load: f64,
+    subsystems: HashMap<String, RegisteredSubsystem>,
+    /// Subsystem names in registration order
+    subsystem_order: Vec<String>,
+    /// Subsystem names in the order they were started
+    startup_order: Vec<String>,
+    /// System load samples and averages
+    load: Mutex<LoadTracker>,
+    /// Number of tool executions in progress
+    running_tool_executions: AtomicUsize,
+    /// Handle to the built-in scheduler subsystem
+    scheduler: Scheduler,
+    /// Number of cognitive tasks the scheduler runs at the same time
+    task_slots: usize,
+    /// Kernel services shared with all registered subsystems
+    context: KernelContext,
+    /// Tracer recording system calls while tracing is enabled
+    tracer: Mutex<Option<SyscallTracer>>,
+    /// Registered agent principals and their quota accounting
+    principals: Mutex<Principals>,
+    /// Jobs scheduled on the timer service
+    timers: Mutex<Timers>,
+}
+
+impl std::fmt::Debug for Kernel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let subsystems: Vec<(&String, SubsystemState)> = self.subsystem_order.iter()
+            .map(|name| (name, self.subsystems[name].status().state))
+            .collect();
+        
+        f.debug_struct("Kernel")
+            .field("running", &self.running)
+            .field("version", &self.version)
+            .field("subsystems", &subsystems)
+            .field("load", &self.load())
+            .finish()
+    }
 }
 
 impl Kernel {
@@ -43,37 +188,106 @@ impl Kernel {
     ///
     /// A new Kernel instance in a non-running state
     pub fn new(version: &str) -> Self {
+        Self::with_task_slots(version, DEFAULT_TASK_SLOTS)
+    }
+    
+    /// Create a new kernel instance with a specific number of scheduler slots
+    ///
+    /// # Arguments
+    ///
+    /// * `version` - The version string for the kernel
+    /// * `task_slots` - Number of cognitive tasks the scheduler runs at the same time
+    ///
+    /// # Returns
+    ///
+    /// A new Kernel instance in a non-running state
+    pub fn with_task_slots(version: &str, task_slots: usize) -> Self {
+        Self::with_context(version, task_slots, KernelContext::new(EventBus::new(EVENT_BUS_CAPACITY)))
+    }
+    
+    /// Create a new kernel instance with a specific clock and handle generator
+    ///
+    /// The kernel and every subsystem registered with it read the time from the
+    /// clock and draw new handles from the generator. A simulated clock and a
+    /// seeded generator make a run repeat exactly: the same system calls return
+    /// the same handles and timers fire at the same virtual times.
+    ///
+    /// # Arguments
+    ///
+    /// * `version` - The version string for the kernel
+    /// * `clock` - Clock to read the time from
+    /// * `handles` - Generator to draw new handles from
+    ///
+    /// # Returns
+    ///
+    /// A new Kernel instance in a non-running state
+    pub fn with_clock(version: &str, clock: Clock, handles: HandleGenerator) -> Self {
+        let context = KernelContext::with_clock(EventBus::new(EVENT_BUS_CAPACITY), clock, handles);
+        Self::with_context(version, DEFAULT_TASK_SLOTS, context)
+    }
+    
+    /// Create a new kernel instance sharing a kernel context with its subsystems
+    fn with_context(version: &str, task_slots: usize, context: KernelContext) -> Self {
         info!("Creating new kernel instance with version {}", version);
-        Self {
+        let scheduler = Scheduler::new(task_slots);
+        
+        let mut kernel = Self {
             running: false,
             version: version.to_string(),
             subsystems: HashMap::new(),
-            system_load: 0.0,
-        }
+            subsystem_order: Vec::new(),
+            startup_order: Vec::new(),
+            load: Mutex::new(LoadTracker::new(context.clock.clone())),
+            running_tool_executions: AtomicUsize::new(0),
+            scheduler: scheduler.clone(),
+            task_slots,
+            timers: Mutex::new(Timers::new(context.handles.clone())),
+            context,
+            tracer: Mutex::new(None),
+            principals: Mutex::new(Principals::default()),
+        };
+        
+        kernel.register_subsystem(Box::new(scheduler))
+            .expect("A new kernel has no registered subsystems");
+        
+        kernel
     }
     
     /// Initialize the kernel and all its subsystems
     ///
     /// This method prepares the kernel for operation by:
     /// 1. Setting up core kernel data structures
-    /// 2. Initializing all required subsystems
+    /// 2. Initializing all required subsystems in dependency order
     /// 3. Establishing communication channels
     /// 4. Preparing the execution environment
     ///
+    /// If a subsystem fails to initialize, the subsystems that were already
+    /// started are shut down again in reverse order.
+    ///
     /// # Returns
     ///
-    /// `Ok(())` if initialization is successful, or an error message
-    pub fn initialize(&mut self) -> Result<(), String> {
+    /// `Ok(())` if initialization is successful, or the error that stopped it
+    pub fn initialize(&mut self) -> Result<(), KernelError> {
         info!("Initializing kernel version {}", self.version);
         
-        // Register core subsystems
-        self.register_subsystem("memory")?;
-        self.register_subsystem("tools")?;
-        self.register_subsystem("security")?;
-        self.register_subsystem("interface")?;
+        if self.running {
+            return Err(KernelError::AlreadyRunning);
+        }
+        
+        // Initialize subsystems after the subsystems they depend on
+        let order = self.resolve_startup_order()?;
+        debug!("Subsystem startup order: {:?}", order);
         
-        // Initialize subsystems
-        // TODO: Initialize actual subsystem instances
+        for name in order {
+            if let Err(e) = self.initialize_subsystem(&name) {
+                error!("Rolling back {} started subsystems", self.startup_order.len());
+                if let Err(rollback_error) = self.shutdown_started_subsystems() {
+                    error!("Rollback incomplete: {}", rollback_error);
+                }
+                return Err(e);
+            }
+            self.startup_order.push(name);
+        }
         
         self.running = true;
         info!("Kernel initialization complete");
@@ -91,19 +305,20 @@ impl Kernel {
     ///
     /// # Returns
     ///
-    /// `Ok(())` if shutdown is successful, or an error message
-    pub fn shutdown(&mut self) -> Result<(), String> {
+    /// `Ok(())` if shutdown is successful, or `KernelError::ShutdownFailed`
+    pub fn shutdown(&mut self) -> Result<(), KernelError> {
         info!("Shutting down kernel");
         
+        // Stop cognitive tasks before the subsystems they use go away
+        self.scheduler.stop_all();
+        
         // Shutdown subsystems in reverse order of initialization
-        for subsystem in ["interface", "security", "tools", "memory"].iter() {
-            self.shutdown_subsystem(subsystem)?;
-        }
+        let result = self.shutdown_started_subsystems();
         
         self.running = false;
         info!("Kernel shutdown complete");
         
-        Ok(())
+        result
     }
     
     /// Check if the kernel is currently running
@@ -126,9 +341,32 @@ impl Kernel {
     
     /// Process a system call from the Roya AGI or other components
     ///
-    /// System calls are the primary mechanism for the AGI to interact with
-    /// the operating system. This method routes the call to the appropriate
-    /// subsystem and returns the result.
+    /// This is the string form of `execute_syscall`. The call is parsed into a
+    /// typed `Syscall` and its result is rendered back to a string.
+    ///
+    /// Supported system calls:
+    /// - `memory_alloc <size_bytes> [purpose] [category]` returns the memory handle
+    /// - `memory_free <handle>` returns the released memory handle
+    /// - `memory_put <handle> <value_json>` stores the value and returns the memory handle
+    /// - `memory_get <handle>` returns the stored value as JSON
+    /// - `memory_embed <handle> <vector_json>` sets the embedding, or removes it for `null`,
+    ///   and returns the memory handle
+    /// - `memory_search <vector_json> [k] [category]` returns the `SimilarMemory` matches as JSON
+    /// - `memory_consolidate` returns the handles promoted to long-term memory as JSON
+    /// - `memory_expire <handle> <ttl|none>` sets the time to live, such as `30s` or `5m`,
+    ///   or removes it for `none`, and returns the memory handle
+    /// - `memory_strength <handle>` returns the retention strength from 0 to 1
+    /// - `tool_execute <tool> <capability> [params_json]` returns the `ToolResult` as JSON,
+    ///   where `tool` is either a tool handle or a tool identifier
+    /// - `security_check <resource_type> <operation> <resource>` returns "allowed" or "denied"
+    /// - `security_grant <resource_type> <operation> <resource>` returns "granted", or
+    ///   "unchanged" if the permission was already granted
+    /// - `security_revoke <resource_type> <operation> <resource>` returns "revoked", or
+    ///   "unchanged" if the permission was not granted
+    /// - `task_cancel <task_id>` returns the ID of the cancelled task
+    ///
+    /// While tracing is enabled, the call and its outcome are recorded in the
+    /// trace file.
     ///
     /// # Arguments
     ///
@@ -137,121 +375,2021 @@ impl Kernel {
     ///
     /// # Returns
     ///
-    /// The result of the system call, or an error message
-    pub fn process_syscall(&self, syscall: &str, args: &[&str]) -> Result<String, String> {
-        debug!("Processing syscall: {} with args: {:?}", syscall, args);
+    /// The result of the system call, or the reason it could not be parsed or completed
+    pub fn process_syscall(&self, syscall: &str, args: &[&str]) -> Result<String, SyscallError> {
+        self.process_traced_syscall(None, None, syscall, args)
+    }
+    
+    /// Process a system call received on an interface session
+    ///
+    /// Behaves like `process_syscall`; the session is recorded in the trace.
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The session the call was received on
+    /// * `syscall` - The name of the system call to execute
+    /// * `args` - Arguments for the system call
+    ///
+    /// # Returns
+    ///
+    /// The result of the system call, or the reason it could not be parsed or completed
+    pub fn process_session_syscall(&self, session: Uuid, syscall: &str, args: &[&str]) -> Result<String, SyscallError> {
+        self.process_traced_syscall(Some(session), None, syscall, args)
+    }
+    
+    /// Process a system call on behalf of an agent
+    ///
+    /// Behaves like `process_syscall`, except that the call is charged to the
+    /// agent's principal and rejected with `SyscallError::Quota` if the
+    /// principal is not registered or the call would exceed its quota.
+    ///
+    /// # Arguments
+    ///
+    /// * `principal` - Agent ID of the calling principal
+    /// * `session` - The session the call was received on, if any
+    /// * `syscall` - The name of the system call to execute
+    /// * `args` - Arguments for the system call
+    ///
+    /// # Returns
+    ///
+    /// The result of the system call, or the reason it was rejected or could not be completed
+    pub fn process_principal_syscall(
+        &self,
+        principal: &str,
+        session: Option<Uuid>,
+        syscall: &str,
+        args: &[&str],
+    ) -> Result<String, SyscallError> {
+        self.process_traced_syscall(session, Some(principal), syscall, args)
+    }
+    
+    /// Process a batch of system calls as a unit
+    ///
+    /// The steps run in order, each like a call to `process_syscall`. If a step
+    /// fails, the remaining steps are skipped and the steps that already ran are
+    /// undone in reverse order by compensating system calls, which are traced
+    /// like the steps themselves. An argument of the form `$<n>` is replaced by
+    /// the result of step `n`, counting from 0.
+    ///
+    /// # Arguments
+    ///
+    /// * `steps` - The system calls to run
+    ///
+    /// # Returns
+    ///
+    /// The outcome of every step, or `SyscallError::Batch` if a step refers to
+    /// a later step, in which case no step runs
+    pub fn process_batch(&self, steps: &[BatchStep]) -> Result<BatchResult, SyscallError> {
+        self.process_traced_batch(None, None, steps)
+    }
+    
+    /// Process a batch of system calls received on an interface session
+    ///
+    /// Behaves like `process_batch`; the session is recorded in the trace.
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The session the batch was received on
+    /// * `steps` - The system calls to run
+    ///
+    /// # Returns
+    ///
+    /// The outcome of every step, or the reason the batch was rejected
+    pub fn process_session_batch(&self, session: Uuid, steps: &[BatchStep]) -> Result<BatchResult, SyscallError> {
+        self.process_traced_batch(Some(session), None, steps)
+    }
+    
+    /// Process a batch of system calls on behalf of an agent
+    ///
+    /// Behaves like `process_batch`, except that every step is charged to the
+    /// agent's principal like a call to `process_principal_syscall`. A step
+    /// rejected by the quota fails and rolls the batch back. Compensating calls
+    /// are not charged and are not checked against the security policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `principal` - Agent ID of the calling principal
+    /// * `session` - The session the batch was received on, if any
+    /// * `steps` - The system calls to run
+    ///
+    /// # Returns
+    ///
+    /// The outcome of every step, or the reason the batch was rejected
+    pub fn process_principal_batch(
+        &self,
+        principal: &str,
+        session: Option<Uuid>,
+        steps: &[BatchStep],
+    ) -> Result<BatchResult, SyscallError> {
+        self.process_traced_batch(session, Some(principal), steps)
+    }
+    
+    /// Execute a typed system call from the Roya AGI or other components
+    ///
+    /// System calls are the primary mechanism for the AGI to interact with
+    /// the operating system. Every call is first checked against the security
+    /// subsystem, then routed to the appropriate subsystem.
+    ///
+    /// # Arguments
+    ///
+    /// * `syscall` - The system call to execute
+    ///
+    /// # Returns
+    ///
+    /// The result of the system call, or the reason it could not be completed
+    pub fn execute_syscall(&self, syscall: Syscall) -> Result<SyscallResult, SyscallError> {
+        self.execute_principal_syscall(None, syscall)
+    }
+    
+    /// Register a subsystem with the kernel
+    ///
+    /// The kernel takes ownership of the subsystem and drives its lifecycle
+    /// from then on. Subsystems are registered under the name they report and
+    /// receive the kernel services.
+    ///
+    /// # Arguments
+    ///
+    /// * `subsystem` - The subsystem instance to register
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if registration is successful, or `KernelError::SubsystemAlreadyRegistered`
+    pub fn register_subsystem(&mut self, mut subsystem: Box<dyn Subsystem>) -> Result<(), KernelError> {
+        let name = subsystem.name().to_string();
+        info!("Registering subsystem: {}", name);
         
-        // Validate kernel state
-        if !self.running {
-            return Err("Kernel is not running".to_string());
+        if self.subsystems.contains_key(&name) {
+            let error = KernelError::SubsystemAlreadyRegistered(name);
+            error!("{}", error);
+            return Err(error);
         }
         
-        // Route syscall to appropriate subsystem
-        match syscall {
-            "memory_alloc" => {
-                if args.len() < 1 {
-                    return Err("memory_alloc requires at least 1 argument".to_string());
-                }
-                self.handle_memory_syscall("alloc", args)
-            },
-            "memory_free" => {
-                if args.len() < 1 {
-                    return Err("memory_free requires at least 1 argument".to_string());
-                }
-                self.handle_memory_syscall("free", args)
-            },
-            "tool_execute" => {
-                if args.len() < 2 {
-                    return Err("tool_execute requires at least 2 arguments".to_string());
-                }
-                self.handle_tool_syscall("execute", args)
-            },
-            "security_check" => {
-                if args.len() < 3 {
-                    return Err("security_check requires 3 arguments".to_string());
+        subsystem.attach(&self.context);
+        self.subsystems.insert(name.clone(), RegisteredSubsystem {
+            instance: Mutex::new(subsystem),
+            status: Mutex::new(SubsystemStatus {
+                state: SubsystemState::Registered,
+                health: SubsystemHealth::Healthy,
+                restart: RestartTracker::default(),
+            }),
+        });
+        self.subsystem_order.push(name);
+        
+        Ok(())
+    }
+    
+    /// Check if a subsystem is registered with the kernel
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the subsystem
+    ///
+    /// # Returns
+    ///
+    /// `true` if the subsystem is registered, `false` otherwise
+    pub fn has_subsystem(&self, name: &str) -> bool {
+        self.subsystems.contains_key(name)
+    }
+    
+    /// Get the names of all registered subsystems
+    ///
+    /// # Returns
+    ///
+    /// Subsystem names in registration order
+    pub fn subsystem_names(&self) -> Vec<String> {
+        self.subsystem_order.clone()
+    }
+    
+    /// Get the order in which the running subsystems were started
+    ///
+    /// # Returns
+    ///
+    /// Subsystem names in startup order, empty if the kernel is not running
+    pub fn startup_order(&self) -> &[String] {
+        &self.startup_order
+    }
+    
+    /// Get the lifecycle state of a subsystem
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the subsystem
+    ///
+    /// # Returns
+    ///
+    /// The state of the subsystem, or `None` if it is not registered
+    pub fn subsystem_state(&self, name: &str) -> Option<SubsystemState> {
+        self.subsystems.get(name).map(|subsystem| subsystem.status().state)
+    }
+    
+    /// Probe the health of a subsystem
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the subsystem
+    ///
+    /// # Returns
+    ///
+    /// The health reported by the subsystem, or an error if it is not registered
+    pub fn subsystem_health(&self, name: &str) -> Result<SubsystemHealth, KernelError> {
+        let subsystem = self.get_subsystem(name)?;
+        let instance = subsystem.instance.lock()
+            .map_err(|_| KernelError::SubsystemPoisoned(name.to_string()))?;
+        Ok(instance.health())
+    }
+    
+    /// Set the policy the watchdog applies when a subsystem is unhealthy
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the subsystem
+    /// * `policy` - The restart policy
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the subsystem is registered, or `KernelError::SubsystemNotRegistered`
+    pub fn set_restart_policy(&self, name: &str, policy: RestartPolicy) -> Result<(), KernelError> {
+        let subsystem = self.get_subsystem(name)?;
+        info!("Setting restart policy of subsystem {} to {:?}", name, policy);
+        
+        let mut status = subsystem.status();
+        status.restart.policy = policy;
+        status.restart.reset();
+        
+        Ok(())
+    }
+    
+    /// Get the restart policy of a subsystem
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the subsystem
+    ///
+    /// # Returns
+    ///
+    /// The restart policy, or `None` if the subsystem is not registered
+    pub fn restart_policy(&self, name: &str) -> Option<RestartPolicy> {
+        self.subsystems.get(name).map(|subsystem| subsystem.status().restart.policy)
+    }
+    
+    /// Probe the health of all running subsystems
+    ///
+    /// This is the body of the watchdog. Every started subsystem is probed; its
+    /// state is set to Running, Degraded or Failed to match the probe, and a
+    /// `SubsystemHealthChanged` event is published when the health changes. A
+    /// subsystem whose lock stays taken for `WEDGED_CHECK_LIMIT` consecutive
+    /// checks is considered failed. Unhealthy subsystems are then restarted as
+    /// their restart policy allows.
+    ///
+    /// # Returns
+    ///
+    /// The health of each started subsystem in startup order
+    pub fn check_health(&self) -> Vec<(String, SubsystemHealth)> {
+        let mut report = Vec::with_capacity(self.startup_order.len());
+        
+        for name in &self.startup_order {
+            let subsystem = &self.subsystems[name];
+            
+            // A subsystem that keeps its lock taken cannot be probed; it may be wedged
+            let probed = match subsystem.instance.try_lock() {
+                Ok(instance) => Some(instance.health()),
+                Err(TryLockError::WouldBlock) => None,
+                Err(TryLockError::Poisoned(_)) => Some(SubsystemHealth::Failed("Subsystem lock is poisoned".to_string())),
+            };
+            
+            let mut status = subsystem.status();
+            let health = match &probed {
+                Some(health) => {
+                    status.restart.busy_checks = 0;
+                    health.clone()
+                },
+                None => {
+                    status.restart.busy_checks += 1;
+                    if status.restart.busy_checks >= WEDGED_CHECK_LIMIT {
+                        SubsystemHealth::Failed(format!("Not responding for {} health checks", status.restart.busy_checks))
+                    } else {
+                        status.health.clone()
+                    }
+                },
+            };
+            
+            if health != status.health {
+                match &health {
+                    SubsystemHealth::Healthy => info!("Subsystem {} is healthy", name),
+                    SubsystemHealth::Degraded(reason) => warn!("Subsystem {} is degraded: {}", name, reason),
+                    SubsystemHealth::Failed(reason) => error!("Subsystem {} has failed: {}", name, reason),
                 }
-                self.handle_security_syscall("check", args)
-            },
-            _ => Err(format!("Unknown syscall: {}", syscall))
+                self.context.events.publish(KernelEvent::SubsystemHealthChanged {
+                    subsystem: name.clone(),
+                    health: health.clone(),
+                });
+                status.health = health.clone();
+            }
+            
+            status.state = match health {
+                SubsystemHealth::Healthy => SubsystemState::Running,
+                SubsystemHealth::Degraded(_) => SubsystemState::Degraded,
+                SubsystemHealth::Failed(_) => SubsystemState::Failed,
+            };
+            
+            let now = self.context.clock.now();
+            if health.is_healthy() {
+                status.restart.reset();
+            } else if probed.is_none() {
+                // Restarting needs the lock the subsystem is holding
+                debug!("Subsystem {} is locked, not restarting", name);
+            } else if status.restart.should_restart(&health, now) {
+                let attempt = status.restart.record_attempt(now);
+                drop(status);
+                
+                warn!("Restarting subsystem {} (attempt {})", name, attempt);
+                let success = self.restart_subsystem(name).is_ok();
+                self.context.events.publish(KernelEvent::SubsystemRestarted {
+                    subsystem: name.clone(),
+                    attempt,
+                    success,
+                });
+            }
+            
+            report.push((name.clone(), health));
         }
+        
+        report
     }
     
-    /// Register a subsystem with the kernel
+    /// Restart a running subsystem by shutting it down and initializing it again
     ///
     /// # Arguments
     ///
-    /// * `name` - The name of the subsystem to register
+    /// * `name` - The name of the subsystem
     ///
     /// # Returns
     ///
-    /// `Ok(())` if registration is successful, or an error message
-    fn register_subsystem(&mut self, name: &str) -> Result<(), String> {
-        info!("Registering subsystem: {}", name);
-        self.subsystems.insert(name.to_string(), true);
-        Ok(())
+    /// `Ok(())` if the subsystem initialized again, or the error that stopped it
+    pub fn restart_subsystem(&self, name: &str) -> Result<(), KernelError> {
+        if !self.startup_order.iter().any(|started| started == name) {
+            let error = KernelError::SubsystemNotRunning(name.to_string());
+            error!("{}", error);
+            return Err(error);
+        }
+        
+        info!("Restarting subsystem: {}", name);
+        if let Err(e) = self.shutdown_subsystem(name) {
+            warn!("Continuing restart of {} after failed shutdown: {}", name, e);
+        }
+        self.initialize_subsystem(name)
     }
     
-    /// Shutdown a specific subsystem
+    /// Start the watchdog task on the kernel scheduler
+    ///
+    /// The watchdog calls `check_health` every `interval` until the kernel is
+    /// dropped or the task is cancelled.
     ///
     /// # Arguments
     ///
-    /// * `name` - The name of the subsystem to shutdown
+    /// * `interval` - Time between two health checks
     ///
     /// # Returns
     ///
-    /// `Ok(())` if shutdown is successful, or an error message
-    fn shutdown_subsystem(&mut self, name: &str) -> Result<(), String> {
-        info!("Shutting down subsystem: {}", name);
-        self.subsystems.insert(name.to_string(), false);
-        Ok(())
+    /// The ID of the watchdog task, or the error returned by the scheduler
+    pub fn start_watchdog(self: &Arc<Self>, interval: Duration) -> Result<TaskId, KernelError> {
+        info!("Starting watchdog with {:?} interval", interval);
+        
+        // Hold the kernel weakly so the task does not keep it alive
+        let kernel = Arc::downgrade(self);
+        self.scheduler.spawn("watchdog", MemoryCategory::System, move |ctx| async move {
+            while ctx.sleep(interval).await.is_ok() {
+                match kernel.upgrade() {
+                    Some(kernel) => {
+                        kernel.check_health();
+                    },
+                    None => break,
+                }
+            }
+        }).map_err(KernelError::from)
     }
     
-    /// Handle memory-related system calls
+    /// Start the load sampler task on the kernel scheduler
+    ///
+    /// The sampler calls `sample_load` every `interval` until the kernel is
+    /// dropped or the task is cancelled.
     ///
     /// # Arguments
     ///
-    /// * `operation` - The specific memory operation
-    /// * `args` - Arguments for the operation
+    /// * `interval` - Time between two load samples
     ///
     /// # Returns
     ///
-    /// The result of the operation, or an error message
-    fn handle_memory_syscall(&self, operation: &str, args: &[&str]) -> Result<String, String> {
-        debug!("Handling memory syscall: {} with args: {:?}", operation, args);
-        // TODO: Implement actual memory syscall handling
-        Ok(format!("Memory operation '{}' processed", operation))
+    /// The ID of the sampler task, or the error returned by the scheduler
+    pub fn start_load_sampler(self: &Arc<Self>, interval: Duration) -> Result<TaskId, KernelError> {
+        info!("Starting load sampler with {:?} interval", interval);
+        
+        let kernel = Arc::downgrade(self);
+        self.scheduler.spawn("load_sampler", MemoryCategory::Background, move |ctx| async move {
+            while ctx.sleep(interval).await.is_ok() {
+                match kernel.upgrade() {
+                    Some(kernel) => {
+                        kernel.sample_load();
+                    },
+                    None => break,
+                }
+            }
+        }).map_err(KernelError::from)
     }
     
-    /// Handle tool-related system calls
+    /// Start the timer task on the kernel scheduler
+    ///
+    /// The timer task calls `run_due_jobs` every `tick` until the kernel is
+    /// dropped or the task is cancelled.
+    ///
+    /// # Arguments
+    ///
+    /// * `tick` - Time between two checks for due jobs
+    ///
+    /// # Returns
+    ///
+    /// The ID of the timer task, or the error returned by the scheduler
+    pub fn start_timers(self: &Arc<Self>, tick: Duration) -> Result<TaskId, KernelError> {
+        info!("Starting timer service with {:?} tick", tick);
+        
+        let kernel = Arc::downgrade(self);
+        self.scheduler.spawn("timers", MemoryCategory::System, move |ctx| async move {
+            while ctx.sleep(tick).await.is_ok() {
+                match kernel.upgrade() {
+                    Some(kernel) => {
+                        kernel.run_due_jobs();
+                    },
+                    None => break,
+                }
+            }
+        }).map_err(KernelError::from)
+    }
+    
+    /// Run the jobs whose time has come
+    ///
+    /// Subsystem jobs are skipped while their subsystem is not running. Failed
+    /// runs are logged and recorded with the job.
+    ///
+    /// # Returns
+    ///
+    /// Number of jobs that were due
+    pub fn run_due_jobs(&self) -> usize {
+        let due = self.lock_timers().take_due(self.context.clock.utc_now());
+        
+        for (id, action) in &due {
+            let result = match action {
+                JobAction::Subsystem { subsystem, job } => self.run_subsystem_job(subsystem, job).map_err(|e| e.to_string()),
+                JobAction::Syscall { principal, syscall } => self.execute_principal_syscall(principal.as_deref(), syscall.clone())
+                    .map(|_| ())
+                    .map_err(|e| e.to_string()),
+            };
+            
+            if let Err(e) = &result {
+                warn!("Job {} failed: {}", id, e);
+            }
+            self.lock_timers().finish(*id, result);
+        }
+        
+        due.len()
+    }
+    
+    /// Schedule a system call on the timer service
+    ///
+    /// The call runs with the authority of the principal and is charged to its
+    /// quota each time it runs.
     ///
     /// # Arguments
     ///
-    /// * `operation` - The specific tool operation
-    /// * `args` - Arguments for the operation
+    /// * `principal` - Agent ID of the principal that owns the job, if any
+    /// * `schedule` - When the call runs
+    /// * `syscall` - The system call
     ///
     /// # Returns
     ///
-    /// The result of the operation, or an error message
-    fn handle_tool_syscall(&self, operation: &str, args: &[&str]) -> Result<String, String> {
-        debug!("Handling tool syscall: {} with args: {:?}", operation, args);
-        // TODO: Implement actual tool syscall handling
-        Ok(format!("Tool operation '{}' processed", operation))
+    /// The ID of the job, or an error if the principal is unknown or the schedule is not valid
+    pub fn schedule_syscall(&self, principal: Option<&str>, schedule: Schedule, syscall: Syscall) -> Result<JobId, KernelError> {
+        if let Some(principal) = principal {
+            self.lock_principals().quota(principal)?;
+        }
+        
+        let owner = principal.unwrap_or(KERNEL_JOB_OWNER);
+        let name = syscall.name();
+        let description = schedule.to_string();
+        let action = JobAction::Syscall { principal: principal.map(str::to_string), syscall };
+        let job = self.lock_timers().schedule(owner, name, schedule, action, self.context.clock.utc_now())?;
+        
+        info!("Scheduled job {} for {} to run {} {}", job, owner, name, description);
+        Ok(job)
     }
     
-    /// Handle security-related system calls
+    /// Get the jobs scheduled on the timer service
+    ///
+    /// # Returns
+    ///
+    /// The jobs, ordered by their next run
+    pub fn jobs(&self) -> Vec<JobInfo> {
+        self.lock_timers().list()
+    }
+    
+    /// Cancel a scheduled job
     ///
     /// # Arguments
     ///
-    /// * `operation` - The specific security operation
-    /// * `args` - Arguments for the operation
+    /// * `job` - ID of the job
     ///
     /// # Returns
     ///
-    /// The result of the operation, or an error message
-    fn handle_security_syscall(&self, operation: &str, args: &[&str]) -> Result<String, String> {
-        debug!("Handling security syscall: {} with args: {:?}", operation, args);
-        // TODO: Implement actual security syscall handling
-        Ok(format!("Security operation '{}' processed", operation))
+    /// The cancelled job, or `TimerError::UnknownJob`
+    pub fn cancel_job(&self, job: JobId) -> Result<JobInfo, KernelError> {
+        let cancelled = self.lock_timers().cancel(job, None)?;
+        info!("Cancelled job {} ({})", job, cancelled.name);
+        Ok(cancelled)
+    }
+    
+    /// Let in-flight work finish before the kernel is shut down
+    ///
+    /// The scheduler stops accepting tasks and asks every task to stop at its
+    /// next preemption point. Once all tasks and tool executions have finished,
+    /// or the deadline has passed on the kernel clock, the remaining tasks are
+    /// aborted.
+    ///
+    /// # Arguments
+    ///
+    /// * `deadline` - Longest time to wait for in-flight work
+    ///
+    /// # Returns
+    ///
+    /// `true` if all work finished before the deadline, `false` if tasks had to be aborted
+    pub async fn drain(&self, deadline: Duration) -> bool {
+        info!("Draining kernel work with {:?} deadline", deadline);
+        let started = self.context.clock.now();
+        let tasks = self.scheduler.cancel_all();
+        
+        let drained = loop {
+            let running_tasks = tasks.iter()
+                .filter(|id| self.scheduler.task_info(**id)
+                    .is_ok_and(|info| !matches!(info.state, TaskState::Completed | TaskState::Cancelled)))
+                .count();
+            let running_tools = self.running_tool_executions.load(Ordering::SeqCst);
+            
+            if running_tasks == 0 && running_tools == 0 {
+                break true;
+            }
+            
+            if self.context.clock.elapsed(started) >= deadline {
+                warn!("Drain deadline passed with {} tasks and {} tool executions in flight",
+                      running_tasks, running_tools);
+                break false;
+            }
+            
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        };
+        
+        self.scheduler.stop_all();
+        for id in tasks {
+            let _ = self.scheduler.join(id).await;
+        }
+        
+        drained
+    }
+    
+    /// Capture the state of the running subsystems
+    ///
+    /// Every started subsystem is asked for its state in startup order;
+    /// subsystems without state to save are left out of the snapshot.
+    ///
+    /// # Returns
+    ///
+    /// The snapshot, or an error if the kernel is not running or a subsystem
+    /// failed to save its state
+    pub fn capture_snapshot(&self) -> Result<Snapshot, KernelError> {
+        if !self.running {
+            return Err(KernelError::NotRunning);
+        }
+        
+        let mut snapshot = Snapshot::new(&self.version);
+        for name in &self.startup_order {
+            let instance = self.subsystems[name].instance.lock()
+                .map_err(|_| KernelError::SubsystemPoisoned(name.clone()))?;
+            
+            let state = instance.snapshot().map_err(|source| {
+                let error = KernelError::SubsystemFailed { subsystem: name.clone(), action: "snapshot", source };
+                error!("{}", error);
+                error
+            })?;
+            if let Some(state) = state {
+                snapshot.subsystems.insert(name.clone(), state);
+            }
+        }
+        
+        Ok(snapshot)
+    }
+    
+    /// Save the state of the running subsystems to the data directory
+    ///
+    /// # Arguments
+    ///
+    /// * `data_dir` - The data directory; snapshots are written to its
+    ///   `snapshots` directory
+    ///
+    /// # Returns
+    ///
+    /// The path of the written snapshot, or the error that stopped it
+    pub fn snapshot(&self, data_dir: &Path) -> Result<PathBuf, KernelError> {
+        info!("Saving kernel snapshot to {:?}", data_dir);
+        self.capture_snapshot()?.write(data_dir)
+    }
+    
+    /// Restore the state of the running subsystems from a snapshot
+    ///
+    /// Subsystems are restored in startup order. If a subsystem rejects its
+    /// saved state, the subsystems restored before it are returned to the
+    /// state they had before the call. Saved state of subsystems that are not
+    /// running is skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `snapshot` - The snapshot to restore
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if all saved state was restored, or the error that stopped it
+    pub fn restore_snapshot(&self, snapshot: &Snapshot) -> Result<(), KernelError> {
+        if snapshot.kernel_version != self.version {
+            info!("Restoring snapshot taken by kernel version {}", snapshot.kernel_version);
+        }
+        
+        for name in snapshot.subsystems.keys() {
+            if !self.startup_order.contains(name) {
+                warn!("Skipping saved state of subsystem {}, it is not running", name);
+            }
+        }
+        
+        // Keep the current state to roll back to
+        let previous = self.capture_snapshot()?;
+        let mut restored: Vec<&String> = Vec::new();
+        
+        for name in &self.startup_order {
+            let state = match snapshot.subsystems.get(name) {
+                Some(state) => state.clone(),
+                None => continue,
+            };
+            
+            if let Err(e) = self.restore_subsystem(name, state) {
+                error!("Rolling back {} restored subsystems", restored.len());
+                for name in restored.into_iter().rev() {
+                    if let Some(state) = previous.subsystems.get(name) {
+                        if let Err(rollback_error) = self.restore_subsystem(name, state.clone()) {
+                            error!("Rollback incomplete: {}", rollback_error);
+                        }
+                    }
+                }
+                return Err(e);
+            }
+            restored.push(name);
+        }
+        
+        info!("Restored the state of {} subsystems", restored.len());
+        Ok(())
+    }
+    
+    /// Restore the most recent snapshot in the data directory
+    ///
+    /// # Arguments
+    ///
+    /// * `data_dir` - The data directory the snapshots were saved to
+    ///
+    /// # Returns
+    ///
+    /// The path of the restored snapshot, `None` if there is no snapshot, or
+    /// the error that stopped the restore
+    pub fn restore(&self, data_dir: &Path) -> Result<Option<PathBuf>, KernelError> {
+        let path = match Snapshot::latest(data_dir)? {
+            Some(path) => path,
+            None => {
+                info!("No snapshot found in {:?}", data_dir);
+                return Ok(None);
+            }
+        };
+        
+        info!("Restoring kernel snapshot {:?}", path);
+        let snapshot = Snapshot::read(&path)?;
+        self.restore_snapshot(&snapshot)?;
+        
+        Ok(Some(path))
+    }
+    
+    /// Start recording system calls to a new trace file
+    ///
+    /// # Arguments
+    ///
+    /// * `data_dir` - The data directory; traces are written to its `traces` directory
+    ///
+    /// # Returns
+    ///
+    /// The path of the trace file, or `KernelError::TraceIo` if it cannot be created;
+    /// if tracing is already enabled, the path of the current trace file
+    pub fn start_trace(&self, data_dir: &Path) -> Result<PathBuf, KernelError> {
+        let mut tracer = self.lock_tracer();
+        if let Some(tracer) = tracer.as_ref() {
+            return Ok(tracer.path().to_path_buf());
+        }
+        
+        let tools = if self.has_subsystem(TOOLS_SUBSYSTEM) {
+            self.with_subsystem(TOOLS_SUBSYSTEM, |tools: &mut ToolManager| tools.list_tools())?
+                .into_iter()
+                .map(|(handle, metadata)| (handle, metadata.id))
+                .collect()
+        } else {
+            Default::default()
+        };
+        let header = TraceHeader {
+            format_version: TRACE_FORMAT_VERSION,
+            kernel_version: self.version.clone(),
+            started_at: snapshot::unix_millis(),
+            tools,
+        };
+        
+        let created = SyscallTracer::create(data_dir, &header)?;
+        let path = created.path().to_path_buf();
+        *tracer = Some(created);
+        
+        Ok(path)
+    }
+    
+    /// Stop recording system calls
+    ///
+    /// # Returns
+    ///
+    /// The path of the closed trace file, or `None` if tracing was not enabled
+    pub fn stop_trace(&self) -> Option<PathBuf> {
+        let tracer = self.lock_tracer().take()?;
+        info!("Stopped tracing system calls to {:?}", tracer.path());
+        Some(tracer.path().to_path_buf())
+    }
+    
+    /// Get the trace file system calls are recorded to
+    ///
+    /// # Returns
+    ///
+    /// The path of the trace file, or `None` if tracing is not enabled
+    pub fn trace_path(&self) -> Option<PathBuf> {
+        self.lock_tracer().as_ref().map(|tracer| tracer.path().to_path_buf())
+    }
+    
+    /// Replay a trace into this kernel and compare the outcomes
+    ///
+    /// Each traced system call is processed again in order, with the handles
+    /// returned earlier in the trace replaced by those returned during the
+    /// replay. Tools are matched by the identifiers recorded when tracing
+    /// started, and agents that are not registered are registered under the
+    /// default quota. The kernel should be freshly started with the
+    /// configuration of the traced system.
+    ///
+    /// # Arguments
+    ///
+    /// * `trace` - The trace to replay
+    ///
+    /// # Returns
+    ///
+    /// The number of replayed calls and every call whose outcome diverged
+    pub fn replay_trace(&self, trace: &Trace) -> Result<ReplayReport, KernelError> {
+        if trace.header.kernel_version != self.version {
+            warn!("Replaying trace written by kernel version {} on version {}", trace.header.kernel_version, self.version);
+        }
+        
+        let mut handles = HandleMap::default();
+        if self.has_subsystem(TOOLS_SUBSYSTEM) {
+            self.with_subsystem(TOOLS_SUBSYSTEM, |tools: &mut ToolManager| {
+                for (handle, id) in &trace.header.tools {
+                    match tools.find_tool(id) {
+                        Some(replayed) => handles.insert(&handle.to_string(), &replayed.to_string()),
+                        None => warn!("Traced tool {} is not registered", id),
+                    }
+                }
+            })?;
+        }
+        
+        let mut report = ReplayReport::default();
+        for record in &trace.records {
+            let args = handles.translate(&record.args);
+            let arg_refs: Vec<&str> = args.iter().map(String::as_str).collect();
+            
+            if let Some(principal) = &record.principal {
+                self.register_principal(principal);
+            }
+            let result = self.process_traced_syscall(record.session, record.principal.as_deref(), &record.syscall, &arg_refs);
+            let actual = TraceOutcome::of(&result);
+            if !handles.matches(&record.outcome, &actual) {
+                let divergence = Divergence {
+                    seq: record.seq,
+                    syscall: record.syscall.clone(),
+                    args,
+                    expected: record.outcome.clone(),
+                    actual,
+                };
+                warn!("Replay diverged: {}", divergence);
+                report.divergences.push(divergence);
+            }
+            report.replayed += 1;
+        }
+        
+        info!("Replayed {} system calls, {} diverged", report.replayed, report.divergences.len());
+        Ok(report)
+    }
+    
+    /// Set the quota of principals without a quota of their own
+    ///
+    /// # Arguments
+    ///
+    /// * `quota` - The new default quota
+    pub fn set_default_quota(&self, quota: Quota) {
+        info!("Setting default agent quota to {:?}", quota);
+        self.lock_principals().set_default_quota(quota);
+    }
+    
+    /// Get the quota of principals without a quota of their own
+    ///
+    /// # Returns
+    ///
+    /// The default quota
+    pub fn default_quota(&self) -> Quota {
+        self.lock_principals().default_quota().clone()
+    }
+    
+    /// Register an agent as a principal under the default quota
+    ///
+    /// Registering an agent that is already registered keeps its quota and usage.
+    ///
+    /// # Arguments
+    ///
+    /// * `principal` - Agent ID of the principal
+    ///
+    /// # Returns
+    ///
+    /// `true` if the principal is new, `false` if it was already registered
+    pub fn register_principal(&self, principal: &str) -> bool {
+        let registered = self.lock_principals().register(principal);
+        if registered {
+            info!("Registered principal {}", principal);
+        }
+        registered
+    }
+    
+    /// Remove a principal and its usage accounting
+    ///
+    /// Memory the principal holds stays allocated but is no longer charged to it.
+    /// The jobs the principal scheduled are cancelled.
+    ///
+    /// # Arguments
+    ///
+    /// * `principal` - Agent ID of the principal
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the principal was removed, or `QuotaError::UnknownPrincipal`
+    pub fn remove_principal(&self, principal: &str) -> Result<(), KernelError> {
+        self.lock_principals().remove(principal)?;
+        let cancelled = self.lock_timers().cancel_owned(principal);
+        info!("Removed principal {} and cancelled its {} jobs", principal, cancelled);
+        Ok(())
+    }
+    
+    /// Get the agent IDs of the registered principals
+    ///
+    /// # Returns
+    ///
+    /// The agent IDs in sorted order
+    pub fn principals(&self) -> Vec<String> {
+        self.lock_principals().ids()
+    }
+    
+    /// Set the quota of a principal
+    ///
+    /// # Arguments
+    ///
+    /// * `principal` - Agent ID of the principal
+    /// * `quota` - The quota, or `None` to follow the default quota
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the quota was set, or `QuotaError::UnknownPrincipal`
+    pub fn set_principal_quota(&self, principal: &str, quota: Option<Quota>) -> Result<(), KernelError> {
+        self.lock_principals().set_quota(principal, quota)?;
+        Ok(())
+    }
+    
+    /// Get the quota that applies to a principal
+    ///
+    /// # Arguments
+    ///
+    /// * `principal` - Agent ID of the principal
+    ///
+    /// # Returns
+    ///
+    /// The principal's own quota or the default quota, or `QuotaError::UnknownPrincipal`
+    pub fn principal_quota(&self, principal: &str) -> Result<Quota, KernelError> {
+        Ok(self.lock_principals().quota(principal)?)
+    }
+    
+    /// Get the resource usage of a principal
+    ///
+    /// # Arguments
+    ///
+    /// * `principal` - Agent ID of the principal
+    ///
+    /// # Returns
+    ///
+    /// The principal's usage, or `QuotaError::UnknownPrincipal`
+    pub fn principal_usage(&self, principal: &str) -> Result<PrincipalUsage, KernelError> {
+        self.refresh_principal_memory(Some(principal));
+        let usage = self.lock_principals().usage(principal, self.context.clock.now())?;
+        Ok(usage)
+    }
+    
+    /// Get the number of tool executions in progress
+    ///
+    /// # Returns
+    ///
+    /// The number of tool executions that have not finished yet
+    pub fn running_tool_executions(&self) -> usize {
+        self.running_tool_executions.load(Ordering::SeqCst)
+    }
+    
+    /// Run a closure against a subsystem downcast to its concrete type
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the subsystem
+    /// * `f` - Closure receiving mutable access to the subsystem
+    ///
+    /// # Returns
+    ///
+    /// The value returned by the closure, or an error if the subsystem is not
+    /// registered or is not of type `T`
+    pub fn with_subsystem<T, R, F>(&self, name: &str, f: F) -> Result<R, KernelError>
+    where
+        T: Subsystem,
+        F: FnOnce(&mut T) -> R,
+    {
+        let subsystem = self.get_subsystem(name)?;
+        let mut instance = subsystem.instance.lock()
+            .map_err(|_| KernelError::SubsystemPoisoned(name.to_string()))?;
+        
+        let concrete = instance.as_any_mut().downcast_mut::<T>().ok_or_else(|| {
+            let error = KernelError::SubsystemTypeMismatch(name.to_string());
+            error!("{}", error);
+            error
+        })?;
+        
+        Ok(f(concrete))
+    }
+    
+    /// Get the clock the kernel and its subsystems read the time from
+    ///
+    /// # Returns
+    ///
+    /// The kernel clock; advancing a simulated clock moves the time of the whole kernel
+    pub fn clock(&self) -> &Clock {
+        &self.context.clock
+    }
+    
+    /// Get a handle to the kernel's task scheduler
+    ///
+    /// # Returns
+    ///
+    /// A scheduler handle sharing its tasks with the kernel
+    pub fn scheduler(&self) -> Scheduler {
+        self.scheduler.clone()
+    }
+    
+    /// Get a handle to the kernel event bus
+    ///
+    /// # Returns
+    ///
+    /// An event bus handle publishing to the same subscribers as the kernel
+    pub fn event_bus(&self) -> EventBus {
+        self.context.events.clone()
+    }
+    
+    /// Subscribe to kernel events
+    ///
+    /// # Arguments
+    ///
+    /// * `kinds` - The event kinds to receive, or an empty slice for all events
+    ///
+    /// # Returns
+    ///
+    /// A subscriber receiving the matching events published from now on
+    pub fn subscribe(&self, kinds: &[EventKind]) -> EventSubscriber {
+        if kinds.is_empty() {
+            self.context.events.subscribe()
+        } else {
+            self.context.events.subscribe_to(kinds)
+        }
+    }
+    
+    /// Get the current system load
+    ///
+    /// # Returns
+    ///
+    /// The most recent load sample in the range 0.0-1.0
+    pub fn system_load(&self) -> f64 {
+        self.load().current
+    }
+    
+    /// Record a system load measured outside the kernel
+    ///
+    /// The value is folded into the load averages like a computed sample.
+    ///
+    /// # Arguments
+    ///
+    /// * `load` - The new system load, clamped to the range 0.0-1.0
+    pub fn set_system_load(&self, load: f64) {
+        let mut tracker = self.lock_load();
+        let signals = tracker.load().signals;
+        let load = tracker.record(load, signals);
+        self.context.load.set(load);
+    }
+    
+    /// Get the system load and its averages
+    ///
+    /// # Returns
+    ///
+    /// The latest load sample with its 1, 5 and 15 minute averages
+    pub fn load(&self) -> SystemLoad {
+        self.lock_load().load()
+    }
+    
+    /// Sample the system load from the live subsystem signals
+    ///
+    /// The sample combines memory usage, running tool executions, the scheduler
+    /// queue depth and process CPU usage. Call this periodically, for example
+    /// every `LOAD_SAMPLE_INTERVAL`, to keep the averages current.
+    ///
+    /// # Returns
+    ///
+    /// The updated load figures
+    pub fn sample_load(&self) -> SystemLoad {
+        let memory_usage_percentage = if self.has_subsystem(MEMORY_SUBSYSTEM) {
+            self.with_subsystem(MEMORY_SUBSYSTEM, |memory: &mut MemoryManager| memory.usage_percentage())
+                .unwrap_or(0.0)
+        } else {
+            0.0
+        };
+        
+        let signals = LoadSignals {
+            memory_usage_percentage,
+            running_tool_executions: self.running_tool_executions.load(Ordering::SeqCst),
+            scheduler_queue_depth: self.scheduler.queue_depth(),
+            cpu_usage: self.lock_load().sample_cpu(),
+        };
+        
+        let load = self.lock_load().sample(signals, self.task_slots);
+        debug!("Sampled system load: {:.2} (1m {:.2}, 5m {:.2}, 15m {:.2})",
+               load.current, load.one_minute, load.five_minutes, load.fifteen_minutes);
+        self.context.load.set(load);
+        
+        load
+    }
+    
+    /// Process a system call and record it in the trace if tracing is enabled
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The session the call was received on, if any
+    /// * `principal` - Agent ID of the calling principal, if any
+    /// * `syscall` - The name of the system call to execute
+    /// * `args` - Arguments for the system call
+    ///
+    /// # Returns
+    ///
+    /// The result of the system call, or the reason it could not be parsed or completed
+    fn process_traced_syscall(
+        &self,
+        session: Option<Uuid>,
+        principal: Option<&str>,
+        syscall: &str,
+        args: &[&str],
+    ) -> Result<String, SyscallError> {
+        self.run_traced_syscall(session, principal, syscall, args)
+            .map(|result| result.to_string())
+    }
+    
+    /// Process a system call, record it in the trace and keep its typed result
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The session the call was received on, if any
+    /// * `principal` - Agent ID of the calling principal, if any
+    /// * `syscall` - The name of the system call to execute
+    /// * `args` - Arguments for the system call
+    ///
+    /// # Returns
+    ///
+    /// The result of the system call, or the reason it could not be parsed or completed
+    fn run_traced_syscall(
+        &self,
+        session: Option<Uuid>,
+        principal: Option<&str>,
+        syscall: &str,
+        args: &[&str],
+    ) -> Result<SyscallResult, SyscallError> {
+        debug!("Processing syscall: {} with args: {:?}", syscall, args);
+        let started = self.context.clock.now();
+        
+        let result = Syscall::parse(syscall, args)
+            .and_then(|parsed| self.execute_principal_syscall(principal, parsed));
+        
+        self.trace_syscall(session, principal, syscall, args, &result, started);
+        result
+    }
+    
+    /// Record a finished system call in the trace if tracing is enabled
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The session the call was received on, if any
+    /// * `principal` - Agent ID of the calling principal, if any
+    /// * `syscall` - The name of the system call
+    /// * `args` - Arguments of the system call
+    /// * `result` - The result of the system call
+    /// * `started` - Time the kernel started processing the call
+    fn trace_syscall<T: std::fmt::Display>(
+        &self,
+        session: Option<Uuid>,
+        principal: Option<&str>,
+        syscall: &str,
+        args: &[&str],
+        result: &Result<T, SyscallError>,
+        started: Instant,
+    ) {
+        let mut tracer = self.lock_tracer();
+        if let Some(active) = tracer.as_mut() {
+            if let Err(e) = active.record(session, principal, syscall, args, result, self.context.clock.elapsed(started)) {
+                error!("Failed to write syscall trace {:?}, tracing stopped: {}", active.path(), e);
+                *tracer = None;
+            }
+        }
+    }
+    
+    /// Process a batch of system calls and roll it back if a step fails
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The session the batch was received on, if any
+    /// * `principal` - Agent ID of the calling principal, if any
+    /// * `steps` - The system calls to run
+    ///
+    /// # Returns
+    ///
+    /// The outcome of every step, or the reason the batch was rejected
+    fn process_traced_batch(
+        &self,
+        session: Option<Uuid>,
+        principal: Option<&str>,
+        steps: &[BatchStep],
+    ) -> Result<BatchResult, SyscallError> {
+        debug!("Processing batch of {} syscalls", steps.len());
+        
+        if !self.running {
+            return Err(SyscallError::NotRunning);
+        }
+        batch::check_references(steps)?;
+        
+        let mut outcomes = Vec::with_capacity(steps.len());
+        let mut results = Vec::with_capacity(steps.len());
+        let mut committed = true;
+        for step in steps {
+            if !committed {
+                outcomes.push(StepOutcome::Skipped);
+                continue;
+            }
+            
+            let args = batch::resolve_args(&step.args, &results);
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            match self.run_traced_syscall(session, principal, &step.syscall, &args).and_then(batch::check_step) {
+                Ok(result) => {
+                    results.push(result.to_string());
+                    outcomes.push(StepOutcome::Committed { result });
+                },
+                Err(e) => {
+                    warn!("Batch step {} failed, rolling back: {}", step.syscall, e);
+                    outcomes.push(StepOutcome::Failed { error_code: e.code().to_string(), error: e.to_string() });
+                    committed = false;
+                },
+            }
+        }
+        
+        if !committed {
+            self.roll_back_batch(session, principal, &mut outcomes);
+        }
+        
+        Ok(BatchResult { committed, steps: outcomes })
+    }
+    
+    /// Undo the committed steps of a failed batch in reverse order
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The session the batch was received on, if any
+    /// * `principal` - Agent ID of the calling principal, if any
+    /// * `outcomes` - The outcomes of the steps, updated with the rollback
+    fn roll_back_batch(&self, session: Option<Uuid>, principal: Option<&str>, outcomes: &mut [StepOutcome]) {
+        for outcome in outcomes.iter_mut().rev() {
+            let StepOutcome::Committed { result } = outcome else {
+                continue;
+            };
+            let result = result.clone();
+            
+            *outcome = match Compensation::of(&result) {
+                Compensation::Nothing => StepOutcome::RolledBack { result },
+                Compensation::Run(syscall) => match self.run_compensation(session, principal, syscall) {
+                    Ok(_) => StepOutcome::RolledBack { result },
+                    Err(e) => {
+                        error!("Failed to roll back batch step: {}", e);
+                        StepOutcome::NotRolledBack { result, reason: e.to_string() }
+                    },
+                },
+                Compensation::Impossible(reason) => {
+                    warn!("Batch step not rolled back: {}", reason);
+                    StepOutcome::NotRolledBack { result, reason: reason.to_string() }
+                },
+            };
+        }
+    }
+    
+    /// Run the system call undoing a step of a failed batch
+    ///
+    /// The caller was already admitted and authorized for the step being
+    /// undone, so the compensating call is neither charged nor checked against
+    /// the security policy. It is traced like any other call.
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The session the batch was received on, if any
+    /// * `principal` - Agent ID of the calling principal, if any
+    /// * `syscall` - The compensating system call
+    ///
+    /// # Returns
+    ///
+    /// The result of the compensating call, or the reason it could not be completed
+    fn run_compensation(&self, session: Option<Uuid>, principal: Option<&str>, syscall: Syscall) -> Result<SyscallResult, SyscallError> {
+        debug!("Compensating with syscall: {:?}", syscall);
+        let started = self.context.clock.now();
+        let (name, args) = (syscall.name(), syscall.args());
+        
+        let result = self.dispatch_syscall(principal, syscall);
+        self.settle_syscall(principal, Charge::None, &result);
+        
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        self.trace_syscall(session, principal, name, &args, &result, started);
+        result
+    }
+    
+    /// Execute a typed system call, charging it to a principal if one is given
+    ///
+    /// # Arguments
+    ///
+    /// * `principal` - Agent ID of the calling principal, if any
+    /// * `syscall` - The system call to execute
+    ///
+    /// # Returns
+    ///
+    /// The result of the system call, or the reason it was rejected or could not be completed
+    fn execute_principal_syscall(&self, principal: Option<&str>, syscall: Syscall) -> Result<SyscallResult, SyscallError> {
+        debug!("Executing syscall: {:?}", syscall);
+        
+        // Validate kernel state
+        if !self.running {
+            return Err(SyscallError::NotRunning);
+        }
+        
+        // Charge the call to the principal's quota, then check it against the security policy
+        let charge = match principal {
+            Some(principal) => self.admit_syscall(principal, &syscall)?,
+            None => Charge::None,
+        };
+        let result = self.authorize_syscall(principal, &syscall)
+            .and_then(|()| self.dispatch_syscall(principal, syscall));
+        
+        self.settle_syscall(principal, charge, &result);
+        result
+    }
+    
+    /// Route an admitted and authorized system call to the subsystem handling it
+    ///
+    /// # Arguments
+    ///
+    /// * `principal` - Agent ID of the calling principal, if any
+    /// * `syscall` - The system call to execute
+    ///
+    /// # Returns
+    ///
+    /// The result of the system call, or the reason it could not be completed
+    fn dispatch_syscall(&self, principal: Option<&str>, syscall: Syscall) -> Result<SyscallResult, SyscallError> {
+        match syscall {
+            Syscall::MemoryAlloc { .. }
+            | Syscall::MemoryFree { .. }
+            | Syscall::MemoryPut { .. }
+            | Syscall::MemoryGet { .. }
+            | Syscall::MemoryEmbed { .. }
+            | Syscall::MemorySearch { .. }
+            | Syscall::MemoryConsolidate
+            | Syscall::MemoryExpire { .. }
+            | Syscall::MemoryStrength { .. } => self.handle_memory_syscall(principal, syscall),
+            Syscall::ToolExecute { .. } => self.handle_tool_syscall(syscall),
+            Syscall::SecurityCheck { .. } | Syscall::SecurityGrant { .. } | Syscall::SecurityRevoke { .. } => {
+                self.handle_security_syscall(syscall)
+            },
+            Syscall::TaskCancel { .. } => self.handle_task_syscall(principal, syscall),
+            Syscall::JobSchedule { .. } | Syscall::JobList | Syscall::JobCancel { .. } => {
+                self.handle_job_syscall(principal, syscall)
+            },
+        }
+    }
+    
+    /// Release the resources reserved for a finished system call
+    ///
+    /// Memory the call allocated is charged to the principal, and memory it
+    /// released is no longer charged to anyone.
+    ///
+    /// # Arguments
+    ///
+    /// * `principal` - Agent ID of the calling principal, if any
+    /// * `charge` - The resources reserved when the call was admitted
+    /// * `result` - The result of the call
+    fn settle_syscall(&self, principal: Option<&str>, charge: Charge, result: &Result<SyscallResult, SyscallError>) {
+        let mut principals = self.lock_principals();
+        if let Some(principal) = principal {
+            let allocated = match result {
+                Ok(SyscallResult::MemoryAllocated { handle }) => Some(*handle),
+                _ => None,
+            };
+            principals.settle(principal, charge, allocated);
+        }
+        if let Ok(SyscallResult::MemoryFreed { handle }) = result {
+            principals.release_memory(*handle);
+        }
+    }
+    
+    /// Admit a system call from a principal and reserve the resources it needs
+    ///
+    /// Rejections are recorded in the audit log under the principal's name,
+    /// unless the principal has exhausted its audit volume.
+    ///
+    /// # Arguments
+    ///
+    /// * `principal` - Agent ID of the calling principal
+    /// * `syscall` - The system call
+    ///
+    /// # Returns
+    ///
+    /// The resources reserved for the call, or the reason it was rejected
+    fn admit_syscall(&self, principal: &str, syscall: &Syscall) -> Result<Charge, SyscallError> {
+        // Only memory allocations are checked against the memory a principal holds
+        if matches!(syscall, Syscall::MemoryAlloc { .. }) {
+            self.refresh_principal_memory(Some(principal));
+        }
+        
+        let now = self.context.clock.now();
+        let mut principals = self.lock_principals();
+        let error = match principals.admit(principal, syscall, now) {
+            Ok(charge) => return Ok(charge),
+            Err(error) => error,
+        };
+        
+        warn!("Syscall {} rejected: {}", syscall.name(), error);
+        if error.is_audited() {
+            principals.record_audit_event(principal, now);
+            drop(principals);
+            self.record_security_event(principal, "quota_exceeded", &format!("Rejected {}: {}", syscall.name(), error));
+        }
+        
+        Err(error.into())
+    }
+    
+    /// Promote frequently used memory to long-term memory within the quotas of its holders
+    ///
+    /// An allocation is only promoted while the LongTerm quota of the principal
+    /// holding it has room for it. The scheduled consolidation job runs through
+    /// here as well, since the memory subsystem does not know the quotas.
+    ///
+    /// # Arguments
+    ///
+    /// * `principal` - Agent ID of the calling principal, whose allocations alone are promoted, or `None` for all memory
+    ///
+    /// # Returns
+    ///
+    /// The handles of the promoted allocations, or the reason consolidation failed
+    fn consolidate_memory(&self, principal: Option<&str>) -> Result<Vec<Uuid>, SyscallError> {
+        self.refresh_principal_memory(None);
+        
+        // The principal table stays locked so allocations cannot take the room promotions were given
+        let mut principals = self.lock_principals();
+        let now = self.context.clock.now();
+        let mut budget = principals.promotion_budget(principal, now)?;
+        
+        let promoted = self.with_subsystem(MEMORY_SUBSYSTEM, |memory: &mut MemoryManager| {
+            memory.consolidate_where(|handle, size| budget.admit(handle, size))
+        })??;
+        Ok(promoted)
+    }
+    
+    /// Update the memory allocations charged to principals from the memory subsystem
+    ///
+    /// The categories of all held handles are looked up under a single lock of
+    /// the memory subsystem, taken while the principal table is unlocked. If
+    /// the memory subsystem cannot be reached, the recorded allocations are kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `principal` - Agent ID of the principal whose allocations to update, or `None` for all principals
+    fn refresh_principal_memory(&self, principal: Option<&str>) {
+        if !self.has_subsystem(MEMORY_SUBSYSTEM) {
+            return;
+        }
+        let held = self.lock_principals().held_memory(principal);
+        if held.is_empty() {
+            return;
+        }
+        
+        let categories = self.with_subsystem(MEMORY_SUBSYSTEM, |memory: &mut MemoryManager| {
+            held.iter().map(|handle| (*handle, memory.category_of(*handle))).collect::<HashMap<_, _>>()
+        });
+        match categories {
+            Ok(categories) => self.lock_principals().refresh_memory(&categories),
+            Err(e) => warn!("Failed to look up the memory held by principals: {}", e),
+        }
+    }
+    
+    /// Record a rejected operation in the security audit log
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The component or principal the event is attributed to
+    /// * `event_type` - Event type
+    /// * `details` - Event details
+    fn record_security_event(&self, source: &str, event_type: &str, details: &str) {
+        if let Err(e) = self.with_subsystem(SECURITY_SUBSYSTEM, |security: &mut SecurityManager| {
+            security.record_event(source, event_type, details, false);
+        }) {
+            warn!("Failed to record {} event: {}", event_type, e);
+        }
+    }
+    
+    /// Lock the principal table
+    fn lock_principals(&self) -> std::sync::MutexGuard<'_, Principals> {
+        self.principals.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+    
+    /// Lock the timer service
+    fn lock_timers(&self) -> std::sync::MutexGuard<'_, Timers> {
+        self.timers.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+    
+    /// Lock the syscall tracer
+    fn lock_tracer(&self) -> std::sync::MutexGuard<'_, Option<SyscallTracer>> {
+        self.tracer.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+    
+    /// Lock the load tracker
+    fn lock_load(&self) -> std::sync::MutexGuard<'_, LoadTracker> {
+        self.load.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+    
+    /// Look up a registered subsystem
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the subsystem
+    ///
+    /// # Returns
+    ///
+    /// The registered subsystem, or `KernelError::SubsystemNotRegistered`
+    fn get_subsystem(&self, name: &str) -> Result<&RegisteredSubsystem, KernelError> {
+        self.subsystems.get(name).ok_or_else(|| {
+            let error = KernelError::SubsystemNotRegistered(name.to_string());
+            error!("{}", error);
+            error
+        })
+    }
+    
+    /// Compute the order in which subsystems must be started
+    ///
+    /// Subsystems are ordered so that every subsystem starts after all of its
+    /// dependencies. Ties are broken by registration order.
+    ///
+    /// # Returns
+    ///
+    /// Subsystem names in startup order, or an error if a dependency is missing
+    /// or the dependencies form a cycle
+    fn resolve_startup_order(&self) -> Result<Vec<String>, KernelError> {
+        let mut dependencies = HashMap::new();
+        for name in &self.subsystem_order {
+            let subsystem_deps = self.get_subsystem(name)?.instance.lock()
+                .map_err(|_| KernelError::SubsystemPoisoned(name.clone()))?
+                .dependencies();
+            
+            for dependency in &subsystem_deps {
+                if !self.subsystems.contains_key(dependency) {
+                    let error = KernelError::MissingDependency {
+                        subsystem: name.clone(),
+                        dependency: dependency.clone(),
+                    };
+                    error!("{}", error);
+                    return Err(error);
+                }
+            }
+            
+            dependencies.insert(name.clone(), subsystem_deps);
+        }
+        
+        let mut order: Vec<String> = Vec::with_capacity(self.subsystem_order.len());
+        let mut pending = self.subsystem_order.clone();
+        
+        while !pending.is_empty() {
+            let ready = pending.iter().position(|name| {
+                dependencies[name].iter().all(|dependency| order.contains(dependency))
+            });
+            
+            match ready {
+                Some(index) => order.push(pending.remove(index)),
+                None => {
+                    let error = KernelError::DependencyCycle(pending);
+                    error!("{}", error);
+                    return Err(error);
+                }
+            }
+        }
+        
+        Ok(order)
+    }
+    
+    /// Shutdown all started subsystems in exact reverse of their startup order
+    ///
+    /// Every started subsystem is shut down even if an earlier one fails.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if all subsystems shut down cleanly, or the collected errors
+    fn shutdown_started_subsystems(&mut self) -> Result<(), KernelError> {
+        let mut errors = Vec::new();
+        
+        while let Some(name) = self.startup_order.pop() {
+            if let Err(e) = self.shutdown_subsystem(&name) {
+                errors.push(e);
+            }
+        }
+        
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(KernelError::ShutdownFailed(errors))
+        }
+    }
+    
+    /// Initialize a specific subsystem
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the subsystem to initialize
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if initialization is successful, or the error that stopped it
+    fn initialize_subsystem(&self, name: &str) -> Result<(), KernelError> {
+        info!("Initializing subsystem: {}", name);
+        self.transition_subsystem(name, true)?;
+        self.schedule_subsystem_jobs(name)
+    }
+    
+    /// Schedule the jobs a subsystem declares on the timer service
+    ///
+    /// Jobs with an invalid schedule are logged and skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the subsystem
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` once the jobs are scheduled, or an error if the subsystem cannot be reached
+    fn schedule_subsystem_jobs(&self, name: &str) -> Result<(), KernelError> {
+        let specs = self.get_subsystem(name)?.instance.lock()
+            .map_err(|_| KernelError::SubsystemPoisoned(name.to_string()))?
+            .jobs();
+        
+        for (job, error) in self.lock_timers().sync_subsystem_jobs(name, specs, self.context.clock.utc_now()) {
+            error!("Subsystem {} job {} not scheduled: {}", name, job, error);
+        }
+        Ok(())
+    }
+    
+    /// Run a job declared by a subsystem
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the subsystem
+    /// * `job` - Name of the job
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the job ran, or the error that stopped it
+    fn run_subsystem_job(&self, name: &str, job: &str) -> Result<(), KernelError> {
+        let subsystem = self.get_subsystem(name)?;
+        if !matches!(subsystem.status().state, SubsystemState::Running | SubsystemState::Degraded) {
+            return Err(KernelError::SubsystemNotRunning(name.to_string()));
+        }
+        
+        debug!("Running job {} of subsystem {}", job, name);
+        if name == MEMORY_SUBSYSTEM && job == CONSOLIDATE_JOB {
+            return self.consolidate_memory(None).map(|_| ()).map_err(|e| KernelError::SubsystemFailed {
+                subsystem: name.to_string(),
+                action: "run job",
+                source: SubsystemError::new(e),
+            });
+        }
+        let mut instance = subsystem.instance.lock()
+            .map_err(|_| KernelError::SubsystemPoisoned(name.to_string()))?;
+        instance.run_job(job).map_err(|source| KernelError::SubsystemFailed {
+            subsystem: name.to_string(),
+            action: "run job",
+            source,
+        })
+    }
+    
+    /// Shutdown a specific subsystem
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the subsystem to shutdown
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if shutdown is successful, or the error that stopped it
+    fn shutdown_subsystem(&self, name: &str) -> Result<(), KernelError> {
+        info!("Shutting down subsystem: {}", name);
+        self.transition_subsystem(name, false)
+    }
+    
+    /// Start or stop a subsystem and record its new state
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the subsystem
+    /// * `start` - `true` to initialize the subsystem, `false` to shut it down
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the transition is successful, or the error that stopped it
+    fn transition_subsystem(&self, name: &str, start: bool) -> Result<(), KernelError> {
+        let subsystem = self.get_subsystem(name)?;
+        
+        let result = match subsystem.instance.lock() {
+            Ok(mut instance) => {
+                let result = if start { instance.initialize() } else { instance.shutdown() };
+                result.map_err(|source| KernelError::SubsystemFailed {
+                    subsystem: name.to_string(),
+                    action: if start { "initialize" } else { "shutdown" },
+                    source,
+                })
+            },
+            Err(_) => Err(KernelError::SubsystemPoisoned(name.to_string())),
+        };
+        
+        match result {
+            Ok(()) => {
+                subsystem.status().state = if start { SubsystemState::Running } else { SubsystemState::Stopped };
+                Ok(())
+            },
+            Err(error) => {
+                subsystem.status().state = SubsystemState::Failed;
+                error!("{}", error);
+                Err(error)
+            }
+        }
+    }
+    
+    /// Hand saved state to a running subsystem
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the subsystem
+    /// * `state` - The state saved by the subsystem
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the subsystem restored the state, or the error it was rejected with
+    fn restore_subsystem(&self, name: &str, state: serde_json::Value) -> Result<(), KernelError> {
+        let mut instance = self.get_subsystem(name)?.instance.lock()
+            .map_err(|_| KernelError::SubsystemPoisoned(name.to_string()))?;
+        
+        instance.restore(state).map_err(|source| {
+            let error = KernelError::SubsystemFailed { subsystem: name.to_string(), action: "restore", source };
+            error!("{}", error);
+            error
+        })
+    }
+    
+    /// Check a system call against the security subsystem
+    ///
+    /// Denied calls are recorded in the security event log, attributed to the
+    /// calling principal if there is one.
+    ///
+    /// # Arguments
+    ///
+    /// * `principal` - Agent ID of the calling principal, if any
+    /// * `syscall` - The system call to check
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the call is permitted, or the reason it was rejected
+    fn authorize_syscall(&self, principal: Option<&str>, syscall: &Syscall) -> Result<(), SyscallError> {
+        let permission = syscall.required_permission();
+        
+        let allowed = self.with_subsystem(SECURITY_SUBSYSTEM, |security: &mut SecurityManager| {
+            let allowed = security.check_permission(
+                &permission.resource_type,
+                &permission.operation,
+                &permission.resource,
+            );
+            
+            if !allowed {
+                security.record_event(
+                    principal.unwrap_or("kernel"),
+                    "syscall_denied",
+                    &format!("Denied {} ({} {} {})", syscall.name(),
+                            permission.resource_type, permission.operation, permission.resource),
+                    false,
+                );
+            }
+            
+            allowed
+        })?;
+        
+        if allowed {
+            return Ok(());
+        }
+        
+        error!("Syscall {} denied by security policy", syscall.name());
+        if let Some(principal) = principal {
+            self.lock_principals().record_audit_event(principal, self.context.clock.now());
+        }
+        Err(SyscallError::PermissionDenied(permission))
+    }
+    
+    /// Handle memory-related system calls
+    ///
+    /// Principals only reach the memory they hold: handles held by anyone else
+    /// are reported as not found, and searches skip their memory. Calls without
+    /// a principal reach all memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `principal` - Agent ID of the calling principal, if any
+    /// * `syscall` - The memory system call
+    ///
+    /// # Returns
+    ///
+    /// The result of the operation, or the reason it failed
+    fn handle_memory_syscall(&self, principal: Option<&str>, syscall: Syscall) -> Result<SyscallResult, SyscallError> {
+        debug!("Handling memory syscall: {}", syscall.name());
+        
+        match syscall {
+            Syscall::MemoryAlloc { size, purpose, category } => {
+                let handle = self.with_subsystem(MEMORY_SUBSYSTEM, |memory: &mut MemoryManager| {
+                    memory.allocate(size, &purpose, category)
+                })??;
+                Ok(SyscallResult::MemoryAllocated { handle })
+            },
+            Syscall::MemoryFree { handle } => {
+                self.check_memory_holder(principal, handle)?;
+                self.with_subsystem(MEMORY_SUBSYSTEM, |memory: &mut MemoryManager| {
+                    memory.deallocate(handle)
+                })??;
+                Ok(SyscallResult::MemoryFreed { handle })
+            },
+            Syscall::MemoryPut { handle, value } => {
+                self.check_memory_holder(principal, handle)?;
+                self.with_subsystem(MEMORY_SUBSYSTEM, |memory: &mut MemoryManager| {
+                    memory.put_value(handle, &value)
+                })??;
+                Ok(SyscallResult::MemoryStored { handle })
+            },
+            Syscall::MemoryGet { handle } => {
+                self.check_memory_holder(principal, handle)?;
+                let value = self.with_subsystem(MEMORY_SUBSYSTEM, |memory: &mut MemoryManager| {
+                    memory.get_value(handle)
+                })??;
+                Ok(SyscallResult::MemoryLoaded { value })
+            },
+            Syscall::MemoryEmbed { handle, embedding } => {
+                self.check_memory_holder(principal, handle)?;
+                self.with_subsystem(MEMORY_SUBSYSTEM, |memory: &mut MemoryManager| {
+                    memory.set_embedding(handle, embedding)
+                })??;
+                Ok(SyscallResult::MemoryEmbedded { handle })
+            },
+            Syscall::MemorySearch { query, k, category } => {
+                let held = principal.map(|principal| self.lock_principals().memory_handles(principal)).transpose()?;
+                let matches = self.with_subsystem(MEMORY_SUBSYSTEM, |memory: &mut MemoryManager| {
+                    memory.search_similar_where(&query, k, category, |handle| held.as_ref().is_none_or(|held| held.contains(&handle)))
+                })??;
+                Ok(SyscallResult::MemoryFound { matches })
+            },
+            Syscall::MemoryConsolidate => {
+                let promoted = self.consolidate_memory(principal)?;
+                Ok(SyscallResult::MemoryConsolidated { promoted })
+            },
+            Syscall::MemoryExpire { handle, ttl } => {
+                self.check_memory_holder(principal, handle)?;
+                self.with_subsystem(MEMORY_SUBSYSTEM, |memory: &mut MemoryManager| {
+                    memory.set_ttl(handle, ttl)
+                })??;
+                Ok(SyscallResult::MemoryExpirySet { handle })
+            },
+            Syscall::MemoryStrength { handle } => {
+                self.check_memory_holder(principal, handle)?;
+                let strength = self.with_subsystem(MEMORY_SUBSYSTEM, |memory: &mut MemoryManager| {
+                    memory.retention_strength(handle)
+                })??;
+                Ok(SyscallResult::MemoryStrength { strength })
+            },
+            other => Err(SyscallError::InvalidArguments(format!("{} is not a memory syscall", other.name()))),
+        }
+    }
+    
+    /// Check that the calling principal holds a memory allocation
+    ///
+    /// # Arguments
+    ///
+    /// * `principal` - Agent ID of the calling principal, if any
+    /// * `handle` - Handle to the allocation
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the principal holds the allocation or there is no principal,
+    /// or `MemoryError::NotFound` if the allocation is held by anyone else
+    fn check_memory_holder(&self, principal: Option<&str>, handle: Uuid) -> Result<(), SyscallError> {
+        match principal {
+            Some(principal) if !self.lock_principals().holds_memory(principal, handle)? => {
+                Err(MemoryError::NotFound(handle).into())
+            },
+            _ => Ok(()),
+        }
+    }
+    
+    /// Handle tool-related system calls
+    ///
+    /// # Arguments
+    ///
+    /// * `syscall` - The tool system call
+    ///
+    /// # Returns
+    ///
+    /// The result of the operation, or the reason it failed
+    fn handle_tool_syscall(&self, syscall: Syscall) -> Result<SyscallResult, SyscallError> {
+        debug!("Handling tool syscall: {}", syscall.name());
+        
+        match syscall {
+            Syscall::ToolExecute { tool, capability, params } => {
+                self.running_tool_executions.fetch_add(1, Ordering::SeqCst);
+                let result = self.execute_tool(&tool, &capability, &params.to_string());
+                self.running_tool_executions.fetch_sub(1, Ordering::SeqCst);
+                
+                Ok(SyscallResult::ToolExecuted { result: result? })
+            },
+            other => Err(SyscallError::InvalidArguments(format!("{} is not a tool syscall", other.name()))),
+        }
+    }
+    
+    /// Execute a tool capability
+    ///
+    /// The tools subsystem is only locked to start and finish the execution, so
+    /// executions run in parallel.
+    ///
+    /// # Arguments
+    ///
+    /// * `tool` - The tool to execute
+    /// * `capability` - Name of the capability to execute
+    /// * `params` - Parameters for the capability
+    ///
+    /// # Returns
+    ///
+    /// Result of the tool execution, or the reason it could not be completed
+    fn execute_tool(&self, tool: &ToolRef, capability: &str, params: &str) -> Result<ToolResult, SyscallError> {
+        let execution = self.with_subsystem(TOOLS_SUBSYSTEM, |tools: &mut ToolManager| {
+            let handle = match tool {
+                ToolRef::Handle(handle) => *handle,
+                ToolRef::Id(id) => tools.find_tool(id)
+                    .ok_or_else(|| ToolError::UnknownTool(id.clone()))?,
+            };
+            tools.start_execution(handle, capability)
+        })??;
+        
+        let result = execution.run(params)?;
+        self.with_subsystem(TOOLS_SUBSYSTEM, |tools: &mut ToolManager| {
+            tools.finish_execution(&execution, &result)
+        })?;
+        
+        Ok(result)
+    }
+    
+    /// Handle security-related system calls
+    ///
+    /// # Arguments
+    ///
+    /// * `syscall` - The security system call
+    ///
+    /// # Returns
+    ///
+    /// The result of the operation, or the reason it failed
+    fn handle_security_syscall(&self, syscall: Syscall) -> Result<SyscallResult, SyscallError> {
+        debug!("Handling security syscall: {}", syscall.name());
+        
+        match syscall {
+            Syscall::SecurityCheck { resource_type, operation, resource } => {
+                let allowed = self.with_subsystem(SECURITY_SUBSYSTEM, |security: &mut SecurityManager| {
+                    security.check_permission(&resource_type, &operation, &resource)
+                })?;
+                Ok(SyscallResult::PermissionChecked { allowed })
+            },
+            Syscall::SecurityGrant { resource_type, operation, resource } => {
+                let changed = self.with_subsystem(SECURITY_SUBSYSTEM, |security: &mut SecurityManager| {
+                    let changed = !security.has_permission(&resource_type, &operation, &resource);
+                    security.add_permission(&resource_type, &operation, &resource).map(|()| changed)
+                })??;
+                let permission = Permission { resource_type, operation, resource };
+                Ok(SyscallResult::PermissionGranted { permission, changed })
+            },
+            Syscall::SecurityRevoke { resource_type, operation, resource } => {
+                let changed = self.with_subsystem(SECURITY_SUBSYSTEM, |security: &mut SecurityManager| {
+                    let changed = security.has_permission(&resource_type, &operation, &resource);
+                    security.remove_permission(&resource_type, &operation, &resource).map(|()| changed)
+                })??;
+                let permission = Permission { resource_type, operation, resource };
+                Ok(SyscallResult::PermissionRevoked { permission, changed })
+            },
+            other => Err(SyscallError::InvalidArguments(format!("{} is not a security syscall", other.name()))),
+        }
+    }
+    
+    /// Handle task-related system calls
+    ///
+    /// Principals can only cancel the tasks spawned for them; calls without a
+    /// principal can cancel the task of any principal. Kernel tasks cannot be
+    /// cancelled by a system call.
+    ///
+    /// # Arguments
+    ///
+    /// * `principal` - Agent ID of the calling principal, if any
+    /// * `syscall` - The task system call
+    ///
+    /// # Returns
+    ///
+    /// The result of the operation, or the reason it failed
+    fn handle_task_syscall(&self, principal: Option<&str>, syscall: Syscall) -> Result<SyscallResult, SyscallError> {
+        debug!("Handling task syscall: {}", syscall.name());
+        
+        match syscall {
+            Syscall::TaskCancel { task } => {
+                self.scheduler.cancel_principal_task(task, principal)?;
+                Ok(SyscallResult::TaskCancelled { task })
+            },
+            other => Err(SyscallError::InvalidArguments(format!("{} is not a task syscall", other.name()))),
+        }
+    }
+    
+    /// Handle timer-related system calls
+    ///
+    /// Principals can only cancel the jobs they scheduled; calls without a
+    /// principal can cancel any job.
+    ///
+    /// # Arguments
+    ///
+    /// * `principal` - Agent ID of the calling principal, if any
+    /// * `syscall` - The timer system call
+    ///
+    /// # Returns
+    ///
+    /// The result of the operation, or the reason it failed
+    fn handle_job_syscall(&self, principal: Option<&str>, syscall: Syscall) -> Result<SyscallResult, SyscallError> {
+        debug!("Handling job syscall: {}", syscall.name());
+        
+        match syscall {
+            Syscall::JobSchedule { schedule, syscall } => {
+                let job = self.schedule_syscall(principal, schedule, *syscall)?;
+                Ok(SyscallResult::JobScheduled { job })
+            },
+            Syscall::JobList => Ok(SyscallResult::JobsListed { jobs: self.jobs() }),
+            Syscall::JobCancel { job } => {
+                let cancelled = self.lock_timers().cancel(job, principal)?;
+                info!("Cancelled job {} ({})", job, cancelled.name);
+                Ok(SyscallResult::JobCancelled { job })
+            },
+            other => Err(SyscallError::InvalidArguments(format!("{} is not a job syscall", other.name()))),
+        }
     }
 }
 
@@ -0,0 +1,166 @@
+//! System load tracking for the RoyaOS kernel
+//!
+//! This module turns the live signals of the kernel's subsystems into a single load
+//! figure and smooths it into 1, 5 and 15 minute averages in the style of the Unix
+//! load average.
+//!
+//! Each signal is normalized to the range 0.0-1.0 and the load sample is the highest
+//! of them, so the load reflects whichever resource is closest to saturation.
+
+use royaos_common::{Clock, LoadSignals, SystemLoad};
+use std::time::{Duration, Instant};
+
+/// Recommended interval between two load samples
+pub const LOAD_SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Clock ticks per second used by /proc for CPU times
+const PROC_TICKS_PER_SECOND: f64 = 100.0;
+
+/// Averaging periods of the three load averages in seconds
+const AVERAGE_PERIODS: [f64; 3] = [60.0, 300.0, 900.0];
+
+/// Tracker computing load samples and their moving averages
+#[derive(Debug)]
+pub(crate) struct LoadTracker {
+    /// Latest load figures
+    load: SystemLoad,
+    /// Time of the latest sample
+    last_sample: Instant,
+    /// Process CPU time in ticks at the time of the latest CPU reading
+    last_cpu: Option<(Instant, u64)>,
+    /// Clock the time between samples is measured on
+    clock: Clock,
+}
+
+impl LoadTracker {
+    /// Create a new tracker reporting zero load
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - Clock the time between samples is measured on
+    pub(crate) fn new(clock: Clock) -> Self {
+        let now = clock.now();
+        Self {
+            load: SystemLoad::default(),
+            last_sample: now,
+            last_cpu: read_process_cpu_ticks().map(|ticks| (now, ticks)),
+            clock,
+        }
+    }
+    
+    /// Get the latest load figures
+    pub(crate) fn load(&self) -> SystemLoad {
+        self.load
+    }
+    
+    /// Compute a load sample from live signals and fold it into the averages
+    ///
+    /// # Arguments
+    ///
+    /// * `signals` - The signals gathered from the subsystems and `sample_cpu`
+    /// * `capacity` - Number of scheduler slots used to normalize tool and queue counts
+    ///
+    /// # Returns
+    ///
+    /// The updated load figures
+    pub(crate) fn sample(&mut self, signals: LoadSignals, capacity: usize) -> SystemLoad {
+        let capacity = capacity.max(1) as f64;
+        let value = [
+            signals.memory_usage_percentage / 100.0,
+            signals.running_tool_executions as f64 / capacity,
+            signals.scheduler_queue_depth as f64 / capacity,
+            signals.cpu_usage,
+        ].into_iter().fold(0.0, f64::max);
+        
+        self.record(value, signals)
+    }
+    
+    /// Fold a load value into the averages
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The load sample, clamped to the range 0.0-1.0
+    /// * `signals` - The signals behind the sample
+    ///
+    /// # Returns
+    ///
+    /// The updated load figures
+    pub(crate) fn record(&mut self, value: f64, signals: LoadSignals) -> SystemLoad {
+        let value = value.clamp(0.0, 1.0);
+        let now = self.clock.now();
+        let elapsed = now.duration_since(self.last_sample).as_secs_f64();
+        self.last_sample = now;
+        
+        let averages = [
+            &mut self.load.one_minute,
+            &mut self.load.five_minutes,
+            &mut self.load.fifteen_minutes,
+        ];
+        for (average, period) in averages.into_iter().zip(AVERAGE_PERIODS) {
+            let decay = (-elapsed / period).exp();
+            *average = *average * decay + value * (1.0 - decay);
+        }
+        
+        self.load.current = value;
+        self.load.signals = signals;
+        self.load
+    }
+    
+    /// Measure process CPU usage since the previous reading
+    ///
+    /// # Returns
+    ///
+    /// CPU usage as a fraction of all available cores, or 0.0 if /proc is
+    /// unavailable or no time has passed on the clock
+    pub(crate) fn sample_cpu(&mut self) -> f64 {
+        let ticks = match read_process_cpu_ticks() {
+            Some(ticks) => ticks,
+            None => return 0.0,
+        };
+        let now = self.clock.now();
+        
+        let usage = match self.last_cpu {
+            Some((previous_at, previous_ticks)) => {
+                let wall = now.duration_since(previous_at).as_secs_f64();
+                let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get()) as f64;
+                if wall > 0.0 {
+                    let cpu = ticks.saturating_sub(previous_ticks) as f64 / PROC_TICKS_PER_SECOND;
+                    (cpu / wall / cores).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                }
+            },
+            None => 0.0,
+        };
+        
+        self.last_cpu = Some((now, ticks));
+        usage
+    }
+}
+
+/// Read the CPU time used by this process from /proc
+///
+/// # Returns
+///
+/// User plus system time in clock ticks, or `None` if /proc is unavailable
+fn read_process_cpu_ticks() -> Option<u64> {
+    let stat = std::fs::read_to_string("/proc/self/stat").ok()?;
+    parse_cpu_ticks(&stat)
+}
+
+/// Extract user plus system time from the contents of /proc/<pid>/stat
+///
+/// # Arguments
+///
+/// * `stat` - Contents of the stat file
+///
+/// # Returns
+///
+/// User plus system time in clock ticks, or `None` if the contents are malformed
+pub(crate) fn parse_cpu_ticks(stat: &str) -> Option<u64> {
+    // The command name may contain spaces, so fields are counted after its closing parenthesis
+    let fields: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    Some(utime + stime)
+}
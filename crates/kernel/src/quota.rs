@@ -0,0 +1,573 @@
+//! Per-agent resource quotas for the RoyaOS kernel
+//!
+//! Several agent processes can share one kernel. Each agent is registered as a
+//! principal under its agent ID, and the system calls it issues are charged against
+//! its quota: memory bytes per category, concurrent tool executions, system calls per
+//! second and audit-log entries per minute. A call that would exceed a limit is
+//! rejected before it reaches any subsystem.
+//!
+//! Principals without a quota of their own follow the kernel's default quota, so a
+//! change to the default applies to them immediately. System calls issued without a
+//! principal are not subject to quotas.
+//!
+//! Memory that consolidation promotes to long-term memory moves to the LongTerm
+//! quota of the principal holding it, so it is only promoted while that quota
+//! has room for it.
+
+use log::warn;
+use royaos_common::ErrorCode;
+use royaos_memory::{MemoryCategory, MemoryHandle};
+use serde::{Serialize, Deserialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+use crate::syscall::Syscall;
+
+/// Window over which system calls are counted for the rate limit
+pub const SYSCALL_RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Window over which audit-log entries are counted for the audit volume limit
+pub const AUDIT_VOLUME_WINDOW: Duration = Duration::from_secs(60);
+
+/// Resource limits of a principal
+///
+/// Limits that are not set are unlimited.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quota {
+    /// Maximum bytes of memory the principal holds per category
+    #[serde(default)]
+    pub memory_bytes: HashMap<MemoryCategory, usize>,
+    /// Maximum number of tool executions the principal runs at the same time
+    #[serde(default)]
+    pub concurrent_tool_executions: Option<usize>,
+    /// Maximum number of system calls the principal issues per second
+    #[serde(default)]
+    pub syscalls_per_second: Option<u32>,
+    /// Maximum number of audit-log entries the principal causes per minute
+    #[serde(default)]
+    pub audit_events_per_minute: Option<u32>,
+}
+
+/// Resource usage of a principal
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PrincipalUsage {
+    /// Bytes of memory held per category, including allocations in progress
+    pub memory_bytes: HashMap<MemoryCategory, usize>,
+    /// Number of tool executions in progress
+    pub running_tool_executions: usize,
+    /// Number of system calls admitted in the last second
+    pub syscalls_last_second: usize,
+    /// Number of audit-log entries caused in the last minute
+    pub audit_events_last_minute: usize,
+    /// Number of system calls admitted since the principal was registered
+    pub admitted_syscalls: u64,
+    /// Number of system calls rejected for exceeding the quota
+    pub rejected_syscalls: u64,
+}
+
+/// Error returned when a principal is unknown or exceeds its quota
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum QuotaError {
+    /// No principal is registered under the agent ID
+    #[error("No principal registered for agent {0}")]
+    UnknownPrincipal(String),
+    
+    /// The allocation would exceed the principal's memory quota for the category
+    #[error("Agent {principal} memory quota for {} exceeded: requested {requested} bytes, {available} bytes available", .category.as_str())]
+    Memory {
+        /// Agent ID of the principal
+        principal: String,
+        /// Category of the allocation
+        category: MemoryCategory,
+        /// Requested size in bytes
+        requested: usize,
+        /// Bytes left in the quota
+        available: usize,
+    },
+    
+    /// The principal already runs as many tool executions as it may
+    #[error("Agent {principal} already runs {limit} tool executions")]
+    ToolExecutions {
+        /// Agent ID of the principal
+        principal: String,
+        /// Maximum number of concurrent tool executions
+        limit: usize,
+    },
+    
+    /// The principal issued as many system calls in the last second as it may
+    #[error("Agent {principal} exceeded {limit} system calls per second")]
+    SyscallRate {
+        /// Agent ID of the principal
+        principal: String,
+        /// Maximum number of system calls per second
+        limit: u32,
+    },
+    
+    /// The principal caused as many audit-log entries in the last minute as it may
+    #[error("Agent {principal} exceeded {limit} audit-log entries per minute")]
+    AuditVolume {
+        /// Agent ID of the principal
+        principal: String,
+        /// Maximum number of audit-log entries per minute
+        limit: u32,
+    },
+}
+
+impl ErrorCode for QuotaError {
+    fn code(&self) -> &'static str {
+        match self {
+            QuotaError::UnknownPrincipal(_) => "QUOTA_UNKNOWN_PRINCIPAL",
+            QuotaError::Memory { .. } => "QUOTA_MEMORY_EXCEEDED",
+            QuotaError::ToolExecutions { .. } => "QUOTA_TOOL_EXECUTIONS_EXCEEDED",
+            QuotaError::SyscallRate { .. } => "QUOTA_SYSCALL_RATE_EXCEEDED",
+            QuotaError::AuditVolume { .. } => "QUOTA_AUDIT_VOLUME_EXCEEDED",
+        }
+    }
+}
+
+impl QuotaError {
+    /// Check whether the rejection is recorded in the audit log
+    ///
+    /// Rejections for an exhausted audit volume are not recorded, since they
+    /// would add to the volume the quota limits.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the rejection is audited, `false` otherwise
+    pub fn is_audited(&self) -> bool {
+        !matches!(self, QuotaError::UnknownPrincipal(_) | QuotaError::AuditVolume { .. })
+    }
+}
+
+/// Resources reserved for a system call while it runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Charge {
+    /// The call reserves nothing
+    None,
+    /// Memory reserved for an allocation
+    Memory {
+        /// Category of the allocation
+        category: MemoryCategory,
+        /// Size of the allocation in bytes
+        size: usize,
+    },
+    /// A tool execution slot
+    ToolExecution,
+}
+
+/// Room for the memory a consolidation pass promotes to long-term memory
+#[derive(Debug)]
+pub(crate) struct PromotionBudget {
+    /// Principal holding each charged allocation
+    holders: HashMap<MemoryHandle, String>,
+    /// Long-term bytes left to principals whose quota limits long-term memory
+    available: HashMap<String, usize>,
+    /// Principal whose allocations alone may be promoted, if any
+    only: Option<String>,
+}
+
+impl PromotionBudget {
+    /// Check whether an allocation may be promoted and charge it to its holder
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - Handle of the allocation
+    /// * `size` - Size of the allocation in bytes
+    ///
+    /// # Returns
+    ///
+    /// `true` if the allocation may be promoted, `false` if it is not in scope
+    /// or its holder's long-term quota has no room for it
+    pub(crate) fn admit(&mut self, handle: MemoryHandle, size: usize) -> bool {
+        let holder = self.holders.get(&handle);
+        if self.only.is_some() && holder != self.only.as_ref() {
+            return false;
+        }
+        let Some(holder) = holder else {
+            return true;
+        };
+        
+        match self.available.get_mut(holder) {
+            Some(available) if size > *available => {
+                warn!(
+                    "Not promoting allocation {} of principal {}: {} bytes exceed the {} bytes left in its long_term quota",
+                    handle, holder, size, available
+                );
+                false
+            },
+            Some(available) => {
+                *available -= size;
+                true
+            },
+            None => true,
+        }
+    }
+}
+
+/// Accounting of one principal
+#[derive(Debug, Default)]
+struct Principal {
+    /// Quota of the principal, or `None` to follow the default quota
+    quota: Option<Quota>,
+    /// Category and size of the allocations the principal holds
+    allocations: HashMap<MemoryHandle, (MemoryCategory, usize)>,
+    /// Bytes reserved for allocations in progress per category
+    reserved: HashMap<MemoryCategory, usize>,
+    /// Number of tool executions in progress
+    running_tool_executions: usize,
+    /// Times of the system calls admitted within the rate window
+    recent_syscalls: VecDeque<Instant>,
+    /// Times of the audit-log entries caused within the audit window
+    recent_audit_events: VecDeque<Instant>,
+    /// Number of system calls admitted since registration
+    admitted_syscalls: u64,
+    /// Number of system calls rejected since registration
+    rejected_syscalls: u64,
+}
+
+impl Principal {
+    /// Drop counts that left their window
+    fn expire(&mut self, now: Instant) {
+        expire_window(&mut self.recent_syscalls, now, SYSCALL_RATE_WINDOW);
+        expire_window(&mut self.recent_audit_events, now, AUDIT_VOLUME_WINDOW);
+    }
+    
+    /// Get the bytes held and reserved in a category
+    fn memory_in(&self, category: MemoryCategory) -> usize {
+        let allocated: usize = self.allocations.values()
+            .filter(|(allocation_category, _)| *allocation_category == category)
+            .map(|(_, size)| size)
+            .sum();
+        allocated.saturating_add(self.reserved.get(&category).copied().unwrap_or(0))
+    }
+}
+
+/// Remove the times that lie a window or more before now
+fn expire_window(times: &mut VecDeque<Instant>, now: Instant, window: Duration) {
+    while times.front().is_some_and(|time| now.duration_since(*time) >= window) {
+        times.pop_front();
+    }
+}
+
+/// Registered principals and their accounting
+#[derive(Debug, Default)]
+pub(crate) struct Principals {
+    /// Quota of principals without a quota of their own
+    default_quota: Quota,
+    /// Principals by agent ID
+    principals: HashMap<String, Principal>,
+}
+
+impl Principals {
+    /// Get the default quota
+    pub(crate) fn default_quota(&self) -> &Quota {
+        &self.default_quota
+    }
+    
+    /// Replace the default quota
+    pub(crate) fn set_default_quota(&mut self, quota: Quota) {
+        self.default_quota = quota;
+    }
+    
+    /// Register a principal under the default quota
+    ///
+    /// # Returns
+    ///
+    /// `true` if the principal is new, `false` if it was already registered
+    pub(crate) fn register(&mut self, principal: &str) -> bool {
+        if self.principals.contains_key(principal) {
+            return false;
+        }
+        
+        self.principals.insert(principal.to_string(), Principal::default());
+        true
+    }
+    
+    /// Remove a principal and its accounting
+    pub(crate) fn remove(&mut self, principal: &str) -> Result<(), QuotaError> {
+        self.principals.remove(principal)
+            .map(|_| ())
+            .ok_or_else(|| QuotaError::UnknownPrincipal(principal.to_string()))
+    }
+    
+    /// Get the agent IDs of the registered principals in sorted order
+    pub(crate) fn ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.principals.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+    
+    /// Set the quota of a principal, or `None` to make it follow the default quota
+    pub(crate) fn set_quota(&mut self, principal: &str, quota: Option<Quota>) -> Result<(), QuotaError> {
+        self.get_mut(principal)?.quota = quota;
+        Ok(())
+    }
+    
+    /// Get the quota that applies to a principal
+    pub(crate) fn quota(&self, principal: &str) -> Result<Quota, QuotaError> {
+        let entry = self.principals.get(principal)
+            .ok_or_else(|| QuotaError::UnknownPrincipal(principal.to_string()))?;
+        Ok(entry.quota.clone().unwrap_or_else(|| self.default_quota.clone()))
+    }
+    
+    /// Get the resource usage of a principal
+    ///
+    /// # Arguments
+    ///
+    /// * `principal` - Agent ID of the principal
+    /// * `now` - The current time
+    ///
+    /// # Returns
+    ///
+    /// The usage, or `QuotaError::UnknownPrincipal`
+    pub(crate) fn usage(&mut self, principal: &str, now: Instant) -> Result<PrincipalUsage, QuotaError> {
+        let entry = self.get_mut(principal)?;
+        entry.expire(now);
+        
+        let mut memory_bytes: HashMap<MemoryCategory, usize> = HashMap::new();
+        for &(category, size) in entry.allocations.values() {
+            *memory_bytes.entry(category).or_default() += size;
+        }
+        for (&category, &size) in &entry.reserved {
+            *memory_bytes.entry(category).or_default() += size;
+        }
+        
+        Ok(PrincipalUsage {
+            memory_bytes,
+            running_tool_executions: entry.running_tool_executions,
+            syscalls_last_second: entry.recent_syscalls.len(),
+            audit_events_last_minute: entry.recent_audit_events.len(),
+            admitted_syscalls: entry.admitted_syscalls,
+            rejected_syscalls: entry.rejected_syscalls,
+        })
+    }
+    
+    /// Admit a system call from a principal and reserve the resources it needs
+    ///
+    /// # Arguments
+    ///
+    /// * `principal` - Agent ID of the principal
+    /// * `syscall` - The system call
+    /// * `now` - The current time
+    ///
+    /// # Returns
+    ///
+    /// The reserved resources, to be settled once the call finishes, or the
+    /// limit the call would exceed
+    pub(crate) fn admit(&mut self, principal: &str, syscall: &Syscall, now: Instant) -> Result<Charge, QuotaError> {
+        let quota = self.quota(principal)?;
+        let entry = self.get_mut(principal)?;
+        entry.expire(now);
+        
+        let admitted = check_limits(principal, entry, &quota, syscall);
+        match admitted {
+            Ok(charge) => {
+                match charge {
+                    Charge::Memory { category, size } => {
+                        let reserved = entry.reserved.entry(category).or_default();
+                        *reserved = reserved.saturating_add(size);
+                    },
+                    Charge::ToolExecution => entry.running_tool_executions += 1,
+                    Charge::None => {},
+                }
+                entry.recent_syscalls.push_back(now);
+                entry.admitted_syscalls += 1;
+            },
+            Err(_) => entry.rejected_syscalls += 1,
+        }
+        
+        admitted
+    }
+    
+    /// Release the resources reserved for a finished system call
+    ///
+    /// # Arguments
+    ///
+    /// * `principal` - Agent ID of the principal
+    /// * `charge` - The resources reserved when the call was admitted
+    /// * `allocated` - Handle of the memory the call allocated, if any
+    pub(crate) fn settle(&mut self, principal: &str, charge: Charge, allocated: Option<MemoryHandle>) {
+        // The principal may have been removed while the call ran
+        let Some(entry) = self.principals.get_mut(principal) else {
+            return;
+        };
+        
+        match charge {
+            Charge::Memory { category, size } => {
+                let remaining = entry.reserved.get(&category).copied().unwrap_or(0).saturating_sub(size);
+                if remaining == 0 {
+                    entry.reserved.remove(&category);
+                } else {
+                    entry.reserved.insert(category, remaining);
+                }
+                if let Some(handle) = allocated {
+                    entry.allocations.insert(handle, (category, size));
+                }
+            },
+            Charge::ToolExecution => {
+                entry.running_tool_executions = entry.running_tool_executions.saturating_sub(1);
+            },
+            Charge::None => {},
+        }
+    }
+    
+    /// Stop charging a released memory allocation to the principal holding it
+    pub(crate) fn release_memory(&mut self, handle: MemoryHandle) {
+        for entry in self.principals.values_mut() {
+            if entry.allocations.remove(&handle).is_some() {
+                return;
+            }
+        }
+    }
+    
+    /// Work out which memory a consolidation pass may promote
+    ///
+    /// # Arguments
+    ///
+    /// * `principal` - Agent ID of the principal whose allocations alone may be promoted, or `None` for all memory
+    /// * `now` - The current time
+    ///
+    /// # Returns
+    ///
+    /// The room every principal has for promoted memory, or `QuotaError::UnknownPrincipal`
+    pub(crate) fn promotion_budget(&mut self, principal: Option<&str>, now: Instant) -> Result<PromotionBudget, QuotaError> {
+        if let Some(principal) = principal {
+            self.get_mut(principal)?;
+        }
+        
+        let mut budget = PromotionBudget {
+            holders: HashMap::new(),
+            available: HashMap::new(),
+            only: principal.map(str::to_string),
+        };
+        for (id, entry) in &mut self.principals {
+            entry.expire(now);
+            budget.holders.extend(entry.allocations.keys().map(|handle| (*handle, id.clone())));
+            
+            let quota = entry.quota.as_ref().unwrap_or(&self.default_quota);
+            if let Some(&limit) = quota.memory_bytes.get(&MemoryCategory::LongTerm) {
+                budget.available.insert(id.clone(), limit.saturating_sub(entry.memory_in(MemoryCategory::LongTerm)));
+            }
+        }
+        
+        Ok(budget)
+    }
+    
+    /// Get the handles of the memory allocations held by one or all principals
+    ///
+    /// # Arguments
+    ///
+    /// * `principal` - Agent ID of the principal, or `None` for all principals
+    ///
+    /// # Returns
+    ///
+    /// The handles, or none if the principal is unknown
+    pub(crate) fn held_memory(&self, principal: Option<&str>) -> Vec<MemoryHandle> {
+        self.principals.iter()
+            .filter(|(id, _)| principal.is_none_or(|principal| principal == id.as_str()))
+            .flat_map(|(_, entry)| entry.allocations.keys().copied())
+            .collect()
+    }
+    
+    /// Bring the recorded memory allocations up to date with the memory subsystem
+    ///
+    /// Allocations can be released or change category without a system call,
+    /// for example when the memory subsystem optimizes or consolidates its
+    /// allocations or restores a snapshot. Handles missing from `categories`
+    /// are left as they are.
+    ///
+    /// # Arguments
+    ///
+    /// * `categories` - The category of each looked up handle, or `None` if it was released
+    pub(crate) fn refresh_memory(&mut self, categories: &HashMap<MemoryHandle, Option<MemoryCategory>>) {
+        for entry in self.principals.values_mut() {
+            entry.allocations.retain(|handle, (category, _)| match categories.get(handle) {
+                Some(Some(current)) => {
+                    *category = *current;
+                    true
+                },
+                Some(None) => false,
+                None => true,
+            });
+        }
+    }
+    
+    /// Get the handles of the memory allocations a principal holds
+    pub(crate) fn memory_handles(&self, principal: &str) -> Result<HashSet<MemoryHandle>, QuotaError> {
+        let entry = self.principals.get(principal)
+            .ok_or_else(|| QuotaError::UnknownPrincipal(principal.to_string()))?;
+        Ok(entry.allocations.keys().copied().collect())
+    }
+    
+    /// Check whether a principal holds a memory allocation
+    pub(crate) fn holds_memory(&self, principal: &str, handle: MemoryHandle) -> Result<bool, QuotaError> {
+        let entry = self.principals.get(principal)
+            .ok_or_else(|| QuotaError::UnknownPrincipal(principal.to_string()))?;
+        Ok(entry.allocations.contains_key(&handle))
+    }
+    
+    /// Charge an audit-log entry to a principal
+    pub(crate) fn record_audit_event(&mut self, principal: &str, now: Instant) {
+        if let Some(entry) = self.principals.get_mut(principal) {
+            entry.recent_audit_events.push_back(now);
+        }
+    }
+    
+    /// Get a registered principal
+    fn get_mut(&mut self, principal: &str) -> Result<&mut Principal, QuotaError> {
+        self.principals.get_mut(principal)
+            .ok_or_else(|| QuotaError::UnknownPrincipal(principal.to_string()))
+    }
+}
+
+/// Check a system call against the limits of a principal
+///
+/// # Arguments
+///
+/// * `principal` - Agent ID of the principal
+/// * `entry` - Accounting of the principal, with expired entries removed
+/// * `quota` - The quota that applies to the principal
+/// * `syscall` - The system call
+///
+/// # Returns
+///
+/// The resources the call needs, or the limit it would exceed
+fn check_limits(principal: &str, entry: &Principal, quota: &Quota, syscall: &Syscall) -> Result<Charge, QuotaError> {
+    if let Some(limit) = quota.audit_events_per_minute {
+        if entry.recent_audit_events.len() >= limit as usize {
+            return Err(QuotaError::AuditVolume { principal: principal.to_string(), limit });
+        }
+    }
+    if let Some(limit) = quota.syscalls_per_second {
+        if entry.recent_syscalls.len() >= limit as usize {
+            return Err(QuotaError::SyscallRate { principal: principal.to_string(), limit });
+        }
+    }
+    
+    match syscall {
+        Syscall::MemoryAlloc { size, category, .. } => {
+            if let Some(&limit) = quota.memory_bytes.get(category) {
+                let available = limit.saturating_sub(entry.memory_in(*category));
+                if *size > available {
+                    return Err(QuotaError::Memory {
+                        principal: principal.to_string(),
+                        category: *category,
+                        requested: *size,
+                        available,
+                    });
+                }
+            }
+            Ok(Charge::Memory { category: *category, size: *size })
+        },
+        Syscall::ToolExecute { .. } => {
+            if let Some(limit) = quota.concurrent_tool_executions {
+                if entry.running_tool_executions >= limit {
+                    return Err(QuotaError::ToolExecutions { principal: principal.to_string(), limit });
+                }
+            }
+            Ok(Charge::ToolExecution)
+        },
+        _ => Ok(Charge::None),
+    }
+}
@@ -0,0 +1,847 @@
+//! Cognitive task scheduler for the RoyaOS kernel
+//!
+//! This module implements the scheduler subsystem that runs the cognitive tasks of the
+//! Roya AGI. Tasks are named async futures spawned with a priority class that mirrors
+//! the memory categories: perception work runs as `ShortTerm`, reasoning as `Working`,
+//! reflection as `LongTerm` and housekeeping as `Background`.
+//!
+//! The scheduler provides:
+//! - A bounded number of concurrently running tasks
+//! - Fair-share scheduling between priority classes using stride scheduling, so a busy
+//!   high-priority class slows lower classes down but never starves them
+//! - Preemption points where a task hands its slot back to the scheduler
+//! - Cooperative per-task cancellation, with hard abort as a fallback
+//!
+//! Scheduling is cooperative: a running task keeps its slot until it reaches a
+//! preemption point by awaiting `TaskContext::yield_now` or until it completes.
+
+use log::{info, debug, warn};
+use royaos_common::{ErrorCode, HandleGenerator, KernelContext, Subsystem, SubsystemError, SubsystemHealth};
+use royaos_memory::MemoryCategory;
+use serde::{Serialize, Deserialize};
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{oneshot, Notify};
+use thiserror::Error;
+use tokio::task::{AbortHandle, JoinHandle};
+use uuid::Uuid;
+
+/// Task identifier type used to reference scheduled tasks
+pub type TaskId = Uuid;
+
+/// Priority class of a cognitive task
+pub type TaskPriority = MemoryCategory;
+
+/// Error returned by the scheduler
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchedulerError {
+    /// Tasks were spawned outside a Tokio runtime
+    #[error("Tasks can only be spawned from within a Tokio runtime")]
+    NoRuntime,
+    
+    /// The scheduler is stopped or draining
+    #[error("Scheduler is not accepting new tasks")]
+    NotAccepting,
+    
+    /// No task exists with the ID
+    #[error("No task found for ID {0}")]
+    TaskNotFound(TaskId),
+    
+    /// The task runs for the kernel or for another principal
+    #[error("Task {0} belongs to the kernel or another principal")]
+    NotOwner(TaskId),
+}
+
+impl ErrorCode for SchedulerError {
+    fn code(&self) -> &'static str {
+        match self {
+            SchedulerError::NoRuntime => "SCHEDULER_NO_RUNTIME",
+            SchedulerError::NotAccepting => "SCHEDULER_NOT_ACCEPTING",
+            SchedulerError::TaskNotFound(_) => "TASK_NOT_FOUND",
+            SchedulerError::NotOwner(_) => "TASK_NOT_OWNER",
+        }
+    }
+}
+
+/// Stride numerator used to advance the virtual time of a priority class
+const STRIDE: u64 = 1 << 16;
+
+/// Get the share of scheduling slots a priority class receives
+///
+/// # Arguments
+///
+/// * `priority` - The priority class
+///
+/// # Returns
+///
+/// The relative weight of the class; a class with twice the weight is
+/// dispatched twice as often when both classes have waiting tasks
+pub fn priority_share(priority: TaskPriority) -> u64 {
+    match priority {
+        MemoryCategory::System => 16,
+        MemoryCategory::ShortTerm => 8,
+        MemoryCategory::Working => 4,
+        MemoryCategory::LongTerm => 2,
+        MemoryCategory::Background => 1,
+    }
+}
+
+/// Lifecycle state of a scheduled task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    /// Waiting for a scheduling slot
+    Waiting,
+    /// Holding a scheduling slot
+    Running,
+    /// Sleeping without holding a scheduling slot
+    Sleeping,
+    /// Finished normally
+    Completed,
+    /// Stopped because it was cancelled or aborted
+    Cancelled,
+}
+
+/// Information about a scheduled task
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskInfo {
+    /// Task ID
+    pub id: TaskId,
+    /// Task name
+    pub name: String,
+    /// Task priority class
+    pub priority: TaskPriority,
+    /// Current task state
+    pub state: TaskState,
+    /// Number of times the task has been granted a scheduling slot
+    pub dispatch_count: usize,
+    /// Agent ID of the principal the task runs for, or `None` for kernel tasks
+    #[serde(default)]
+    pub owner: Option<String>,
+}
+
+/// Error returned from a preemption point when the task has been cancelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaskCancelled;
+
+impl std::fmt::Display for TaskCancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Task was cancelled")
+    }
+}
+
+impl std::error::Error for TaskCancelled {}
+
+/// Cooperative cancellation flag that wakes sleeping tasks when set
+#[derive(Default)]
+struct Cancellation {
+    /// Whether the task has been asked to stop
+    flag: AtomicBool,
+    /// Wakes tasks sleeping at a preemption point
+    notify: Notify,
+}
+
+impl Cancellation {
+    /// Ask the task to stop and wake it if it is sleeping
+    fn cancel(&self) {
+        self.flag.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+    
+    fn is_cancelled(&self) -> bool {
+        self.flag.load(Ordering::SeqCst)
+    }
+    
+    /// Wait until the task is asked to stop
+    async fn cancelled(&self) {
+        let notified = self.notify.notified();
+        tokio::pin!(notified);
+        
+        // Register for the wakeup before checking the flag so a concurrent cancel is not lost
+        notified.as_mut().enable();
+        if !self.is_cancelled() {
+            notified.await;
+        }
+    }
+}
+
+/// Task waiting in a priority queue for a scheduling slot
+struct Waiter {
+    /// Task ID
+    task: TaskId,
+    /// Channel used to hand the slot to the task
+    sender: oneshot::Sender<SlotPermit>,
+}
+
+/// Task entry tracked by the scheduler
+struct TaskEntry {
+    /// Public task information
+    info: TaskInfo,
+    /// Cooperative cancellation flag shared with the task context
+    cancelled: Arc<Cancellation>,
+    /// Handle used to stop the underlying Tokio task
+    abort_handle: Option<AbortHandle>,
+    /// Handle used to wait for the underlying Tokio task
+    handle: Option<JoinHandle<()>>,
+}
+
+/// Mutable scheduler state protected by a lock
+struct SchedulerState {
+    /// Whether new tasks are accepted
+    accepting: bool,
+    /// Maximum number of tasks holding a slot at the same time
+    max_running: usize,
+    /// Number of slots currently handed out
+    running: usize,
+    /// Waiting tasks per priority class
+    queues: HashMap<TaskPriority, VecDeque<Waiter>>,
+    /// Virtual time per priority class for stride scheduling
+    pass: HashMap<TaskPriority, u64>,
+    /// Virtual time of the most recent dispatch
+    global_pass: u64,
+    /// All tasks known to the scheduler
+    tasks: HashMap<TaskId, TaskEntry>,
+    /// Generator of task IDs
+    handles: HandleGenerator,
+}
+
+/// State shared between the scheduler, its tasks and their slot permits
+struct SchedulerShared {
+    state: Mutex<SchedulerState>,
+}
+
+impl SchedulerShared {
+    fn lock(&self) -> std::sync::MutexGuard<'_, SchedulerState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+    
+    /// Hand free slots to waiting tasks, lowest virtual time first
+    ///
+    /// Must be called with the state lock held.
+    fn dispatch(self: &Arc<Self>, state: &mut SchedulerState) {
+        while state.running < state.max_running {
+            let next = state.queues.iter()
+                .filter(|(_, queue)| !queue.is_empty())
+                .map(|(priority, _)| *priority)
+                .min_by_key(|priority| (state.pass[priority], std::cmp::Reverse(priority_share(*priority))));
+            
+            let priority = match next {
+                Some(priority) => priority,
+                None => return,
+            };
+            
+            let waiter = match state.queues.get_mut(&priority).and_then(|queue| queue.pop_front()) {
+                Some(waiter) => waiter,
+                None => return,
+            };
+            
+            let pass = state.pass.entry(priority).or_insert(0);
+            state.global_pass = *pass;
+            *pass += STRIDE / priority_share(priority);
+            state.running += 1;
+            
+            let permit = SlotPermit { shared: self.clone() };
+            match waiter.sender.send(permit) {
+                Ok(()) => {
+                    if let Some(entry) = state.tasks.get_mut(&waiter.task) {
+                        entry.info.state = TaskState::Running;
+                        entry.info.dispatch_count += 1;
+                    }
+                },
+                Err(permit) => {
+                    // The task went away while waiting; reclaim the slot without
+                    // running the permit destructor, which would take the lock again
+                    std::mem::forget(permit);
+                    state.running -= 1;
+                },
+            }
+        }
+    }
+    
+    /// Queue a task for a scheduling slot
+    fn enqueue(self: &Arc<Self>, task: TaskId, priority: TaskPriority) -> oneshot::Receiver<SlotPermit> {
+        let (sender, receiver) = oneshot::channel();
+        let mut state = self.lock();
+        
+        // A class that was idle resumes at the current virtual time instead of
+        // catching up on the time it did not use
+        let global_pass = state.global_pass;
+        let idle = state.queues.get(&priority).is_none_or(|queue| queue.is_empty());
+        let pass = state.pass.entry(priority).or_insert(0);
+        if idle && *pass < global_pass {
+            *pass = global_pass;
+        }
+        
+        if let Some(entry) = state.tasks.get_mut(&task) {
+            entry.info.state = TaskState::Waiting;
+        }
+        state.queues.entry(priority).or_default().push_back(Waiter { task, sender });
+        self.dispatch(&mut state);
+        
+        receiver
+    }
+}
+
+/// Scheduling slot held by a running task; the slot is returned when dropped
+struct SlotPermit {
+    shared: Arc<SchedulerShared>,
+}
+
+impl Drop for SlotPermit {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.running = state.running.saturating_sub(1);
+        self.shared.dispatch(&mut state);
+    }
+}
+
+/// Context handed to a cognitive task
+///
+/// The context identifies the task and provides its preemption points.
+pub struct TaskContext {
+    /// Task ID
+    id: TaskId,
+    /// Task name
+    name: String,
+    /// Task priority class
+    priority: TaskPriority,
+    /// Cooperative cancellation flag
+    cancelled: Arc<Cancellation>,
+    /// Scheduler state
+    shared: Arc<SchedulerShared>,
+    /// Slot currently held by the task
+    permit: Mutex<Option<SlotPermit>>,
+}
+
+impl TaskContext {
+    /// Get the task ID
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+    
+    /// Get the task name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    
+    /// Get the task priority class
+    pub fn priority(&self) -> TaskPriority {
+        self.priority
+    }
+    
+    /// Check whether the task has been asked to stop
+    ///
+    /// # Returns
+    ///
+    /// `true` if the task has been cancelled, `false` otherwise
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.is_cancelled()
+    }
+    
+    /// Preemption point: hand the slot back and wait for the next turn
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` once the task holds a slot again, or `TaskCancelled` if the
+    /// task has been cancelled and should stop
+    pub async fn yield_now(&self) -> Result<(), TaskCancelled> {
+        if self.is_cancelled() {
+            return Err(TaskCancelled);
+        }
+        
+        // Queue up before returning the slot so this task competes with the
+        // waiting tasks when the slot is handed out again
+        let next = self.shared.enqueue(self.id, self.priority);
+        let previous = self.permit.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+        drop(previous);
+        
+        // Also give the runtime a turn in case the slot comes straight back
+        tokio::task::yield_now().await;
+        let permit = next.await.map_err(|_| TaskCancelled)?;
+        *self.permit.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(permit);
+        
+        if self.is_cancelled() {
+            return Err(TaskCancelled);
+        }
+        
+        Ok(())
+    }
+    
+    /// Preemption point: hand the slot back, sleep, then wait for the next turn
+    ///
+    /// The task does not hold a slot while it sleeps, so other tasks can run.
+    ///
+    /// # Arguments
+    ///
+    /// * `duration` - How long to sleep
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` once the task holds a slot again, or `TaskCancelled` if the
+    /// task has been cancelled and should stop
+    pub async fn sleep(&self, duration: Duration) -> Result<(), TaskCancelled> {
+        if self.is_cancelled() {
+            return Err(TaskCancelled);
+        }
+        
+        if let Some(entry) = self.shared.lock().tasks.get_mut(&self.id) {
+            entry.info.state = TaskState::Sleeping;
+        }
+        let previous = self.permit.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+        drop(previous);
+        
+        tokio::select! {
+            _ = tokio::time::sleep(duration) => {},
+            _ = self.cancelled.cancelled() => {},
+        }
+        if self.is_cancelled() {
+            return Err(TaskCancelled);
+        }
+        
+        let permit = self.shared.enqueue(self.id, self.priority).await.map_err(|_| TaskCancelled)?;
+        *self.permit.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(permit);
+        
+        if self.is_cancelled() {
+            return Err(TaskCancelled);
+        }
+        
+        Ok(())
+    }
+}
+
+/// Scheduler subsystem running the cognitive tasks of the Roya AGI
+///
+/// The scheduler is a cheap handle; clones share the same task set.
+#[derive(Clone)]
+pub struct Scheduler {
+    shared: Arc<SchedulerShared>,
+}
+
+impl std::fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.shared.lock();
+        f.debug_struct("Scheduler")
+            .field("max_running", &state.max_running)
+            .field("running", &state.running)
+            .field("tasks", &state.tasks.len())
+            .finish()
+    }
+}
+
+impl Scheduler {
+    /// Create a new scheduler
+    ///
+    /// # Arguments
+    ///
+    /// * `max_running` - Maximum number of tasks that hold a slot at the same time
+    ///
+    /// # Returns
+    ///
+    /// A new Scheduler instance
+    pub fn new(max_running: usize) -> Self {
+        info!("Creating scheduler with {} concurrent task slots", max_running);
+        
+        Self {
+            shared: Arc::new(SchedulerShared {
+                state: Mutex::new(SchedulerState {
+                    accepting: true,
+                    max_running: max_running.max(1),
+                    running: 0,
+                    queues: HashMap::new(),
+                    pass: HashMap::new(),
+                    global_pass: 0,
+                    tasks: HashMap::new(),
+                    handles: HandleGenerator::random(),
+                }),
+            }),
+        }
+    }
+    
+    /// Spawn a named cognitive task
+    ///
+    /// The task starts once the scheduler grants it a slot. This method must be
+    /// called from within a Tokio runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the task
+    /// * `priority` - Priority class of the task
+    /// * `task` - Function producing the task future from its context
+    ///
+    /// # Returns
+    ///
+    /// The ID of the spawned task, or an error if no runtime is available or the
+    /// scheduler is not accepting tasks
+    pub fn spawn<F, Fut>(&self, name: &str, priority: TaskPriority, task: F) -> Result<TaskId, SchedulerError>
+    where
+        F: FnOnce(Arc<TaskContext>) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.spawn_owned(None, name, priority, task)
+    }
+    
+    /// Spawn a named cognitive task on behalf of a principal
+    ///
+    /// The principal can cancel the task with the `task_cancel` system call.
+    ///
+    /// # Arguments
+    ///
+    /// * `principal` - Agent ID of the principal the task runs for
+    /// * `name` - Name of the task
+    /// * `priority` - Priority class of the task
+    /// * `task` - Function producing the task future from its context
+    ///
+    /// # Returns
+    ///
+    /// The ID of the spawned task, or an error if no runtime is available or the
+    /// scheduler is not accepting tasks
+    pub fn spawn_for<F, Fut>(&self, principal: &str, name: &str, priority: TaskPriority, task: F) -> Result<TaskId, SchedulerError>
+    where
+        F: FnOnce(Arc<TaskContext>) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.spawn_owned(Some(principal), name, priority, task)
+    }
+    
+    /// Spawn a named cognitive task for a principal or for the kernel
+    fn spawn_owned<F, Fut>(&self, owner: Option<&str>, name: &str, priority: TaskPriority, task: F) -> Result<TaskId, SchedulerError>
+    where
+        F: FnOnce(Arc<TaskContext>) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let runtime = tokio::runtime::Handle::try_current()
+            .map_err(|_| SchedulerError::NoRuntime)?;
+        
+        let id = self.shared.lock().handles.next_handle();
+        let cancelled = Arc::new(Cancellation::default());
+        let context = Arc::new(TaskContext {
+            id,
+            name: name.to_string(),
+            priority,
+            cancelled: cancelled.clone(),
+            shared: self.shared.clone(),
+            permit: Mutex::new(None),
+        });
+        
+        {
+            let mut state = self.shared.lock();
+            if !state.accepting {
+                return Err(SchedulerError::NotAccepting);
+            }
+            
+            state.tasks.insert(id, TaskEntry {
+                info: TaskInfo {
+                    id,
+                    name: name.to_string(),
+                    priority,
+                    state: TaskState::Waiting,
+                    dispatch_count: 0,
+                    owner: owner.map(str::to_string),
+                },
+                cancelled,
+                abort_handle: None,
+                handle: None,
+            });
+        }
+        
+        debug!("Spawning task {} ({}) with priority {:?}", name, id, priority);
+        
+        let shared = self.shared.clone();
+        let handle = runtime.spawn(async move {
+            let granted = shared.enqueue(id, priority).await;
+            if let Ok(permit) = granted {
+                *context.permit.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(permit);
+                if !context.is_cancelled() {
+                    task(context.clone()).await;
+                }
+            }
+            
+            let cancelled = context.is_cancelled();
+            let mut state = shared.lock();
+            if let Some(entry) = state.tasks.get_mut(&id) {
+                entry.info.state = if cancelled { TaskState::Cancelled } else { TaskState::Completed };
+            }
+            drop(state);
+            
+            // Return the slot last so the next task sees the final state
+            let permit = context.permit.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+            drop(permit);
+        });
+        
+        if let Some(entry) = self.shared.lock().tasks.get_mut(&id) {
+            entry.abort_handle = Some(handle.abort_handle());
+            entry.handle = Some(handle);
+        }
+        
+        Ok(id)
+    }
+    
+    /// Ask a task to stop at its next preemption point
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - ID of the task to cancel
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the task exists, or `SchedulerError::TaskNotFound`
+    pub fn cancel(&self, id: TaskId) -> Result<(), SchedulerError> {
+        let state = self.shared.lock();
+        let entry = state.tasks.get(&id).ok_or(SchedulerError::TaskNotFound(id))?;
+        
+        info!("Cancelling task {} ({})", entry.info.name, id);
+        entry.cancelled.cancel();
+        
+        Ok(())
+    }
+    
+    /// Ask a task spawned for a principal to stop at its next preemption point
+    ///
+    /// Tasks of the kernel, such as its watchdog and timer service, cannot be
+    /// cancelled this way.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - ID of the task to cancel
+    /// * `principal` - The principal cancelling the task, or `None` to cancel the task of any principal
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the task was cancelled, `SchedulerError::TaskNotFound`, or
+    /// `SchedulerError::NotOwner` if it belongs to the kernel or another principal
+    pub fn cancel_principal_task(&self, id: TaskId, principal: Option<&str>) -> Result<(), SchedulerError> {
+        let state = self.shared.lock();
+        let entry = state.tasks.get(&id).ok_or(SchedulerError::TaskNotFound(id))?;
+        let permitted = match (&entry.info.owner, principal) {
+            (None, _) => false,
+            (Some(owner), Some(principal)) => owner == principal,
+            (Some(_), None) => true,
+        };
+        if !permitted {
+            warn!("Refused to cancel task {} ({}) of {}", entry.info.name, id,
+                  entry.info.owner.as_deref().unwrap_or("the kernel"));
+            return Err(SchedulerError::NotOwner(id));
+        }
+        
+        info!("Cancelling task {} ({})", entry.info.name, id);
+        entry.cancelled.cancel();
+        
+        Ok(())
+    }
+    
+    /// Stop accepting new tasks and ask all unfinished tasks to stop at their
+    /// next preemption point
+    ///
+    /// # Returns
+    ///
+    /// The IDs of the tasks that were still unfinished
+    pub fn cancel_all(&self) -> Vec<TaskId> {
+        let mut state = self.shared.lock();
+        state.accepting = false;
+        
+        let ids: Vec<TaskId> = state.tasks.values()
+            .filter(|entry| !matches!(entry.info.state, TaskState::Completed | TaskState::Cancelled))
+            .map(|entry| {
+                entry.cancelled.cancel();
+                entry.info.id
+            })
+            .collect();
+        
+        if !ids.is_empty() {
+            info!("Cancelling {} scheduled tasks", ids.len());
+        }
+        
+        ids
+    }
+    
+    /// Stop a task immediately without waiting for a preemption point
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - ID of the task to abort
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the task exists, or `SchedulerError::TaskNotFound`
+    pub fn abort(&self, id: TaskId) -> Result<(), SchedulerError> {
+        let handle = {
+            let mut state = self.shared.lock();
+            let entry = state.tasks.get_mut(&id).ok_or(SchedulerError::TaskNotFound(id))?;
+            
+            warn!("Aborting task {} ({})", entry.info.name, id);
+            entry.cancelled.cancel();
+            if !matches!(entry.info.state, TaskState::Completed) {
+                entry.info.state = TaskState::Cancelled;
+            }
+            let handle = entry.abort_handle.clone();
+            
+            for queue in state.queues.values_mut() {
+                queue.retain(|waiter| waiter.task != id);
+            }
+            handle
+        };
+        
+        // Abort outside the lock: dropping the task future may return its slot
+        if let Some(handle) = handle {
+            handle.abort();
+        }
+        
+        Ok(())
+    }
+    
+    /// Wait for a task to finish
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - ID of the task to wait for
+    ///
+    /// # Returns
+    ///
+    /// The final state of the task, or `SchedulerError::TaskNotFound`
+    pub async fn join(&self, id: TaskId) -> Result<TaskState, SchedulerError> {
+        let handle = {
+            let mut state = self.shared.lock();
+            let entry = state.tasks.get_mut(&id).ok_or(SchedulerError::TaskNotFound(id))?;
+            entry.handle.take()
+        };
+        
+        if let Some(handle) = handle {
+            if let Err(e) = handle.await {
+                if e.is_panic() {
+                    warn!("Task {} panicked", id);
+                }
+                if let Some(entry) = self.shared.lock().tasks.get_mut(&id) {
+                    entry.info.state = TaskState::Cancelled;
+                }
+            }
+        }
+        
+        self.task_info(id).map(|info| info.state)
+    }
+    
+    /// Get information about a task
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - ID of the task
+    ///
+    /// # Returns
+    ///
+    /// Task information, or `SchedulerError::TaskNotFound`
+    pub fn task_info(&self, id: TaskId) -> Result<TaskInfo, SchedulerError> {
+        self.shared.lock().tasks.get(&id)
+            .map(|entry| entry.info.clone())
+            .ok_or(SchedulerError::TaskNotFound(id))
+    }
+    
+    /// Get information about all tasks known to the scheduler
+    ///
+    /// # Returns
+    ///
+    /// Vector of task information
+    pub fn list_tasks(&self) -> Vec<TaskInfo> {
+        self.shared.lock().tasks.values().map(|entry| entry.info.clone()).collect()
+    }
+    
+    /// Get the number of tasks waiting for a slot
+    ///
+    /// # Returns
+    ///
+    /// The total number of queued tasks across all priority classes
+    pub fn queue_depth(&self) -> usize {
+        self.shared.lock().queues.values().map(|queue| queue.len()).sum()
+    }
+    
+    /// Remove finished tasks from the task table
+    ///
+    /// # Returns
+    ///
+    /// The number of tasks removed
+    pub fn reap_finished(&self) -> usize {
+        let mut state = self.shared.lock();
+        let before = state.tasks.len();
+        state.tasks.retain(|_, entry| {
+            !matches!(entry.info.state, TaskState::Completed | TaskState::Cancelled)
+        });
+        before - state.tasks.len()
+    }
+}
+
+impl Scheduler {
+    /// Stop accepting new tasks and abort all unfinished tasks
+    ///
+    /// # Returns
+    ///
+    /// The number of tasks that were aborted
+    pub fn stop_all(&self) -> usize {
+        let handles: Vec<AbortHandle> = {
+            let mut state = self.shared.lock();
+            state.accepting = false;
+            let handles = state.tasks.values_mut()
+                .filter(|entry| !matches!(entry.info.state, TaskState::Completed | TaskState::Cancelled))
+                .filter_map(|entry| {
+                    entry.cancelled.cancel();
+                    entry.info.state = TaskState::Cancelled;
+                    entry.abort_handle.clone()
+                })
+                .collect();
+            
+            state.queues.clear();
+            handles
+        };
+        
+        if !handles.is_empty() {
+            info!("Stopping {} scheduled tasks", handles.len());
+        }
+        
+        // Abort outside the lock: dropping a task future may return its slot
+        let count = handles.len();
+        for handle in handles {
+            handle.abort();
+        }
+        
+        count
+    }
+}
+
+impl Subsystem for Scheduler {
+    fn name(&self) -> &str {
+        "scheduler"
+    }
+    
+    fn initialize(&mut self) -> Result<(), SubsystemError> {
+        self.shared.lock().accepting = true;
+        info!("Scheduler ready");
+        Ok(())
+    }
+    
+    fn shutdown(&mut self) -> Result<(), SubsystemError> {
+        self.stop_all();
+        Ok(())
+    }
+    
+    fn health(&self) -> SubsystemHealth {
+        let state = self.shared.lock();
+        if !state.accepting {
+            SubsystemHealth::Failed("Scheduler is not accepting tasks".to_string())
+        } else {
+            SubsystemHealth::Healthy
+        }
+    }
+    
+    fn attach(&mut self, context: &KernelContext) {
+        self.shared.lock().handles = context.handles.clone();
+    }
+    
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
@@ -0,0 +1,233 @@
+//! Kernel snapshots for RoyaOS
+//!
+//! A snapshot holds the state every running subsystem saves through its `snapshot`
+//! hook, keyed by subsystem name. Snapshots are stored as versioned JSON archives in
+//! the `snapshots` directory below the data directory, one file per snapshot, and
+//! only the most recent `SNAPSHOT_RETENTION` files are kept.
+//!
+//! Files are written to a temporary name and renamed into place once they are on
+//! disk, so a crash while saving never leaves a truncated snapshot behind.
+
+use log::{info, debug, warn};
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::KernelError;
+
+/// Version of the snapshot archive format written by this kernel
+pub const SNAPSHOT_FORMAT_VERSION: u32 = 1;
+
+/// Directory below the data directory that holds the snapshots
+pub const SNAPSHOT_DIR: &str = "snapshots";
+
+/// Number of snapshot files kept in the snapshot directory
+pub const SNAPSHOT_RETENTION: usize = 5;
+
+/// Prefix of snapshot file names
+const FILE_PREFIX: &str = "snapshot-";
+
+/// Extension of snapshot file names
+const FILE_EXTENSION: &str = ".json";
+
+/// Saved state of a running kernel
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Version of the archive format
+    pub format_version: u32,
+    /// Version of the kernel that took the snapshot
+    pub kernel_version: String,
+    /// Time the snapshot was taken in milliseconds since the Unix epoch
+    pub created_at: u64,
+    /// Saved state of each subsystem that has state to save
+    pub subsystems: BTreeMap<String, serde_json::Value>,
+}
+
+/// Leading part of a snapshot file, read before the rest to check the format version
+#[derive(Deserialize)]
+struct FormatProbe {
+    /// Version of the archive format
+    format_version: u32,
+}
+
+impl Snapshot {
+    /// Create an empty snapshot taken now
+    ///
+    /// # Arguments
+    ///
+    /// * `kernel_version` - Version of the kernel taking the snapshot
+    ///
+    /// # Returns
+    ///
+    /// A new Snapshot without subsystem state
+    pub fn new(kernel_version: &str) -> Self {
+        Self {
+            format_version: SNAPSHOT_FORMAT_VERSION,
+            kernel_version: kernel_version.to_string(),
+            created_at: unix_millis(),
+            subsystems: BTreeMap::new(),
+        }
+    }
+    
+    /// Write the snapshot to the snapshot directory below a data directory
+    ///
+    /// The snapshot directory is created if needed, and snapshots beyond the
+    /// most recent `SNAPSHOT_RETENTION` are removed once the new one is saved.
+    ///
+    /// # Arguments
+    ///
+    /// * `data_dir` - The data directory
+    ///
+    /// # Returns
+    ///
+    /// The path of the written snapshot file, or the error that stopped it
+    pub fn write(&self, data_dir: &Path) -> Result<PathBuf, KernelError> {
+        let dir = data_dir.join(SNAPSHOT_DIR);
+        fs::create_dir_all(&dir).map_err(|source| KernelError::SnapshotIo { path: dir.clone(), source })?;
+        
+        let name = format!("{}{:020}{}", FILE_PREFIX, self.created_at, FILE_EXTENSION);
+        let path = dir.join(&name);
+        let temporary = dir.join(format!(".{}.tmp", name));
+        
+        let encoded = serde_json::to_vec_pretty(self)
+            .map_err(|source| KernelError::SnapshotFormat { path: path.clone(), source })?;
+        write_synced(&temporary, &encoded)
+            .and_then(|()| fs::rename(&temporary, &path))
+            .map_err(|source| {
+                let _ = fs::remove_file(&temporary);
+                KernelError::SnapshotIo { path: path.clone(), source }
+            })?;
+        
+        // Persist the rename itself
+        if let Err(e) = File::open(&dir).and_then(|dir| dir.sync_all()) {
+            warn!("Failed to sync snapshot directory {:?}: {}", dir, e);
+        }
+        
+        info!("Saved snapshot of {} subsystems to {:?}", self.subsystems.len(), path);
+        prune(&dir);
+        
+        Ok(path)
+    }
+    
+    /// Read a snapshot file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the snapshot file
+    ///
+    /// # Returns
+    ///
+    /// The snapshot, or an error if the file cannot be read, is not a snapshot
+    /// or was written in an unsupported format version
+    pub fn read(path: &Path) -> Result<Self, KernelError> {
+        let contents = fs::read(path).map_err(|source| KernelError::SnapshotIo { path: path.to_path_buf(), source })?;
+        
+        let probe: FormatProbe = serde_json::from_slice(&contents)
+            .map_err(|source| KernelError::SnapshotFormat { path: path.to_path_buf(), source })?;
+        if probe.format_version != SNAPSHOT_FORMAT_VERSION {
+            return Err(KernelError::SnapshotVersion {
+                path: path.to_path_buf(),
+                found: probe.format_version,
+                supported: SNAPSHOT_FORMAT_VERSION,
+            });
+        }
+        
+        serde_json::from_slice(&contents).map_err(|source| KernelError::SnapshotFormat { path: path.to_path_buf(), source })
+    }
+    
+    /// Find the most recent snapshot below a data directory
+    ///
+    /// # Arguments
+    ///
+    /// * `data_dir` - The data directory
+    ///
+    /// # Returns
+    ///
+    /// The path of the most recent snapshot file, `None` if there is none, or an
+    /// error if the snapshot directory cannot be read
+    pub fn latest(data_dir: &Path) -> Result<Option<PathBuf>, KernelError> {
+        let dir = data_dir.join(SNAPSHOT_DIR);
+        if !dir.exists() {
+            return Ok(None);
+        }
+        
+        let files = snapshot_files(&dir).map_err(|source| KernelError::SnapshotIo { path: dir, source })?;
+        Ok(files.into_iter().last())
+    }
+}
+
+/// Get the current time in milliseconds since the Unix epoch
+pub(crate) fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Write a file and wait until its contents are on disk
+///
+/// # Arguments
+///
+/// * `path` - Path of the file
+/// * `contents` - The contents to write
+///
+/// # Returns
+///
+/// `Ok(())` once the file is synced, or the I/O error that stopped it
+fn write_synced(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+/// List the snapshot files in a snapshot directory
+///
+/// # Arguments
+///
+/// * `dir` - The snapshot directory
+///
+/// # Returns
+///
+/// Paths of the snapshot files from oldest to newest
+fn snapshot_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_snapshot = path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with(FILE_PREFIX) && name.ends_with(FILE_EXTENSION));
+        if is_snapshot {
+            files.push(path);
+        }
+    }
+    
+    // Names embed the zero-padded creation time, so they sort chronologically
+    files.sort();
+    Ok(files)
+}
+
+/// Remove all but the most recent `SNAPSHOT_RETENTION` snapshot files
+///
+/// # Arguments
+///
+/// * `dir` - The snapshot directory
+fn prune(dir: &Path) {
+    let files = match snapshot_files(dir) {
+        Ok(files) => files,
+        Err(e) => {
+            warn!("Failed to list snapshots in {:?}: {}", dir, e);
+            return;
+        }
+    };
+    
+    let excess = files.len().saturating_sub(SNAPSHOT_RETENTION);
+    for path in &files[..excess] {
+        match fs::remove_file(path) {
+            Ok(()) => debug!("Removed old snapshot {:?}", path),
+            Err(e) => warn!("Failed to remove old snapshot {:?}: {}", path, e),
+        }
+    }
+}
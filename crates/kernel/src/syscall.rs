@@ -0,0 +1,684 @@
+//! System call types for the RoyaOS kernel
+//!
+//! This module defines the typed system calls that the Roya AGI issues to the kernel
+//! and the structured results the kernel returns. Both types support serde so they can
+//! travel unchanged over the interface layer.
+//!
+//! The string form used by `Kernel::process_syscall` is a thin layer on top of these
+//! types: `Syscall::parse` turns a syscall name and its arguments into a `Syscall`, and
+//! the `Display` implementation of `SyscallResult` renders the result back to a string.
+//!
+//! Every system call maps to the permission it requires, which the kernel checks with
+//! the security subsystem before the call reaches any other subsystem.
+
+use crate::batch::BatchError;
+use crate::error::KernelError;
+use crate::quota::QuotaError;
+use crate::scheduler::{SchedulerError, TaskId};
+use crate::timer::{JobId, JobInfo, TimerError};
+use royaos_common::{format_duration, parse_duration, ErrorCode, Schedule};
+use royaos_memory::{MemoryCategory, MemoryError, MemoryHandle, SimilarMemory};
+use royaos_security::{Permission, SecurityError};
+use royaos_tools::{ToolError, ToolHandle, ToolResult};
+use serde::{Serialize, Deserialize};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Number of memories `memory_search` returns when no limit is given
+const DEFAULT_SEARCH_LIMIT: usize = 5;
+
+/// Reference to a registered tool
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ToolRef {
+    /// Tool referenced by its handle
+    Handle(ToolHandle),
+    /// Tool referenced by the identifier declared in its metadata
+    Id(String),
+}
+
+impl From<&str> for ToolRef {
+    fn from(s: &str) -> Self {
+        match s.parse() {
+            Ok(handle) => ToolRef::Handle(handle),
+            Err(_) => ToolRef::Id(s.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ToolRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ToolRef::Handle(handle) => write!(f, "{}", handle),
+            ToolRef::Id(id) => write!(f, "{}", id),
+        }
+    }
+}
+
+/// System call issued to the kernel
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Syscall {
+    /// Allocate a block of memory
+    MemoryAlloc {
+        /// Size of the allocation in bytes
+        size: usize,
+        /// Description of the memory's purpose
+        purpose: String,
+        /// Memory category for prioritization
+        category: MemoryCategory,
+    },
+    /// Release a block of memory
+    MemoryFree {
+        /// Handle to the allocation
+        handle: MemoryHandle,
+    },
+    /// Store a value in a block of memory
+    MemoryPut {
+        /// Handle to the allocation
+        handle: MemoryHandle,
+        /// The value to store
+        value: serde_json::Value,
+    },
+    /// Load the value stored in a block of memory
+    MemoryGet {
+        /// Handle to the allocation
+        handle: MemoryHandle,
+    },
+    /// Set or remove the embedding of a block of memory
+    MemoryEmbed {
+        /// Handle to the allocation
+        handle: MemoryHandle,
+        /// The embedding vector, or `None` to remove it
+        embedding: Option<Vec<f32>>,
+    },
+    /// Find the blocks of memory whose embeddings are most similar to a query
+    MemorySearch {
+        /// The query vector
+        query: Vec<f32>,
+        /// Maximum number of blocks to return
+        k: usize,
+        /// Only return blocks of this category, if given
+        category: Option<MemoryCategory>,
+    },
+    /// Promote frequently accessed short-term and working memory to long-term memory
+    MemoryConsolidate,
+    /// Set or remove the time to live of a block of memory
+    MemoryExpire {
+        /// Handle to the allocation
+        handle: MemoryHandle,
+        /// Time to live from now, or `None` to keep the block until it is freed
+        ttl: Option<Duration>,
+    },
+    /// Get the retention strength of a block of memory
+    MemoryStrength {
+        /// Handle to the allocation
+        handle: MemoryHandle,
+    },
+    /// Execute a tool capability
+    ToolExecute {
+        /// Tool to execute
+        tool: ToolRef,
+        /// Name of the capability to execute
+        capability: String,
+        /// Parameters for the capability
+        params: serde_json::Value,
+    },
+    /// Check whether an operation is permitted
+    SecurityCheck {
+        /// Type of resource being accessed
+        resource_type: String,
+        /// Operation being performed
+        operation: String,
+        /// Resource being accessed
+        resource: String,
+    },
+    /// Add a permission to the security policy
+    SecurityGrant {
+        /// Type of resource the permission covers
+        resource_type: String,
+        /// Operation the permission allows
+        operation: String,
+        /// Resource the permission covers, or `*` for all resources
+        resource: String,
+    },
+    /// Remove a permission from the security policy
+    SecurityRevoke {
+        /// Type of resource the permission covers
+        resource_type: String,
+        /// Operation the permission allows
+        operation: String,
+        /// Resource the permission covers, or `*` for all resources
+        resource: String,
+    },
+    /// Ask a cognitive task to stop at its next preemption point
+    TaskCancel {
+        /// ID of the task to cancel
+        task: TaskId,
+    },
+    /// Schedule a system call on the timer service
+    JobSchedule {
+        /// When the system call runs
+        schedule: Schedule,
+        /// The system call to run
+        syscall: Box<Syscall>,
+    },
+    /// List the jobs scheduled on the timer service
+    JobList,
+    /// Cancel a scheduled job
+    JobCancel {
+        /// ID of the job to cancel
+        job: JobId,
+    },
+}
+
+impl Syscall {
+    /// Parse a system call from its string form
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the system call
+    /// * `args` - Arguments for the system call
+    ///
+    /// # Returns
+    ///
+    /// The parsed system call, or the reason the arguments were rejected
+    pub fn parse(name: &str, args: &[&str]) -> Result<Self, SyscallError> {
+        match name {
+            "memory_alloc" => {
+                if args.is_empty() {
+                    return Err(SyscallError::InvalidArguments("memory_alloc requires at least 1 argument".to_string()));
+                }
+                
+                let size = args[0].parse()
+                    .map_err(|_| SyscallError::InvalidArguments(format!("Invalid allocation size: {}", args[0])))?;
+                let purpose = args.get(1).copied().unwrap_or("syscall").to_string();
+                let category = match args.get(2) {
+                    Some(category) => MemoryCategory::from_str(category)?,
+                    None => MemoryCategory::Working,
+                };
+                
+                Ok(Syscall::MemoryAlloc { size, purpose, category })
+            },
+            "memory_free" => {
+                if args.is_empty() {
+                    return Err(SyscallError::InvalidArguments("memory_free requires at least 1 argument".to_string()));
+                }
+                
+                let handle = args[0].parse()
+                    .map_err(|_| SyscallError::InvalidArguments(format!("Invalid handle: {}", args[0])))?;
+                
+                Ok(Syscall::MemoryFree { handle })
+            },
+            "memory_put" => {
+                if args.len() < 2 {
+                    return Err(SyscallError::InvalidArguments("memory_put requires 2 arguments".to_string()));
+                }
+                
+                let handle = args[0].parse()
+                    .map_err(|_| SyscallError::InvalidArguments(format!("Invalid handle: {}", args[0])))?;
+                let value = serde_json::from_str(args[1])
+                    .map_err(|e| SyscallError::InvalidArguments(format!("Failed to parse value: {}", e)))?;
+                
+                Ok(Syscall::MemoryPut { handle, value })
+            },
+            "memory_get" => {
+                if args.is_empty() {
+                    return Err(SyscallError::InvalidArguments("memory_get requires 1 argument".to_string()));
+                }
+                
+                let handle = args[0].parse()
+                    .map_err(|_| SyscallError::InvalidArguments(format!("Invalid handle: {}", args[0])))?;
+                
+                Ok(Syscall::MemoryGet { handle })
+            },
+            "memory_embed" => {
+                if args.len() < 2 {
+                    return Err(SyscallError::InvalidArguments("memory_embed requires 2 arguments".to_string()));
+                }
+                
+                let handle = args[0].parse()
+                    .map_err(|_| SyscallError::InvalidArguments(format!("Invalid handle: {}", args[0])))?;
+                let embedding = serde_json::from_str(args[1])
+                    .map_err(|e| SyscallError::InvalidArguments(format!("Failed to parse embedding: {}", e)))?;
+                
+                Ok(Syscall::MemoryEmbed { handle, embedding })
+            },
+            "memory_search" => {
+                if args.is_empty() {
+                    return Err(SyscallError::InvalidArguments("memory_search requires at least 1 argument".to_string()));
+                }
+                
+                let query = serde_json::from_str(args[0])
+                    .map_err(|e| SyscallError::InvalidArguments(format!("Failed to parse query vector: {}", e)))?;
+                let k = match args.get(1) {
+                    Some(k) => k.parse().map_err(|_| SyscallError::InvalidArguments(format!("Invalid result limit: {}", k)))?,
+                    None => DEFAULT_SEARCH_LIMIT,
+                };
+                let category = args.get(2).map(|category| MemoryCategory::from_str(category)).transpose()?;
+                
+                Ok(Syscall::MemorySearch { query, k, category })
+            },
+            "memory_consolidate" => Ok(Syscall::MemoryConsolidate),
+            "memory_expire" => {
+                if args.len() < 2 {
+                    return Err(SyscallError::InvalidArguments("memory_expire requires 2 arguments".to_string()));
+                }
+                
+                let handle = args[0].parse()
+                    .map_err(|_| SyscallError::InvalidArguments(format!("Invalid handle: {}", args[0])))?;
+                let ttl = match args[1] {
+                    "none" => None,
+                    ttl => Some(parse_duration(ttl)
+                        .ok_or_else(|| SyscallError::InvalidArguments(format!("Invalid time to live: {}", ttl)))?),
+                };
+                
+                Ok(Syscall::MemoryExpire { handle, ttl })
+            },
+            "memory_strength" => {
+                if args.is_empty() {
+                    return Err(SyscallError::InvalidArguments("memory_strength requires 1 argument".to_string()));
+                }
+                
+                let handle = args[0].parse()
+                    .map_err(|_| SyscallError::InvalidArguments(format!("Invalid handle: {}", args[0])))?;
+                
+                Ok(Syscall::MemoryStrength { handle })
+            },
+            "tool_execute" => {
+                if args.len() < 2 {
+                    return Err(SyscallError::InvalidArguments("tool_execute requires at least 2 arguments".to_string()));
+                }
+                
+                let params = match args.get(2) {
+                    Some(params) => serde_json::from_str(params)
+                        .map_err(|e| SyscallError::InvalidArguments(format!("Failed to parse parameters: {}", e)))?,
+                    None => serde_json::json!({}),
+                };
+                
+                Ok(Syscall::ToolExecute {
+                    tool: ToolRef::from(args[0]),
+                    capability: args[1].to_string(),
+                    params,
+                })
+            },
+            "security_check" => {
+                if args.len() < 3 {
+                    return Err(SyscallError::InvalidArguments("security_check requires 3 arguments".to_string()));
+                }
+                
+                Ok(Syscall::SecurityCheck {
+                    resource_type: args[0].to_string(),
+                    operation: args[1].to_string(),
+                    resource: args[2].to_string(),
+                })
+            },
+            "security_grant" | "security_revoke" => {
+                if args.len() < 3 {
+                    return Err(SyscallError::InvalidArguments(format!("{} requires 3 arguments", name)));
+                }
+                
+                let (resource_type, operation, resource) = (args[0].to_string(), args[1].to_string(), args[2].to_string());
+                if name == "security_grant" {
+                    Ok(Syscall::SecurityGrant { resource_type, operation, resource })
+                } else {
+                    Ok(Syscall::SecurityRevoke { resource_type, operation, resource })
+                }
+            },
+            "task_cancel" => {
+                if args.is_empty() {
+                    return Err(SyscallError::InvalidArguments("task_cancel requires 1 argument".to_string()));
+                }
+                
+                let task = args[0].parse()
+                    .map_err(|_| SyscallError::InvalidArguments(format!("Invalid task ID: {}", args[0])))?;
+                
+                Ok(Syscall::TaskCancel { task })
+            },
+            "job_schedule" => {
+                if args.len() < 2 {
+                    return Err(SyscallError::InvalidArguments("job_schedule requires at least 2 arguments".to_string()));
+                }
+                
+                let schedule = args[0].parse().map_err(TimerError::from)?;
+                let syscall = Syscall::parse(args[1], &args[2..])?;
+                if let Syscall::JobSchedule { .. } = syscall {
+                    return Err(SyscallError::InvalidArguments("job_schedule cannot schedule job_schedule".to_string()));
+                }
+                
+                Ok(Syscall::JobSchedule { schedule, syscall: Box::new(syscall) })
+            },
+            "job_list" => Ok(Syscall::JobList),
+            "job_cancel" => {
+                if args.is_empty() {
+                    return Err(SyscallError::InvalidArguments("job_cancel requires 1 argument".to_string()));
+                }
+                
+                let job = args[0].parse()
+                    .map_err(|_| SyscallError::InvalidArguments(format!("Invalid job ID: {}", args[0])))?;
+                
+                Ok(Syscall::JobCancel { job })
+            },
+            _ => Err(SyscallError::UnknownSyscall(name.to_string())),
+        }
+    }
+    
+    /// Get the name of the system call
+    ///
+    /// # Returns
+    ///
+    /// The name used for this system call in its string form
+    pub fn name(&self) -> &'static str {
+        match self {
+            Syscall::MemoryAlloc { .. } => "memory_alloc",
+            Syscall::MemoryFree { .. } => "memory_free",
+            Syscall::MemoryPut { .. } => "memory_put",
+            Syscall::MemoryGet { .. } => "memory_get",
+            Syscall::MemoryEmbed { .. } => "memory_embed",
+            Syscall::MemorySearch { .. } => "memory_search",
+            Syscall::MemoryConsolidate => "memory_consolidate",
+            Syscall::MemoryExpire { .. } => "memory_expire",
+            Syscall::MemoryStrength { .. } => "memory_strength",
+            Syscall::ToolExecute { .. } => "tool_execute",
+            Syscall::SecurityCheck { .. } => "security_check",
+            Syscall::SecurityGrant { .. } => "security_grant",
+            Syscall::SecurityRevoke { .. } => "security_revoke",
+            Syscall::TaskCancel { .. } => "task_cancel",
+            Syscall::JobSchedule { .. } => "job_schedule",
+            Syscall::JobList => "job_list",
+            Syscall::JobCancel { .. } => "job_cancel",
+        }
+    }
+    
+    /// Get the arguments of the system call in its string form
+    ///
+    /// # Returns
+    ///
+    /// The arguments `parse` turns back into this system call
+    pub fn args(&self) -> Vec<String> {
+        match self {
+            Syscall::MemoryAlloc { size, purpose, category } => {
+                vec![size.to_string(), purpose.clone(), category.as_str().to_string()]
+            },
+            Syscall::MemoryFree { handle } | Syscall::MemoryGet { handle } | Syscall::MemoryStrength { handle } => {
+                vec![handle.to_string()]
+            },
+            Syscall::MemoryPut { handle, value } => vec![handle.to_string(), value.to_string()],
+            Syscall::MemoryEmbed { handle, embedding } => vec![handle.to_string(), vector_arg(embedding)],
+            Syscall::MemoryExpire { handle, ttl } => {
+                vec![handle.to_string(), ttl.map_or("none".to_string(), format_duration)]
+            },
+            Syscall::MemorySearch { query, k, category } => {
+                let mut args = vec![vector_arg(query), k.to_string()];
+                args.extend(category.map(|category| category.as_str().to_string()));
+                args
+            },
+            Syscall::ToolExecute { tool, capability, params } => {
+                vec![tool.to_string(), capability.clone(), params.to_string()]
+            },
+            Syscall::SecurityCheck { resource_type, operation, resource }
+            | Syscall::SecurityGrant { resource_type, operation, resource }
+            | Syscall::SecurityRevoke { resource_type, operation, resource } => {
+                vec![resource_type.clone(), operation.clone(), resource.clone()]
+            },
+            Syscall::TaskCancel { task } => vec![task.to_string()],
+            Syscall::JobSchedule { schedule, syscall } => {
+                let mut args = vec![schedule.to_string(), syscall.name().to_string()];
+                args.extend(syscall.args());
+                args
+            },
+            Syscall::MemoryConsolidate | Syscall::JobList => Vec::new(),
+            Syscall::JobCancel { job } => vec![job.to_string()],
+        }
+    }
+    
+    /// Get the permission required to execute the system call
+    ///
+    /// # Returns
+    ///
+    /// The resource type, operation and resource the call accesses
+    pub fn required_permission(&self) -> Permission {
+        let (resource_type, operation, resource) = match self {
+            Syscall::MemoryAlloc { category, .. } => ("memory", "allocate", category.as_str().to_string()),
+            Syscall::MemoryFree { handle } => ("memory", "free", handle.to_string()),
+            Syscall::MemoryPut { handle, .. } => ("memory", "write", handle.to_string()),
+            Syscall::MemoryGet { handle } => ("memory", "read", handle.to_string()),
+            Syscall::MemoryEmbed { handle, .. } => ("memory", "write", handle.to_string()),
+            Syscall::MemorySearch { category, .. } => {
+                ("memory", "read", category.map_or("*", |category| category.as_str()).to_string())
+            },
+            Syscall::MemoryConsolidate => ("memory", "consolidate", "*".to_string()),
+            Syscall::MemoryExpire { handle, .. } => ("memory", "write", handle.to_string()),
+            Syscall::MemoryStrength { handle } => ("memory", "read", handle.to_string()),
+            Syscall::ToolExecute { tool, .. } => ("tool", "execute", tool.to_string()),
+            Syscall::SecurityCheck { resource_type, .. } => ("security", "check", resource_type.clone()),
+            Syscall::SecurityGrant { resource_type, .. } => ("security", "grant", resource_type.clone()),
+            Syscall::SecurityRevoke { resource_type, .. } => ("security", "revoke", resource_type.clone()),
+            Syscall::TaskCancel { task } => ("task", "cancel", task.to_string()),
+            Syscall::JobSchedule { syscall, .. } => ("job", "schedule", syscall.name().to_string()),
+            Syscall::JobList => ("job", "list", "*".to_string()),
+            Syscall::JobCancel { job } => ("job", "cancel", job.to_string()),
+        };
+        
+        Permission {
+            resource_type: resource_type.to_string(),
+            operation: operation.to_string(),
+            resource,
+        }
+    }
+}
+
+/// Render a vector argument as the JSON `Syscall::parse` reads back
+fn vector_arg<T: Serialize>(vector: &T) -> String {
+    serde_json::to_string(vector).expect("Vectors of numbers serialize to JSON")
+}
+
+/// Result of a successful system call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SyscallResult {
+    /// Memory was allocated
+    MemoryAllocated {
+        /// Handle to the new allocation
+        handle: MemoryHandle,
+    },
+    /// Memory was released
+    MemoryFreed {
+        /// Handle to the released allocation
+        handle: MemoryHandle,
+    },
+    /// A value was stored in memory
+    MemoryStored {
+        /// Handle to the allocation holding the value
+        handle: MemoryHandle,
+    },
+    /// A value was loaded from memory
+    MemoryLoaded {
+        /// The stored value
+        value: serde_json::Value,
+    },
+    /// The embedding of a block of memory was set or removed
+    MemoryEmbedded {
+        /// Handle to the allocation
+        handle: MemoryHandle,
+    },
+    /// Blocks of memory were found by similarity
+    MemoryFound {
+        /// The blocks found, most similar first
+        matches: Vec<SimilarMemory>,
+    },
+    /// Memory was consolidated
+    MemoryConsolidated {
+        /// Handles to the blocks promoted to long-term memory
+        promoted: Vec<MemoryHandle>,
+    },
+    /// The time to live of a block of memory was set or removed
+    MemoryExpirySet {
+        /// Handle to the allocation
+        handle: MemoryHandle,
+    },
+    /// The retention strength of a block of memory was read
+    MemoryStrength {
+        /// The strength, from 0.0 to 1.0
+        strength: f64,
+    },
+    /// A tool capability was executed
+    ToolExecuted {
+        /// Result reported by the tool
+        result: ToolResult,
+    },
+    /// A permission check was performed
+    PermissionChecked {
+        /// Whether the operation is permitted
+        allowed: bool,
+    },
+    /// A permission was added to the security policy
+    PermissionGranted {
+        /// The granted permission
+        permission: Permission,
+        /// Whether the policy lacked the permission before
+        changed: bool,
+    },
+    /// A permission was removed from the security policy
+    PermissionRevoked {
+        /// The revoked permission
+        permission: Permission,
+        /// Whether the policy held the permission before
+        changed: bool,
+    },
+    /// A task was asked to stop
+    TaskCancelled {
+        /// ID of the cancelled task
+        task: TaskId,
+    },
+    /// A system call was scheduled
+    JobScheduled {
+        /// ID of the new job
+        job: JobId,
+    },
+    /// The scheduled jobs were listed
+    JobsListed {
+        /// The scheduled jobs, ordered by their next run
+        jobs: Vec<JobInfo>,
+    },
+    /// A job was cancelled
+    JobCancelled {
+        /// ID of the cancelled job
+        job: JobId,
+    },
+}
+
+impl fmt::Display for SyscallResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyscallResult::MemoryAllocated { handle } => write!(f, "{}", handle),
+            SyscallResult::MemoryFreed { handle } => write!(f, "{}", handle),
+            SyscallResult::MemoryStored { handle } => write!(f, "{}", handle),
+            SyscallResult::MemoryLoaded { value } => write!(f, "{}", value),
+            SyscallResult::MemoryEmbedded { handle } => write!(f, "{}", handle),
+            SyscallResult::MemoryFound { matches } => {
+                let json = serde_json::to_string(matches).map_err(|_| fmt::Error)?;
+                write!(f, "{}", json)
+            },
+            SyscallResult::MemoryExpirySet { handle } => write!(f, "{}", handle),
+            SyscallResult::MemoryStrength { strength } => write!(f, "{}", strength),
+            SyscallResult::MemoryConsolidated { promoted } => {
+                let json = serde_json::to_string(promoted).map_err(|_| fmt::Error)?;
+                write!(f, "{}", json)
+            },
+            SyscallResult::ToolExecuted { result } => {
+                let json = serde_json::to_string(result).map_err(|_| fmt::Error)?;
+                write!(f, "{}", json)
+            },
+            SyscallResult::PermissionChecked { allowed } => {
+                write!(f, "{}", if *allowed { "allowed" } else { "denied" })
+            },
+            SyscallResult::PermissionGranted { changed, .. } => {
+                write!(f, "{}", if *changed { "granted" } else { "unchanged" })
+            },
+            SyscallResult::PermissionRevoked { changed, .. } => {
+                write!(f, "{}", if *changed { "revoked" } else { "unchanged" })
+            },
+            SyscallResult::TaskCancelled { task } => write!(f, "{}", task),
+            SyscallResult::JobScheduled { job } => write!(f, "{}", job),
+            SyscallResult::JobsListed { jobs } => {
+                let json = serde_json::to_string(jobs).map_err(|_| fmt::Error)?;
+                write!(f, "{}", json)
+            },
+            SyscallResult::JobCancelled { job } => write!(f, "{}", job),
+        }
+    }
+}
+
+/// Error returned when a system call cannot be completed
+#[derive(Error, Debug)]
+pub enum SyscallError {
+    /// The kernel is not running
+    #[error("Kernel is not running")]
+    NotRunning,
+    
+    /// The security subsystem denied the call
+    #[error("Permission denied: {0}")]
+    PermissionDenied(Permission),
+    
+    /// No system call has the name
+    #[error("Unknown syscall: {0}")]
+    UnknownSyscall(String),
+    
+    /// The arguments do not match what the system call expects
+    #[error("{0}")]
+    InvalidArguments(String),
+    
+    /// The memory subsystem rejected the call
+    #[error(transparent)]
+    Memory(#[from] MemoryError),
+    
+    /// The tools subsystem rejected the call
+    #[error(transparent)]
+    Tool(#[from] ToolError),
+    
+    /// The security subsystem rejected a change to the security policy
+    #[error(transparent)]
+    Security(#[from] SecurityError),
+    
+    /// The scheduler rejected the call
+    #[error(transparent)]
+    Scheduler(#[from] SchedulerError),
+    
+    /// The calling principal is unknown or exceeded its quota
+    #[error(transparent)]
+    Quota(#[from] QuotaError),
+    
+    /// The timer service rejected the call
+    #[error(transparent)]
+    Timer(#[from] TimerError),
+    
+    /// The batch or one of its steps was rejected
+    #[error(transparent)]
+    Batch(#[from] BatchError),
+    
+    /// The kernel could not reach the subsystem handling the call
+    #[error(transparent)]
+    Kernel(#[from] KernelError),
+}
+
+impl ErrorCode for SyscallError {
+    fn code(&self) -> &'static str {
+        match self {
+            SyscallError::NotRunning => "KERNEL_NOT_RUNNING",
+            SyscallError::PermissionDenied(_) => "SECURITY_DENIED",
+            SyscallError::UnknownSyscall(_) => "SYSCALL_UNKNOWN",
+            SyscallError::InvalidArguments(_) => "SYSCALL_INVALID_ARGUMENTS",
+            SyscallError::Memory(error) => error.code(),
+            SyscallError::Tool(error) => error.code(),
+            SyscallError::Security(error) => error.code(),
+            SyscallError::Scheduler(error) => error.code(),
+            SyscallError::Quota(error) => error.code(),
+            SyscallError::Timer(error) => error.code(),
+            SyscallError::Batch(error) => error.code(),
+            SyscallError::Kernel(error) => error.code(),
+        }
+    }
+}
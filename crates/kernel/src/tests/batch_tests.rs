@@ -0,0 +1,175 @@
+//! System call batch tests
+//!
+//! This module tests running system calls as a batch, referring to earlier results,
+//! and rolling a batch back when one of its steps fails.
+
+use crate::{BatchStep, ErrorCode, Kernel, Quota, StepOutcome, SyscallResult};
+use crate::tests::test_utils::{create_initialized_kernel, register_test_calculator};
+use royaos_memory::{MemoryCategory, MemoryManager};
+use royaos_security::SecurityManager;
+use royaos_tools::{ToolCapability, ToolManager, ToolMetadata};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Get the bytes allocated in working memory
+fn working_memory(kernel: &Kernel) -> usize {
+    kernel.with_subsystem("memory", |memory: &mut MemoryManager| memory.category_usage(MemoryCategory::Working)).unwrap()
+}
+
+/// Check whether the security policy holds an exact permission
+fn has_permission(kernel: &Kernel, resource_type: &str, operation: &str, resource: &str) -> bool {
+    kernel.with_subsystem("security", |security: &mut SecurityManager| {
+        security.has_permission(resource_type, operation, resource)
+    }).unwrap()
+}
+
+/// Register a tool whose "solve" capability always reports failure
+fn register_failing_tool(kernel: &Kernel) {
+    let metadata = ToolMetadata {
+        id: "solver".to_string(),
+        name: "Solver".to_string(),
+        description: "Solves nothing".to_string(),
+        version: "1.0.0".to_string(),
+        author: "Test Author".to_string(),
+        categories: vec![],
+        capabilities: vec![ToolCapability {
+            name: "solve".to_string(),
+            description: "Fail to solve".to_string(),
+            parameters: vec![],
+            return_type: "string".to_string(),
+        }],
+    };
+    
+    kernel.with_subsystem("tools", |tools: &mut ToolManager| {
+        tools.register_tool(metadata, PathBuf::from("./tools/solver"))
+    }).unwrap().unwrap();
+}
+
+/// Get the status of every step of a batch
+fn statuses(steps: &[StepOutcome]) -> Vec<&'static str> {
+    steps.iter().map(|step| match step {
+        StepOutcome::Committed { .. } => "committed",
+        StepOutcome::RolledBack { .. } => "rolled_back",
+        StepOutcome::NotRolledBack { .. } => "not_rolled_back",
+        StepOutcome::Failed { .. } => "failed",
+        StepOutcome::Skipped => "skipped",
+    }).collect()
+}
+
+/// Test suite for system call batches
+#[cfg(test)]
+mod syscall_batch_tests {
+    use super::*;
+    
+    /// Test that a batch whose steps all succeed keeps their effects
+    #[test]
+    fn test_batch_commits() {
+        let kernel = create_initialized_kernel().unwrap();
+        register_test_calculator(&kernel);
+        
+        let result = kernel.process_batch(&[
+            BatchStep::new("memory_alloc", &["4096", "scratch"]),
+            BatchStep::new("tool_execute", &["calculator", "add", r#"{"a": 2, "b": 3}"#]),
+            BatchStep::new("memory_free", &["$0"]),
+            BatchStep::new("memory_alloc", &["1024", "result"]),
+        ]).unwrap();
+        
+        assert!(result.committed);
+        assert_eq!(statuses(&result.steps), ["committed"; 4]);
+        match (&result.steps[0], &result.steps[2]) {
+            (
+                StepOutcome::Committed { result: SyscallResult::MemoryAllocated { handle: allocated } },
+                StepOutcome::Committed { result: SyscallResult::MemoryFreed { handle: freed } },
+            ) => assert_eq!(allocated, freed),
+            other => panic!("Unexpected outcomes: {:?}", other),
+        }
+        assert_eq!(working_memory(&kernel), 1024);
+        
+        // A step may only refer to the steps before it
+        let result = kernel.process_batch(&[
+            BatchStep::new("memory_alloc", &["4096"]),
+            BatchStep::new("memory_free", &["$1"]),
+        ]);
+        assert_eq!(result.unwrap_err().code(), "BATCH_INVALID_REFERENCE");
+        assert_eq!(working_memory(&kernel), 1024);
+    }
+    
+    /// Test that a failed step undoes the allocations and permissions of earlier steps
+    #[test]
+    fn test_batch_rolls_back() {
+        let kernel = create_initialized_kernel().unwrap();
+        register_failing_tool(&kernel);
+        kernel.process_syscall("security_grant", &["file", "read", "/tmp/kept"]).unwrap();
+        
+        let result = kernel.process_batch(&[
+            BatchStep::new("memory_alloc", &["4096", "scratch"]),
+            BatchStep::new("security_grant", &["file", "write", "/tmp/scratch"]),
+            BatchStep::new("security_grant", &["file", "read", "/tmp/kept"]),
+            BatchStep::new("tool_execute", &["solver", "solve"]),
+            BatchStep::new("memory_alloc", &["1024", "result"]),
+        ]).unwrap();
+        
+        assert!(!result.committed);
+        assert_eq!(statuses(&result.steps), ["rolled_back", "rolled_back", "rolled_back", "failed", "skipped"]);
+        match &result.steps[3] {
+            StepOutcome::Failed { error_code, .. } => assert_eq!(error_code, "BATCH_TOOL_FAILED"),
+            other => panic!("Unexpected outcome: {:?}", other),
+        }
+        assert_eq!(working_memory(&kernel), 0);
+        assert!(!has_permission(&kernel, "file", "write", "/tmp/scratch"));
+        
+        // Permissions granted before the batch stay granted
+        assert!(has_permission(&kernel, "file", "read", "/tmp/kept"));
+    }
+    
+    /// Test that steps whose effects cannot be undone are reported
+    #[test]
+    fn test_batch_irreversible_steps() {
+        let kernel = create_initialized_kernel().unwrap();
+        let handle = kernel.process_syscall("memory_alloc", &["2048"]).unwrap();
+        
+        let result = kernel.process_batch(&[
+            BatchStep::new("job_schedule", &["every 5m", "job_list"]),
+            BatchStep::new("memory_free", &[&handle]),
+            BatchStep::new("memory_alloc", &["not-a-size"]),
+        ]).unwrap();
+        
+        assert!(!result.committed);
+        assert_eq!(statuses(&result.steps), ["rolled_back", "not_rolled_back", "failed"]);
+        match &result.steps[2] {
+            StepOutcome::Failed { error_code, .. } => assert_eq!(error_code, "SYSCALL_INVALID_ARGUMENTS"),
+            other => panic!("Unexpected outcome: {:?}", other),
+        }
+        
+        // The scheduled job was cancelled, the freed memory stays freed
+        assert_eq!(kernel.jobs().len(), 2);
+        assert_eq!(working_memory(&kernel), 0);
+    }
+    
+    /// Test that a batch rolled back for exceeding a quota leaves the agent's usage unchanged
+    #[test]
+    fn test_principal_batch_quota() {
+        let kernel = create_initialized_kernel().unwrap();
+        kernel.register_principal("planner");
+        kernel.set_principal_quota("planner", Some(Quota {
+            memory_bytes: HashMap::from([(MemoryCategory::Working, 4096)]),
+            ..Quota::default()
+        })).unwrap();
+        
+        let steps = [
+            BatchStep::new("memory_alloc", &["3072"]),
+            BatchStep::new("memory_alloc", &["2048"]),
+        ];
+        let result = kernel.process_principal_batch("planner", None, &steps).unwrap();
+        
+        assert!(!result.committed);
+        assert_eq!(statuses(&result.steps), ["rolled_back", "failed"]);
+        match &result.steps[1] {
+            StepOutcome::Failed { error_code, .. } => assert_eq!(error_code, "QUOTA_MEMORY_EXCEEDED"),
+            other => panic!("Unexpected outcome: {:?}", other),
+        }
+        let usage = kernel.principal_usage("planner").unwrap();
+        assert_eq!(usage.memory_bytes.get(&MemoryCategory::Working), None);
+        assert_eq!(working_memory(&kernel), 0);
+    }
+}
@@ -0,0 +1,84 @@
+//! Event bus tests for the kernel
+//!
+//! This module tests that the kernel hands its event bus to registered
+//! subsystems and that their notifications reach kernel subscribers.
+
+use crate::{EventError, EventKind, KernelEvent, Syscall};
+use crate::tests::test_utils::{
+    create_initialized_kernel, create_test_kernel_with_security, register_test_calculator,
+};
+use royaos_interface::InterfaceManager;
+use royaos_memory::MemoryCategory;
+use royaos_security::SecurityManager;
+use std::collections::HashMap;
+
+/// Test suite for kernel event delivery
+#[cfg(test)]
+mod event_delivery_tests {
+    use super::*;
+    
+    /// Test that subsystem notifications reach a kernel subscriber
+    #[test]
+    fn test_subsystem_events_reach_kernel_subscribers() {
+        let kernel = create_initialized_kernel().unwrap();
+        let mut events = kernel.subscribe(&[]);
+        
+        let handle = register_test_calculator(&kernel);
+        let result = kernel.process_syscall("tool_execute", &["calculator", "add", r#"{"a": 1, "b": 2}"#]);
+        assert!(result.is_ok());
+        
+        match events.try_recv() {
+            Ok(Some(KernelEvent::ToolExecuted { tool, success, .. })) => {
+                assert_eq!(tool, handle);
+                assert!(success);
+            },
+            other => panic!("Expected a tool execution event, got {:?}", other),
+        }
+        
+        let session = kernel.with_subsystem("interface", |interface: &mut InterfaceManager| {
+            let session = interface.create_session(HashMap::new());
+            interface.close_session(session).map(|_| session)
+        }).unwrap().unwrap();
+        assert_eq!(events.try_recv(), Ok(Some(KernelEvent::SessionClosed { session })));
+    }
+    
+    /// Test that a denied syscall is announced as a permission denial
+    #[test]
+    fn test_denied_syscall_publishes_event() {
+        let security = SecurityManager::new("standard", vec![]).unwrap();
+        let mut kernel = create_test_kernel_with_security(security);
+        assert!(kernel.initialize().is_ok());
+        
+        let mut denials = kernel.subscribe(&[EventKind::PermissionDenied]);
+        let result = kernel.execute_syscall(Syscall::MemoryAlloc {
+            size: 1024,
+            purpose: "denied".to_string(),
+            category: MemoryCategory::Working,
+        });
+        assert!(result.is_err());
+        
+        assert_eq!(denials.try_recv(), Ok(Some(KernelEvent::PermissionDenied {
+            resource_type: "memory".to_string(),
+            operation: "allocate".to_string(),
+            resource: "working".to_string(),
+        })));
+        assert_eq!(denials.try_recv(), Ok(None));
+    }
+    
+    /// Test that a slow subscriber is told how many events it missed
+    #[test]
+    fn test_slow_subscriber_lag() {
+        let kernel = create_initialized_kernel().unwrap();
+        let bus = kernel.event_bus();
+        let mut events = kernel.subscribe(&[EventKind::SessionClosed]);
+        
+        let overflow = 10;
+        for _ in 0..bus.capacity() + overflow {
+            bus.publish(KernelEvent::SessionClosed { session: uuid::Uuid::new_v4() });
+        }
+        
+        assert_eq!(events.try_recv(), Err(EventError::Lagged(overflow as u64)));
+        assert_eq!(events.missed(), overflow as u64);
+        assert!(events.try_recv().unwrap().is_some(), "Subscriber should resume after the lag");
+    }
+}
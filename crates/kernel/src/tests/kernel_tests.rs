@@ -4,7 +4,10 @@
 //! including initialization, shutdown, and state management.
 
 use crate::Kernel;
-use crate::tests::test_utils::{create_test_kernel, create_initialized_kernel};
+use crate::load::parse_cpu_ticks;
+use crate::tests::test_utils::{create_test_kernel, create_initialized_kernel, create_simulated_kernel};
+use royaos_interface::{InterfaceManager, Request};
+use std::collections::HashMap;
 
 /// Test suite for kernel lifecycle operations
 #[cfg(test)]
@@ -18,7 +21,7 @@ mod lifecycle_tests {
         let kernel = Kernel::new(version);
         
         assert_eq!(kernel.version(), version, "Kernel should have the correct version");
-        assert_eq!(kernel.is_running(), false, "New kernel should not be running");
+        assert!(!kernel.is_running(), "New kernel should not be running");
     }
     
     /// Test complete kernel initialization sequence
@@ -27,12 +30,12 @@ mod lifecycle_tests {
         let mut kernel = create_test_kernel();
         
         // Kernel should start in non-running state
-        assert_eq!(kernel.is_running(), false, "Kernel should start in non-running state");
+        assert!(!kernel.is_running(), "Kernel should start in non-running state");
         
         // Initialize the kernel
         let init_result = kernel.initialize();
         assert!(init_result.is_ok(), "Kernel initialization should succeed");
-        assert_eq!(kernel.is_running(), true, "Kernel should be running after initialization");
+        assert!(kernel.is_running(), "Kernel should be running after initialization");
         
         // Verify subsystems are registered
         // This would require exposing subsystem state or adding a method to check
@@ -48,12 +51,12 @@ mod lifecycle_tests {
         };
         
         // Kernel should be running after initialization
-        assert_eq!(kernel.is_running(), true, "Kernel should be running after initialization");
+        assert!(kernel.is_running(), "Kernel should be running after initialization");
         
         // Shutdown the kernel
         let shutdown_result = kernel.shutdown();
         assert!(shutdown_result.is_ok(), "Kernel shutdown should succeed");
-        assert_eq!(kernel.is_running(), false, "Kernel should not be running after shutdown");
+        assert!(!kernel.is_running(), "Kernel should not be running after shutdown");
     }
     
     /// Test kernel initialization and shutdown multiple times
@@ -63,15 +66,15 @@ mod lifecycle_tests {
         
         // First cycle
         assert!(kernel.initialize().is_ok(), "First initialization should succeed");
-        assert_eq!(kernel.is_running(), true, "Kernel should be running after first init");
+        assert!(kernel.is_running(), "Kernel should be running after first init");
         assert!(kernel.shutdown().is_ok(), "First shutdown should succeed");
-        assert_eq!(kernel.is_running(), false, "Kernel should not be running after first shutdown");
+        assert!(!kernel.is_running(), "Kernel should not be running after first shutdown");
         
         // Second cycle
         assert!(kernel.initialize().is_ok(), "Second initialization should succeed");
-        assert_eq!(kernel.is_running(), true, "Kernel should be running after second init");
+        assert!(kernel.is_running(), "Kernel should be running after second init");
         assert!(kernel.shutdown().is_ok(), "Second shutdown should succeed");
-        assert_eq!(kernel.is_running(), false, "Kernel should not be running after second shutdown");
+        assert!(!kernel.is_running(), "Kernel should not be running after second shutdown");
     }
 }
 
@@ -96,36 +99,43 @@ mod version_tests {
 #[cfg(test)]
 mod subsystem_tests {
     use super::*;
+    use crate::SubsystemState;
+    use crate::tests::test_utils::MockSubsystem;
     
     /// Test subsystem registration
     #[test]
     fn test_subsystem_registration() {
-        let mut kernel = create_test_kernel();
+        let mut kernel = Kernel::new("test-version");
         
         // Register a test subsystem
-        let result = kernel.register_subsystem("test_subsystem");
+        let result = kernel.register_subsystem(Box::new(MockSubsystem::new("test_subsystem")));
         assert!(result.is_ok(), "Subsystem registration should succeed");
+        assert!(kernel.has_subsystem("test_subsystem"), "Kernel should have registered test_subsystem");
+        assert_eq!(kernel.subsystem_state("test_subsystem"), Some(SubsystemState::Registered));
         
-        // Try registering the same subsystem again (implementation dependent)
-        // This test assumes re-registration is allowed
-        let result = kernel.register_subsystem("test_subsystem");
-        assert!(result.is_ok(), "Re-registering a subsystem should be allowed");
+        // Registering another subsystem with the same name is rejected
+        let result = kernel.register_subsystem(Box::new(MockSubsystem::new("test_subsystem")));
+        assert!(result.is_err(), "Re-registering a subsystem should fail");
     }
     
     /// Test subsystem shutdown
     #[test]
     fn test_subsystem_shutdown() {
-        let mut kernel = create_test_kernel();
+        let mut kernel = Kernel::new("test-version");
         
-        // Register and then shutdown a subsystem
-        assert!(kernel.register_subsystem("test_subsystem").is_ok());
+        // Register, initialize and then shutdown a subsystem
+        assert!(kernel.register_subsystem(Box::new(MockSubsystem::new("test_subsystem"))).is_ok());
+        assert!(kernel.initialize().is_ok());
         let result = kernel.shutdown_subsystem("test_subsystem");
         assert!(result.is_ok(), "Subsystem shutdown should succeed");
+        assert_eq!(kernel.subsystem_state("test_subsystem"), Some(SubsystemState::Stopped));
         
-        // Shutdown a non-existent subsystem (implementation dependent)
-        // This test assumes shutting down non-existent subsystems returns an error
+        let initialized = kernel.with_subsystem("test_subsystem", |mock: &mut MockSubsystem| mock.is_initialized());
+        assert!(!initialized.unwrap(), "Mock subsystem should have been shut down");
+        
+        // Shutting down a non-existent subsystem is an error
         let result = kernel.shutdown_subsystem("nonexistent_subsystem");
-        assert!(result.is_ok(), "Shutting down a non-existent subsystem should be handled gracefully");
+        assert!(result.is_err(), "Shutting down a non-existent subsystem should fail");
     }
     
     /// Test initialization registers the expected subsystems
@@ -134,26 +144,43 @@ mod subsystem_tests {
         let mut kernel = create_test_kernel();
         assert!(kernel.initialize().is_ok());
         
-        // The kernel should have registered these subsystems during initialization
+        // The test kernel registers these subsystems before initialization
         let expected_subsystems = ["memory", "tools", "security", "interface"];
         
-        // This test assumes there's a way to check if a subsystem is registered
-        // If there's no such method, this test would need to be modified
         for subsystem in expected_subsystems.iter() {
-            // This is a placeholder for a method that would check if a subsystem is registered
-            // assert!(kernel.has_subsystem(subsystem), "Kernel should have registered {}", subsystem);
-            
-            // Instead, we can test that shutting down these subsystems works
-            assert!(kernel.shutdown_subsystem(subsystem).is_ok(), 
-                   "Should be able to shutdown {} subsystem", subsystem);
+            assert!(kernel.has_subsystem(subsystem), "Kernel should have registered {}", subsystem);
+            assert_eq!(kernel.subsystem_state(subsystem), Some(SubsystemState::Running),
+                       "{} subsystem should be running", subsystem);
+            assert!(kernel.subsystem_health(subsystem).unwrap().is_healthy(),
+                    "{} subsystem should be healthy", subsystem);
         }
     }
+    
+    /// Test typed access to a subsystem
+    #[test]
+    fn test_with_subsystem_downcast() {
+        let kernel = match create_initialized_kernel() {
+            Ok(k) => k,
+            Err(e) => panic!("Failed to create initialized kernel: {}", e),
+        };
+        
+        let usage = kernel.with_subsystem("memory", |memory: &mut royaos_memory::MemoryManager| {
+            memory.current_usage()
+        });
+        assert_eq!(usage.unwrap(), 0, "Memory subsystem should be reachable by its concrete type");
+        
+        let result = kernel.with_subsystem("memory", |_: &mut MockSubsystem| ());
+        assert!(result.is_err(), "Downcasting to the wrong type should fail");
+    }
 }
 
 /// Test suite for syscall handling
 #[cfg(test)]
 mod syscall_tests {
     use super::*;
+    use crate::{Syscall, SyscallResult, ToolRef};
+    use crate::tests::test_utils::register_test_calculator;
+    use royaos_memory::MemoryCategory;
     
     /// Test memory syscalls
     #[test]
@@ -164,14 +191,20 @@ mod syscall_tests {
         };
         
         // Test memory allocation syscall
-        // This assumes the kernel has a method to handle syscalls
-        // If there's no such public method, this test would need to be modified
-        let result = kernel.handle_memory_syscall("allocate", &["1024"]);
-        assert!(result.is_ok(), "Memory allocation syscall should succeed");
+        let result = kernel.handle_memory_syscall(None, Syscall::MemoryAlloc {
+            size: 1024,
+            purpose: "test".to_string(),
+            category: MemoryCategory::Working,
+        });
+        assert!(matches!(result, Ok(SyscallResult::MemoryAllocated { .. })), "Memory allocation syscall should succeed");
         
-        // Test invalid memory operation
-        let result = kernel.handle_memory_syscall("invalid_operation", &[]);
-        assert!(result.is_ok(), "Invalid memory operation should be handled gracefully");
+        // Test routing a non-memory syscall to the memory handler
+        let result = kernel.handle_memory_syscall(None, Syscall::SecurityCheck {
+            resource_type: "file".to_string(),
+            operation: "read".to_string(),
+            resource: "/test.txt".to_string(),
+        });
+        assert!(result.is_err(), "Invalid memory operation should be rejected");
     }
     
     /// Test tool syscalls
@@ -181,14 +214,22 @@ mod syscall_tests {
             Ok(k) => k,
             Err(e) => panic!("Failed to create initialized kernel: {}", e),
         };
+        register_test_calculator(&kernel);
         
         // Test tool execution syscall
-        let result = kernel.handle_tool_syscall("execute", &["test_tool", "arg1", "arg2"]);
-        assert!(result.is_ok(), "Tool execution syscall should succeed");
+        let result = kernel.handle_tool_syscall(Syscall::ToolExecute {
+            tool: ToolRef::Id("calculator".to_string()),
+            capability: "add".to_string(),
+            params: serde_json::json!({"a": 1, "b": 2}),
+        });
+        match result {
+            Ok(SyscallResult::ToolExecuted { result }) => assert_eq!(result.data, Some("3".to_string())),
+            other => panic!("Tool execution syscall should succeed, got {:?}", other),
+        }
         
-        // Test invalid tool operation
-        let result = kernel.handle_tool_syscall("invalid_operation", &[]);
-        assert!(result.is_ok(), "Invalid tool operation should be handled gracefully");
+        // Test routing a non-tool syscall to the tool handler
+        let result = kernel.handle_tool_syscall(Syscall::MemoryFree { handle: uuid::Uuid::new_v4() });
+        assert!(result.is_err(), "Invalid tool operation should be rejected");
     }
     
     /// Test security syscalls
@@ -200,12 +241,17 @@ mod syscall_tests {
         };
         
         // Test permission check syscall
-        let result = kernel.handle_security_syscall("check_permission", &["file", "read", "/test.txt"]);
-        assert!(result.is_ok(), "Security permission check syscall should succeed");
+        let result = kernel.handle_security_syscall(Syscall::SecurityCheck {
+            resource_type: "file".to_string(),
+            operation: "read".to_string(),
+            resource: "/test.txt".to_string(),
+        });
+        assert!(matches!(result, Ok(SyscallResult::PermissionChecked { allowed: true })),
+                "Security permission check syscall should succeed");
         
-        // Test invalid security operation
-        let result = kernel.handle_security_syscall("invalid_operation", &[]);
-        assert!(result.is_ok(), "Invalid security operation should be handled gracefully");
+        // Test routing a non-security syscall to the security handler
+        let result = kernel.handle_security_syscall(Syscall::MemoryFree { handle: uuid::Uuid::new_v4() });
+        assert!(result.is_err(), "Invalid security operation should be rejected");
     }
 }
 
@@ -217,7 +263,8 @@ mod system_load_tests {
     /// Test system load tracking
     #[test]
     fn test_system_load() {
-        let mut kernel = create_test_kernel();
+        // The load can be set through a shared kernel
+        let kernel = std::sync::Arc::new(create_test_kernel());
         
         // This assumes the kernel has methods to get and set system load
         // If there are no such public methods, this test would need to be modified
@@ -237,4 +284,100 @@ mod system_load_tests {
         kernel.set_system_load(-0.5);
         assert_eq!(kernel.system_load(), 0.0, "System load should be clamped to 0.0");
     }
+    
+    /// Test that the load is computed from memory usage and smoothed
+    #[test]
+    fn test_load_from_memory_usage() {
+        let kernel = create_initialized_kernel().unwrap();
+        
+        // The test kernel has 100 MB of memory
+        let size = (60 * 1024 * 1024).to_string();
+        assert!(kernel.process_syscall("memory_alloc", &[&size]).is_ok());
+        
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        let load = kernel.sample_load();
+        assert!((load.signals.memory_usage_percentage - 60.0).abs() < 1e-9);
+        assert!(load.current >= 0.6, "Load should reflect memory usage, got {}", load.current);
+        assert_eq!(load.signals.scheduler_queue_depth, 0);
+        assert_eq!(load.signals.running_tool_executions, 0);
+        
+        // Averages move towards the sample, the longer ones more slowly
+        assert!(load.one_minute > 0.0 && load.one_minute < load.current);
+        assert!(load.five_minutes < load.one_minute);
+        assert!(load.fifteen_minutes < load.five_minutes);
+        assert_eq!(kernel.load(), load);
+        assert_eq!(kernel.system_load(), load.current);
+    }
+    
+    /// Test that the load averages decay with the time passed on the kernel clock
+    #[test]
+    fn test_load_averages_follow_the_clock() {
+        let kernel = create_simulated_kernel(3);
+        let size = (60 * 1024 * 1024).to_string();
+        assert!(kernel.process_syscall("memory_alloc", &[&size]).is_ok());
+        
+        // No time has passed, so the sample does not move the averages
+        let load = kernel.sample_load();
+        assert!(load.current >= 0.6);
+        assert_eq!(load.one_minute, 0.0);
+        
+        kernel.clock().advance(std::time::Duration::from_secs(60));
+        let load = kernel.sample_load();
+        let expected = load.current * (1.0 - (-1.0f64).exp());
+        assert!((load.one_minute - expected).abs() < 1e-9, "Expected {}, got {}", expected, load.one_minute);
+        assert!((load.five_minutes - load.current * (1.0 - (-0.2f64).exp())).abs() < 1e-9);
+    }
+    
+    /// Test that the load is reported in the system_info response
+    #[test]
+    fn test_load_in_system_info() {
+        let kernel = create_initialized_kernel().unwrap();
+        let size = (30 * 1024 * 1024).to_string();
+        assert!(kernel.process_syscall("memory_alloc", &[&size]).is_ok());
+        let load = kernel.sample_load();
+        
+        let data = kernel.with_subsystem("interface", |interface: &mut InterfaceManager| {
+            let session = interface.create_session(HashMap::new());
+            let request = Request {
+                id: "info".to_string(),
+                request_type: "system_info".to_string(),
+                parameters: serde_json::json!({}),
+                timestamp: 0,
+            };
+            interface.process_request(session, request)
+        }).unwrap().unwrap().data.unwrap();
+        
+        assert_eq!(data["load"]["current"], load.current);
+        assert_eq!(data["load"]["one_minute"], load.one_minute);
+        assert_eq!(data["load"]["signals"]["memory_usage_percentage"], 30.0);
+    }
+    
+    /// Test that the load sampler task keeps the load current
+    #[tokio::test]
+    async fn test_load_sampler() {
+        let kernel = std::sync::Arc::new(create_initialized_kernel().unwrap());
+        let size = (50 * 1024 * 1024).to_string();
+        assert!(kernel.process_syscall("memory_alloc", &[&size]).is_ok());
+        
+        let sampler = kernel.start_load_sampler(std::time::Duration::from_millis(5)).unwrap();
+        let sampled = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while kernel.load().signals.memory_usage_percentage == 0.0 {
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            }
+        }).await;
+        assert!(sampled.is_ok(), "Load sampler should record memory usage");
+        
+        let scheduler = kernel.scheduler();
+        scheduler.cancel(sampler).unwrap();
+        assert!(scheduler.join(sampler).await.is_ok());
+    }
+    
+    /// Test reading process CPU time from /proc stat contents
+    #[test]
+    fn test_parse_cpu_ticks() {
+        let stat = "4242 (cognitive loop) S 1 4242 4242 0 -1 4194560 900 0 0 0 250 50 0 0 20 0 8 0";
+        assert_eq!(parse_cpu_ticks(stat), Some(300));
+        assert_eq!(parse_cpu_ticks("4242 (truncated) S 1"), None);
+        assert_eq!(parse_cpu_ticks("garbage"), None);
+    }
 }
@@ -6,7 +6,16 @@
 
 mod kernel_tests;
 mod syscall_tests;
+mod event_tests;
+mod watchdog_tests;
 mod subsystem_tests;
+mod scheduler_tests;
+mod snapshot_tests;
+mod trace_tests;
+mod quota_tests;
+mod timer_tests;
+mod simulation_tests;
+mod batch_tests;
 
 // Re-export test utilities for use in other test modules
 pub(crate) mod test_utils;
@@ -0,0 +1,300 @@
+//! Agent quota tests
+//!
+//! This module tests registering agents as principals and enforcing their
+//! quotas on the system calls they issue.
+
+use crate::{ErrorCode, EventKind, KernelEvent, Quota};
+use crate::tests::test_utils::{create_initialized_kernel, register_test_calculator};
+use royaos_memory::{MemoryCategory, MemoryManager, CONSOLIDATE_JOB};
+use royaos_security::SecurityManager;
+use royaos_tools::{ToolError, ToolExecutor, ToolManager};
+use std::collections::HashMap;
+use std::sync::{mpsc, Arc, Mutex};
+use uuid::Uuid;
+
+/// Build a quota limiting working memory
+fn working_memory_quota(bytes: usize) -> Quota {
+    Quota {
+        memory_bytes: HashMap::from([(MemoryCategory::Working, bytes)]),
+        ..Quota::default()
+    }
+}
+
+/// Tool executor that signals when it starts and runs until it is released
+#[derive(Debug)]
+struct GateExecutor {
+    /// Signalled when an execution starts
+    started: Mutex<mpsc::Sender<()>>,
+    /// Received from to let an execution finish
+    release: Mutex<mpsc::Receiver<()>>,
+}
+
+impl ToolExecutor for GateExecutor {
+    fn execute(&self, _capability: &str, _params: &serde_json::Value) -> Result<String, ToolError> {
+        self.started.lock().unwrap().send(()).unwrap();
+        self.release.lock().unwrap().recv().unwrap();
+        Ok("released".to_string())
+    }
+}
+
+/// Test suite for agent quotas
+#[cfg(test)]
+mod principal_quota_tests {
+    use super::*;
+    
+    /// Test that calls from unregistered agents are rejected
+    #[test]
+    fn test_unknown_principal() {
+        let kernel = create_initialized_kernel().unwrap();
+        
+        let result = kernel.process_principal_syscall("planner", None, "memory_alloc", &["1024"]);
+        assert_eq!(result.unwrap_err().code(), "QUOTA_UNKNOWN_PRINCIPAL");
+        assert_eq!(kernel.principal_usage("planner").unwrap_err().code(), "QUOTA_UNKNOWN_PRINCIPAL");
+        
+        assert!(kernel.register_principal("planner"));
+        assert!(!kernel.register_principal("planner"));
+        assert_eq!(kernel.principals(), vec!["planner".to_string()]);
+        assert!(kernel.process_principal_syscall("planner", None, "memory_alloc", &["1024"]).is_ok());
+        
+        kernel.remove_principal("planner").unwrap();
+        assert!(kernel.principals().is_empty());
+        assert_eq!(kernel.remove_principal("planner").unwrap_err().code(), "QUOTA_UNKNOWN_PRINCIPAL");
+    }
+    
+    /// Test that memory is limited per category and released memory is credited back
+    #[test]
+    fn test_memory_quota() {
+        let kernel = create_initialized_kernel().unwrap();
+        kernel.register_principal("planner");
+        kernel.set_principal_quota("planner", Some(working_memory_quota(4096))).unwrap();
+        
+        let first = kernel.process_principal_syscall("planner", None, "memory_alloc", &["3000", "plan", "working"]).unwrap();
+        let result = kernel.process_principal_syscall("planner", None, "memory_alloc", &["2000", "plan", "working"]);
+        assert_eq!(result.unwrap_err().code(), "QUOTA_MEMORY_EXCEEDED");
+        
+        // Other categories, other agents and calls without an agent are not limited
+        assert!(kernel.process_principal_syscall("planner", None, "memory_alloc", &["2000", "notes", "long_term"]).is_ok());
+        kernel.register_principal("critic");
+        assert!(kernel.process_principal_syscall("critic", None, "memory_alloc", &["8192", "review", "working"]).is_ok());
+        assert!(kernel.process_syscall("memory_alloc", &["8192", "system", "working"]).is_ok());
+        
+        let usage = kernel.principal_usage("planner").unwrap();
+        assert_eq!(usage.memory_bytes.get(&MemoryCategory::Working), Some(&3000));
+        assert_eq!(usage.memory_bytes.get(&MemoryCategory::LongTerm), Some(&2000));
+        assert_eq!(usage.admitted_syscalls, 2);
+        assert_eq!(usage.rejected_syscalls, 1);
+        
+        kernel.process_principal_syscall("planner", None, "memory_free", &[&first]).unwrap();
+        assert!(kernel.process_principal_syscall("planner", None, "memory_alloc", &["2000", "plan", "working"]).is_ok());
+        assert_eq!(kernel.principal_usage("planner").unwrap().memory_bytes.get(&MemoryCategory::Working), Some(&2000));
+    }
+    
+    /// Test that memory released without a system call is no longer charged
+    #[test]
+    fn test_memory_released_outside_syscalls() {
+        let kernel = create_initialized_kernel().unwrap();
+        kernel.register_principal("planner");
+        kernel.set_principal_quota("planner", Some(working_memory_quota(4096))).unwrap();
+        
+        let handle = kernel.process_principal_syscall("planner", None, "memory_alloc", &["4096"]).unwrap();
+        let handle = Uuid::parse_str(&handle).unwrap();
+        kernel.with_subsystem("memory", |memory: &mut MemoryManager| memory.deallocate(handle)).unwrap().unwrap();
+        
+        assert!(kernel.principal_usage("planner").unwrap().memory_bytes.is_empty());
+        assert!(kernel.process_principal_syscall("planner", None, "memory_alloc", &["4096"]).is_ok());
+    }
+    
+    /// Test that consolidation promotes memory only within the long-term quota of its holder
+    #[test]
+    fn test_memory_consolidation_long_term_quota() {
+        let kernel = create_initialized_kernel().unwrap();
+        kernel.register_principal("planner");
+        kernel.register_principal("critic");
+        let mut quota = working_memory_quota(8192);
+        quota.memory_bytes.insert(MemoryCategory::LongTerm, 4096);
+        kernel.set_principal_quota("planner", Some(quota)).unwrap();
+        let mut promotions = kernel.subscribe(&[EventKind::MemoryPromoted]);
+        
+        let rehearse = |principal: &str, size: &str| {
+            let handle = kernel.process_principal_syscall(principal, None, "memory_alloc", &[size]).unwrap();
+            let handle = Uuid::parse_str(&handle).unwrap();
+            kernel.with_subsystem("memory", |memory: &mut MemoryManager| {
+                (0..5).try_for_each(|_| memory.read(handle, 0..1).map(|_| ()))
+            }).unwrap().unwrap();
+            handle
+        };
+        let plans = [rehearse("planner", "4096"), rehearse("planner", "4096")];
+        let review = rehearse("critic", "1024");
+        
+        // The planner's long-term quota has room for one of its allocations, and the critic's is not touched
+        let promoted: Vec<Uuid> = serde_json::from_str(&kernel.process_principal_syscall("planner", None, "memory_consolidate", &[]).unwrap()).unwrap();
+        assert_eq!(promoted.len(), 1);
+        assert!(plans.contains(&promoted[0]));
+        assert_eq!(promotions.try_recv(), Ok(Some(KernelEvent::MemoryPromoted {
+            handle: promoted[0],
+            from: "working".to_string(),
+            to: "long_term".to_string(),
+        })));
+        assert_eq!(promotions.try_recv(), Ok(None));
+        
+        let usage = kernel.principal_usage("planner").unwrap();
+        assert_eq!(usage.memory_bytes.get(&MemoryCategory::Working), Some(&4096));
+        assert_eq!(usage.memory_bytes.get(&MemoryCategory::LongTerm), Some(&4096));
+        let result = kernel.process_principal_syscall("planner", None, "memory_alloc", &["1", "notes", "long_term"]);
+        assert_eq!(result.unwrap_err().code(), "QUOTA_MEMORY_EXCEEDED");
+        
+        // The scheduled job promotes the critic's memory but still holds back the planner's
+        kernel.run_subsystem_job("memory", CONSOLIDATE_JOB).unwrap();
+        assert_eq!(kernel.principal_usage("critic").unwrap().memory_bytes.get(&MemoryCategory::LongTerm), Some(&1024));
+        assert_eq!(kernel.principal_usage("planner").unwrap().memory_bytes.get(&MemoryCategory::Working), Some(&4096));
+        assert_eq!(promotions.try_recv(), Ok(Some(KernelEvent::MemoryPromoted {
+            handle: review,
+            from: "working".to_string(),
+            to: "long_term".to_string(),
+        })));
+        assert_eq!(promotions.try_recv(), Ok(None));
+        assert_eq!(kernel.process_syscall("memory_consolidate", &[]).unwrap(), "[]");
+        
+        // Freeing long-term memory makes room for the held-back allocation
+        kernel.process_principal_syscall("planner", None, "memory_free", &[&promoted[0].to_string()]).unwrap();
+        let held_back = plans.iter().find(|plan| **plan != promoted[0]).unwrap();
+        let promoted = kernel.process_principal_syscall("planner", None, "memory_consolidate", &[]).unwrap();
+        assert_eq!(promoted, format!("[\"{}\"]", held_back));
+        let usage = kernel.principal_usage("planner").unwrap();
+        assert_eq!(usage.memory_bytes.get(&MemoryCategory::Working), None);
+        assert_eq!(usage.memory_bytes.get(&MemoryCategory::LongTerm), Some(&4096));
+    }
+    
+    /// Test that system calls beyond the rate limit are rejected
+    #[test]
+    fn test_syscall_rate_quota() {
+        let kernel = create_initialized_kernel().unwrap();
+        kernel.register_principal("planner");
+        kernel.set_principal_quota("planner", Some(Quota {
+            syscalls_per_second: Some(2),
+            ..Quota::default()
+        })).unwrap();
+        
+        let check = ["memory", "allocate", "working"];
+        assert!(kernel.process_principal_syscall("planner", None, "security_check", &check).is_ok());
+        assert!(kernel.process_principal_syscall("planner", None, "security_check", &check).is_ok());
+        let result = kernel.process_principal_syscall("planner", None, "security_check", &check);
+        assert_eq!(result.unwrap_err().code(), "QUOTA_SYSCALL_RATE_EXCEEDED");
+        
+        let usage = kernel.principal_usage("planner").unwrap();
+        assert_eq!(usage.syscalls_last_second, 2);
+        assert_eq!(usage.rejected_syscalls, 1);
+    }
+    
+    /// Test that tool executions beyond the concurrency limit are rejected
+    #[test]
+    fn test_tool_execution_quota() {
+        let kernel = create_initialized_kernel().unwrap();
+        register_test_calculator(&kernel);
+        kernel.register_principal("planner");
+        let args = ["calculator", "add", r#"{"a": 1, "b": 2}"#];
+        
+        assert!(kernel.process_principal_syscall("planner", None, "tool_execute", &args).is_ok());
+        assert_eq!(kernel.principal_usage("planner").unwrap().running_tool_executions, 0);
+        
+        kernel.set_principal_quota("planner", Some(Quota {
+            concurrent_tool_executions: Some(0),
+            ..Quota::default()
+        })).unwrap();
+        let result = kernel.process_principal_syscall("planner", None, "tool_execute", &args);
+        assert_eq!(result.unwrap_err().code(), "QUOTA_TOOL_EXECUTIONS_EXCEEDED");
+    }
+    
+    /// Test that a running tool execution counts against the limit while other executions proceed
+    #[test]
+    fn test_concurrent_tool_executions() {
+        let kernel = create_initialized_kernel().unwrap();
+        register_test_calculator(&kernel);
+        let (started, wait_started) = mpsc::channel();
+        let (release, wait_release) = mpsc::channel();
+        let gate = GateExecutor { started: Mutex::new(started), release: Mutex::new(wait_release) };
+        kernel.with_subsystem("tools", |tools: &mut ToolManager| {
+            let calculator = tools.find_tool("calculator").unwrap();
+            tools.set_executor(calculator, Arc::new(gate))
+        }).unwrap().unwrap();
+        kernel.register_principal("planner");
+        kernel.register_principal("critic");
+        kernel.set_principal_quota("planner", Some(Quota {
+            concurrent_tool_executions: Some(1),
+            ..Quota::default()
+        })).unwrap();
+        let args = ["calculator", "add", r#"{"a": 1, "b": 2}"#];
+        
+        let (usage, rejected, running) = std::thread::scope(|scope| {
+            let first = scope.spawn(|| kernel.process_principal_syscall("planner", None, "tool_execute", &args));
+            wait_started.recv().unwrap();
+            let usage = kernel.principal_usage("planner").unwrap().running_tool_executions;
+            
+            // The planner's second execution is rejected while the first one runs,
+            // but the critic's execution runs next to it
+            let rejected = kernel.process_principal_syscall("planner", None, "tool_execute", &args);
+            let critic = scope.spawn(|| kernel.process_principal_syscall("critic", None, "tool_execute", &args));
+            wait_started.recv().unwrap();
+            let running = kernel.running_tool_executions();
+            
+            release.send(()).unwrap();
+            release.send(()).unwrap();
+            assert!(first.join().unwrap().is_ok());
+            assert!(critic.join().unwrap().is_ok());
+            (usage, rejected, running)
+        });
+        assert_eq!(usage, 1);
+        assert_eq!(rejected.unwrap_err().code(), "QUOTA_TOOL_EXECUTIONS_EXCEEDED");
+        assert_eq!(running, 2);
+        
+        assert_eq!(kernel.principal_usage("planner").unwrap().running_tool_executions, 0);
+        assert_eq!(kernel.running_tool_executions(), 0);
+        release.send(()).unwrap();
+        assert!(kernel.process_principal_syscall("planner", None, "tool_execute", &args).is_ok());
+    }
+    
+    /// Test that rejections are audited until the audit volume is exhausted
+    #[test]
+    fn test_audit_volume_quota() {
+        let kernel = create_initialized_kernel().unwrap();
+        kernel.register_principal("planner");
+        kernel.set_principal_quota("planner", Some(Quota {
+            audit_events_per_minute: Some(2),
+            ..working_memory_quota(0)
+        })).unwrap();
+        
+        for _ in 0..2 {
+            let result = kernel.process_principal_syscall("planner", None, "memory_alloc", &["1024"]);
+            assert_eq!(result.unwrap_err().code(), "QUOTA_MEMORY_EXCEEDED");
+        }
+        let result = kernel.process_principal_syscall("planner", None, "security_check", &["memory", "allocate", "working"]);
+        assert_eq!(result.unwrap_err().code(), "QUOTA_AUDIT_VOLUME_EXCEEDED");
+        
+        let audited = kernel.with_subsystem("security", |security: &mut SecurityManager| {
+            security.get_recent_events(1000).iter()
+                .filter(|event| event.source == "planner" && event.event_type == "quota_exceeded")
+                .count()
+        }).unwrap();
+        assert_eq!(audited, 2);
+        assert_eq!(kernel.principal_usage("planner").unwrap().audit_events_last_minute, 2);
+    }
+    
+    /// Test that principals without their own quota follow the default quota
+    #[test]
+    fn test_default_quota() {
+        let kernel = create_initialized_kernel().unwrap();
+        kernel.register_principal("planner");
+        assert_eq!(kernel.principal_quota("planner").unwrap(), Quota::default());
+        
+        kernel.set_default_quota(working_memory_quota(1024));
+        assert_eq!(kernel.principal_quota("planner").unwrap(), working_memory_quota(1024));
+        let result = kernel.process_principal_syscall("planner", None, "memory_alloc", &["2048"]);
+        assert_eq!(result.unwrap_err().code(), "QUOTA_MEMORY_EXCEEDED");
+        
+        kernel.set_principal_quota("planner", Some(working_memory_quota(4096))).unwrap();
+        assert!(kernel.process_principal_syscall("planner", None, "memory_alloc", &["2048"]).is_ok());
+        
+        kernel.set_principal_quota("planner", None).unwrap();
+        assert_eq!(kernel.principal_quota("planner").unwrap(), kernel.default_quota());
+    }
+}
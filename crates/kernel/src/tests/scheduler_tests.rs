@@ -0,0 +1,375 @@
+//! Task scheduler tests
+//!
+//! This module tests the kernel's cognitive task scheduler, including
+//! fair-share dispatch between priority classes, preemption points and
+//! per-task cancellation.
+
+use crate::{priority_share, ErrorCode, Scheduler, Syscall, TaskState};
+use crate::tests::test_utils::{create_initialized_kernel, create_simulated_kernel};
+use royaos_memory::MemoryCategory;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Test suite for task execution
+#[cfg(test)]
+mod task_execution_tests {
+    use super::*;
+    
+    /// Test that a spawned task runs to completion
+    #[tokio::test]
+    async fn test_spawn_and_join() {
+        let scheduler = Scheduler::new(2);
+        let ran = Arc::new(AtomicBool::new(false));
+        
+        let flag = ran.clone();
+        let id = scheduler.spawn("perceive", MemoryCategory::ShortTerm, move |ctx| async move {
+            assert_eq!(ctx.name(), "perceive");
+            flag.store(true, Ordering::SeqCst);
+        }).unwrap();
+        
+        assert_eq!(scheduler.join(id).await, Ok(TaskState::Completed));
+        assert!(ran.load(Ordering::SeqCst));
+        
+        let info = scheduler.task_info(id).unwrap();
+        assert_eq!(info.priority, MemoryCategory::ShortTerm);
+        assert_eq!(info.dispatch_count, 1);
+        assert_eq!(scheduler.reap_finished(), 1);
+        assert!(scheduler.list_tasks().is_empty());
+    }
+    
+    /// Test that spawning outside a runtime is rejected
+    #[test]
+    fn test_spawn_requires_runtime() {
+        let scheduler = Scheduler::new(1);
+        let result = scheduler.spawn("orphan", MemoryCategory::Working, |_| async {});
+        assert!(result.is_err(), "Spawning without a runtime should fail");
+    }
+    
+    /// Test that a sleeping task gives its slot to another task
+    #[tokio::test]
+    async fn test_sleep_releases_slot() {
+        let scheduler = Scheduler::new(1);
+        
+        let sleeper = scheduler.spawn("sleeper", MemoryCategory::Working, |ctx| async move {
+            ctx.sleep(Duration::from_millis(50)).await.unwrap();
+        }).unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(scheduler.task_info(sleeper).unwrap().state, TaskState::Sleeping);
+        
+        let other = scheduler.spawn("other", MemoryCategory::Working, |_| async {}).unwrap();
+        assert_eq!(scheduler.join(other).await, Ok(TaskState::Completed));
+        assert_eq!(scheduler.task_info(sleeper).unwrap().state, TaskState::Sleeping);
+        assert_eq!(scheduler.join(sleeper).await, Ok(TaskState::Completed));
+    }
+    
+    /// Test that no more tasks hold a slot than the scheduler allows
+    #[tokio::test]
+    async fn test_slot_limit() {
+        let scheduler = Scheduler::new(2);
+        let active = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        
+        let mut ids = Vec::new();
+        for i in 0..6 {
+            let (active, peak) = (active.clone(), peak.clone());
+            let id = scheduler.spawn(&format!("task-{}", i), MemoryCategory::Working, move |_| async move {
+                let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                active.fetch_sub(1, Ordering::SeqCst);
+            }).unwrap();
+            ids.push(id);
+        }
+        
+        for id in ids {
+            assert_eq!(scheduler.join(id).await, Ok(TaskState::Completed));
+        }
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        assert_eq!(scheduler.queue_depth(), 0);
+    }
+}
+
+/// Test suite for fair-share scheduling
+#[cfg(test)]
+mod fair_share_tests {
+    use super::*;
+    
+    /// Test that priority classes are weighted like memory categories
+    #[test]
+    fn test_priority_shares() {
+        assert!(priority_share(MemoryCategory::System) > priority_share(MemoryCategory::ShortTerm));
+        assert!(priority_share(MemoryCategory::ShortTerm) > priority_share(MemoryCategory::Working));
+        assert!(priority_share(MemoryCategory::Working) > priority_share(MemoryCategory::LongTerm));
+        assert!(priority_share(MemoryCategory::LongTerm) > priority_share(MemoryCategory::Background));
+    }
+    
+    /// Test that busy perception tasks cannot starve a reflection task
+    #[tokio::test]
+    async fn test_perception_does_not_starve_reflection() {
+        let scheduler = Scheduler::new(1);
+        let stop = Arc::new(AtomicBool::new(false));
+        
+        let mut perception = Vec::new();
+        for i in 0..4 {
+            let stop = stop.clone();
+            let id = scheduler.spawn(&format!("perception-{}", i), MemoryCategory::ShortTerm, move |ctx| async move {
+                while !stop.load(Ordering::SeqCst) {
+                    if ctx.yield_now().await.is_err() {
+                        break;
+                    }
+                }
+            }).unwrap();
+            perception.push(id);
+        }
+        
+        let reflection = scheduler.spawn("reflection", MemoryCategory::LongTerm, |ctx| async move {
+            for _ in 0..3 {
+                ctx.yield_now().await.unwrap();
+            }
+        }).unwrap();
+        
+        let state = tokio::time::timeout(Duration::from_secs(5), scheduler.join(reflection)).await
+            .expect("Reflection task should finish while perception tasks are busy");
+        assert_eq!(state, Ok(TaskState::Completed));
+        
+        stop.store(true, Ordering::SeqCst);
+        for id in perception {
+            assert_eq!(scheduler.join(id).await, Ok(TaskState::Completed));
+        }
+    }
+    
+    /// Test that higher classes are dispatched more often than lower ones
+    #[tokio::test]
+    async fn test_dispatch_proportional_to_share() {
+        let scheduler = Scheduler::new(1);
+        let order = Arc::new(Mutex::new(Vec::new()));
+        
+        // Hold the only slot so both classes queue up before dispatch starts
+        let (release, gate) = tokio::sync::oneshot::channel::<()>();
+        let blocker = scheduler.spawn("blocker", MemoryCategory::System, |_| async move {
+            let _ = gate.await;
+        }).unwrap();
+        tokio::task::yield_now().await;
+        
+        let mut ids = Vec::new();
+        for category in [MemoryCategory::Working, MemoryCategory::Background] {
+            let order = order.clone();
+            let id = scheduler.spawn(category.as_str(), category, move |ctx| async move {
+                for _ in 0..10 {
+                    order.lock().unwrap().push(ctx.priority());
+                    if ctx.yield_now().await.is_err() {
+                        break;
+                    }
+                }
+            }).unwrap();
+            ids.push(id);
+        }
+        
+        release.send(()).unwrap();
+        scheduler.join(blocker).await.unwrap();
+        for id in ids {
+            scheduler.join(id).await.unwrap();
+        }
+        
+        // While both classes compete, Working gets four turns for each Background turn
+        let order = order.lock().unwrap();
+        let first_ten = &order[..10];
+        let working = first_ten.iter().filter(|c| **c == MemoryCategory::Working).count();
+        assert!(working >= 7, "Working should dominate early dispatch, got {:?}", first_ten);
+        assert!(first_ten.contains(&MemoryCategory::Background), "Background should still run");
+    }
+}
+
+/// Test suite for task cancellation
+#[cfg(test)]
+mod task_cancellation_tests {
+    use super::*;
+    
+    /// Test cooperative cancellation at a preemption point
+    #[tokio::test]
+    async fn test_cancel_at_preemption_point() {
+        let scheduler = Scheduler::new(1);
+        
+        let id = scheduler.spawn("daydream", MemoryCategory::Background, |ctx| async move {
+            while ctx.yield_now().await.is_ok() {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        }).unwrap();
+        
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(scheduler.cancel(id).is_ok());
+        assert_eq!(scheduler.join(id).await, Ok(TaskState::Cancelled));
+        assert!(scheduler.cancel(uuid::Uuid::new_v4()).is_err());
+    }
+    
+    /// Test that aborting a task releases its slot for the next task
+    #[tokio::test]
+    async fn test_abort_releases_slot() {
+        let scheduler = Scheduler::new(1);
+        
+        let stuck = scheduler.spawn("stuck", MemoryCategory::Working, |_| async {
+            std::future::pending::<()>().await;
+        }).unwrap();
+        let next = scheduler.spawn("next", MemoryCategory::Working, |_| async {}).unwrap();
+        
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(scheduler.task_info(next).unwrap().state, TaskState::Waiting);
+        
+        assert!(scheduler.abort(stuck).is_ok());
+        assert_eq!(scheduler.join(stuck).await, Ok(TaskState::Cancelled));
+        assert_eq!(scheduler.join(next).await, Ok(TaskState::Completed));
+    }
+    
+    /// Test cancelling a task through the task_cancel system call
+    #[tokio::test]
+    async fn test_task_cancel_syscall() {
+        let kernel = create_initialized_kernel().unwrap();
+        let scheduler = kernel.scheduler();
+        
+        let id = scheduler.spawn_for("planner", "reflect", MemoryCategory::LongTerm, |ctx| async move {
+            while ctx.yield_now().await.is_ok() {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        }).unwrap();
+        assert_eq!(scheduler.task_info(id).unwrap().owner.as_deref(), Some("planner"));
+        
+        let result = kernel.process_syscall("task_cancel", &[&id.to_string()]);
+        assert_eq!(result.unwrap(), id.to_string());
+        assert_eq!(scheduler.join(id).await, Ok(TaskState::Cancelled));
+        
+        let result = kernel.execute_syscall(Syscall::TaskCancel { task: uuid::Uuid::new_v4() });
+        assert!(result.is_err(), "Cancelling an unknown task should fail");
+    }
+    
+    /// Test that principals can only cancel their own tasks and never kernel tasks
+    #[tokio::test]
+    async fn test_task_cancel_by_principal() {
+        let kernel = Arc::new(create_initialized_kernel().unwrap());
+        let scheduler = kernel.scheduler();
+        kernel.register_principal("planner");
+        kernel.register_principal("critic");
+        
+        let watchdog = kernel.start_watchdog(Duration::from_secs(3600)).unwrap();
+        let plan = scheduler.spawn_for("planner", "plan", MemoryCategory::Working, |ctx| async move {
+            let _ = ctx.sleep(Duration::from_secs(3600)).await;
+        }).unwrap();
+        assert_eq!(scheduler.task_info(watchdog).unwrap().owner, None);
+        
+        // Another principal's task and kernel services are out of reach
+        let result = kernel.process_principal_syscall("critic", None, "task_cancel", &[&plan.to_string()]);
+        assert_eq!(result.unwrap_err().code(), "TASK_NOT_OWNER");
+        for principal in [Some("planner"), None] {
+            let result = match principal {
+                Some(principal) => kernel.process_principal_syscall(principal, None, "task_cancel", &[&watchdog.to_string()]),
+                None => kernel.process_syscall("task_cancel", &[&watchdog.to_string()]),
+            };
+            assert_eq!(result.unwrap_err().code(), "TASK_NOT_OWNER");
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!matches!(scheduler.task_info(watchdog).unwrap().state, TaskState::Completed | TaskState::Cancelled));
+        
+        // The owner cancels its own task
+        let result = kernel.process_principal_syscall("planner", None, "task_cancel", &[&plan.to_string()]);
+        assert_eq!(result.unwrap(), plan.to_string());
+        assert_eq!(scheduler.join(plan).await, Ok(TaskState::Cancelled));
+        
+        scheduler.cancel(watchdog).unwrap();
+        assert!(scheduler.join(watchdog).await.is_ok());
+    }
+    
+    /// Test that cancelling a sleeping task wakes it
+    #[tokio::test]
+    async fn test_cancel_wakes_sleeping_task() {
+        let scheduler = Scheduler::new(1);
+        
+        let id = scheduler.spawn("dream", MemoryCategory::Background, |ctx| async move {
+            assert!(ctx.sleep(Duration::from_secs(3600)).await.is_err());
+        }).unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        
+        assert!(scheduler.cancel(id).is_ok());
+        let state = tokio::time::timeout(Duration::from_secs(5), scheduler.join(id)).await
+            .expect("Cancelled task should wake from its sleep");
+        assert_eq!(state, Ok(TaskState::Cancelled));
+    }
+    
+    /// Test that draining lets tasks finish at their next preemption point
+    #[tokio::test]
+    async fn test_drain_finishes_tasks() {
+        let kernel = create_initialized_kernel().unwrap();
+        let scheduler = kernel.scheduler();
+        let cleaned_up = Arc::new(AtomicBool::new(false));
+        
+        let flag = cleaned_up.clone();
+        let id = scheduler.spawn("consolidate", MemoryCategory::LongTerm, move |ctx| async move {
+            while ctx.sleep(Duration::from_millis(1)).await.is_ok() {}
+            flag.store(true, Ordering::SeqCst);
+        }).unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        
+        assert!(kernel.drain(Duration::from_secs(5)).await);
+        assert!(cleaned_up.load(Ordering::SeqCst), "Task should run its cleanup");
+        assert_eq!(scheduler.task_info(id).unwrap().state, TaskState::Cancelled);
+        assert!(scheduler.spawn("late", MemoryCategory::Working, |_| async {}).is_err());
+    }
+    
+    /// Test that draining aborts tasks still running at the deadline
+    #[tokio::test]
+    async fn test_drain_deadline() {
+        let kernel = create_initialized_kernel().unwrap();
+        let scheduler = kernel.scheduler();
+        
+        let id = scheduler.spawn("stuck", MemoryCategory::Working, |_| async {
+            std::future::pending::<()>().await;
+        }).unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        
+        assert!(!kernel.drain(Duration::from_millis(20)).await);
+        assert_eq!(scheduler.task_info(id).unwrap().state, TaskState::Cancelled);
+        assert_eq!(kernel.running_tool_executions(), 0);
+    }
+    
+    /// Test that the drain deadline is measured on the kernel clock
+    #[tokio::test]
+    async fn test_drain_deadline_follows_the_clock() {
+        let kernel = create_simulated_kernel(9);
+        let scheduler = kernel.scheduler();
+        
+        let id = scheduler.spawn("stuck", MemoryCategory::Working, |_| async {
+            std::future::pending::<()>().await;
+        }).unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        
+        // Real time passing does not reach the deadline, the simulated clock does
+        let finished = AtomicBool::new(false);
+        let drain = async {
+            let drained = kernel.drain(Duration::from_secs(60)).await;
+            finished.store(true, Ordering::SeqCst);
+            drained
+        };
+        let advance = async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            assert!(!finished.load(Ordering::SeqCst), "Drain should wait for the clock");
+            kernel.clock().advance(Duration::from_secs(60));
+        };
+        let (drained, ()) = tokio::join!(drain, advance);
+        assert!(!drained);
+        assert_eq!(scheduler.task_info(id).unwrap().state, TaskState::Cancelled);
+    }
+    
+    /// Test that kernel shutdown stops running tasks
+    #[tokio::test]
+    async fn test_kernel_shutdown_stops_tasks() {
+        let mut kernel = create_initialized_kernel().unwrap();
+        let scheduler = kernel.scheduler();
+        
+        let id = scheduler.spawn("forever", MemoryCategory::Working, |_| async {
+            std::future::pending::<()>().await;
+        }).unwrap();
+        
+        assert!(kernel.shutdown().is_ok());
+        assert_eq!(scheduler.join(id).await, Ok(TaskState::Cancelled));
+        assert!(scheduler.spawn("late", MemoryCategory::Working, |_| async {}).is_err());
+    }
+}
@@ -0,0 +1,116 @@
+//! Deterministic simulation tests
+//!
+//! This module tests kernels running on a simulated clock with a seeded handle
+//! generator, where time only passes when the test advances it.
+
+use crate::{ErrorCode, Kernel, Quota};
+use crate::tests::test_utils::{create_simulated_kernel, register_test_calculator};
+use royaos_interface::InterfaceManager;
+use royaos_memory::{MemoryCategory, MemoryManager};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Run the same sequence of operations on a kernel
+///
+/// # Returns
+///
+/// The results of the operations in order
+fn run_script(kernel: &Kernel) -> Vec<String> {
+    let mut results = vec![
+        kernel.process_syscall("memory_alloc", &["1024", "plan", "working"]).unwrap(),
+        kernel.process_syscall("job_schedule", &["every 5m", "memory_alloc", "2048"]).unwrap(),
+        register_test_calculator(kernel).to_string(),
+    ];
+    
+    kernel.clock().advance(Duration::from_secs(300));
+    results.push(kernel.run_due_jobs().to_string());
+    results.push(kernel.process_syscall("job_list", &[]).unwrap());
+    
+    let session = kernel.with_subsystem("interface", |interface: &mut InterfaceManager| {
+        interface.create_session(HashMap::new())
+    }).unwrap();
+    results.push(session.to_string());
+    results
+}
+
+/// Test suite for deterministic simulation
+#[cfg(test)]
+mod deterministic_simulation_tests {
+    use super::*;
+    
+    /// Test that runs with the same seed return the same handles and results
+    #[test]
+    fn test_seeded_runs_repeat() {
+        let first = run_script(&create_simulated_kernel(7));
+        let second = run_script(&create_simulated_kernel(7));
+        assert_eq!(first, second);
+        
+        let other = run_script(&create_simulated_kernel(8));
+        assert_ne!(first[0], other[0]);
+    }
+    
+    /// Test that timers fire when virtual time reaches them
+    #[test]
+    fn test_virtual_time_drives_timers() {
+        let kernel = create_simulated_kernel(1);
+        kernel.process_syscall("job_schedule", &["once 90s", "memory_alloc", "4096"]).unwrap();
+        
+        // Only the memory optimization job is due after a minute
+        kernel.clock().advance(Duration::from_secs(60));
+        assert_eq!(kernel.run_due_jobs(), 1);
+        kernel.clock().advance(Duration::from_secs(29));
+        assert_eq!(kernel.run_due_jobs(), 0);
+        
+        kernel.clock().advance(Duration::from_secs(1));
+        assert_eq!(kernel.run_due_jobs(), 1);
+        let working = kernel.with_subsystem("memory", |memory: &mut MemoryManager| {
+            memory.category_usage(MemoryCategory::Working)
+        }).unwrap();
+        assert_eq!(working, 4096);
+        assert_eq!(kernel.jobs().len(), 2);
+    }
+    
+    /// Test that quota windows move with virtual time
+    #[test]
+    fn test_virtual_time_drives_quotas() {
+        let kernel = create_simulated_kernel(1);
+        kernel.register_principal("planner");
+        kernel.set_principal_quota("planner", Some(Quota {
+            syscalls_per_second: Some(1),
+            ..Quota::default()
+        })).unwrap();
+        
+        let check = ["memory", "allocate", "working"];
+        assert!(kernel.process_principal_syscall("planner", None, "security_check", &check).is_ok());
+        let result = kernel.process_principal_syscall("planner", None, "security_check", &check);
+        assert_eq!(result.unwrap_err().code(), "QUOTA_SYSCALL_RATE_EXCEEDED");
+        
+        kernel.clock().advance(Duration::from_secs(1));
+        assert!(kernel.process_principal_syscall("planner", None, "security_check", &check).is_ok());
+    }
+    
+    /// Test that memory expires and fades with virtual time
+    #[test]
+    fn test_virtual_time_drives_forgetting() {
+        let kernel = create_simulated_kernel(1);
+        let notes = kernel.process_syscall("memory_alloc", &["1024", "notes", "short_term"]).unwrap();
+        let scratch = kernel.process_syscall("memory_alloc", &["1024", "scratch", "working"]).unwrap();
+        assert_eq!(kernel.process_syscall("memory_expire", &[&scratch, "90s"]).unwrap(), scratch);
+        assert_eq!(kernel.process_syscall("memory_strength", &[&notes]).unwrap(), "1");
+        
+        kernel.clock().advance(Duration::from_secs(600));
+        assert_eq!(kernel.process_syscall("memory_strength", &[&notes]).unwrap(), "0.5");
+        assert_eq!(kernel.process_syscall("memory_get", &[&scratch]).unwrap_err().code(), "MEMORY_EXPIRED");
+        
+        // The optimization job releases expired memory, and faded memory once it is weak enough
+        kernel.run_due_jobs();
+        assert_eq!(kernel.process_syscall("memory_strength", &[&scratch]).unwrap_err().code(), "MEMORY_NOT_FOUND");
+        assert!(kernel.process_syscall("memory_strength", &[&notes]).is_ok());
+        
+        kernel.clock().advance(Duration::from_secs(2400));
+        kernel.run_due_jobs();
+        assert_eq!(kernel.process_syscall("memory_strength", &[&notes]).unwrap_err().code(), "MEMORY_NOT_FOUND");
+        let usage = kernel.with_subsystem("memory", |memory: &mut MemoryManager| memory.current_usage()).unwrap();
+        assert_eq!(usage, 0);
+    }
+}
@@ -0,0 +1,141 @@
+//! Snapshot and restore tests
+//!
+//! This module tests saving the state of the running subsystems to the data
+//! directory and restoring it into a fresh kernel.
+
+use crate::{ErrorCode, KernelError, Snapshot, SNAPSHOT_DIR, SNAPSHOT_RETENTION};
+use crate::tests::test_utils::{create_initialized_kernel, register_test_calculator};
+use royaos_memory::MemoryManager;
+use royaos_security::SecurityManager;
+use royaos_tools::ToolManager;
+use std::fs;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// Create an empty data directory for one test
+fn create_data_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("royaos-snapshot-{}", Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Test suite for kernel snapshots
+#[cfg(test)]
+mod snapshot_restore_tests {
+    use super::*;
+    
+    /// Test that subsystem state survives a snapshot and restore
+    #[test]
+    fn test_snapshot_round_trip() {
+        let data_dir = create_data_dir();
+        
+        let kernel = create_initialized_kernel().unwrap();
+        let memory = kernel.process_syscall("memory_alloc", &["2048", "context", "long_term"]).unwrap();
+        let memory = Uuid::parse_str(&memory).unwrap();
+        let tool = register_test_calculator(&kernel);
+        kernel.with_subsystem("tools", |tools: &mut ToolManager| tools.set_tool_enabled(tool, false)).unwrap().unwrap();
+        kernel.with_subsystem("security", |security: &mut SecurityManager| {
+            security.add_permission("file", "read", "/data/notes").unwrap();
+        }).unwrap();
+        
+        let path = kernel.snapshot(&data_dir).unwrap();
+        assert!(path.starts_with(data_dir.join(SNAPSHOT_DIR)));
+        
+        // A fresh kernel discovers the same tool under a new handle
+        let restored = create_initialized_kernel().unwrap();
+        let rediscovered = register_test_calculator(&restored);
+        assert_ne!(rediscovered, tool);
+        assert_eq!(restored.restore(&data_dir).unwrap(), Some(path));
+        
+        restored.with_subsystem("memory", |manager: &mut MemoryManager| {
+            assert_eq!(manager.current_usage(), 2048);
+            assert!(manager.read(memory, 0..8).is_ok());
+        }).unwrap();
+        restored.with_subsystem("tools", |tools: &mut ToolManager| {
+            assert_eq!(tools.find_tool("calculator"), Some(tool));
+            assert_eq!(tools.list_tools().len(), 1);
+        }).unwrap();
+        let result = restored.process_syscall("tool_execute", &["calculator", "add", r#"{"a": 1, "b": 2}"#]);
+        assert_eq!(result.unwrap_err().code(), "TOOL_DISABLED");
+        restored.with_subsystem("security", |security: &mut SecurityManager| {
+            assert!(security.check_permission("file", "read", "/data/notes"));
+            assert!(security.get_recent_events(1000).iter().any(|event| event.event_type == "add_permission"));
+        }).unwrap();
+        
+        fs::remove_dir_all(&data_dir).unwrap();
+    }
+    
+    /// Test restoring from a data directory without snapshots
+    #[test]
+    fn test_restore_without_snapshot() {
+        let data_dir = create_data_dir();
+        let kernel = create_initialized_kernel().unwrap();
+        
+        assert_eq!(kernel.restore(&data_dir).unwrap(), None);
+        
+        fs::remove_dir_all(&data_dir).unwrap();
+    }
+    
+    /// Test that snapshots written in another format version are rejected
+    #[test]
+    fn test_restore_rejects_unsupported_version() {
+        let data_dir = create_data_dir();
+        let kernel = create_initialized_kernel().unwrap();
+        
+        let mut snapshot = kernel.capture_snapshot().unwrap();
+        snapshot.format_version = 99;
+        snapshot.write(&data_dir).unwrap();
+        
+        match kernel.restore(&data_dir) {
+            Err(error @ KernelError::SnapshotVersion { found: 99, supported: 1, .. }) => {
+                assert_eq!(error.code(), "SNAPSHOT_UNSUPPORTED_VERSION");
+            },
+            other => panic!("Expected an unsupported version, got {:?}", other),
+        }
+        
+        fs::remove_dir_all(&data_dir).unwrap();
+    }
+    
+    /// Test that a failed restore returns the restored subsystems to their previous state
+    #[test]
+    fn test_restore_rolls_back_on_failure() {
+        let kernel = create_initialized_kernel().unwrap();
+        kernel.process_syscall("memory_alloc", &["4096"]).unwrap();
+        let mut snapshot = kernel.capture_snapshot().unwrap();
+        
+        // The interface subsystem starts after memory and rejects its state
+        snapshot.subsystems.insert("interface".to_string(), serde_json::json!("not a session list"));
+        
+        let restored = create_initialized_kernel().unwrap();
+        match restored.restore_snapshot(&snapshot) {
+            Err(error @ KernelError::SubsystemFailed { action: "restore", .. }) => {
+                assert_eq!(error.code(), "STATE_INVALID");
+            },
+            other => panic!("Expected a failed restore, got {:?}", other),
+        }
+        
+        let usage = restored.with_subsystem("memory", |memory: &mut MemoryManager| memory.current_usage()).unwrap();
+        assert_eq!(usage, 0, "Memory restored before the failure should be rolled back");
+    }
+    
+    /// Test that only the most recent snapshots are kept
+    #[test]
+    fn test_snapshot_retention() {
+        let data_dir = create_data_dir();
+        let kernel = create_initialized_kernel().unwrap();
+        
+        let mut snapshot = kernel.capture_snapshot().unwrap();
+        let mut newest = PathBuf::new();
+        for created_at in 1..=SNAPSHOT_RETENTION as u64 + 2 {
+            snapshot.created_at = created_at;
+            newest = snapshot.write(&data_dir).unwrap();
+        }
+        
+        let kept = fs::read_dir(data_dir.join(SNAPSHOT_DIR)).unwrap().count();
+        assert_eq!(kept, SNAPSHOT_RETENTION);
+        assert_eq!(Snapshot::latest(&data_dir).unwrap(), Some(newest.clone()));
+        assert_eq!(Snapshot::read(&newest).unwrap(), snapshot);
+        
+        fs::remove_dir_all(&data_dir).unwrap();
+    }
+}
@@ -3,8 +3,14 @@
 //! This module tests the kernel's ability to process system calls
 //! and route them to the appropriate subsystems.
 
-use crate::Kernel;
-use crate::tests::test_utils::create_initialized_kernel;
+use crate::{ErrorCode, Syscall, SyscallError, SyscallResult, ToolRef};
+use crate::tests::test_utils::{
+    create_initialized_kernel, create_test_kernel_with_security, register_test_calculator,
+    test_allowed_operations,
+};
+use royaos_memory::{MemoryCategory, MemoryError, MemoryManager, SimilarMemory};
+use royaos_security::SecurityManager;
+use royaos_tools::ToolError;
 
 /// Test suite for system call processing
 #[cfg(test)]
@@ -22,10 +28,173 @@ mod syscall_processing_tests {
         // Test memory allocation syscall
         let result = kernel.process_syscall("memory_alloc", &["1024", "heap"]);
         assert!(result.is_ok(), "Memory allocation syscall should succeed");
+        let handle = result.unwrap();
+        
+        let usage = kernel.with_subsystem("memory", |memory: &mut royaos_memory::MemoryManager| {
+            memory.current_usage()
+        });
+        assert_eq!(usage.unwrap(), 1024, "Allocation should be recorded by the memory manager");
         
         // Test memory free syscall
-        let result = kernel.process_syscall("memory_free", &["0x12345678"]);
+        let result = kernel.process_syscall("memory_free", &[&handle]);
         assert!(result.is_ok(), "Memory free syscall should succeed");
+        
+        // Freeing the same handle twice or an invalid handle should fail
+        assert!(kernel.process_syscall("memory_free", &[&handle]).is_err());
+        assert!(kernel.process_syscall("memory_free", &["0x12345678"]).is_err());
+    }
+    
+    /// Test memory allocation with an explicit category
+    #[test]
+    fn test_memory_alloc_category() {
+        let kernel = match create_initialized_kernel() {
+            Ok(k) => k,
+            Err(e) => panic!("Failed to create initialized kernel: {}", e),
+        };
+        
+        let result = kernel.process_syscall("memory_alloc", &["2048", "facts", "long_term"]);
+        assert!(result.is_ok(), "Memory allocation with category should succeed");
+        
+        let usage = kernel.with_subsystem("memory", |memory: &mut royaos_memory::MemoryManager| {
+            memory.category_usage(royaos_memory::MemoryCategory::LongTerm)
+        });
+        assert_eq!(usage.unwrap(), 2048);
+        
+        // Invalid sizes and categories are rejected
+        assert!(kernel.process_syscall("memory_alloc", &["lots"]).is_err());
+        let error = kernel.process_syscall("memory_alloc", &["1024", "facts", "heap"]).unwrap_err();
+        assert_eq!(error.code(), "MEMORY_INVALID_CATEGORY");
+        
+        // Allocations beyond the limit report what is still available
+        match kernel.process_syscall("memory_alloc", &["1073741824", "everything"]) {
+            Err(SyscallError::Memory(MemoryError::LimitExceeded { requested, available })) => {
+                assert_eq!(requested, 1024 * 1024 * 1024);
+                assert_eq!(available, 100 * 1024 * 1024 - 2048);
+            },
+            other => panic!("Expected the memory limit to be exceeded, got {:?}", other),
+        }
+        
+        // A size the usage cannot be added to is rejected without harming the memory subsystem
+        let error = kernel.process_syscall("memory_alloc", &[&usize::MAX.to_string()]).unwrap_err();
+        assert_eq!(error.code(), "MEMORY_LIMIT_EXCEEDED");
+        assert!(kernel.process_syscall("memory_alloc", &["1024"]).is_ok());
+    }
+    
+    /// Test storing and loading values through system calls
+    #[test]
+    fn test_memory_value_syscalls() {
+        let kernel = create_initialized_kernel().unwrap();
+        let handle = kernel.process_syscall("memory_alloc", &["256", "notes"]).unwrap();
+        
+        let value = r#"{"facts":["rain tomorrow"],"topic":"weather"}"#;
+        assert_eq!(kernel.process_syscall("memory_put", &[&handle, value]).unwrap(), handle);
+        assert_eq!(kernel.process_syscall("memory_get", &[&handle]).unwrap(), value);
+        
+        // Values must be valid JSON and fit into the allocation
+        let error = kernel.process_syscall("memory_put", &[&handle, "not json"]).unwrap_err();
+        assert_eq!(error.code(), "SYSCALL_INVALID_ARGUMENTS");
+        let long = serde_json::Value::String("x".repeat(256)).to_string();
+        assert_eq!(kernel.process_syscall("memory_put", &[&handle, &long]).unwrap_err().code(), "MEMORY_OUT_OF_BOUNDS");
+        
+        let empty = kernel.process_syscall("memory_alloc", &["256"]).unwrap();
+        assert_eq!(kernel.process_syscall("memory_get", &[&empty]).unwrap_err().code(), "MEMORY_INVALID_VALUE");
+    }
+    
+    /// Test embedding memory and searching it by similarity through system calls
+    #[test]
+    fn test_memory_search_syscalls() {
+        let kernel = create_initialized_kernel().unwrap();
+        let weather = kernel.process_syscall("memory_alloc", &["256", "weather", "long_term"]).unwrap();
+        let rain = kernel.process_syscall("memory_alloc", &["256", "rain", "short_term"]).unwrap();
+        assert_eq!(kernel.process_syscall("memory_embed", &[&weather, "[1.0, 0.0]"]).unwrap(), weather);
+        assert_eq!(kernel.process_syscall("memory_embed", &[&rain, "[0.8, 0.6]"]).unwrap(), rain);
+        
+        let found: Vec<SimilarMemory> = serde_json::from_str(&kernel.process_syscall("memory_search", &["[0.0, 1.0]"]).unwrap()).unwrap();
+        assert_eq!(found.iter().map(|found| found.handle.to_string()).collect::<Vec<_>>(), [rain.clone(), weather.clone()]);
+        assert_eq!(found[0].category, MemoryCategory::ShortTerm);
+        assert!((found[0].similarity - 0.6).abs() < 1e-6);
+        
+        let found = kernel.process_syscall("memory_search", &["[0.0, 1.0]", "5", "long_term"]).unwrap();
+        let found: Vec<SimilarMemory> = serde_json::from_str(&found).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].purpose, "weather");
+        
+        // Removing an embedding takes the memory out of the search
+        kernel.process_syscall("memory_embed", &[&weather, "null"]).unwrap();
+        assert_eq!(kernel.process_syscall("memory_search", &["[1.0, 0.0]", "1", "long_term"]).unwrap(), "[]");
+        
+        let error = kernel.process_syscall("memory_search", &["[1.0, 0.0, 0.0]"]).unwrap_err();
+        assert_eq!(error.code(), "MEMORY_INVALID_EMBEDDING");
+        let error = kernel.process_syscall("memory_embed", &[&rain, "[\"a\"]"]).unwrap_err();
+        assert_eq!(error.code(), "SYSCALL_INVALID_ARGUMENTS");
+    }
+    
+    /// Test that agents only find their own memory when searching
+    #[test]
+    fn test_memory_search_by_principal() {
+        let kernel = create_initialized_kernel().unwrap();
+        kernel.register_principal("planner");
+        kernel.register_principal("critic");
+        let plan = kernel.process_principal_syscall("planner", None, "memory_alloc", &["256", "plan", "long_term"]).unwrap();
+        let review = kernel.process_principal_syscall("critic", None, "memory_alloc", &["256", "review", "long_term"]).unwrap();
+        kernel.process_principal_syscall("planner", None, "memory_embed", &[&plan, "[0.6, 0.8]"]).unwrap();
+        kernel.process_principal_syscall("critic", None, "memory_embed", &[&review, "[1.0, 0.0]"]).unwrap();
+        
+        let search = |principal| {
+            let found = kernel.process_principal_syscall(principal, None, "memory_search", &["[1.0, 0.0]", "1"]).unwrap();
+            serde_json::from_str::<Vec<SimilarMemory>>(&found).unwrap()
+                .into_iter()
+                .map(|found| found.handle.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(search("planner"), vec![plan.as_str()]);
+        assert_eq!(search("critic"), vec![review.as_str()]);
+        
+        // Calls without a principal search all memory
+        let found: Vec<SimilarMemory> = serde_json::from_str(&kernel.process_syscall("memory_search", &["[1.0, 0.0]"]).unwrap()).unwrap();
+        assert_eq!(found.len(), 2);
+        
+        // Released memory is no longer found by the agent that held it
+        kernel.process_principal_syscall("planner", None, "memory_free", &[&plan]).unwrap();
+        assert!(search("planner").is_empty());
+    }
+    
+    /// Test that agents cannot use memory another agent holds
+    #[test]
+    fn test_memory_handles_by_principal() {
+        let kernel = create_initialized_kernel().unwrap();
+        kernel.register_principal("planner");
+        kernel.register_principal("critic");
+        let plan = kernel.process_principal_syscall("planner", None, "memory_alloc", &["256", "plan"]).unwrap();
+        kernel.process_principal_syscall("planner", None, "memory_put", &[&plan, r#""buy milk""#]).unwrap();
+        
+        // Every call naming the planner's memory fails for the critic as if the memory did not exist
+        let calls: [(&str, Vec<&str>); 6] = [
+            ("memory_get", vec![&plan]),
+            ("memory_put", vec![&plan, r#""sell milk""#]),
+            ("memory_embed", vec![&plan, "[1.0, 0.0]"]),
+            ("memory_expire", vec![&plan, "1s"]),
+            ("memory_strength", vec![&plan]),
+            ("memory_free", vec![&plan]),
+        ];
+        for (name, args) in &calls {
+            let error = kernel.process_principal_syscall("critic", None, name, args).unwrap_err();
+            assert_eq!(error.code(), "MEMORY_NOT_FOUND", "{} with another agent's memory", name);
+        }
+        
+        // The memory and the planner's quota charge are untouched
+        assert_eq!(kernel.process_principal_syscall("planner", None, "memory_get", &[&plan]).unwrap(), r#""buy milk""#);
+        let usage = kernel.principal_usage("planner").unwrap();
+        assert_eq!(usage.memory_bytes.get(&MemoryCategory::Working), Some(&256));
+        
+        // The holder and calls without a principal can still use it
+        for (name, args) in &calls {
+            assert!(kernel.process_principal_syscall("planner", None, name, args).is_ok(), "{} by the holder", name);
+        }
+        assert!(kernel.principal_usage("planner").unwrap().memory_bytes.is_empty());
+        let system = kernel.process_syscall("memory_alloc", &["256", "system"]).unwrap();
+        assert_eq!(kernel.process_principal_syscall("planner", None, "memory_get", &[&system]).unwrap_err().code(), "MEMORY_NOT_FOUND");
+        assert!(kernel.process_syscall("memory_free", &[&system]).is_ok());
     }
     
     /// Test processing of tool-related system calls
@@ -36,9 +205,26 @@ mod syscall_processing_tests {
             Err(e) => panic!("Failed to create initialized kernel: {}", e),
         };
         
-        // Test tool execution syscall
-        let result = kernel.process_syscall("tool_execute", &["calculator", "add", "5", "3"]);
+        let handle = register_test_calculator(&kernel).to_string();
+        
+        // Test tool execution syscall by tool identifier
+        let result = kernel.process_syscall("tool_execute", &["calculator", "add", r#"{"a": 5, "b": 3}"#]);
         assert!(result.is_ok(), "Tool execution syscall should succeed");
+        let tool_result: royaos_tools::ToolResult = serde_json::from_str(&result.unwrap()).unwrap();
+        assert!(tool_result.success);
+        assert_eq!(tool_result.data, Some("8".to_string()));
+        
+        // Test tool execution syscall by tool handle
+        let result = kernel.process_syscall("tool_execute", &[&handle, "add", r#"{"a": 1, "b": 1}"#]);
+        assert!(result.is_ok(), "Tool execution by handle should succeed");
+        
+        // Unknown tools and capabilities are errors
+        let error = kernel.process_syscall("tool_execute", &["unknown", "add"]).unwrap_err();
+        assert!(matches!(error, SyscallError::Tool(ToolError::UnknownTool(_))));
+        assert_eq!(error.code(), "TOOL_NOT_FOUND");
+        let error = kernel.process_syscall("tool_execute", &["calculator", "divide"]).unwrap_err();
+        assert!(matches!(error, SyscallError::Tool(ToolError::CapabilityNotFound { .. })));
+        assert_eq!(error.code(), "TOOL_CAPABILITY_NOT_FOUND");
     }
     
     /// Test processing of security-related system calls
@@ -51,7 +237,14 @@ mod syscall_processing_tests {
         
         // Test security check syscall
         let result = kernel.process_syscall("security_check", &["file", "read", "/tmp/test.txt"]);
-        assert!(result.is_ok(), "Security check syscall should succeed");
+        assert_eq!(result.unwrap(), "allowed".to_string(), "Security check syscall should succeed");
+        
+        // Under standard security the test kernel does not allow file access
+        assert!(kernel.with_subsystem("security", |security: &mut royaos_security::SecurityManager| {
+            security.set_security_level("standard")
+        }).unwrap().is_ok());
+        let result = kernel.process_syscall("security_check", &["file", "read", "/tmp/test.txt"]);
+        assert_eq!(result.unwrap(), "denied".to_string(), "Unlisted operation should be denied");
     }
     
     /// Test handling of invalid system calls
@@ -64,10 +257,171 @@ mod syscall_processing_tests {
         
         // Test non-existent syscall
         let result = kernel.process_syscall("nonexistent_syscall", &[]);
-        assert!(result.is_err(), "Non-existent syscall should fail");
+        assert!(matches!(result, Err(SyscallError::UnknownSyscall(_))), "Non-existent syscall should fail");
         
         // Test syscall with invalid arguments
         let result = kernel.process_syscall("memory_alloc", &[]);
-        assert!(result.is_err(), "Syscall with invalid arguments should fail");
+        assert!(matches!(result, Err(SyscallError::InvalidArguments(_))), "Syscall with invalid arguments should fail");
+    }
+}
+
+/// Test suite for typed system calls
+#[cfg(test)]
+mod typed_syscall_tests {
+    use super::*;
+    
+    /// Test parsing the string form of system calls
+    #[test]
+    fn test_syscall_parsing() {
+        let syscall = Syscall::parse("memory_alloc", &["1024", "scratch", "short_term"]).unwrap();
+        assert_eq!(syscall, Syscall::MemoryAlloc {
+            size: 1024,
+            purpose: "scratch".to_string(),
+            category: MemoryCategory::ShortTerm,
+        });
+        
+        let syscall = Syscall::parse("tool_execute", &["calculator", "add", r#"{"a": 1}"#]).unwrap();
+        assert_eq!(syscall, Syscall::ToolExecute {
+            tool: ToolRef::Id("calculator".to_string()),
+            capability: "add".to_string(),
+            params: serde_json::json!({"a": 1}),
+        });
+        assert_eq!(syscall.name(), "tool_execute");
+        
+        // The arguments of a system call parse back into the same call
+        for syscall in [
+            syscall,
+            Syscall::parse("memory_put", &["6f1c0b52-8d6e-4a57-9a0e-3c1e2f4b5a69", r#"{"a": [1, 2]}"#]).unwrap(),
+            Syscall::parse("memory_embed", &["6f1c0b52-8d6e-4a57-9a0e-3c1e2f4b5a69", "[0.1, -2.5e-3]"]).unwrap(),
+            Syscall::parse("memory_search", &["[0.3, 0.7]", "3", "long_term"]).unwrap(),
+            Syscall::parse("memory_search", &["[1]"]).unwrap(),
+            Syscall::parse("memory_consolidate", &[]).unwrap(),
+            Syscall::parse("memory_expire", &["6f1c0b52-8d6e-4a57-9a0e-3c1e2f4b5a69", "90s"]).unwrap(),
+            Syscall::parse("memory_expire", &["6f1c0b52-8d6e-4a57-9a0e-3c1e2f4b5a69", "none"]).unwrap(),
+            Syscall::parse("memory_strength", &["6f1c0b52-8d6e-4a57-9a0e-3c1e2f4b5a69"]).unwrap(),
+            Syscall::parse("security_grant", &["file", "read", "*"]).unwrap(),
+            Syscall::parse("job_schedule", &["every 5m", "memory_alloc", "64", "scratch"]).unwrap(),
+        ] {
+            let args = syscall.args();
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            assert_eq!(Syscall::parse(syscall.name(), &args).unwrap(), syscall);
+        }
+        
+        assert!(Syscall::parse("tool_execute", &["calculator", "add", "not json"]).is_err());
+        assert!(Syscall::parse("memory_free", &["not-a-handle"]).is_err());
+        assert!(Syscall::parse("memory_expire", &["6f1c0b52-8d6e-4a57-9a0e-3c1e2f4b5a69", "soon"]).is_err());
+        assert!(Syscall::parse("unknown", &[]).is_err());
+    }
+    
+    /// Test that system calls and results survive a serde round trip
+    #[test]
+    fn test_syscall_serialization() {
+        let handle = uuid::Uuid::new_v4();
+        let syscall = Syscall::ToolExecute {
+            tool: ToolRef::Handle(handle),
+            capability: "add".to_string(),
+            params: serde_json::json!({"a": 1, "b": 2}),
+        };
+        
+        let json = serde_json::to_value(&syscall).unwrap();
+        assert_eq!(json["type"], "tool_execute");
+        assert_eq!(json["tool"], handle.to_string());
+        assert_eq!(serde_json::from_value::<Syscall>(json).unwrap(), syscall);
+        
+        let json = serde_json::json!({
+            "type": "memory_alloc",
+            "size": 64,
+            "purpose": "scratch",
+            "category": "long_term",
+        });
+        assert_eq!(serde_json::from_value::<Syscall>(json).unwrap(), Syscall::MemoryAlloc {
+            size: 64,
+            purpose: "scratch".to_string(),
+            category: MemoryCategory::LongTerm,
+        });
+        
+        let result = SyscallResult::PermissionChecked { allowed: false };
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json, serde_json::json!({"type": "permission_checked", "allowed": false}));
+    }
+    
+    /// Test executing typed system calls
+    #[test]
+    fn test_execute_syscall() {
+        let kernel = match create_initialized_kernel() {
+            Ok(k) => k,
+            Err(e) => panic!("Failed to create initialized kernel: {}", e),
+        };
+        
+        let result = kernel.execute_syscall(Syscall::MemoryAlloc {
+            size: 512,
+            purpose: "typed".to_string(),
+            category: MemoryCategory::Working,
+        });
+        let handle = match result {
+            Ok(SyscallResult::MemoryAllocated { handle }) => handle,
+            other => panic!("Expected a memory allocation, got {:?}", other),
+        };
+        
+        let result = kernel.execute_syscall(Syscall::MemoryFree { handle });
+        assert!(matches!(result, Ok(SyscallResult::MemoryFreed { handle: freed }) if freed == handle));
+    }
+}
+
+/// Test suite for the security gate in front of system calls
+#[cfg(test)]
+mod syscall_security_tests {
+    use super::*;
+    
+    /// Test that a denied syscall never reaches its subsystem
+    #[test]
+    fn test_denied_syscall() {
+        let security = SecurityManager::new("standard", vec!["security_query".to_string()]).unwrap();
+        let mut kernel = create_test_kernel_with_security(security);
+        assert!(kernel.initialize().is_ok());
+        
+        let result = kernel.execute_syscall(Syscall::MemoryAlloc {
+            size: 1024,
+            purpose: "denied".to_string(),
+            category: MemoryCategory::Working,
+        });
+        match result {
+            Err(SyscallError::PermissionDenied(permission)) => {
+                assert_eq!(permission.resource_type, "memory");
+                assert_eq!(permission.operation, "allocate");
+                assert_eq!(permission.resource, "working");
+            },
+            other => panic!("Expected a permission denial, got {:?}", other),
+        }
+        
+        let usage = kernel.with_subsystem("memory", |memory: &mut MemoryManager| memory.current_usage());
+        assert_eq!(usage.unwrap(), 0, "Denied allocation should not reach the memory manager");
+        
+        // The string form reports the denial as an error
+        let error = kernel.process_syscall("tool_execute", &["calculator", "add"]).unwrap_err();
+        assert_eq!(error.to_string(), "Permission denied: tool execute calculator");
+        assert_eq!(error.code(), "SECURITY_DENIED");
+        
+        // Denials are recorded in the security event log
+        let events = kernel.with_subsystem("security", |security: &mut SecurityManager| {
+            security.get_recent_events(10)
+        }).unwrap();
+        let denials = events.iter().filter(|event| event.event_type == "syscall_denied").count();
+        assert_eq!(denials, 2, "Both denied syscalls should be logged");
+    }
+    
+    /// Test that permitted syscalls pass the gate under standard security
+    #[test]
+    fn test_permitted_syscall() {
+        let security = SecurityManager::new("standard", test_allowed_operations()).unwrap();
+        let mut kernel = create_test_kernel_with_security(security);
+        assert!(kernel.initialize().is_ok());
+        register_test_calculator(&kernel);
+        
+        let result = kernel.process_syscall("memory_alloc", &["1024"]);
+        assert!(result.is_ok(), "Allowed memory allocation should succeed");
+        
+        let result = kernel.process_syscall("tool_execute", &["calculator", "add", r#"{"a": 2, "b": 2}"#]);
+        assert!(result.is_ok(), "Allowed tool execution should succeed");
     }
 }
@@ -3,16 +3,84 @@
 //! This module provides helper functions and mock implementations
 //! to facilitate testing of kernel components.
 
-use crate::Kernel;
+use crate::{Clock, HandleGenerator, JobSpec, Kernel, KernelError, Subsystem, SubsystemError, SubsystemHealth};
+use royaos_interface::InterfaceManager;
+use royaos_memory::MemoryManager;
+use royaos_security::SecurityManager;
+use royaos_tools::{ToolCapability, ToolHandle, ToolManager, ToolMetadata, ToolParameter};
+use std::any::Any;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
 /// Create a test kernel instance with standard configuration
 ///
+/// The kernel has the memory, tools, security and interface subsystems
+/// registered but not yet initialized.
+///
 /// # Returns
 ///
 /// A kernel instance ready for testing
 pub fn create_test_kernel() -> Kernel {
-    Kernel::new("test-version")
+    let security = SecurityManager::new("low", test_allowed_operations())
+        .expect("Test security configuration should be valid");
+    
+    create_test_kernel_with_security(security)
+}
+
+/// Create a test kernel instance with a specific security manager
+///
+/// # Arguments
+///
+/// * `security` - The security manager to register
+///
+/// # Returns
+///
+/// A kernel instance ready for testing
+pub fn create_test_kernel_with_security(security: SecurityManager) -> Kernel {
+    let mut kernel = Kernel::new("test-version");
+    register_test_subsystems(&mut kernel, security);
+    kernel
+}
+
+/// Create and initialize a test kernel running on a simulated clock
+///
+/// # Arguments
+///
+/// * `seed` - Seed of the kernel's handle generator
+///
+/// # Returns
+///
+/// An initialized kernel whose time only moves when its clock is advanced
+pub fn create_simulated_kernel(seed: u64) -> Kernel {
+    let security = SecurityManager::new("low", test_allowed_operations())
+        .expect("Test security configuration should be valid");
+    
+    let mut kernel = Kernel::with_clock("test-version", Clock::simulated(), HandleGenerator::seeded(seed));
+    register_test_subsystems(&mut kernel, security);
+    kernel.initialize().expect("Simulated test kernel should initialize");
+    kernel
+}
+
+/// Register the memory, tools, security and interface subsystems
+fn register_test_subsystems(kernel: &mut Kernel, security: SecurityManager) {
+    kernel.register_subsystem(Box::new(MemoryManager::new(100, "balanced"))).unwrap();
+    kernel.register_subsystem(Box::new(ToolManager::new(vec![], false))).unwrap();
+    kernel.register_subsystem(Box::new(security)).unwrap();
+    kernel.register_subsystem(Box::new(InterfaceManager::new("1.0"))).unwrap();
+}
+
+/// Operations allowed by the test security configuration
+///
+/// # Returns
+///
+/// The operations needed for the kernel syscalls exercised by the tests
+pub fn test_allowed_operations() -> Vec<String> {
+    vec![
+        "tool_execution".to_string(),
+        "memory_access".to_string(),
+        "security_query".to_string(),
+        "task_management".to_string(),
+    ]
 }
 
 /// Create and initialize a test kernel
@@ -20,16 +88,55 @@ pub fn create_test_kernel() -> Kernel {
 /// # Returns
 ///
 /// An initialized kernel instance ready for testing
-pub fn create_initialized_kernel() -> Result<Kernel, String> {
+pub fn create_initialized_kernel() -> Result<Kernel, KernelError> {
     let mut kernel = create_test_kernel();
     kernel.initialize()?;
     Ok(kernel)
 }
 
+/// Register a calculator tool with an "add" capability in the kernel's tools subsystem
+///
+/// # Returns
+///
+/// Handle to the registered tool
+pub fn register_test_calculator(kernel: &Kernel) -> ToolHandle {
+    let number_param = |name: &str| ToolParameter {
+        name: name.to_string(),
+        description: format!("Operand {}", name),
+        param_type: "number".to_string(),
+        required: true,
+        default_value: None,
+    };
+    
+    let metadata = ToolMetadata {
+        id: "calculator".to_string(),
+        name: "Calculator".to_string(),
+        description: "Performs mathematical calculations".to_string(),
+        version: "1.0.0".to_string(),
+        author: "Test Author".to_string(),
+        categories: vec!["math".to_string()],
+        capabilities: vec![ToolCapability {
+            name: "add".to_string(),
+            description: "Add two numbers".to_string(),
+            parameters: vec![number_param("a"), number_param("b")],
+            return_type: "number".to_string(),
+        }],
+    };
+    
+    kernel.with_subsystem("tools", |tools: &mut ToolManager| {
+        tools.register_tool(metadata, PathBuf::from("./tools/calculator"))
+    }).unwrap().unwrap()
+}
+
 /// Mock subsystem for testing kernel interactions
 pub struct MockSubsystem {
     name: String,
     initialized: bool,
+    dependencies: Vec<String>,
+    fail_on_initialize: bool,
+    lifecycle_log: Option<Arc<Mutex<Vec<String>>>>,
+    reported_health: Option<Arc<Mutex<SubsystemHealth>>>,
+    jobs: Vec<JobSpec>,
 }
 
 impl MockSubsystem {
@@ -37,24 +144,108 @@ impl MockSubsystem {
         Self {
             name: name.to_string(),
             initialized: false,
+            dependencies: Vec::new(),
+            fail_on_initialize: false,
+            lifecycle_log: None,
+            reported_health: None,
+            jobs: Vec::new(),
         }
     }
     
-    pub fn initialize(&mut self) -> Result<(), String> {
+    /// Declare the subsystems this mock depends on
+    pub fn depends_on(mut self, dependencies: &[&str]) -> Self {
+        self.dependencies = dependencies.iter().map(|dep| dep.to_string()).collect();
+        self
+    }
+    
+    /// Make initialization of this mock always fail
+    pub fn fail_on_initialize(mut self) -> Self {
+        self.fail_on_initialize = true;
+        self
+    }
+    
+    /// Record "init:<name>" and "shutdown:<name>" entries in a shared log
+    pub fn with_lifecycle_log(mut self, log: Arc<Mutex<Vec<String>>>) -> Self {
+        self.lifecycle_log = Some(log);
+        self
+    }
+    
+    /// Report the health stored in a shared cell instead of the lifecycle state
+    pub fn with_health(mut self, health: Arc<Mutex<SubsystemHealth>>) -> Self {
+        self.reported_health = Some(health);
+        self
+    }
+    
+    /// Declare jobs for the kernel timer service; runs are recorded as "job <job>:<name>"
+    pub fn with_jobs(mut self, jobs: Vec<JobSpec>) -> Self {
+        self.jobs = jobs;
+        self
+    }
+    
+    pub fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+    
+    fn record(&self, event: &str) {
+        if let Some(log) = &self.lifecycle_log {
+            log.lock().unwrap().push(format!("{}:{}", event, self.name));
+        }
+    }
+}
+
+impl Subsystem for MockSubsystem {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    
+    fn dependencies(&self) -> Vec<String> {
+        self.dependencies.clone()
+    }
+    
+    fn initialize(&mut self) -> Result<(), SubsystemError> {
+        if self.fail_on_initialize {
+            return Err(SubsystemError::msg(format!("{} configured to fail", self.name)));
+        }
+        self.record("init");
         self.initialized = true;
         Ok(())
     }
     
-    pub fn shutdown(&mut self) -> Result<(), String> {
+    fn shutdown(&mut self) -> Result<(), SubsystemError> {
+        self.record("shutdown");
         self.initialized = false;
         Ok(())
     }
     
-    pub fn is_initialized(&self) -> bool {
-        self.initialized
+    fn health(&self) -> SubsystemHealth {
+        if let Some(health) = &self.reported_health {
+            return health.lock().unwrap().clone();
+        }
+        
+        if self.initialized {
+            SubsystemHealth::Healthy
+        } else {
+            SubsystemHealth::Failed("Not initialized".to_string())
+        }
     }
     
-    pub fn name(&self) -> &str {
-        &self.name
+    fn jobs(&self) -> Vec<JobSpec> {
+        self.jobs.clone()
+    }
+    
+    fn run_job(&mut self, job: &str) -> Result<(), SubsystemError> {
+        if !self.jobs.iter().any(|spec| spec.name == job) {
+            return Err(SubsystemError::msg(format!("Unknown job {}", job)));
+        }
+        self.record(&format!("job {}", job));
+        Ok(())
+    }
+    
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
     }
 }
@@ -0,0 +1,174 @@
+//! Timer service tests
+//!
+//! This module tests the jobs subsystems declare, the system calls the AGI
+//! schedules, and the timer task running them.
+
+use crate::{ErrorCode, JobAction, JobInfo, JobSpec, Kernel, Quota, Schedule, Syscall};
+use crate::tests::test_utils::{create_initialized_kernel, create_test_kernel, MockSubsystem};
+use royaos_memory::{CONSOLIDATE_INTERVAL, CONSOLIDATE_JOB, MemoryCategory, MemoryManager, OPTIMIZE_INTERVAL, OPTIMIZE_JOB};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Create an initialized kernel with a mock subsystem declaring jobs
+///
+/// # Returns
+///
+/// The kernel and the lifecycle log of the mock subsystem
+fn create_kernel_with_jobs(jobs: Vec<JobSpec>) -> (Kernel, Arc<Mutex<Vec<String>>>) {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut kernel = create_test_kernel();
+    kernel.register_subsystem(Box::new(MockSubsystem::new("mock").with_jobs(jobs).with_lifecycle_log(log.clone()))).unwrap();
+    kernel.initialize().unwrap();
+    (kernel, log)
+}
+
+/// Find the job with an ID among the scheduled jobs
+fn find_job(kernel: &Kernel, id: &str) -> Option<JobInfo> {
+    kernel.jobs().into_iter().find(|job| job.id.to_string() == id)
+}
+
+/// Get the bytes allocated in working memory
+fn working_memory(kernel: &Kernel) -> usize {
+    kernel.with_subsystem("memory", |memory: &mut MemoryManager| memory.category_usage(MemoryCategory::Working)).unwrap()
+}
+
+/// Test suite for the timer service
+#[cfg(test)]
+mod timer_service_tests {
+    use super::*;
+    
+    /// Test that the jobs subsystems declare are scheduled on initialization
+    #[test]
+    fn test_subsystem_jobs_scheduled() {
+        let kernel = create_initialized_kernel().unwrap();
+        
+        let jobs = kernel.jobs();
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[0].owner, "memory");
+        assert_eq!(jobs[0].name, OPTIMIZE_JOB);
+        assert_eq!(jobs[0].schedule, Schedule::Interval(OPTIMIZE_INTERVAL));
+        assert_eq!(jobs[0].action, JobAction::Subsystem { subsystem: "memory".to_string(), job: OPTIMIZE_JOB.to_string() });
+        assert!(jobs[0].next_run.is_some());
+        assert_eq!(jobs[1].owner, "memory");
+        assert_eq!(jobs[1].name, CONSOLIDATE_JOB);
+        assert_eq!(jobs[1].schedule, Schedule::Interval(CONSOLIDATE_INTERVAL));
+        
+        // Nothing is due right after startup
+        assert_eq!(kernel.run_due_jobs(), 0);
+    }
+    
+    /// Test that due subsystem jobs run and keep their ID across restarts
+    #[test]
+    fn test_subsystem_job_runs() {
+        let (kernel, log) = create_kernel_with_jobs(vec![
+            JobSpec::new("reindex", Schedule::Interval(Duration::from_millis(10))),
+            JobSpec::new("invalid", Schedule::Cron("every tuesday".to_string())),
+        ]);
+        let job = kernel.jobs().into_iter().find(|job| job.owner == "mock").unwrap();
+        assert_eq!(kernel.jobs().iter().filter(|job| job.owner == "mock").count(), 1);
+        
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(kernel.run_due_jobs(), 1);
+        assert!(log.lock().unwrap().contains(&"job reindex:mock".to_string()));
+        
+        kernel.restart_subsystem("mock").unwrap();
+        let restarted = find_job(&kernel, &job.id.to_string()).unwrap();
+        assert_eq!(restarted.runs, 1);
+        assert_eq!(restarted.failures, 0);
+        
+        // A cancelled subsystem job is gone until the subsystem is initialized again
+        kernel.cancel_job(restarted.id).unwrap();
+        assert!(find_job(&kernel, &job.id.to_string()).is_none());
+        assert_eq!(kernel.cancel_job(restarted.id).unwrap_err().code(), "TIMER_UNKNOWN_JOB");
+    }
+    
+    /// Test scheduling, listing and cancelling jobs through system calls
+    #[test]
+    fn test_job_syscalls() {
+        let kernel = create_initialized_kernel().unwrap();
+        
+        let once = kernel.process_syscall("job_schedule", &["once 0s", "memory_alloc", "1024"]).unwrap();
+        let hourly = kernel.process_syscall("job_schedule", &["cron 0 0 * * * *", "security_check", "memory", "allocate", "working"]).unwrap();
+        
+        let listed: Vec<JobInfo> = serde_json::from_str(&kernel.process_syscall("job_list", &[]).unwrap()).unwrap();
+        assert_eq!(listed.len(), 4);
+        assert_eq!(listed[0].id.to_string(), once);
+        assert_eq!(listed[0].owner, "kernel");
+        assert_eq!(listed[0].name, "memory_alloc");
+        
+        // A one-shot job runs once and is removed
+        assert_eq!(kernel.run_due_jobs(), 1);
+        assert_eq!(working_memory(&kernel), 1024);
+        assert!(find_job(&kernel, &once).is_none());
+        assert_eq!(kernel.run_due_jobs(), 0);
+        
+        assert_eq!(kernel.process_syscall("job_cancel", &[&hourly]).unwrap(), hourly);
+        assert_eq!(kernel.process_syscall("job_cancel", &[&hourly]).unwrap_err().code(), "TIMER_UNKNOWN_JOB");
+        
+        let result = kernel.process_syscall("job_schedule", &["cron every tuesday", "job_list"]);
+        assert_eq!(result.unwrap_err().code(), "TIMER_INVALID_SCHEDULE");
+        let result = kernel.process_syscall("job_schedule", &["every 0s", "job_list"]);
+        assert_eq!(result.unwrap_err().code(), "TIMER_INVALID_SCHEDULE");
+        let result = kernel.process_syscall("job_schedule", &["every 1m", "job_schedule", "once 1s", "job_list"]);
+        assert_eq!(result.unwrap_err().code(), "SYSCALL_INVALID_ARGUMENTS");
+    }
+    
+    /// Test that jobs scheduled by a principal run under its quota and belong to it
+    #[test]
+    fn test_principal_jobs() {
+        let kernel = create_initialized_kernel().unwrap();
+        kernel.register_principal("planner");
+        kernel.register_principal("critic");
+        
+        let job = kernel.process_principal_syscall("planner", None, "job_schedule", &["every 10ms", "memory_alloc", "2048"]).unwrap();
+        assert_eq!(find_job(&kernel, &job).unwrap().owner, "planner");
+        
+        let result = kernel.process_principal_syscall("critic", None, "job_cancel", &[&job]);
+        assert_eq!(result.unwrap_err().code(), "TIMER_NOT_OWNER");
+        
+        std::thread::sleep(Duration::from_millis(20));
+        kernel.run_due_jobs();
+        assert_eq!(kernel.principal_usage("planner").unwrap().memory_bytes.get(&MemoryCategory::Working), Some(&2048));
+        
+        // Runs rejected by the quota are recorded with the job
+        kernel.set_principal_quota("planner", Some(Quota {
+            memory_bytes: HashMap::from([(MemoryCategory::Working, 2048)]),
+            ..Quota::default()
+        })).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        kernel.run_due_jobs();
+        let failed = find_job(&kernel, &job).unwrap();
+        assert_eq!((failed.runs, failed.failures), (2, 1));
+        assert!(failed.last_error.is_some());
+        assert_eq!(working_memory(&kernel), 2048);
+        
+        // Removing the principal cancels its jobs
+        kernel.remove_principal("planner").unwrap();
+        assert!(find_job(&kernel, &job).is_none());
+        
+        let result = kernel.schedule_syscall(Some("planner"), Schedule::Once(Duration::ZERO), Syscall::JobList);
+        assert_eq!(result.unwrap_err().code(), "QUOTA_UNKNOWN_PRINCIPAL");
+    }
+    
+    /// Test that the timer task runs due jobs
+    #[tokio::test]
+    async fn test_timer_task() {
+        let kernel = Arc::new(create_initialized_kernel().unwrap());
+        let timers = kernel.start_timers(Duration::from_millis(5)).unwrap();
+        
+        let syscall = Syscall::MemoryAlloc { size: 4096, purpose: "scheduled".to_string(), category: MemoryCategory::Working };
+        kernel.schedule_syscall(None, Schedule::Once(Duration::from_millis(10)), syscall).unwrap();
+        
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while working_memory(&kernel) == 0 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        }).await.expect("Timer task should run the job");
+        assert_eq!(kernel.jobs().len(), 2);
+        
+        let scheduler = kernel.scheduler();
+        scheduler.cancel(timers).unwrap();
+        assert!(scheduler.join(timers).await.is_ok());
+    }
+}
@@ -0,0 +1,164 @@
+//! Syscall trace and replay tests
+//!
+//! This module tests recording system calls to a trace file and replaying the
+//! trace into a fresh kernel.
+
+use crate::{ErrorCode, KernelError, Trace, TraceOutcome, TRACE_DIR};
+use crate::tests::test_utils::{create_initialized_kernel, register_test_calculator};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Create an empty data directory for one test
+fn create_data_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("royaos-trace-{}", Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Record a trace of allocations, a tool execution and a failing call
+///
+/// # Returns
+///
+/// Path of the trace file
+fn record_trace(data_dir: &Path) -> PathBuf {
+    let kernel = create_initialized_kernel().unwrap();
+    let tool = register_test_calculator(&kernel).to_string();
+    let session = Uuid::new_v4();
+    
+    let path = kernel.start_trace(data_dir).unwrap();
+    let memory = kernel.process_syscall("memory_alloc", &["1024", "scratch"]).unwrap();
+    kernel.process_session_syscall(session, "tool_execute", &[&tool, "add", r#"{"a": 1, "b": 2}"#]).unwrap();
+    kernel.process_syscall("memory_free", &[&memory]).unwrap();
+    kernel.process_syscall("memory_free", &[&memory]).unwrap_err();
+    assert_eq!(kernel.stop_trace(), Some(path.clone()));
+    
+    path
+}
+
+/// Test suite for syscall tracing
+#[cfg(test)]
+mod syscall_trace_tests {
+    use super::*;
+    
+    /// Test that traced calls are recorded in order with their outcomes
+    #[test]
+    fn test_trace_records_calls() {
+        let data_dir = create_data_dir();
+        let path = record_trace(&data_dir);
+        assert!(path.starts_with(data_dir.join(TRACE_DIR)));
+        
+        let trace = Trace::read(&path).unwrap();
+        assert_eq!(trace.header.tools.values().collect::<Vec<_>>(), vec!["calculator"]);
+        
+        let syscalls: Vec<&str> = trace.records.iter().map(|record| record.syscall.as_str()).collect();
+        assert_eq!(syscalls, vec!["memory_alloc", "tool_execute", "memory_free", "memory_free"]);
+        assert_eq!(trace.records.iter().map(|record| record.seq).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+        assert!(trace.records[0].session.is_none());
+        assert!(trace.records[1].session.is_some());
+        assert!(matches!(&trace.records[3].outcome, TraceOutcome::Err { code, .. } if code == "MEMORY_NOT_FOUND"));
+        
+        fs::remove_dir_all(&data_dir).unwrap();
+    }
+    
+    /// Test that calls made after tracing stops are not recorded
+    #[test]
+    fn test_stop_trace() {
+        let data_dir = create_data_dir();
+        let kernel = create_initialized_kernel().unwrap();
+        assert_eq!(kernel.stop_trace(), None);
+        
+        let path = kernel.start_trace(&data_dir).unwrap();
+        assert_eq!(kernel.start_trace(&data_dir).unwrap(), path);
+        assert_eq!(kernel.trace_path(), Some(path.clone()));
+        kernel.process_syscall("memory_alloc", &["1024"]).unwrap();
+        kernel.stop_trace();
+        kernel.process_syscall("memory_alloc", &["1024"]).unwrap();
+        
+        assert_eq!(kernel.trace_path(), None);
+        assert_eq!(Trace::read(&path).unwrap().records.len(), 1);
+        
+        fs::remove_dir_all(&data_dir).unwrap();
+    }
+    
+    /// Test that replaying a trace into a fresh kernel reproduces it
+    #[test]
+    fn test_replay_is_clean() {
+        let data_dir = create_data_dir();
+        let trace = Trace::read(&record_trace(&data_dir)).unwrap();
+        
+        // Handles differ in the fresh kernel and are mapped during the replay
+        let kernel = create_initialized_kernel().unwrap();
+        register_test_calculator(&kernel);
+        let report = kernel.replay_trace(&trace).unwrap();
+        
+        assert_eq!(report.replayed, 4);
+        assert!(report.is_clean(), "Unexpected divergences: {:?}", report.divergences);
+        
+        fs::remove_dir_all(&data_dir).unwrap();
+    }
+    
+    /// Test that a replay reports calls whose outcome differs from the trace
+    #[test]
+    fn test_replay_reports_divergence() {
+        let data_dir = create_data_dir();
+        let recorded = Trace::read(&record_trace(&data_dir)).unwrap();
+        let mut trace = recorded.clone();
+        trace.records[1].outcome = TraceOutcome::Ok(r#"{"result": 4}"#.to_string());
+        
+        let kernel = create_initialized_kernel().unwrap();
+        register_test_calculator(&kernel);
+        let report = kernel.replay_trace(&trace).unwrap();
+        
+        assert_eq!(report.replayed, 4);
+        assert_eq!(report.divergences.len(), 1);
+        assert_eq!(report.divergences[0].seq, 2);
+        assert_eq!(report.divergences[0].syscall, "tool_execute");
+        
+        // A kernel without the traced tool fails to execute it
+        let kernel = create_initialized_kernel().unwrap();
+        let report = kernel.replay_trace(&recorded).unwrap();
+        assert_eq!(report.divergences.iter().map(|divergence| divergence.seq).collect::<Vec<_>>(), vec![2]);
+        
+        fs::remove_dir_all(&data_dir).unwrap();
+    }
+    
+    /// Test that an incomplete last line is ignored and other invalid lines are not
+    #[test]
+    fn test_read_incomplete_trace() {
+        let data_dir = create_data_dir();
+        let path = record_trace(&data_dir);
+        
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"seq": 5, "syscall": "memory_"#).unwrap();
+        assert_eq!(Trace::read(&path).unwrap().records.len(), 4);
+        
+        file.write_all(b"\n{}\n").unwrap();
+        match Trace::read(&path) {
+            Err(error @ KernelError::TraceFormat { line: 6, .. }) => assert_eq!(error.code(), "TRACE_INVALID"),
+            other => panic!("Expected an invalid trace, got {:?}", other),
+        }
+        
+        fs::remove_dir_all(&data_dir).unwrap();
+    }
+    
+    /// Test that traces written in another format version are rejected
+    #[test]
+    fn test_read_rejects_unsupported_version() {
+        let data_dir = create_data_dir();
+        let path = record_trace(&data_dir);
+        
+        let contents = fs::read_to_string(&path).unwrap().replacen(r#""format_version":1"#, r#""format_version":99"#, 1);
+        fs::write(&path, contents).unwrap();
+        
+        match Trace::read(&path) {
+            Err(error @ KernelError::TraceVersion { found: 99, supported: 1, .. }) => {
+                assert_eq!(error.code(), "TRACE_UNSUPPORTED_VERSION");
+            },
+            other => panic!("Expected an unsupported version, got {:?}", other),
+        }
+        
+        fs::remove_dir_all(&data_dir).unwrap();
+    }
+}
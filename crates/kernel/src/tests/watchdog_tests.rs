@@ -0,0 +1,240 @@
+//! Health check and watchdog tests
+//!
+//! This module tests the kernel's health checks, the state changes and
+//! events they cause, and the restart policies applied by the watchdog.
+
+use crate::{EventKind, Kernel, KernelEvent, RestartPolicy, SubsystemHealth, SubsystemState};
+use crate::tests::test_utils::{create_initialized_kernel, MockSubsystem};
+use royaos_memory::{MemoryCategory, MemoryManager};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Kernel with a controllable mock subsystem, its health cell and its lifecycle log
+type WatchedKernel = (Kernel, Arc<Mutex<SubsystemHealth>>, Arc<Mutex<Vec<String>>>);
+
+/// Create a running kernel with one mock subsystem whose health the test controls
+///
+/// # Arguments
+///
+/// * `policy` - Restart policy of the mock subsystem
+///
+/// # Returns
+///
+/// The kernel, the shared health cell and the shared lifecycle log
+fn create_watched_kernel(policy: RestartPolicy) -> WatchedKernel {
+    let health = Arc::new(Mutex::new(SubsystemHealth::Healthy));
+    let log = Arc::new(Mutex::new(Vec::new()));
+    
+    let mut kernel = Kernel::new("test-version");
+    let mock = MockSubsystem::new("mock")
+        .with_health(health.clone())
+        .with_lifecycle_log(log.clone());
+    kernel.register_subsystem(Box::new(mock)).unwrap();
+    kernel.set_restart_policy("mock", policy).unwrap();
+    kernel.initialize().unwrap();
+    
+    (kernel, health, log)
+}
+
+/// Count the initializations recorded in a lifecycle log
+fn init_count(log: &Arc<Mutex<Vec<String>>>) -> usize {
+    log.lock().unwrap().iter().filter(|entry| entry.starts_with("init:")).count()
+}
+
+/// Test suite for subsystem health checks
+#[cfg(test)]
+mod health_check_tests {
+    use super::*;
+    
+    /// Test that healthy subsystems are reported without events
+    #[test]
+    fn test_all_healthy() {
+        let kernel = create_initialized_kernel().unwrap();
+        let mut events = kernel.subscribe(&[EventKind::SubsystemHealthChanged]);
+        
+        let report = kernel.check_health();
+        let names: Vec<&str> = report.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, kernel.startup_order());
+        assert!(report.iter().all(|(_, health)| health.is_healthy()));
+        assert_eq!(events.try_recv(), Ok(None));
+    }
+    
+    /// Test that health changes update the subsystem state and publish events
+    #[test]
+    fn test_health_changes() {
+        let (kernel, health, _) = create_watched_kernel(RestartPolicy::Never);
+        let mut events = kernel.subscribe(&[EventKind::SubsystemHealthChanged]);
+        
+        *health.lock().unwrap() = SubsystemHealth::Degraded("slow".to_string());
+        kernel.check_health();
+        assert_eq!(kernel.subsystem_state("mock"), Some(SubsystemState::Degraded));
+        assert_eq!(events.try_recv(), Ok(Some(KernelEvent::SubsystemHealthChanged {
+            subsystem: "mock".to_string(),
+            health: SubsystemHealth::Degraded("slow".to_string()),
+        })));
+        
+        // An unchanged health is not announced again
+        kernel.check_health();
+        assert_eq!(events.try_recv(), Ok(None));
+        
+        *health.lock().unwrap() = SubsystemHealth::Failed("down".to_string());
+        kernel.check_health();
+        assert_eq!(kernel.subsystem_state("mock"), Some(SubsystemState::Failed));
+        assert!(matches!(events.try_recv(), Ok(Some(KernelEvent::SubsystemHealthChanged { .. }))));
+        
+        *health.lock().unwrap() = SubsystemHealth::Healthy;
+        kernel.check_health();
+        assert_eq!(kernel.subsystem_state("mock"), Some(SubsystemState::Running));
+        assert!(matches!(events.try_recv(), Ok(Some(KernelEvent::SubsystemHealthChanged {
+            health: SubsystemHealth::Healthy, ..
+        }))));
+    }
+    
+    /// Test that a subsystem holding its lock across checks is marked failed
+    #[test]
+    fn test_wedged_subsystem() {
+        let (kernel, _, log) = create_watched_kernel(RestartPolicy::Always { backoff: Duration::ZERO });
+        
+        kernel.with_subsystem("mock", |_: &mut MockSubsystem| {
+            for _ in 0..crate::WEDGED_CHECK_LIMIT - 1 {
+                kernel.check_health();
+                assert_eq!(kernel.subsystem_state("mock"), Some(SubsystemState::Running));
+            }
+            
+            let report = kernel.check_health();
+            let (_, health) = report.iter().find(|(name, _)| name == "mock").unwrap();
+            assert!(matches!(health, SubsystemHealth::Failed(_)));
+        }).unwrap();
+        
+        // A wedged subsystem is not restarted while it holds its lock
+        assert_eq!(init_count(&log), 1);
+        assert_eq!(kernel.subsystem_state("mock"), Some(SubsystemState::Failed));
+    }
+}
+
+/// Test suite for watchdog restart policies
+#[cfg(test)]
+mod restart_policy_tests {
+    use super::*;
+    
+    /// Test parsing restart policy names
+    #[test]
+    fn test_restart_policy_parsing() {
+        assert_eq!("never".parse::<RestartPolicy>().unwrap(), RestartPolicy::Never);
+        assert!(matches!("on-failure".parse::<RestartPolicy>(), Ok(RestartPolicy::OnFailure { .. })));
+        assert!(matches!("always".parse::<RestartPolicy>(), Ok(RestartPolicy::Always { .. })));
+        assert!("sometimes".parse::<RestartPolicy>().is_err());
+        assert_eq!(RestartPolicy::default(), RestartPolicy::Never);
+    }
+    
+    /// Test that the never policy leaves a failed subsystem alone
+    #[test]
+    fn test_never_restart() {
+        let (kernel, health, log) = create_watched_kernel(RestartPolicy::Never);
+        
+        *health.lock().unwrap() = SubsystemHealth::Failed("down".to_string());
+        kernel.check_health();
+        kernel.check_health();
+        
+        assert_eq!(init_count(&log), 1);
+    }
+    
+    /// Test restarts with backoff and a restart limit
+    #[test]
+    fn test_on_failure_restart_with_backoff() {
+        let backoff = Duration::from_millis(50);
+        let (kernel, health, log) = create_watched_kernel(RestartPolicy::OnFailure { max_restarts: 2, backoff });
+        let mut restarts = kernel.subscribe(&[EventKind::SubsystemRestarted]);
+        
+        // Degraded subsystems are not restarted on failure only
+        *health.lock().unwrap() = SubsystemHealth::Degraded("slow".to_string());
+        kernel.check_health();
+        assert_eq!(init_count(&log), 1);
+        
+        *health.lock().unwrap() = SubsystemHealth::Failed("down".to_string());
+        kernel.check_health();
+        assert_eq!(init_count(&log), 2);
+        assert_eq!(restarts.try_recv(), Ok(Some(KernelEvent::SubsystemRestarted {
+            subsystem: "mock".to_string(),
+            attempt: 1,
+            success: true,
+        })));
+        
+        // The next attempt waits for the backoff
+        kernel.check_health();
+        assert_eq!(init_count(&log), 2);
+        std::thread::sleep(backoff);
+        kernel.check_health();
+        assert_eq!(init_count(&log), 3);
+        
+        // The limit has been reached
+        std::thread::sleep(backoff * 4);
+        kernel.check_health();
+        assert_eq!(init_count(&log), 3);
+        
+        // Recovery resets the attempts
+        *health.lock().unwrap() = SubsystemHealth::Healthy;
+        kernel.check_health();
+        *health.lock().unwrap() = SubsystemHealth::Failed("down again".to_string());
+        kernel.check_health();
+        assert_eq!(init_count(&log), 4);
+    }
+    
+    /// Test that the always policy restarts failed subsystems without a limit
+    #[test]
+    fn test_always_restart() {
+        let (kernel, health, log) = create_watched_kernel(RestartPolicy::Always { backoff: Duration::ZERO });
+        
+        // Degraded subsystems keep running
+        *health.lock().unwrap() = SubsystemHealth::Degraded("slow".to_string());
+        kernel.check_health();
+        assert_eq!(init_count(&log), 1);
+        assert_eq!(kernel.subsystem_state("mock"), Some(SubsystemState::Degraded));
+        
+        *health.lock().unwrap() = SubsystemHealth::Failed("down".to_string());
+        for _ in 0..10 {
+            kernel.check_health();
+        }
+        
+        // A restarted subsystem is running again until the next check probes it
+        assert_eq!(init_count(&log), 11);
+        assert_eq!(kernel.subsystem_state("mock"), Some(SubsystemState::Running));
+        assert!(kernel.restart_policy("missing").is_none());
+        assert!(kernel.set_restart_policy("missing", RestartPolicy::Never).is_err());
+    }
+    
+    /// Test that a nearly full memory subsystem keeps its allocations under the always policy
+    #[test]
+    fn test_degraded_memory_is_not_restarted() {
+        let kernel = create_initialized_kernel().unwrap();
+        kernel.set_restart_policy("memory", RestartPolicy::Always { backoff: Duration::ZERO }).unwrap();
+        let handle = kernel.with_subsystem("memory", |memory: &mut MemoryManager| {
+            memory.allocate(96 * 1024 * 1024, "working set", MemoryCategory::Working)
+        }).unwrap().unwrap();
+        
+        kernel.check_health();
+        kernel.check_health();
+        
+        assert_eq!(kernel.subsystem_state("memory"), Some(SubsystemState::Degraded));
+        assert!(kernel.with_subsystem("memory", |memory: &mut MemoryManager| memory.contains(handle)).unwrap());
+    }
+    
+    /// Test that the watchdog task notices a failing subsystem
+    #[tokio::test]
+    async fn test_watchdog_task() {
+        let (kernel, health, _) = create_watched_kernel(RestartPolicy::Never);
+        let kernel = Arc::new(kernel);
+        let mut events = kernel.subscribe(&[EventKind::SubsystemHealthChanged]);
+        
+        let watchdog = kernel.start_watchdog(Duration::from_millis(5)).unwrap();
+        *health.lock().unwrap() = SubsystemHealth::Failed("down".to_string());
+        
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv()).await
+            .expect("Watchdog should report the failure");
+        assert!(matches!(event, Ok(KernelEvent::SubsystemHealthChanged { health: SubsystemHealth::Failed(_), .. })));
+        
+        let scheduler = kernel.scheduler();
+        scheduler.cancel(watchdog).unwrap();
+        assert!(scheduler.join(watchdog).await.is_ok());
+    }
+}
@@ -0,0 +1,435 @@
+//! Timer service for the RoyaOS kernel
+//!
+//! The timer service runs jobs on a schedule: once after a delay, at a fixed
+//! interval, or at the times matched by a cron expression. Jobs come from two
+//! places. Subsystems declare theirs through `Subsystem::jobs` and the kernel
+//! schedules them every time the subsystem is initialized; the AGI schedules system
+//! calls through the `job_schedule` system call, which run with the authority and
+//! quota of the principal that scheduled them.
+//!
+//! The kernel checks for due jobs on every tick of the timer task. A job that falls
+//! behind, for example while the system was busy, runs once when it is noticed
+//! rather than once for every missed time.
+
+use chrono::{DateTime, Utc};
+use royaos_common::{ErrorCode, HandleGenerator, JobSpec, Schedule, ScheduleError};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::syscall::Syscall;
+
+/// Time between two checks for due jobs
+pub const TIMER_TICK: Duration = Duration::from_secs(1);
+
+/// Owner of the jobs scheduled by system calls issued without a principal
+pub const KERNEL_JOB_OWNER: &str = "kernel";
+
+/// Job ID type used to reference scheduled jobs
+pub type JobId = Uuid;
+
+/// Work a job does when it runs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JobAction {
+    /// Run a job declared by a subsystem
+    Subsystem {
+        /// Name of the subsystem
+        subsystem: String,
+        /// Name of the job within the subsystem
+        job: String,
+    },
+    /// Execute a system call
+    Syscall {
+        /// Agent ID of the principal the call is charged to, if any
+        principal: Option<String>,
+        /// The system call
+        syscall: Syscall,
+    },
+}
+
+/// Description of a scheduled job
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobInfo {
+    /// Job ID
+    pub id: JobId,
+    /// Name of the job
+    pub name: String,
+    /// Subsystem or principal that owns the job
+    pub owner: String,
+    /// When the job runs
+    pub schedule: Schedule,
+    /// Work the job does when it runs
+    pub action: JobAction,
+    /// Next time the job runs, or `None` if it will not run again
+    pub next_run: Option<DateTime<Utc>>,
+    /// Number of times the job ran
+    pub runs: u64,
+    /// Number of runs that failed
+    pub failures: u64,
+    /// Error of the most recent run, if it failed
+    pub last_error: Option<String>,
+}
+
+/// Error returned by the timer service
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum TimerError {
+    /// No job is scheduled with the ID
+    #[error("Job {0} not found")]
+    UnknownJob(JobId),
+    
+    /// The job belongs to another subsystem or principal
+    #[error("Job {job} belongs to {owner}")]
+    NotOwner {
+        /// ID of the job
+        job: JobId,
+        /// Owner of the job
+        owner: String,
+    },
+    
+    /// The schedule is not valid
+    #[error(transparent)]
+    Schedule(#[from] ScheduleError),
+}
+
+impl ErrorCode for TimerError {
+    fn code(&self) -> &'static str {
+        match self {
+            TimerError::UnknownJob(_) => "TIMER_UNKNOWN_JOB",
+            TimerError::NotOwner { .. } => "TIMER_NOT_OWNER",
+            TimerError::Schedule(error) => error.code(),
+        }
+    }
+}
+
+/// Schedule with its cron expression parsed
+#[derive(Debug)]
+enum Trigger {
+    /// Run once after a delay
+    Once(Duration),
+    /// Run repeatedly with a fixed delay between two runs
+    Interval(Duration),
+    /// Run at the times matched by a cron expression
+    Cron(Box<cron::Schedule>),
+}
+
+impl Trigger {
+    /// Parse a schedule
+    ///
+    /// # Arguments
+    ///
+    /// * `schedule` - The schedule
+    ///
+    /// # Returns
+    ///
+    /// The trigger, or `ScheduleError` if the cron expression is not valid
+    fn new(schedule: &Schedule) -> Result<Self, ScheduleError> {
+        match schedule {
+            Schedule::Once(delay) => Ok(Trigger::Once(*delay)),
+            Schedule::Interval(interval) => Ok(Trigger::Interval(*interval)),
+            Schedule::Cron(expression) => cron::Schedule::from_str(expression)
+                .map(|parsed| Trigger::Cron(Box::new(parsed)))
+                .map_err(|e| ScheduleError {
+                    schedule: schedule.to_string(),
+                    reason: e.to_string(),
+                }),
+        }
+    }
+    
+    /// Get the first time a newly scheduled job runs
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time
+    ///
+    /// # Returns
+    ///
+    /// The first run time, or `None` if the job never runs
+    fn first_run(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Trigger::Once(delay) | Trigger::Interval(delay) => add(now, *delay),
+            Trigger::Cron(schedule) => schedule.after(&now).next(),
+        }
+    }
+    
+    /// Get the time a job runs next after running now
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time
+    ///
+    /// # Returns
+    ///
+    /// The next run time, or `None` if the job does not run again
+    fn next_run(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Trigger::Once(_) => None,
+            Trigger::Interval(interval) => add(now, *interval),
+            Trigger::Cron(schedule) => schedule.after(&now).next(),
+        }
+    }
+}
+
+/// Add a duration to a time
+///
+/// # Returns
+///
+/// The later time, or `None` if it cannot be represented
+fn add(time: DateTime<Utc>, duration: Duration) -> Option<DateTime<Utc>> {
+    chrono::Duration::from_std(duration).ok().and_then(|duration| time.checked_add_signed(duration))
+}
+
+/// A scheduled job
+#[derive(Debug)]
+struct Job {
+    /// Name of the job
+    name: String,
+    /// Subsystem or principal that owns the job
+    owner: String,
+    /// When the job runs
+    schedule: Schedule,
+    /// The parsed schedule
+    trigger: Trigger,
+    /// Work the job does when it runs
+    action: JobAction,
+    /// Next time the job runs, or `None` once it will not run again
+    next_run: Option<DateTime<Utc>>,
+    /// Number of times the job ran
+    runs: u64,
+    /// Number of runs that failed
+    failures: u64,
+    /// Error of the most recent run, if it failed
+    last_error: Option<String>,
+}
+
+impl Job {
+    /// Describe the job
+    fn info(&self, id: JobId) -> JobInfo {
+        JobInfo {
+            id,
+            name: self.name.clone(),
+            owner: self.owner.clone(),
+            schedule: self.schedule.clone(),
+            action: self.action.clone(),
+            next_run: self.next_run,
+            runs: self.runs,
+            failures: self.failures,
+            last_error: self.last_error.clone(),
+        }
+    }
+}
+
+/// Jobs scheduled on the timer service
+#[derive(Debug)]
+pub(crate) struct Timers {
+    /// Jobs by ID
+    jobs: HashMap<JobId, Job>,
+    /// Generator of job IDs
+    handles: HandleGenerator,
+}
+
+impl Timers {
+    /// Create an empty timer service
+    ///
+    /// # Arguments
+    ///
+    /// * `handles` - Generator to draw job IDs from
+    pub(crate) fn new(handles: HandleGenerator) -> Self {
+        Self {
+            jobs: HashMap::new(),
+            handles,
+        }
+    }
+    
+    /// Schedule a job
+    ///
+    /// # Arguments
+    ///
+    /// * `owner` - Subsystem or principal that owns the job
+    /// * `name` - Name of the job
+    /// * `schedule` - When the job runs
+    /// * `action` - Work the job does when it runs
+    /// * `now` - The current time
+    ///
+    /// # Returns
+    ///
+    /// The ID of the job, or `TimerError::Schedule` if the schedule is not valid
+    pub(crate) fn schedule(
+        &mut self,
+        owner: &str,
+        name: &str,
+        schedule: Schedule,
+        action: JobAction,
+        now: DateTime<Utc>,
+    ) -> Result<JobId, TimerError> {
+        let trigger = Trigger::new(&schedule)?;
+        let id = self.handles.next_handle();
+        
+        self.jobs.insert(id, Job {
+            name: name.to_string(),
+            owner: owner.to_string(),
+            next_run: trigger.first_run(now),
+            schedule,
+            trigger,
+            action,
+            runs: 0,
+            failures: 0,
+            last_error: None,
+        });
+        
+        Ok(id)
+    }
+    
+    /// Replace the jobs of a subsystem with the jobs it declares
+    ///
+    /// Jobs the subsystem declared before keep their ID and history, and are
+    /// rescheduled only if their schedule changed. Jobs it no longer declares
+    /// are removed.
+    ///
+    /// # Arguments
+    ///
+    /// * `subsystem` - Name of the subsystem
+    /// * `specs` - The jobs the subsystem declares
+    /// * `now` - The current time
+    ///
+    /// # Returns
+    ///
+    /// The declared jobs whose schedule is not valid, with the reason
+    pub(crate) fn sync_subsystem_jobs(
+        &mut self,
+        subsystem: &str,
+        specs: Vec<JobSpec>,
+        now: DateTime<Utc>,
+    ) -> Vec<(String, ScheduleError)> {
+        let is_declared_by = |action: &JobAction, name: &str| {
+            matches!(action, JobAction::Subsystem { subsystem: owner, job } if owner == subsystem && job == name)
+        };
+        self.jobs.retain(|_, job| {
+            !is_declared_by(&job.action, &job.name) || specs.iter().any(|spec| spec.name == job.name)
+        });
+        
+        let mut rejected = Vec::new();
+        for spec in specs {
+            let existing = self.jobs.values_mut().find(|job| is_declared_by(&job.action, &spec.name));
+            match existing {
+                Some(job) if job.schedule == spec.schedule => {},
+                Some(job) => match Trigger::new(&spec.schedule) {
+                    Ok(trigger) => {
+                        job.next_run = trigger.first_run(now);
+                        job.trigger = trigger;
+                        job.schedule = spec.schedule;
+                    },
+                    Err(error) => rejected.push((spec.name, error)),
+                },
+                None => {
+                    let action = JobAction::Subsystem {
+                        subsystem: subsystem.to_string(),
+                        job: spec.name.clone(),
+                    };
+                    if let Err(TimerError::Schedule(error)) = self.schedule(subsystem, &spec.name, spec.schedule, action, now) {
+                        rejected.push((spec.name, error));
+                    }
+                },
+            }
+        }
+        
+        rejected
+    }
+    
+    /// Cancel a job
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - ID of the job
+    /// * `owner` - The principal cancelling the job, or `None` to cancel any job
+    ///
+    /// # Returns
+    ///
+    /// The cancelled job, or the reason it could not be cancelled
+    pub(crate) fn cancel(&mut self, id: JobId, owner: Option<&str>) -> Result<JobInfo, TimerError> {
+        let job = self.jobs.get(&id).ok_or(TimerError::UnknownJob(id))?;
+        if let Some(owner) = owner {
+            if job.owner != owner {
+                return Err(TimerError::NotOwner { job: id, owner: job.owner.clone() });
+            }
+        }
+        
+        let info = job.info(id);
+        self.jobs.remove(&id);
+        Ok(info)
+    }
+    
+    /// Cancel all jobs of an owner
+    ///
+    /// # Returns
+    ///
+    /// Number of cancelled jobs
+    pub(crate) fn cancel_owned(&mut self, owner: &str) -> usize {
+        let before = self.jobs.len();
+        self.jobs.retain(|_, job| job.owner != owner);
+        before - self.jobs.len()
+    }
+    
+    /// Describe the scheduled jobs, ordered by their next run
+    pub(crate) fn list(&self) -> Vec<JobInfo> {
+        let mut jobs: Vec<JobInfo> = self.jobs.iter().map(|(id, job)| job.info(*id)).collect();
+        jobs.sort_by(|a, b| {
+            // Jobs that will not run again go last
+            let key = |job: &JobInfo| (job.next_run.is_none(), job.next_run, job.name.clone(), job.id);
+            key(a).cmp(&key(b))
+        });
+        jobs
+    }
+    
+    /// Take the jobs that are due and advance them to their next run
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time
+    ///
+    /// # Returns
+    ///
+    /// The IDs and actions of the due jobs, to be reported to `finish` once they ran
+    pub(crate) fn take_due(&mut self, now: DateTime<Utc>) -> Vec<(JobId, JobAction)> {
+        let mut due = Vec::new();
+        for (id, job) in &mut self.jobs {
+            if let Some(next_run) = job.next_run.filter(|next_run| *next_run <= now) {
+                job.next_run = job.trigger.next_run(now);
+                due.push((next_run, *id, job.action.clone()));
+            }
+        }
+        
+        // Run the jobs in the order they were due, whatever the map order
+        due.sort_by_key(|(next_run, id, _)| (*next_run, *id));
+        due.into_iter().map(|(_, id, action)| (id, action)).collect()
+    }
+    
+    /// Record the outcome of a job run and remove the job if it will not run again
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - ID of the job
+    /// * `result` - `Ok(())` if the job ran, or the error it failed with
+    pub(crate) fn finish(&mut self, id: JobId, result: Result<(), String>) {
+        // The job may have been cancelled while it ran
+        let Some(job) = self.jobs.get_mut(&id) else {
+            return;
+        };
+        
+        job.runs += 1;
+        match result {
+            Ok(()) => job.last_error = None,
+            Err(error) => {
+                job.failures += 1;
+                job.last_error = Some(error);
+            },
+        }
+        
+        if job.next_run.is_none() {
+            self.jobs.remove(&id);
+        }
+    }
+}
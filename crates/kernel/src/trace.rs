@@ -0,0 +1,401 @@
+//! System call tracing for the RoyaOS kernel
+//!
+//! When tracing is enabled, every call to `Kernel::process_syscall` is appended to a
+//! trace file together with its arguments, result, latency, calling session and
+//! calling agent. Trace files are JSON Lines: a header line describing the kernel that
+//! wrote the trace, followed by one line per system call, so a trace cut short by a
+//! crash is still readable up to its last complete line.
+//!
+//! A trace can be replayed into a fresh kernel to reproduce an incident offline. Handles
+//! are generated anew on every run, so the replay maps every handle returned in the
+//! trace to the handle returned during the replay and rewrites later arguments with it.
+//! Results are compared with that mapping applied, errors are compared by their code,
+//! and every difference is reported as a divergence.
+
+use log::{info, debug, warn};
+use royaos_common::ErrorCode;
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::error::KernelError;
+use crate::snapshot::unix_millis;
+use crate::syscall::SyscallError;
+
+/// Version of the trace file format written by this kernel
+pub const TRACE_FORMAT_VERSION: u32 = 1;
+
+/// Directory below the data directory that holds the trace files
+pub const TRACE_DIR: &str = "traces";
+
+/// Fields of a system call result that legitimately differ between runs
+const VOLATILE_FIELDS: [&str; 1] = ["execution_time_ms"];
+
+/// First line of a trace file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceHeader {
+    /// Version of the trace file format
+    pub format_version: u32,
+    /// Version of the kernel that wrote the trace
+    pub kernel_version: String,
+    /// Time tracing started in milliseconds since the Unix epoch
+    pub started_at: u64,
+    /// Identifiers of the tools registered when tracing started, by handle
+    pub tools: BTreeMap<Uuid, String>,
+}
+
+/// Outcome of a traced system call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceOutcome {
+    /// The call succeeded with this result
+    Ok(String),
+    /// The call failed
+    Err {
+        /// Stable code of the error
+        code: String,
+        /// Error message
+        message: String,
+    },
+}
+
+impl TraceOutcome {
+    /// Record the outcome of a system call
+    ///
+    /// # Arguments
+    ///
+    /// * `result` - The result returned by `process_syscall`, or the typed result it renders
+    ///
+    /// # Returns
+    ///
+    /// The outcome as stored in a trace
+    pub fn of<T: fmt::Display>(result: &Result<T, SyscallError>) -> Self {
+        match result {
+            Ok(value) => TraceOutcome::Ok(value.to_string()),
+            Err(error) => TraceOutcome::Err {
+                code: error.code().to_string(),
+                message: error.to_string(),
+            },
+        }
+    }
+}
+
+impl fmt::Display for TraceOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceOutcome::Ok(value) => write!(f, "ok {}", value),
+            TraceOutcome::Err { code, message } => write!(f, "error {} ({})", code, message),
+        }
+    }
+}
+
+/// One traced system call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceRecord {
+    /// Position of the call in the trace, starting at 1
+    pub seq: u64,
+    /// Time the call was made in milliseconds since the Unix epoch
+    pub at: u64,
+    /// Session the call was received on, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<Uuid>,
+    /// Agent ID of the calling principal, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub principal: Option<String>,
+    /// Name of the system call
+    pub syscall: String,
+    /// Arguments of the system call
+    pub args: Vec<String>,
+    /// Outcome of the call
+    pub outcome: TraceOutcome,
+    /// Time the kernel took to process the call in microseconds
+    pub latency_us: u64,
+}
+
+/// Writer appending system calls to a trace file
+#[derive(Debug)]
+pub(crate) struct SyscallTracer {
+    /// Path of the trace file
+    path: PathBuf,
+    /// Buffered writer for the trace file
+    writer: BufWriter<File>,
+    /// Sequence number of the next record
+    next_seq: u64,
+}
+
+impl SyscallTracer {
+    /// Create a new trace file in the trace directory below a data directory
+    ///
+    /// # Arguments
+    ///
+    /// * `data_dir` - The data directory
+    /// * `header` - Header describing the traced kernel
+    ///
+    /// # Returns
+    ///
+    /// The tracer writing to the new file, or `KernelError::TraceIo`
+    pub(crate) fn create(data_dir: &Path, header: &TraceHeader) -> Result<Self, KernelError> {
+        let dir = data_dir.join(TRACE_DIR);
+        let path = dir.join(format!("syscalls-{:020}.jsonl", header.started_at));
+        let io_error = |source| KernelError::TraceIo { path: path.clone(), source };
+        
+        fs::create_dir_all(&dir).map_err(io_error)?;
+        let file = File::options().write(true).create_new(true).open(&path).map_err(io_error)?;
+        
+        let mut tracer = Self {
+            path: path.clone(),
+            writer: BufWriter::new(file),
+            next_seq: 1,
+        };
+        tracer.write_line(header).map_err(io_error)?;
+        
+        info!("Tracing system calls to {:?}", path);
+        Ok(tracer)
+    }
+    
+    /// Get the path of the trace file
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+    
+    /// Append a system call to the trace
+    ///
+    /// The record is flushed to the file before returning, so it survives a
+    /// crash of the process.
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - Session the call was received on, if any
+    /// * `principal` - Agent ID of the calling principal, if any
+    /// * `syscall` - Name of the system call
+    /// * `args` - Arguments of the system call
+    /// * `result` - The result of the call
+    /// * `latency` - Time the kernel took to process the call
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the record was written, or the I/O error that stopped it
+    pub(crate) fn record<T: fmt::Display>(
+        &mut self,
+        session: Option<Uuid>,
+        principal: Option<&str>,
+        syscall: &str,
+        args: &[&str],
+        result: &Result<T, SyscallError>,
+        latency: Duration,
+    ) -> io::Result<()> {
+        let record = TraceRecord {
+            seq: self.next_seq,
+            at: unix_millis(),
+            session,
+            principal: principal.map(str::to_string),
+            syscall: syscall.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            outcome: TraceOutcome::of(result),
+            latency_us: latency.as_micros() as u64,
+        };
+        
+        self.write_line(&record)?;
+        self.next_seq += 1;
+        Ok(())
+    }
+    
+    /// Write one value as a line of the trace file and flush it
+    fn write_line<T: Serialize>(&mut self, value: &T) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, value)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+}
+
+/// A trace file read back for replay
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trace {
+    /// Header describing the traced kernel
+    pub header: TraceHeader,
+    /// The traced system calls in order
+    pub records: Vec<TraceRecord>,
+}
+
+impl Trace {
+    /// Read a trace file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the trace file
+    ///
+    /// # Returns
+    ///
+    /// The trace, or an error if the file cannot be read, a line is not valid,
+    /// or the trace was written in an unsupported format version
+    pub fn read(path: &Path) -> Result<Self, KernelError> {
+        let file = File::open(path).map_err(|source| KernelError::TraceIo { path: path.to_path_buf(), source })?;
+        let mut lines = BufReader::new(file).lines();
+        
+        let header: TraceHeader = match lines.next() {
+            Some(line) => parse_line(path, 1, line)?,
+            None => {
+                let source = io::Error::new(io::ErrorKind::UnexpectedEof, "trace file is empty");
+                return Err(KernelError::TraceIo { path: path.to_path_buf(), source });
+            }
+        };
+        if header.format_version != TRACE_FORMAT_VERSION {
+            return Err(KernelError::TraceVersion {
+                path: path.to_path_buf(),
+                found: header.format_version,
+                supported: TRACE_FORMAT_VERSION,
+            });
+        }
+        
+        let lines: Vec<io::Result<String>> = lines.collect();
+        let last = lines.len();
+        let mut records = Vec::new();
+        for (index, line) in lines.into_iter().enumerate() {
+            match parse_line(path, index + 2, line) {
+                Ok(record) => records.push(record),
+                // A crash while writing leaves at most the last line incomplete
+                Err(e @ KernelError::TraceFormat { .. }) if index + 1 == last => {
+                    warn!("Ignoring incomplete last line of trace: {}", e);
+                },
+                Err(e) => return Err(e),
+            }
+        }
+        
+        Ok(Self { header, records })
+    }
+}
+
+/// Parse one line of a trace file
+///
+/// # Arguments
+///
+/// * `path` - Path of the trace file
+/// * `number` - Line number, starting at 1
+/// * `line` - The line as read from the file
+///
+/// # Returns
+///
+/// The parsed value, or the error that stopped it
+fn parse_line<T: for<'de> Deserialize<'de>>(path: &Path, number: usize, line: io::Result<String>) -> Result<T, KernelError> {
+    let line = line.map_err(|source| KernelError::TraceIo { path: path.to_path_buf(), source })?;
+    serde_json::from_str(&line).map_err(|source| KernelError::TraceFormat {
+        path: path.to_path_buf(),
+        line: number,
+        source,
+    })
+}
+
+/// A system call whose replayed outcome differs from the traced one
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Divergence {
+    /// Position of the call in the trace
+    pub seq: u64,
+    /// Name of the system call
+    pub syscall: String,
+    /// Arguments the call was replayed with
+    pub args: Vec<String>,
+    /// Outcome recorded in the trace
+    pub expected: TraceOutcome,
+    /// Outcome of the replayed call
+    pub actual: TraceOutcome,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{} {} {}: expected {}, got {}", self.seq, self.syscall, self.args.join(" "), self.expected, self.actual)
+    }
+}
+
+/// Result of replaying a trace
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ReplayReport {
+    /// Number of system calls replayed
+    pub replayed: usize,
+    /// Calls whose outcome differed from the trace
+    pub divergences: Vec<Divergence>,
+}
+
+impl ReplayReport {
+    /// Check whether the replay reproduced the trace exactly
+    ///
+    /// # Returns
+    ///
+    /// `true` if no call diverged, `false` otherwise
+    pub fn is_clean(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+/// Handle translation between a trace and its replay
+#[derive(Debug, Default)]
+pub(crate) struct HandleMap {
+    /// Replayed handle for each traced handle
+    handles: HashMap<String, String>,
+}
+
+impl HandleMap {
+    /// Map a traced handle to the handle it has in the replaying kernel
+    pub(crate) fn insert(&mut self, traced: &str, replayed: &str) {
+        if traced != replayed {
+            debug!("Replaying handle {} as {}", traced, replayed);
+            self.handles.insert(traced.to_string(), replayed.to_string());
+        }
+    }
+    
+    /// Rewrite traced arguments with the handles of the replaying kernel
+    pub(crate) fn translate(&self, args: &[String]) -> Vec<String> {
+        args.iter()
+            .map(|arg| self.handles.get(arg).cloned().unwrap_or_else(|| arg.clone()))
+            .collect()
+    }
+    
+    /// Compare a traced outcome with the replayed one
+    ///
+    /// A handle returned for the first time is mapped to the replayed handle
+    /// and matches it. Errors match if their codes are equal.
+    ///
+    /// # Arguments
+    ///
+    /// * `expected` - Outcome recorded in the trace
+    /// * `actual` - Outcome of the replayed call
+    ///
+    /// # Returns
+    ///
+    /// `true` if the outcomes match, `false` otherwise
+    pub(crate) fn matches(&mut self, expected: &TraceOutcome, actual: &TraceOutcome) -> bool {
+        match (expected, actual) {
+            (TraceOutcome::Ok(expected), TraceOutcome::Ok(actual)) => {
+                if let Some(mapped) = self.handles.get(expected) {
+                    return mapped == actual;
+                }
+                if Uuid::parse_str(expected).is_ok() && Uuid::parse_str(actual).is_ok() {
+                    self.insert(expected, actual);
+                    return true;
+                }
+                
+                match (serde_json::from_str(expected), serde_json::from_str(actual)) {
+                    (Ok(expected), Ok(actual)) => stable_value(expected) == stable_value(actual),
+                    _ => expected == actual,
+                }
+            },
+            (TraceOutcome::Err { code: expected, .. }, TraceOutcome::Err { code: actual, .. }) => expected == actual,
+            _ => false,
+        }
+    }
+}
+
+/// Remove the fields that differ between runs from a JSON result
+fn stable_value(mut value: serde_json::Value) -> serde_json::Value {
+    if let Some(fields) = value.as_object_mut() {
+        for field in VOLATILE_FIELDS {
+            fields.remove(field);
+        }
+    }
+    value
+}
+
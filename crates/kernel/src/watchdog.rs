@@ -0,0 +1,152 @@
+//! Subsystem watchdog for the RoyaOS kernel
+//!
+//! This module defines the restart policies the kernel watchdog applies to subsystems
+//! whose health probe reports a problem, and the bookkeeping that spaces restart
+//! attempts out with exponential backoff.
+
+use royaos_common::SubsystemHealth;
+use serde::{Serialize, Deserialize};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::error::KernelError;
+
+/// Default interval between two watchdog health checks
+pub const DEFAULT_WATCHDOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Longest delay between two restart attempts of the same subsystem
+pub const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Number of consecutive health checks a subsystem may stay locked before it is
+/// considered wedged
+pub const WEDGED_CHECK_LIMIT: u32 = 3;
+
+/// Policy deciding when the watchdog restarts an unhealthy subsystem
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "policy", rename_all = "snake_case")]
+pub enum RestartPolicy {
+    /// Never restart the subsystem
+    #[default]
+    Never,
+    /// Restart the subsystem when it fails, giving up after `max_restarts`
+    /// consecutive attempts
+    OnFailure {
+        /// Maximum number of consecutive restart attempts
+        max_restarts: u32,
+        /// Delay before the second attempt; it doubles with every further attempt
+        backoff: Duration,
+    },
+    /// Restart the subsystem whenever it fails, without a limit
+    ///
+    /// Degraded subsystems keep running, since a restart would throw away
+    /// state that is still usable, such as the allocations of a full memory
+    /// subsystem.
+    Always {
+        /// Delay before the second attempt; it doubles with every further attempt
+        backoff: Duration,
+    },
+}
+
+impl FromStr for RestartPolicy {
+    type Err = KernelError;
+    
+    /// Parse a restart policy name using default limits
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "never" => Ok(RestartPolicy::Never),
+            "on-failure" | "on_failure" => Ok(RestartPolicy::OnFailure {
+                max_restarts: 5,
+                backoff: Duration::from_secs(1),
+            }),
+            "always" => Ok(RestartPolicy::Always {
+                backoff: Duration::from_secs(1),
+            }),
+            _ => Err(KernelError::InvalidRestartPolicy(s.to_string())),
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Check whether the policy calls for a restart in the given health
+    ///
+    /// # Arguments
+    ///
+    /// * `health` - The health reported by the subsystem
+    ///
+    /// # Returns
+    ///
+    /// `true` if the subsystem should be restarted, `false` otherwise
+    pub fn applies_to(&self, health: &SubsystemHealth) -> bool {
+        match self {
+            RestartPolicy::Never => false,
+            RestartPolicy::OnFailure { .. } | RestartPolicy::Always { .. } => matches!(health, SubsystemHealth::Failed(_)),
+        }
+    }
+}
+
+/// Restart bookkeeping for one subsystem
+#[derive(Debug, Default)]
+pub(crate) struct RestartTracker {
+    /// Policy applied to the subsystem
+    pub(crate) policy: RestartPolicy,
+    /// Consecutive restart attempts since the subsystem was last healthy
+    pub(crate) attempts: u32,
+    /// Earliest time of the next restart attempt
+    pub(crate) next_attempt: Option<Instant>,
+    /// Consecutive health checks that found the subsystem locked
+    pub(crate) busy_checks: u32,
+}
+
+impl RestartTracker {
+    /// Decide whether a restart should be attempted now
+    ///
+    /// # Arguments
+    ///
+    /// * `health` - The health reported by the subsystem
+    /// * `now` - The current time
+    ///
+    /// # Returns
+    ///
+    /// `true` if a restart should be attempted, `false` otherwise
+    pub(crate) fn should_restart(&self, health: &SubsystemHealth, now: Instant) -> bool {
+        if !self.policy.applies_to(health) {
+            return false;
+        }
+        
+        if let RestartPolicy::OnFailure { max_restarts, .. } = self.policy {
+            if self.attempts >= max_restarts {
+                return false;
+            }
+        }
+        
+        self.next_attempt.is_none_or(|next| now >= next)
+    }
+    
+    /// Record a restart attempt and schedule the earliest next one
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The time of the attempt
+    ///
+    /// # Returns
+    ///
+    /// The number of consecutive attempts including this one
+    pub(crate) fn record_attempt(&mut self, now: Instant) -> u32 {
+        self.attempts += 1;
+        
+        let backoff = match self.policy {
+            RestartPolicy::Never => Duration::ZERO,
+            RestartPolicy::OnFailure { backoff, .. } | RestartPolicy::Always { backoff } => backoff,
+        };
+        let factor = 2u32.saturating_pow(self.attempts - 1);
+        self.next_attempt = Some(now + backoff.saturating_mul(factor).min(MAX_RESTART_BACKOFF));
+        
+        self.attempts
+    }
+    
+    /// Forget earlier restart attempts once the subsystem is healthy again
+    pub(crate) fn reset(&mut self) {
+        self.attempts = 0;
+        self.next_attempt = None;
+    }
+}
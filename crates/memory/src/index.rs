@@ -0,0 +1,492 @@
+//! Approximate nearest-neighbour index over memory embeddings
+//!
+//! The index is a hierarchical navigable small world (HNSW) graph. Every embedded
+//! allocation is a node on layer 0 and, with exponentially falling probability, on
+//! the layers above it, where it links to its nearest neighbours. A search walks
+//! greedily down from the sparse top layer and then explores layer 0 around the
+//! closest node it found, so it visits a small part of the graph.
+//!
+//! Vectors are normalized when they are added and compared by cosine similarity.
+//! A node's layer is derived from its handle, so the same allocations added in the
+//! same order always build the same graph.
+
+use serde::{Serialize, Deserialize};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use crate::{MemoryCategory, MemoryHandle};
+
+/// Number of neighbours a node links to on the layers above layer 0
+const MAX_NEIGHBOURS: usize = 16;
+
+/// Number of neighbours a node links to on layer 0
+const MAX_NEIGHBOURS_BASE: usize = 2 * MAX_NEIGHBOURS;
+
+/// Number of candidates considered when a node is linked into the graph
+const EF_CONSTRUCTION: usize = 100;
+
+/// Number of candidates considered when searching layer 0
+const EF_SEARCH: usize = 64;
+
+/// Highest layer a node can be placed on
+const MAX_LAYER: usize = 16;
+
+/// A memory found by a similarity search
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimilarMemory {
+    /// Handle to the allocation
+    pub handle: MemoryHandle,
+    /// Memory category of the allocation
+    pub category: MemoryCategory,
+    /// Memory purpose/description
+    pub purpose: String,
+    /// Cosine similarity between the allocation's embedding and the query, from -1 to 1
+    pub similarity: f32,
+}
+
+/// A node of the graph
+#[derive(Debug)]
+struct Node {
+    /// The normalized embedding
+    vector: Vec<f32>,
+    /// Neighbours on each layer the node is placed on, from layer 0 up
+    neighbours: Vec<Vec<MemoryHandle>>,
+}
+
+impl Node {
+    /// Get the highest layer the node is placed on
+    fn top_layer(&self) -> usize {
+        self.neighbours.len() - 1
+    }
+}
+
+/// A node found during a search, ordered by similarity and then by handle
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Scored {
+    similarity: f32,
+    handle: MemoryHandle,
+}
+
+impl Eq for Scored {}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.similarity.total_cmp(&other.similarity).then_with(|| other.handle.cmp(&self.handle))
+    }
+}
+
+/// HNSW index over the embeddings of memory allocations
+#[derive(Debug, Default)]
+pub(crate) struct VectorIndex {
+    /// Length of every vector in the index, fixed by the first one added
+    dimension: Option<usize>,
+    /// The nodes by handle
+    nodes: HashMap<MemoryHandle, Node>,
+    /// The node on the highest layer, where searches start
+    entry: Option<MemoryHandle>,
+}
+
+impl VectorIndex {
+    /// Remove every node
+    pub(crate) fn clear(&mut self) {
+        *self = Self::default();
+    }
+    
+    /// Add a vector to the index, replacing the one the handle had
+    ///
+    /// The caller checks the vector with `check_vector` first.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - Handle to the allocation
+    /// * `vector` - The embedding of the allocation
+    pub(crate) fn insert(&mut self, handle: MemoryHandle, vector: &[f32]) {
+        self.remove(handle);
+        let vector = normalize(vector);
+        let layer = layer_of(handle);
+        self.dimension = Some(vector.len());
+        
+        let Some(mut entry) = self.entry else {
+            self.nodes.insert(handle, Node { vector, neighbours: vec![Vec::new(); layer + 1] });
+            self.entry = Some(handle);
+            return;
+        };
+        
+        // Walk down to the highest layer of the new node
+        let top = self.nodes[&entry].top_layer();
+        for current in (layer + 1..=top).rev() {
+            entry = self.search_layer(&vector, &[entry], 1, current, |_| true)[0].handle;
+        }
+        
+        // Link the node to its nearest neighbours on each of its layers
+        let mut neighbours = vec![Vec::new(); layer + 1];
+        let mut entries = vec![entry];
+        for current in (0..=layer.min(top)).rev() {
+            let found = self.search_layer(&vector, &entries, EF_CONSTRUCTION, current, |_| true);
+            neighbours[current] = found.iter().take(max_neighbours(current)).map(|scored| scored.handle).collect();
+            entries = found.iter().map(|scored| scored.handle).collect();
+        }
+        self.nodes.insert(handle, Node { vector, neighbours: neighbours.clone() });
+        
+        for (current, linked) in neighbours.iter().enumerate() {
+            for neighbour in linked {
+                self.link(*neighbour, &[handle], current);
+            }
+        }
+        
+        if layer > top {
+            self.entry = Some(handle);
+        }
+    }
+    
+    /// Remove the vector of an allocation from the index
+    ///
+    /// The former neighbours of the node are linked to each other to keep the
+    /// graph connected.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - Handle to the allocation
+    pub(crate) fn remove(&mut self, handle: MemoryHandle) {
+        let Some(removed) = self.nodes.remove(&handle) else {
+            return;
+        };
+        
+        for node in self.nodes.values_mut() {
+            for linked in &mut node.neighbours {
+                linked.retain(|neighbour| *neighbour != handle);
+            }
+        }
+        
+        for (layer, linked) in removed.neighbours.iter().enumerate() {
+            for neighbour in linked {
+                self.link(*neighbour, linked, layer);
+            }
+        }
+        
+        if self.entry == Some(handle) {
+            self.entry = self.nodes.iter()
+                .max_by(|(a, x), (b, y)| x.top_layer().cmp(&y.top_layer()).then_with(|| b.cmp(a)))
+                .map(|(handle, _)| *handle);
+        }
+        if self.nodes.is_empty() {
+            self.dimension = None;
+        }
+    }
+    
+    /// Find the nodes most similar to a query
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query vector, checked with `check_vector`
+    /// * `k` - Maximum number of nodes to return
+    /// * `accept` - Whether a node may be returned; other nodes are still walked through
+    ///
+    /// # Returns
+    ///
+    /// Up to `k` handles with their similarity, most similar first
+    pub(crate) fn search(&self, query: &[f32], k: usize, accept: impl Fn(&MemoryHandle) -> bool) -> Vec<(MemoryHandle, f32)> {
+        let Some(mut entry) = self.entry else {
+            return Vec::new();
+        };
+        if k == 0 {
+            return Vec::new();
+        }
+        
+        let query = normalize(query);
+        for current in (1..=self.nodes[&entry].top_layer()).rev() {
+            entry = self.search_layer(&query, &[entry], 1, current, |_| true)[0].handle;
+        }
+        
+        self.search_layer(&query, &[entry], EF_SEARCH.max(k), 0, accept)
+            .into_iter()
+            .take(k)
+            .map(|scored| (scored.handle, scored.similarity))
+            .collect()
+    }
+    
+    /// Check that a vector can be added to or searched in the index
+    ///
+    /// # Arguments
+    ///
+    /// * `vector` - The vector
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())`, or why the vector cannot be used
+    pub(crate) fn check_vector(&self, vector: &[f32]) -> Result<(), String> {
+        if vector.is_empty() {
+            return Err("the vector is empty".to_string());
+        }
+        if let Some(dimension) = self.dimension.filter(|dimension| *dimension != vector.len()) {
+            return Err(format!("the vector has {} dimensions, the index has {}", vector.len(), dimension));
+        }
+        if !vector.iter().all(|value| value.is_finite()) {
+            return Err("the vector has a value that is not finite".to_string());
+        }
+        let norm = vector.iter().map(|value| value * value).sum::<f32>().sqrt();
+        if norm == 0.0 {
+            return Err("the vector has no direction".to_string());
+        }
+        if !norm.is_finite() {
+            return Err("the vector is too long to normalize".to_string());
+        }
+        Ok(())
+    }
+    
+    /// Explore one layer of the graph for the nodes most similar to a query
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The normalized query vector
+    /// * `entries` - The nodes to start from
+    /// * `ef` - Number of accepted nodes to keep while exploring
+    /// * `layer` - The layer to explore
+    /// * `accept` - Whether a node may be returned
+    ///
+    /// # Returns
+    ///
+    /// Up to `ef` accepted nodes, most similar first
+    fn search_layer(
+        &self,
+        query: &[f32],
+        entries: &[MemoryHandle],
+        ef: usize,
+        layer: usize,
+        accept: impl Fn(&MemoryHandle) -> bool,
+    ) -> Vec<Scored> {
+        let mut visited: HashSet<MemoryHandle> = entries.iter().copied().collect();
+        let mut candidates = BinaryHeap::new();
+        let mut found = BinaryHeap::new();
+        
+        for handle in entries {
+            let scored = self.score(query, *handle);
+            candidates.push(scored);
+            if accept(handle) {
+                found.push(Reverse(scored));
+            }
+        }
+        while found.len() > ef {
+            found.pop();
+        }
+        
+        while let Some(candidate) = candidates.pop() {
+            if found.len() >= ef && found.peek().is_some_and(|Reverse(worst)| candidate < *worst) {
+                break;
+            }
+            
+            let node = &self.nodes[&candidate.handle];
+            let Some(linked) = node.neighbours.get(layer) else {
+                continue;
+            };
+            for neighbour in linked {
+                if !visited.insert(*neighbour) {
+                    continue;
+                }
+                let scored = self.score(query, *neighbour);
+                if found.len() < ef || found.peek().is_some_and(|Reverse(worst)| scored > *worst) {
+                    candidates.push(scored);
+                    if accept(neighbour) {
+                        found.push(Reverse(scored));
+                        if found.len() > ef {
+                            found.pop();
+                        }
+                    }
+                }
+            }
+        }
+        
+        let mut found: Vec<Scored> = found.into_iter().map(|Reverse(scored)| scored).collect();
+        found.sort_by(|a, b| b.cmp(a));
+        found
+    }
+    
+    /// Link a node to other nodes on a layer, keeping only its nearest neighbours
+    fn link(&mut self, from: MemoryHandle, to: &[MemoryHandle], layer: usize) {
+        let Some(node) = self.nodes.get(&from) else {
+            return;
+        };
+        let Some(current) = node.neighbours.get(layer) else {
+            return;
+        };
+        
+        let mut candidates: HashSet<MemoryHandle> = current.iter().copied().collect();
+        candidates.extend(to.iter().filter(|handle| {
+            **handle != from && self.nodes.get(handle).is_some_and(|other| other.neighbours.len() > layer)
+        }));
+        if candidates.len() == current.len() {
+            return;
+        }
+        
+        let mut linked: Vec<Scored> = candidates.into_iter().map(|handle| self.score(&node.vector, handle)).collect();
+        linked.sort_by(|a, b| b.cmp(a));
+        linked.truncate(max_neighbours(layer));
+        
+        let node = self.nodes.get_mut(&from).expect("The node was found above");
+        node.neighbours[layer] = linked.into_iter().map(|scored| scored.handle).collect();
+    }
+    
+    /// Score a node by its similarity to a normalized vector
+    fn score(&self, vector: &[f32], handle: MemoryHandle) -> Scored {
+        let similarity = vector.iter().zip(&self.nodes[&handle].vector).map(|(a, b)| a * b).sum();
+        Scored { similarity, handle }
+    }
+}
+
+/// Get the number of neighbours a node links to on a layer
+fn max_neighbours(layer: usize) -> usize {
+    if layer == 0 {
+        MAX_NEIGHBOURS_BASE
+    } else {
+        MAX_NEIGHBOURS
+    }
+}
+
+/// Derive the highest layer of a node from its handle
+///
+/// Layers follow the geometric distribution HNSW draws them from, with the
+/// handle's bits in place of a random number.
+fn layer_of(handle: MemoryHandle) -> usize {
+    // splitmix64 finalizer, so handles that share bits still spread over layers
+    let (high, low) = handle.as_u64_pair();
+    let mut bits = high ^ low.rotate_left(32);
+    bits = (bits ^ (bits >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    bits = (bits ^ (bits >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    bits ^= bits >> 31;
+    
+    let uniform = ((bits >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+    let layer = -uniform.ln() / (MAX_NEIGHBOURS as f64).ln();
+    (layer as usize).min(MAX_LAYER)
+}
+
+/// Scale a vector to unit length
+fn normalize(vector: &[f32]) -> Vec<f32> {
+    let norm = vector.iter().map(|value| value * value).sum::<f32>().sqrt();
+    vector.iter().map(|value| value / norm).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+    
+    /// Number of dimensions of the test vectors
+    const DIMENSION: usize = 16;
+    
+    /// Number of nearest neighbours compared when measuring recall
+    const K: usize = 10;
+    
+    /// Generate reproducible vectors with components from -1 to 1
+    fn random_vectors(seed: u64, count: usize) -> Vec<Vec<f32>> {
+        let mut state = seed;
+        let mut next = move || {
+            state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+            ((state >> 40) as f32 / (1u64 << 24) as f32) * 2.0 - 1.0
+        };
+        (0..count).map(|_| (0..DIMENSION).map(|_| next()).collect()).collect()
+    }
+    
+    /// Add reproducible vectors to an index under the handles 1 to `count`
+    fn fill(index: &mut VectorIndex, seed: u64, count: usize) -> HashMap<MemoryHandle, Vec<f32>> {
+        let mut vectors = HashMap::new();
+        for (i, vector) in random_vectors(seed, count).into_iter().enumerate() {
+            let handle = Uuid::from_u128(i as u128 + 1);
+            index.insert(handle, &vector);
+            vectors.insert(handle, vector);
+        }
+        vectors
+    }
+    
+    /// Find the nearest neighbours of a query by comparing it with every vector
+    fn exact_search(vectors: &HashMap<MemoryHandle, Vec<f32>>, query: &[f32]) -> Vec<MemoryHandle> {
+        let query = normalize(query);
+        let mut scored: Vec<Scored> = vectors.iter()
+            .map(|(handle, vector)| Scored {
+                similarity: normalize(vector).iter().zip(&query).map(|(a, b)| a * b).sum(),
+                handle: *handle,
+            })
+            .collect();
+        scored.sort_by(|a, b| b.cmp(a));
+        scored.into_iter().take(K).map(|scored| scored.handle).collect()
+    }
+    
+    /// Measure the share of the exact nearest neighbours the index finds
+    fn recall(index: &VectorIndex, vectors: &HashMap<MemoryHandle, Vec<f32>>, queries: &[Vec<f32>]) -> f64 {
+        let found: usize = queries.iter()
+            .map(|query| {
+                let exact = exact_search(vectors, query);
+                index.search(query, K, |_| true).iter()
+                    .filter(|(handle, _)| exact.contains(handle))
+                    .count()
+            })
+            .sum();
+        found as f64 / (queries.len() * K) as f64
+    }
+    
+    #[test]
+    fn test_search_recall() {
+        let mut index = VectorIndex::default();
+        let vectors = fill(&mut index, 7, 500);
+        
+        let queries = random_vectors(11, 20);
+        let recall = recall(&index, &vectors, &queries);
+        assert!(recall >= 0.9, "Recall of {} is too low", recall);
+        
+        // A stored vector is its own nearest neighbour
+        let (handle, vector) = vectors.iter().next().unwrap();
+        let found = index.search(vector, 1, |_| true);
+        assert_eq!(found[0].0, *handle);
+        assert!((found[0].1 - 1.0).abs() < 1e-5);
+        
+        // Rejected nodes are walked through but not returned
+        let found = index.search(vector, K, |candidate| candidate != handle);
+        assert_eq!(found.len(), K);
+        assert!(found.iter().all(|(candidate, _)| candidate != handle));
+    }
+    
+    #[test]
+    fn test_insert_and_remove() {
+        let mut index = VectorIndex::default();
+        let mut vectors = fill(&mut index, 3, 300);
+        assert!(index.check_vector(&[1.0; DIMENSION - 1]).is_err());
+        
+        // Replacing a vector moves the node
+        let moved = Uuid::from_u128(1);
+        let target = vec![1.0; DIMENSION];
+        index.insert(moved, &target);
+        vectors.insert(moved, target.clone());
+        assert_eq!(index.search(&target, 1, |_| true)[0].0, moved);
+        
+        // Removed nodes, the entry among them, are no longer found and the graph stays connected
+        let removed: Vec<MemoryHandle> = vectors.keys().copied().filter(|handle| handle.as_u128() % 2 == 0).collect();
+        let entry = index.entry.unwrap();
+        index.remove(entry);
+        vectors.remove(&entry);
+        for handle in &removed {
+            index.remove(*handle);
+            vectors.remove(handle);
+        }
+        assert_eq!(index.nodes.len(), vectors.len());
+        assert!(index.entry.is_some_and(|entry| vectors.contains_key(&entry)));
+        
+        let queries = random_vectors(5, 20);
+        for query in &queries {
+            assert!(index.search(query, K, |_| true).iter().all(|(handle, _)| vectors.contains_key(handle)));
+        }
+        let recall = recall(&index, &vectors, &queries);
+        assert!(recall >= 0.9, "Recall of {} after removals is too low", recall);
+        
+        // An empty index takes vectors of any dimension again
+        for handle in vectors.keys() {
+            index.remove(*handle);
+        }
+        assert!(index.search(&target, K, |_| true).is_empty());
+        assert!(index.check_vector(&[1.0; DIMENSION - 1]).is_ok());
+    }
+}
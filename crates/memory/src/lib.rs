@@ -11,17 +11,304 @@
 //!
 //! This design allows Roya AGI to operate with memory patterns similar to human cognition,
 //! while optimizing for computational efficiency.
+//!
+//! Every allocation is backed by bytes that can be written and read within its size,
+//! either raw or as a serialized value. Each read or write counts as an access of the
+//! allocation, which keeps frequently used memory from being reclaimed.
+//!
+//! Given a data directory, the memory manager keeps long-term memory in an on-disk
+//! store there. Long-term allocations are reloaded when the manager is initialized,
+//! so their handles and contents survive restarts of the subsystem and the process.
+//!
+//! An allocation can carry an embedding vector describing its meaning. Embedded
+//! allocations are kept in an approximate nearest-neighbour index, so callers can find
+//! memories by similarity to a query vector instead of by handle.
+//!
+//! Consolidation moves memory between categories the way rehearsal moves it into
+//! long-term memory: short-term and working allocations that were used often and
+//! recently are promoted to long-term memory, keeping their handles.
+//!
+//! Memory also fades. Each category can have a decay model under which the retention
+//! strength of an allocation falls exponentially with the time since its last access,
+//! and more slowly the more often it was accessed. Allocations whose strength falls
+//! below the model's threshold are forgotten, as are allocations whose time to live
+//! has run out.
 
-use log::{info, error, debug};
-use std::collections::HashMap;
+use log::{info, error, debug, warn};
+use royaos_common::{
+    age_millis, instant_from_age, load_state, save_state, Clock, ErrorCode, EventBus, HandleGenerator, JobSpec,
+    KernelContext, KernelEvent, Schedule, Subsystem, SubsystemError, SubsystemHealth,
+};
+use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+use std::any::Any;
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::time::{Instant, Duration};
+use thiserror::Error;
 use uuid::Uuid;
 
+mod index;
+mod store;
+
+use index::VectorIndex;
+use store::{LongTermStore, StoreRecord, StoredAllocation};
+
+pub use index::SimilarMemory;
+pub use store::LONG_TERM_DIR;
+
 /// Memory handle type used to reference allocated memory blocks
 pub type MemoryHandle = Uuid;
 
-/// Memory allocation category for prioritization and optimization
+/// Error returned by the memory manager
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum MemoryError {
+    /// The allocation does not fit into the remaining memory, even after optimizing
+    #[error("Memory allocation of {requested} bytes exceeds the {available} bytes available")]
+    LimitExceeded {
+        /// Size of the refused allocation in bytes
+        requested: usize,
+        /// Memory still available in bytes
+        available: usize,
+    },
+    
+    /// No allocation exists for the handle
+    #[error("No memory allocation found for handle {0}")]
+    NotFound(MemoryHandle),
+    
+    /// The memory category name is not known
+    #[error("Invalid memory category: {0}")]
+    InvalidCategory(String),
+    
+    /// The optimization strategy name is not known
+    #[error("Invalid optimization strategy: {0}")]
+    InvalidStrategy(String),
+    
+    /// A read or write reaches past the end of the allocation
+    #[error("Bytes {start}..{end} are outside the {size} bytes of allocation {handle}")]
+    OutOfBounds {
+        /// Handle to the allocation
+        handle: MemoryHandle,
+        /// First byte of the access
+        start: usize,
+        /// End of the access, exclusive
+        end: usize,
+        /// Size of the allocation in bytes
+        size: usize,
+    },
+    
+    /// The allocation does not hold a value of the requested type
+    #[error("Allocation {handle} does not hold a valid value: {reason}")]
+    InvalidValue {
+        /// Handle to the allocation
+        handle: MemoryHandle,
+        /// Why the value could not be stored or loaded
+        reason: String,
+    },
+    
+    /// An embedding or query vector cannot be used for similarity search
+    #[error("Invalid embedding: {0}")]
+    InvalidEmbedding(String),
+    
+    /// The on-disk store of long-term memory could not be read or written
+    #[error("Long-term memory store failed: {0}")]
+    StoreFailed(String),
+    
+    /// The time to live of the allocation has run out
+    #[error("Memory allocation {0} has expired")]
+    Expired(MemoryHandle),
+    
+    /// A decay model cannot be used
+    #[error("Invalid decay model: {0}")]
+    InvalidDecay(String),
+}
+
+impl ErrorCode for MemoryError {
+    fn code(&self) -> &'static str {
+        match self {
+            MemoryError::LimitExceeded { .. } => "MEMORY_LIMIT_EXCEEDED",
+            MemoryError::NotFound(_) => "MEMORY_NOT_FOUND",
+            MemoryError::InvalidCategory(_) => "MEMORY_INVALID_CATEGORY",
+            MemoryError::InvalidStrategy(_) => "MEMORY_INVALID_STRATEGY",
+            MemoryError::OutOfBounds { .. } => "MEMORY_OUT_OF_BOUNDS",
+            MemoryError::InvalidValue { .. } => "MEMORY_INVALID_VALUE",
+            MemoryError::InvalidEmbedding(_) => "MEMORY_INVALID_EMBEDDING",
+            MemoryError::StoreFailed(_) => "MEMORY_STORE_FAILED",
+            MemoryError::Expired(_) => "MEMORY_EXPIRED",
+            MemoryError::InvalidDecay(_) => "MEMORY_INVALID_DECAY",
+        }
+    }
+}
+
+impl From<MemoryError> for SubsystemError {
+    fn from(error: MemoryError) -> Self {
+        SubsystemError::new(error)
+    }
+}
+
+/// Optimization strategies understood by the memory manager
+pub const OPTIMIZATION_STRATEGIES: [&str; 3] = ["aggressive", "balanced", "conservative"];
+
+/// Strategy deciding how eagerly the memory manager reclaims memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OptimizationStrategy {
+    /// Optimize before failing an allocation and reclaim idle memory quickly
+    Aggressive,
+    /// Reclaim idle memory at a moderate pace
+    Balanced,
+    /// Reclaim only memory that has been idle for a long time
+    Conservative,
+}
+
+impl FromStr for OptimizationStrategy {
+    type Err = MemoryError;
+    
+    /// Convert a string to an OptimizationStrategy
+    fn from_str(s: &str) -> Result<Self, MemoryError> {
+        match s.to_lowercase().as_str() {
+            "aggressive" => Ok(OptimizationStrategy::Aggressive),
+            "balanced" => Ok(OptimizationStrategy::Balanced),
+            "conservative" => Ok(OptimizationStrategy::Conservative),
+            _ => Err(MemoryError::InvalidStrategy(s.to_string())),
+        }
+    }
+}
+
+impl OptimizationStrategy {
+    /// Convert an OptimizationStrategy to a string
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OptimizationStrategy::Aggressive => "aggressive",
+            OptimizationStrategy::Balanced => "balanced",
+            OptimizationStrategy::Conservative => "conservative",
+        }
+    }
+    
+    /// Get the thresholds consolidation applies under this strategy
+    ///
+    /// More aggressive strategies promote memory after fewer accesses and keep
+    /// considering it for a shorter time after its last access.
+    ///
+    /// # Returns
+    ///
+    /// The consolidation thresholds
+    pub fn consolidation_thresholds(&self) -> ConsolidationThresholds {
+        match self {
+            OptimizationStrategy::Aggressive => ConsolidationThresholds {
+                min_access_count: 3,
+                max_idle: Duration::from_secs(300), // 5 minutes
+            },
+            OptimizationStrategy::Balanced => ConsolidationThresholds {
+                min_access_count: 5,
+                max_idle: Duration::from_secs(900), // 15 minutes
+            },
+            OptimizationStrategy::Conservative => ConsolidationThresholds {
+                min_access_count: 10,
+                max_idle: Duration::from_secs(3600), // 1 hour
+            },
+        }
+    }
+}
+
+/// Thresholds a short-term or working allocation must meet to be promoted to long-term memory
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConsolidationThresholds {
+    /// Minimum number of accesses of the allocation
+    pub min_access_count: usize,
+    /// Maximum time since the last access of the allocation
+    pub max_idle: Duration,
+}
+
+/// How the retention strength of the allocations in a category fades
+///
+/// The strength of an allocation starts at 1.0 and halves every half-life after
+/// its last access. Each access stretches the half-life by one more half-life,
+/// so memory that is used often fades more slowly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecayModel {
+    /// Time after which the strength of an allocation that was never accessed halves
+    pub half_life: Duration,
+    /// Strength below which an allocation is forgotten, or 0.0 to keep it
+    pub forget_below: f64,
+}
+
+impl DecayModel {
+    /// Check that the model can be used
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())`, or `MemoryError::InvalidDecay`
+    pub fn validate(&self) -> Result<(), MemoryError> {
+        if self.half_life.is_zero() {
+            return Err(MemoryError::InvalidDecay("the half-life must be longer than 0".to_string()));
+        }
+        if !(0.0..1.0).contains(&self.forget_below) {
+            return Err(MemoryError::InvalidDecay(format!(
+                "the forgetting threshold {} is outside 0.0 to 1.0", self.forget_below
+            )));
+        }
+        Ok(())
+    }
+    
+    /// Get the retention strength of an allocation under this model
+    ///
+    /// # Arguments
+    ///
+    /// * `idle` - Time since the last access of the allocation
+    /// * `access_count` - Number of accesses of the allocation
+    ///
+    /// # Returns
+    ///
+    /// The strength, from 1.0 right after an access down towards 0.0
+    pub fn strength(&self, idle: Duration, access_count: usize) -> f64 {
+        let half_life = self.half_life.as_secs_f64() * (1 + access_count) as f64;
+        0.5f64.powf(idle.as_secs_f64() / half_life)
+    }
+}
+
+/// Decay model of short-term memory unless another one is set
+pub const SHORT_TERM_DECAY: DecayModel = DecayModel {
+    half_life: Duration::from_secs(600), // 10 minutes
+    forget_below: 0.05,
+};
+
+impl std::fmt::Display for OptimizationStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Usage percentage at which the memory manager reports memory pressure
+pub const MEMORY_PRESSURE_THRESHOLD: f64 = 90.0;
+
+/// Name of the job that runs `MemoryManager::optimize` on the kernel timer service
+pub const OPTIMIZE_JOB: &str = "optimize";
+
+/// Time between two runs of the optimization job
+pub const OPTIMIZE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Name of the job that runs `MemoryManager::consolidate` on the kernel timer service
+pub const CONSOLIDATE_JOB: &str = "consolidate";
+
+/// Time between two runs of the consolidation job
+pub const CONSOLIDATE_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Number of long-term allocations read before their access statistics are written to the store
+const READ_BATCH_SIZE: usize = 64;
+
+/// Bytes in front of a stored value holding the length of its serialized form
+///
+/// `put_value` stores a value as its length in little-endian order followed by
+/// the value serialized as JSON, so an allocation must be this much larger than
+/// the serialized value.
+pub const VALUE_HEADER_SIZE: usize = 8;
+
+/// Memory allocation category for prioritization and optimization
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum MemoryCategory {
     /// Critical system memory that must not be paged or compressed
     System,
@@ -35,6 +322,35 @@ pub enum MemoryCategory {
     Background,
 }
 
+impl FromStr for MemoryCategory {
+    type Err = MemoryError;
+    
+    /// Convert a string to a MemoryCategory
+    fn from_str(s: &str) -> Result<Self, MemoryError> {
+        match s.to_lowercase().as_str() {
+            "system" => Ok(MemoryCategory::System),
+            "short_term" => Ok(MemoryCategory::ShortTerm),
+            "working" => Ok(MemoryCategory::Working),
+            "long_term" => Ok(MemoryCategory::LongTerm),
+            "background" => Ok(MemoryCategory::Background),
+            _ => Err(MemoryError::InvalidCategory(s.to_string())),
+        }
+    }
+}
+
+impl MemoryCategory {
+    /// Convert a MemoryCategory to a string
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MemoryCategory::System => "system",
+            MemoryCategory::ShortTerm => "short_term",
+            MemoryCategory::Working => "working",
+            MemoryCategory::LongTerm => "long_term",
+            MemoryCategory::Background => "background",
+        }
+    }
+}
+
 /// Memory allocation representing a block of memory in the system
 #[derive(Debug)]
 struct MemoryAllocation {
@@ -50,6 +366,87 @@ struct MemoryAllocation {
     category: MemoryCategory,
     /// Access count for usage statistics
     access_count: usize,
+    /// Bytes written so far; bytes past the end read as zero
+    data: Vec<u8>,
+    /// Embedding for similarity search
+    embedding: Option<Vec<f32>>,
+    /// When the time to live of the allocation runs out, if it has one
+    expires_at: Option<Instant>,
+}
+
+impl MemoryAllocation {
+    /// Check whether the time to live of the allocation has run out
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+    
+    /// Check that a range of bytes lies within the allocation
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - Handle to the allocation, for the error
+    /// * `start` - First byte of the range
+    /// * `len` - Length of the range in bytes
+    ///
+    /// # Returns
+    ///
+    /// The end of the range, or `MemoryError::OutOfBounds`
+    fn check_bounds(&self, handle: MemoryHandle, start: usize, len: usize) -> Result<usize, MemoryError> {
+        match start.checked_add(len) {
+            Some(end) if end <= self.size => Ok(end),
+            end => Err(MemoryError::OutOfBounds {
+                handle,
+                start,
+                end: end.unwrap_or(usize::MAX),
+                size: self.size,
+            }),
+        }
+    }
+    
+    /// Copy a range of bytes that lies within the allocation
+    fn bytes(&self, range: Range<usize>) -> Vec<u8> {
+        let mut bytes = vec![0; range.len()];
+        if range.start < self.data.len() {
+            let written = &self.data[range.start..range.end.min(self.data.len())];
+            bytes[..written.len()].copy_from_slice(written);
+        }
+        bytes
+    }
+}
+
+/// Saved state of the memory manager in a kernel snapshot
+#[derive(Debug, Serialize, Deserialize)]
+struct MemoryState {
+    /// The allocations that were live when the snapshot was taken
+    allocations: Vec<AllocationState>,
+}
+
+/// Saved state of one memory allocation
+#[derive(Debug, Serialize, Deserialize)]
+struct AllocationState {
+    /// Handle of the allocation
+    handle: MemoryHandle,
+    /// Size of allocation in bytes
+    size: usize,
+    /// Memory purpose/description
+    purpose: String,
+    /// Memory category for prioritization
+    category: MemoryCategory,
+    /// Milliseconds between the allocation and the snapshot
+    age_ms: u64,
+    /// Milliseconds between the last access and the snapshot
+    idle_ms: u64,
+    /// Access count for usage statistics
+    access_count: usize,
+    /// Bytes written to the allocation, hex encoded
+    #[serde(default, with = "hex::serde")]
+    data: Vec<u8>,
+    /// Embedding for similarity search
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    embedding: Option<Vec<f32>>,
+    /// Milliseconds between the snapshot and the end of the time to live
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_in_ms: Option<u64>,
 }
 
 /// Memory manager responsible for all memory operations in RoyaOS
@@ -66,11 +463,27 @@ pub struct MemoryManager {
     /// Map of memory handles to allocations
     allocations: HashMap<MemoryHandle, MemoryAllocation>,
     /// Memory optimization strategy
-    optimization_strategy: String,
+    optimization_strategy: OptimizationStrategy,
     /// Memory usage statistics by category
     category_usage: HashMap<MemoryCategory, usize>,
     /// Last optimization time
     last_optimization: Instant,
+    /// Event bus for memory pressure notifications
+    event_bus: Option<EventBus>,
+    /// Clock for access times and idle-time eviction
+    clock: Clock,
+    /// Generator of allocation handles
+    handles: HandleGenerator,
+    /// Directory of the on-disk store of long-term memory, if any
+    long_term_dir: Option<PathBuf>,
+    /// The open on-disk store of long-term memory, while initialized
+    store: Option<LongTermStore>,
+    /// Long-term allocations read since their access statistics were last written to the store
+    unpersisted_reads: BTreeSet<MemoryHandle>,
+    /// Index of the embedded allocations for similarity search
+    index: VectorIndex,
+    /// Decay model of each category whose memory fades
+    decay: HashMap<MemoryCategory, DecayModel>,
 }
 
 impl MemoryManager {
@@ -86,6 +499,10 @@ impl MemoryManager {
     /// A new MemoryManager instance
     pub fn new(max_allocation_mb: usize, optimization_strategy: &str) -> Self {
         let max_allocation = max_allocation_mb * 1024 * 1024; // Convert MB to bytes
+        let optimization_strategy = OptimizationStrategy::from_str(optimization_strategy).unwrap_or_else(|e| {
+            warn!("{}, using 'balanced'", e);
+            OptimizationStrategy::Balanced
+        });
         info!("Initializing memory manager with {} MB max allocation and '{}' optimization strategy", 
               max_allocation_mb, optimization_strategy);
         
@@ -104,12 +521,38 @@ impl MemoryManager {
             max_allocation,
             current_allocation: 0,
             allocations: HashMap::new(),
-            optimization_strategy: optimization_strategy.to_string(),
+            optimization_strategy,
             category_usage,
             last_optimization: Instant::now(),
+            event_bus: None,
+            clock: Clock::system(),
+            handles: HandleGenerator::random(),
+            long_term_dir: None,
+            store: None,
+            unpersisted_reads: BTreeSet::new(),
+            index: VectorIndex::default(),
+            decay: HashMap::from([(MemoryCategory::ShortTerm, SHORT_TERM_DECAY)]),
         }
     }
     
+    /// Keep long-term memory in an on-disk store below a data directory
+    ///
+    /// The store is opened and its allocations are loaded when the subsystem is
+    /// initialized. Every change to a long-term allocation is then written to the
+    /// store before it is applied.
+    ///
+    /// # Arguments
+    ///
+    /// * `data_dir` - The data directory; the store is kept in its `LONG_TERM_DIR` subdirectory
+    ///
+    /// # Returns
+    ///
+    /// The memory manager with the store configured
+    pub fn with_data_dir(mut self, data_dir: &Path) -> Self {
+        self.long_term_dir = Some(data_dir.join(LONG_TERM_DIR));
+        self
+    }
+    
     /// Allocate memory with the specified size, purpose, and category
     ///
     /// This method allocates a block of memory and returns a handle that can be
@@ -123,31 +566,63 @@ impl MemoryManager {
     ///
     /// # Returns
     ///
-    /// A handle to the allocated memory, or an error message
-    pub fn allocate(&mut self, size_bytes: usize, purpose: &str, category: MemoryCategory) -> Result<MemoryHandle, String> {
+    /// A handle to the allocated memory, or `MemoryError::LimitExceeded` if the
+    /// allocation does not fit
+    pub fn allocate(&mut self, size_bytes: usize, purpose: &str, category: MemoryCategory) -> Result<MemoryHandle, MemoryError> {
+        self.allocate_with_embedding(size_bytes, purpose, category, None)
+    }
+    
+    /// Allocate memory with an embedding describing its meaning
+    ///
+    /// The allocation can be found by `search_similar` while it has the embedding.
+    ///
+    /// # Arguments
+    ///
+    /// * `size_bytes` - Size of the allocation in bytes
+    /// * `purpose` - Description of the memory's purpose
+    /// * `category` - Memory category for prioritization
+    /// * `embedding` - Embedding vector for similarity search, if any
+    ///
+    /// # Returns
+    ///
+    /// A handle to the allocated memory, `MemoryError::InvalidEmbedding`, or
+    /// `MemoryError::LimitExceeded` if the allocation does not fit
+    pub fn allocate_with_embedding(
+        &mut self,
+        size_bytes: usize,
+        purpose: &str,
+        category: MemoryCategory,
+        embedding: Option<Vec<f32>>,
+    ) -> Result<MemoryHandle, MemoryError> {
         debug!("Allocating {} bytes for '{}' in category {:?}", size_bytes, purpose, category);
+        if let Some(embedding) = &embedding {
+            self.check_embedding(embedding)?;
+        }
         
-        // Check if allocation would exceed maximum
-        if self.current_allocation + size_bytes > self.max_allocation {
+        // Check if allocation would exceed maximum; sizes come from clients, so the sum could overflow
+        if size_bytes > self.max_allocation.saturating_sub(self.current_allocation) {
             // Try to optimize memory before failing
-            if self.optimization_strategy == "aggressive" {
+            if self.optimization_strategy == OptimizationStrategy::Aggressive {
                 self.optimize()?;
             }
             
             // Check again after optimization
-            if self.current_allocation + size_bytes > self.max_allocation {
-                let error_msg = format!(
-                    "Memory allocation of {} bytes would exceed maximum of {} bytes",
-                    size_bytes, self.max_allocation
-                );
-                error!("{}", error_msg);
-                return Err(error_msg);
+            if size_bytes > self.max_allocation.saturating_sub(self.current_allocation) {
+                let error = MemoryError::LimitExceeded {
+                    requested: size_bytes,
+                    available: self.max_allocation.saturating_sub(self.current_allocation),
+                };
+                error!("{}", error);
+                self.publish_pressure();
+                return Err(error);
             }
         }
         
+        let was_under_pressure = self.usage_percentage() >= MEMORY_PRESSURE_THRESHOLD;
+        
         // Create allocation
-        let handle = Uuid::new_v4();
-        let now = Instant::now();
+        let handle = self.handles.next_handle();
+        let now = self.clock.now();
         let allocation = MemoryAllocation {
             size: size_bytes,
             allocated_at: now,
@@ -155,162 +630,555 @@ impl MemoryManager {
             purpose: purpose.to_string(),
             category,
             access_count: 0,
+            data: Vec::new(),
+            embedding,
+            expires_at: None,
         };
         
+        if category == MemoryCategory::LongTerm {
+            self.persist(StoreRecord::Put(self.stored_allocation(handle, &allocation)))?;
+        }
+        
         // Update state
+        if let Some(embedding) = &allocation.embedding {
+            self.index.insert(handle, embedding);
+        }
         self.allocations.insert(handle, allocation);
         self.current_allocation += size_bytes;
         
         // Update category usage
         *self.category_usage.entry(category).or_insert(0) += size_bytes;
         
+        if !was_under_pressure && self.usage_percentage() >= MEMORY_PRESSURE_THRESHOLD {
+            warn!("Memory usage crossed {}% threshold", MEMORY_PRESSURE_THRESHOLD);
+            self.publish_pressure();
+        }
+        
+        self.compact_store();
         debug!("Allocated memory with handle {}", handle);
         Ok(handle)
     }
     
-    /// Access memory to update usage statistics
+    /// Write bytes into a memory allocation
+    ///
+    /// The write counts as an access of the allocation.
     ///
     /// # Arguments
     ///
     /// * `handle` - Handle to the memory allocation
+    /// * `offset` - Position of the first byte to write
+    /// * `bytes` - The bytes to write
     ///
     /// # Returns
     ///
-    /// `Ok(())` if access is successful, or an error message
-    pub fn access(&mut self, handle: MemoryHandle) -> Result<(), String> {
-        let allocation = self.allocations.get_mut(&handle).ok_or_else(|| {
-            let error_msg = format!("No memory allocation found for handle {}", handle);
-            error!("{}", error_msg);
-            error_msg
-        })?;
+    /// `Ok(())` if the bytes were written, `MemoryError::NotFound`, or
+    /// `MemoryError::OutOfBounds` if they do not fit into the allocation
+    pub fn write(&mut self, handle: MemoryHandle, offset: usize, bytes: &[u8]) -> Result<(), MemoryError> {
+        let now = self.clock.now();
+        let allocation = self.allocation_mut(handle)?;
+        let end = allocation.check_bounds(handle, offset, bytes.len()).inspect_err(|e| error!("{}", e))?;
+        
+        if allocation.category == MemoryCategory::LongTerm {
+            let at_ms = self.unix_millis(now);
+            self.persist(StoreRecord::Write { handle, offset, bytes: bytes.to_vec(), at_ms })?;
+        }
         
-        allocation.last_accessed = Instant::now();
+        let allocation = self.allocation_mut(handle)?;
+        if allocation.data.len() < end {
+            allocation.data.resize(end, 0);
+        }
+        allocation.data[offset..end].copy_from_slice(bytes);
+        allocation.last_accessed = now;
         allocation.access_count += 1;
         
+        self.compact_store();
         Ok(())
     }
     
-    /// Deallocate memory with the specified handle
+    /// Read bytes from a memory allocation
     ///
-    /// This method releases a previously allocated block of memory.
+    /// Bytes that were never written read as zero. The read counts as an
+    /// access of the allocation; the access statistics of long-term memory
+    /// are written to the on-disk store once `READ_BATCH_SIZE` long-term
+    /// allocations have been read.
     ///
     /// # Arguments
     ///
-    /// * `handle` - Handle to the memory allocation to deallocate
+    /// * `handle` - Handle to the memory allocation
+    /// * `range` - Positions of the bytes to read
     ///
     /// # Returns
     ///
-    /// `Ok(())` if deallocation is successful, or an error message
-    pub fn deallocate(&mut self, handle: MemoryHandle) -> Result<(), String> {
-        debug!("Deallocating memory with handle {}", handle);
-        
-        // Find allocation
-        let allocation = match self.allocations.remove(&handle) {
-            Some(alloc) => alloc,
-            None => {
-                let error_msg = format!("No memory allocation found for handle {}", handle);
-                error!("{}", error_msg);
-                return Err(error_msg);
-            }
-        };
+    /// The bytes, `MemoryError::NotFound`, or `MemoryError::OutOfBounds` if the
+    /// range reaches past the end of the allocation
+    pub fn read(&mut self, handle: MemoryHandle, range: Range<usize>) -> Result<Vec<u8>, MemoryError> {
+        let now = self.clock.now();
+        let allocation = self.allocation_mut(handle)?;
+        if range.start > range.end {
+            let error = MemoryError::OutOfBounds { handle, start: range.start, end: range.end, size: allocation.size };
+            error!("{}", error);
+            return Err(error);
+        }
+        allocation.check_bounds(handle, range.start, range.len()).inspect_err(|e| error!("{}", e))?;
         
-        // Update state
-        self.current_allocation -= allocation.size;
+        allocation.last_accessed = now;
+        allocation.access_count += 1;
+        let bytes = allocation.bytes(range);
+        let long_term = allocation.category == MemoryCategory::LongTerm;
         
-        // Update category usage
-        if let Some(category_size) = self.category_usage.get_mut(&allocation.category) {
-            *category_size = category_size.saturating_sub(allocation.size);
+        if long_term && self.store.is_some() {
+            self.unpersisted_reads.insert(handle);
+            if self.unpersisted_reads.len() >= READ_BATCH_SIZE {
+                self.persist_reads();
+            }
         }
         
-        debug!("Deallocated {} bytes from category {:?}", allocation.size, allocation.category);
-        Ok(())
+        Ok(bytes)
     }
     
-    /// Get current memory usage in bytes
+    /// Store a value in a memory allocation
+    ///
+    /// The value replaces any value stored before. It is serialized as JSON
+    /// behind a header of `VALUE_HEADER_SIZE` bytes, and the store counts as an
+    /// access of the allocation.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - Handle to the memory allocation
+    /// * `value` - The value to store
     ///
     /// # Returns
     ///
-    /// Current memory usage in bytes
-    pub fn current_usage(&self) -> usize {
-        self.current_allocation
+    /// `Ok(())` if the value was stored, `MemoryError::NotFound`,
+    /// `MemoryError::InvalidValue` if it cannot be serialized, or
+    /// `MemoryError::OutOfBounds` if it does not fit into the allocation
+    pub fn put_value<T: Serialize + ?Sized>(&mut self, handle: MemoryHandle, value: &T) -> Result<(), MemoryError> {
+        let json = serde_json::to_vec(value).map_err(|e| {
+            let error = MemoryError::InvalidValue { handle, reason: e.to_string() };
+            error!("{}", error);
+            error
+        })?;
+        
+        let mut bytes = Vec::with_capacity(VALUE_HEADER_SIZE + json.len());
+        bytes.extend_from_slice(&(json.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&json);
+        self.write(handle, 0, &bytes)
     }
     
-    /// Get maximum memory allocation in bytes
+    /// Load the value stored in a memory allocation
+    ///
+    /// The load counts as an access of the allocation.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - Handle to the memory allocation
     ///
     /// # Returns
     ///
-    /// Maximum memory allocation in bytes
-    pub fn max_allocation(&self) -> usize {
-        self.max_allocation
+    /// The value, `MemoryError::NotFound`, or `MemoryError::InvalidValue` if the
+    /// allocation holds no value of type `T`
+    pub fn get_value<T: DeserializeOwned>(&mut self, handle: MemoryHandle) -> Result<T, MemoryError> {
+        let invalid = |reason: String| {
+            let error = MemoryError::InvalidValue { handle, reason };
+            error!("{}", error);
+            error
+        };
+        
+        let size = self.allocation_mut(handle)?.size;
+        if size < VALUE_HEADER_SIZE {
+            return Err(invalid(format!("{} bytes cannot hold a value", size)));
+        }
+        
+        let header = self.read(handle, 0..VALUE_HEADER_SIZE)?;
+        let len = u64::from_le_bytes(header.try_into().expect("The header has VALUE_HEADER_SIZE bytes"));
+        let len = match usize::try_from(len) {
+            Ok(0) => return Err(invalid("no value has been stored".to_string())),
+            Ok(len) if len <= size - VALUE_HEADER_SIZE => len,
+            _ => return Err(invalid(format!("the stored length {} exceeds the allocation", len))),
+        };
+        
+        let allocation = self.allocation_mut(handle)?;
+        let json = allocation.bytes(VALUE_HEADER_SIZE..VALUE_HEADER_SIZE + len);
+        serde_json::from_slice(&json).map_err(|e| invalid(e.to_string()))
     }
     
-    /// Get memory usage percentage
+    /// Set or remove the embedding of a memory allocation
+    ///
+    /// The embedding replaces the one the allocation had. All embeddings have
+    /// the dimension of the first one, until none is left. Setting an embedding
+    /// does not count as an access of the allocation.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - Handle to the memory allocation
+    /// * `embedding` - Embedding vector for similarity search, or `None` to remove it
     ///
     /// # Returns
     ///
-    /// Memory usage as a percentage of maximum allocation
-    pub fn usage_percentage(&self) -> f64 {
-        (self.current_allocation as f64 / self.max_allocation as f64) * 100.0
+    /// `Ok(())` if the embedding was set, `MemoryError::NotFound`, or
+    /// `MemoryError::InvalidEmbedding`
+    pub fn set_embedding(&mut self, handle: MemoryHandle, embedding: Option<Vec<f32>>) -> Result<(), MemoryError> {
+        let category = self.allocation_mut(handle)?.category;
+        if let Some(embedding) = &embedding {
+            self.check_embedding(embedding)?;
+        }
+        
+        if category == MemoryCategory::LongTerm {
+            self.persist(StoreRecord::Embed { handle, embedding: embedding.clone() })?;
+        }
+        
+        match &embedding {
+            Some(embedding) => self.index.insert(handle, embedding),
+            None => self.index.remove(handle),
+        }
+        self.allocation_mut(handle)?.embedding = embedding;
+        
+        self.compact_store();
+        Ok(())
     }
     
-    /// Get memory usage for a specific category
+    /// Find the allocations whose embeddings are most similar to a query vector
+    ///
+    /// Similarity is the cosine of the angle between the embedding and the query.
+    /// The search is approximate: it may miss a close match in a large index,
+    /// but it does not visit every allocation. Searching does not count as an
+    /// access of the allocations found.
     ///
     /// # Arguments
     ///
-    /// * `category` - Memory category to get usage for
+    /// * `query` - The query vector, with as many dimensions as the embeddings
+    /// * `k` - Maximum number of allocations to return
+    /// * `category` - Only return allocations of this category, if given
     ///
     /// # Returns
     ///
-    /// Memory usage for the specified category in bytes
-    pub fn category_usage(&self, category: MemoryCategory) -> usize {
-        *self.category_usage.get(&category).unwrap_or(&0)
+    /// Up to `k` allocations, most similar first, or `MemoryError::InvalidEmbedding`
+    /// if the query cannot be compared with the embeddings
+    pub fn search_similar(&self, query: &[f32], k: usize, category: Option<MemoryCategory>) -> Result<Vec<SimilarMemory>, MemoryError> {
+        self.search_similar_where(query, k, category, |_| true)
     }
     
-    /// Optimize memory usage based on the current strategy
+    /// Find the most similar allocations among those a filter accepts
     ///
-    /// This method attempts to free up memory by:
-    /// 1. Identifying unused or infrequently accessed allocations
-    /// 2. Compressing or paging out low-priority memory
-    /// 3. Consolidating fragmented memory
+    /// Works as [`MemoryManager::search_similar`], but allocations the filter
+    /// rejects are skipped during the search rather than removed from its
+    /// results, so up to `k` accepted allocations are still returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query vector, with as many dimensions as the embeddings
+    /// * `k` - Maximum number of allocations to return
+    /// * `category` - Only return allocations of this category, if given
+    /// * `filter` - Whether an allocation may be returned
     ///
     /// # Returns
     ///
-    /// `Ok(())` if optimization is successful, or an error message
-    pub fn optimize(&mut self) -> Result<(), String> {
-        info!("Optimizing memory with '{}' strategy", self.optimization_strategy);
-        
-        let now = Instant::now();
-        self.last_optimization = now;
-        
-        // Skip if we have plenty of free memory
-        if self.usage_percentage() < 70.0 {
-            debug!("Memory usage below threshold, skipping optimization");
-            return Ok(());
-        }
+    /// Up to `k` accepted allocations, most similar first, or `MemoryError::InvalidEmbedding`
+    /// if the query cannot be compared with the embeddings
+    pub fn search_similar_where(
+        &self,
+        query: &[f32],
+        k: usize,
+        category: Option<MemoryCategory>,
+        filter: impl Fn(MemoryHandle) -> bool,
+    ) -> Result<Vec<SimilarMemory>, MemoryError> {
+        self.check_embedding(query)?;
         
-        // Identify candidates for cleanup based on strategy
-        let mut handles_to_remove = Vec::new();
-        let threshold = match self.optimization_strategy.as_str() {
-            "aggressive" => Duration::from_secs(60), // 1 minute
-            "balanced" => Duration::from_secs(300),  // 5 minutes
-            "conservative" => Duration::from_secs(900), // 15 minutes
-            _ => Duration::from_secs(300), // Default to balanced
+        let now = self.clock.now();
+        let accept = |handle: &MemoryHandle| {
+            self.allocations.get(handle).is_some_and(|allocation| {
+                !allocation.is_expired(now) && category.is_none_or(|category| allocation.category == category)
+            }) && filter(*handle)
         };
+        let matches = self.index.search(query, k, accept)
+            .into_iter()
+            .filter_map(|(handle, similarity)| {
+                let allocation = self.allocations.get(&handle)?;
+                Some(SimilarMemory {
+                    handle,
+                    category: allocation.category,
+                    purpose: allocation.purpose.clone(),
+                    similarity,
+                })
+            })
+            .collect();
         
-        // Find unused allocations in Background category
-        for (handle, allocation) in &self.allocations {
-            if allocation.category == MemoryCategory::Background {
-                let idle_time = now.duration_since(allocation.last_accessed);
-                if idle_time > threshold {
-                    handles_to_remove.push(*handle);
-                }
-            }
+        Ok(matches)
+    }
+    
+    /// Set or remove the time to live of a memory allocation
+    ///
+    /// The allocation expires once the time has passed from now: it can no
+    /// longer be accessed, and the next optimization pass releases it. Setting
+    /// the time to live does not count as an access of the allocation.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - Handle to the memory allocation
+    /// * `ttl` - Time to live from now, or `None` to keep the allocation until it is released;
+    ///   a time to live too long to represent never runs out
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the time to live was set, `MemoryError::NotFound`, or `MemoryError::Expired`
+    pub fn set_ttl(&mut self, handle: MemoryHandle, ttl: Option<Duration>) -> Result<(), MemoryError> {
+        let now = self.clock.now();
+        let category = self.allocation_mut(handle)?.category;
+        let expires_at = ttl.and_then(|ttl| now.checked_add(ttl));
+        
+        if category == MemoryCategory::LongTerm {
+            let expires_at_ms = expires_at.map(|expires_at| self.unix_millis(expires_at));
+            self.persist(StoreRecord::Expire { handle, expires_at_ms })?;
         }
+        self.allocation_mut(handle)?.expires_at = expires_at;
         
-        // Remove identified allocations
-        let mut freed_bytes = 0;
-        for handle in handles_to_remove {
-            if let Some(allocation) = self.allocations.remove(&handle) {
+        self.compact_store();
+        Ok(())
+    }
+    
+    /// Get the current retention strength of a memory allocation
+    ///
+    /// Allocations in a category without a decay model keep a strength of 1.0.
+    /// Querying the strength does not count as an access of the allocation.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - Handle to the memory allocation
+    ///
+    /// # Returns
+    ///
+    /// The strength from 0.0 to 1.0, `MemoryError::NotFound`, or `MemoryError::Expired`
+    pub fn retention_strength(&self, handle: MemoryHandle) -> Result<f64, MemoryError> {
+        let now = self.clock.now();
+        let allocation = self.allocation(handle)?;
+        Ok(self.strength(allocation, now))
+    }
+    
+    /// Get the decay model of a memory category
+    ///
+    /// # Arguments
+    ///
+    /// * `category` - The memory category
+    ///
+    /// # Returns
+    ///
+    /// The decay model, or `None` if memory of the category does not fade
+    pub fn decay(&self, category: MemoryCategory) -> Option<DecayModel> {
+        self.decay.get(&category).copied()
+    }
+    
+    /// Change the decay model of a memory category
+    ///
+    /// The new model applies to the allocations of the category right away.
+    ///
+    /// # Arguments
+    ///
+    /// * `category` - The memory category
+    /// * `decay` - The decay model, or `None` to keep memory of the category from fading
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if successful, or `MemoryError::InvalidDecay` if the model cannot be used
+    pub fn set_decay(&mut self, category: MemoryCategory, decay: Option<DecayModel>) -> Result<(), MemoryError> {
+        match decay {
+            Some(decay) => {
+                decay.validate().inspect_err(|e| error!("{}", e))?;
+                info!("Setting decay of {:?} memory to a half-life of {:?}", category, decay.half_life);
+                self.decay.insert(category, decay);
+            },
+            None => {
+                if self.decay.remove(&category).is_some() {
+                    info!("Turning off decay of {:?} memory", category);
+                }
+            },
+        }
+        
+        Ok(())
+    }
+    
+    /// Deallocate memory with the specified handle
+    ///
+    /// This method releases a previously allocated block of memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - Handle to the memory allocation to deallocate
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if deallocation is successful, or `MemoryError::NotFound`;
+    /// expired allocations can still be deallocated
+    pub fn deallocate(&mut self, handle: MemoryHandle) -> Result<(), MemoryError> {
+        debug!("Deallocating memory with handle {}", handle);
+        
+        // Find allocation
+        let Some(allocation) = self.allocations.get(&handle) else {
+            return Err(missing(handle, false));
+        };
+        if allocation.category == MemoryCategory::LongTerm {
+            self.persist(StoreRecord::Free { handle })?;
+        }
+        let allocation = self.allocations.remove(&handle).expect("The allocation was found above");
+        self.index.remove(handle);
+        
+        // Update state
+        self.current_allocation -= allocation.size;
+        
+        // Update category usage
+        if let Some(category_size) = self.category_usage.get_mut(&allocation.category) {
+            *category_size = category_size.saturating_sub(allocation.size);
+        }
+        
+        self.compact_store();
+        debug!("Deallocated {} bytes from category {:?}", allocation.size, allocation.category);
+        Ok(())
+    }
+    
+    /// Get the category of a memory allocation
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - Handle to the memory allocation
+    ///
+    /// # Returns
+    ///
+    /// The category, or `None` if the handle does not refer to a live allocation
+    pub fn category_of(&self, handle: MemoryHandle) -> Option<MemoryCategory> {
+        let now = self.clock.now();
+        self.allocations.get(&handle)
+            .filter(|allocation| !allocation.is_expired(now))
+            .map(|allocation| allocation.category)
+    }
+    
+    /// Check whether a memory allocation exists
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - Handle to the memory allocation
+    ///
+    /// # Returns
+    ///
+    /// `true` if the handle refers to a live allocation, `false` otherwise
+    pub fn contains(&self, handle: MemoryHandle) -> bool {
+        self.category_of(handle).is_some()
+    }
+    
+    /// Get current memory usage in bytes
+    ///
+    /// # Returns
+    ///
+    /// Current memory usage in bytes
+    pub fn current_usage(&self) -> usize {
+        self.current_allocation
+    }
+    
+    /// Get maximum memory allocation in bytes
+    ///
+    /// # Returns
+    ///
+    /// Maximum memory allocation in bytes
+    pub fn max_allocation(&self) -> usize {
+        self.max_allocation
+    }
+    
+    /// Get memory usage percentage
+    ///
+    /// # Returns
+    ///
+    /// Memory usage as a percentage of maximum allocation
+    pub fn usage_percentage(&self) -> f64 {
+        (self.current_allocation as f64 / self.max_allocation as f64) * 100.0
+    }
+    
+    /// Get memory usage for a specific category
+    ///
+    /// # Arguments
+    ///
+    /// * `category` - Memory category to get usage for
+    ///
+    /// # Returns
+    ///
+    /// Memory usage for the specified category in bytes
+    pub fn category_usage(&self, category: MemoryCategory) -> usize {
+        *self.category_usage.get(&category).unwrap_or(&0)
+    }
+    
+    /// Get the memory optimization strategy
+    ///
+    /// # Returns
+    ///
+    /// The name of the current strategy
+    pub fn optimization_strategy(&self) -> &str {
+        self.optimization_strategy.as_str()
+    }
+    
+    /// Change the memory optimization strategy
+    ///
+    /// The new strategy is used from the next optimization pass on.
+    ///
+    /// # Arguments
+    ///
+    /// * `strategy` - Strategy for memory optimization ("aggressive", "balanced", or "conservative")
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if successful, or `MemoryError::InvalidStrategy` if the strategy is unknown
+    pub fn set_optimization_strategy(&mut self, strategy: &str) -> Result<(), MemoryError> {
+        let strategy = OptimizationStrategy::from_str(strategy).inspect_err(|e| error!("{}", e))?;
+        
+        info!("Changing optimization strategy from '{}' to '{}'", self.optimization_strategy, strategy);
+        self.optimization_strategy = strategy;
+        
+        Ok(())
+    }
+    
+    /// Optimize memory usage based on the current strategy
+    ///
+    /// This method attempts to free up memory by:
+    /// 1. Forgetting expired and faded allocations
+    /// 2. Identifying unused or infrequently accessed allocations
+    /// 3. Compressing or paging out low-priority memory
+    /// 4. Consolidating fragmented memory
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if optimization is successful, or the error that stopped it
+    pub fn optimize(&mut self) -> Result<(), MemoryError> {
+        debug!("Optimizing memory with '{}' strategy", self.optimization_strategy);
+        
+        let now = self.clock.now();
+        self.last_optimization = now;
+        self.forget()?;
+        
+        // Skip if we have plenty of free memory
+        if self.usage_percentage() < 70.0 {
+            debug!("Memory usage below threshold, skipping optimization");
+            return Ok(());
+        }
+        
+        // Identify candidates for cleanup based on strategy
+        let mut handles_to_remove = Vec::new();
+        let threshold = match self.optimization_strategy {
+            OptimizationStrategy::Aggressive => Duration::from_secs(60), // 1 minute
+            OptimizationStrategy::Balanced => Duration::from_secs(300),  // 5 minutes
+            OptimizationStrategy::Conservative => Duration::from_secs(900), // 15 minutes
+        };
+        
+        // Find unused allocations in Background category
+        for (handle, allocation) in &self.allocations {
+            if allocation.category == MemoryCategory::Background {
+                let idle_time = now.duration_since(allocation.last_accessed);
+                if idle_time > threshold {
+                    handles_to_remove.push(*handle);
+                }
+            }
+        }
+        
+        // Remove identified allocations
+        let mut freed_bytes = 0;
+        for handle in handles_to_remove {
+            if let Some(allocation) = self.allocations.remove(&handle) {
+                self.index.remove(handle);
                 self.current_allocation -= allocation.size;
                 freed_bytes += allocation.size;
                 
@@ -324,11 +1192,573 @@ impl MemoryManager {
         info!("Memory optimization complete, freed {} bytes", freed_bytes);
         Ok(())
     }
+    
+    /// Release the allocations that expired or faded
+    ///
+    /// An allocation fades once its retention strength falls below the
+    /// forgetting threshold of the decay model of its category.
+    ///
+    /// # Returns
+    ///
+    /// The handles of the released allocations, or `MemoryError::StoreFailed`;
+    /// allocations released before the store failed stay released
+    pub fn forget(&mut self) -> Result<Vec<MemoryHandle>, MemoryError> {
+        let now = self.clock.now();
+        let mut forgotten: Vec<MemoryHandle> = self.allocations.iter()
+            .filter(|(_, allocation)| {
+                allocation.is_expired(now) || self.decay.get(&allocation.category)
+                    .is_some_and(|decay| self.strength(allocation, now) < decay.forget_below)
+            })
+            .map(|(handle, _)| *handle)
+            .collect();
+        forgotten.sort();
+        
+        for handle in &forgotten {
+            self.deallocate(*handle)?;
+        }
+        
+        if !forgotten.is_empty() {
+            info!("Forgot {} expired or faded memory allocations", forgotten.len());
+        }
+        Ok(forgotten)
+    }
+    
+    /// Promote frequently and recently used memory to long-term memory
+    ///
+    /// Short-term and working allocations that meet the consolidation thresholds
+    /// of the current strategy move to the long-term category. They keep their
+    /// handles, contents and access statistics, and each promotion is published
+    /// as a `KernelEvent::MemoryPromoted` event. With an on-disk store, a
+    /// promotion is written to the store before it takes effect.
+    ///
+    /// # Returns
+    ///
+    /// The handles of the promoted allocations, or `MemoryError::StoreFailed`;
+    /// allocations promoted before the store failed stay promoted
+    pub fn consolidate(&mut self) -> Result<Vec<MemoryHandle>, MemoryError> {
+        self.consolidate_where(|_, _| true)
+    }
+    
+    /// Promote the memory a filter accepts to long-term memory
+    ///
+    /// Works as [`MemoryManager::consolidate`], but asks the filter before each
+    /// promotion, so the caller can hold back allocations it does not own or
+    /// has no room for.
+    ///
+    /// # Arguments
+    ///
+    /// * `accept` - Called with the handle and size of every allocation that meets
+    ///   the thresholds, in handle order; returns whether to promote it
+    ///
+    /// # Returns
+    ///
+    /// The handles of the promoted allocations, or `MemoryError::StoreFailed`;
+    /// allocations promoted before the store failed stay promoted
+    pub fn consolidate_where(&mut self, mut accept: impl FnMut(MemoryHandle, usize) -> bool) -> Result<Vec<MemoryHandle>, MemoryError> {
+        let thresholds = self.optimization_strategy.consolidation_thresholds();
+        debug!("Consolidating memory with '{}' strategy", self.optimization_strategy);
+        
+        let now = self.clock.now();
+        let mut candidates: Vec<MemoryHandle> = self.allocations.iter()
+            .filter(|(_, allocation)| matches!(allocation.category, MemoryCategory::ShortTerm | MemoryCategory::Working))
+            .filter(|(_, allocation)| !allocation.is_expired(now))
+            .filter(|(_, allocation)| allocation.access_count >= thresholds.min_access_count)
+            .filter(|(_, allocation)| now.duration_since(allocation.last_accessed) <= thresholds.max_idle)
+            .map(|(handle, _)| *handle)
+            .collect();
+        candidates.sort();
+        
+        let mut promoted = Vec::new();
+        for handle in candidates {
+            let allocation = &self.allocations[&handle];
+            let (from, size) = (allocation.category, allocation.size);
+            if !accept(handle, size) {
+                debug!("Not promoting allocation {} from {:?}", handle, from);
+                continue;
+            }
+            self.persist(StoreRecord::Put(self.stored_allocation(handle, allocation)))?;
+            
+            self.allocation_mut(handle)?.category = MemoryCategory::LongTerm;
+            if let Some(category_size) = self.category_usage.get_mut(&from) {
+                *category_size = category_size.saturating_sub(size);
+            }
+            *self.category_usage.entry(MemoryCategory::LongTerm).or_insert(0) += size;
+            
+            if let Some(bus) = &self.event_bus {
+                bus.publish(KernelEvent::MemoryPromoted {
+                    handle,
+                    from: from.as_str().to_string(),
+                    to: MemoryCategory::LongTerm.as_str().to_string(),
+                });
+            }
+            debug!("Promoted allocation {} from {:?} to long-term memory", handle, from);
+            promoted.push(handle);
+        }
+        
+        self.compact_store();
+        info!("Memory consolidation complete, promoted {} allocations", promoted.len());
+        Ok(promoted)
+    }
+    
+    /// Replace the allocations with those saved in a kernel snapshot
+    ///
+    /// Allocations keep their handles, so references held across a restart
+    /// stay valid. Long-term allocations kept in the on-disk store are not
+    /// replaced, since the store holds their latest state. Nothing is changed
+    /// if the saved allocations do not fit into the maximum allocation.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The saved memory manager state
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the allocations were restored, or `MemoryError::LimitExceeded`
+    fn restore_state(&mut self, mut state: MemoryState) -> Result<(), MemoryError> {
+        let keep_long_term = self.store.is_some();
+        let kept = if keep_long_term {
+            state.allocations.retain(|saved| saved.category != MemoryCategory::LongTerm);
+            self.category_usage(MemoryCategory::LongTerm)
+        } else {
+            0
+        };
+        
+        let total: usize = kept + state.allocations.iter().map(|allocation| allocation.size).sum::<usize>();
+        if total > self.max_allocation {
+            let error = MemoryError::LimitExceeded {
+                requested: total,
+                available: self.max_allocation,
+            };
+            error!("Cannot restore memory allocations: {}", error);
+            return Err(error);
+        }
+        
+        self.allocations.retain(|_, allocation| keep_long_term && allocation.category == MemoryCategory::LongTerm);
+        for (category, category_size) in self.category_usage.iter_mut() {
+            if !keep_long_term || *category != MemoryCategory::LongTerm {
+                *category_size = 0;
+            }
+        }
+        
+        for saved in state.allocations {
+            *self.category_usage.entry(saved.category).or_insert(0) += saved.size;
+            self.allocations.insert(saved.handle, MemoryAllocation {
+                size: saved.size,
+                allocated_at: instant_from_age(&self.clock, saved.age_ms),
+                last_accessed: instant_from_age(&self.clock, saved.idle_ms),
+                purpose: saved.purpose,
+                category: saved.category,
+                access_count: saved.access_count,
+                data: saved.data,
+                embedding: saved.embedding,
+                expires_at: saved.expires_in_ms.map(|ms| self.clock.now() + Duration::from_millis(ms)),
+            });
+        }
+        self.current_allocation = total;
+        self.rebuild_index();
+        
+        info!("Restored {} memory allocations totalling {} bytes", self.allocations.len(), total);
+        Ok(())
+    }
+    
+    /// Open the on-disk store of long-term memory and load its allocations
+    ///
+    /// Long-term allocations already in memory are replaced by those in the
+    /// store. Nothing happens if no data directory is configured.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the store is open, `MemoryError::StoreFailed`, or
+    /// `MemoryError::LimitExceeded` if its allocations do not fit
+    fn open_store(&mut self) -> Result<(), MemoryError> {
+        let Some(dir) = &self.long_term_dir else {
+            return Ok(());
+        };
+        let (store, stored) = LongTermStore::open(dir).map_err(store_failed)?;
+        
+        let in_memory = self.category_usage(MemoryCategory::LongTerm);
+        let total: usize = stored.iter().map(|allocation| allocation.size).sum();
+        let available = self.max_allocation.saturating_sub(self.current_allocation - in_memory);
+        if total > available {
+            let error = MemoryError::LimitExceeded { requested: total, available };
+            error!("Cannot load long-term memory: {}", error);
+            return Err(error);
+        }
+        
+        self.allocations.retain(|_, allocation| allocation.category != MemoryCategory::LongTerm);
+        let loaded = stored.len();
+        for saved in stored {
+            self.allocations.insert(saved.handle, MemoryAllocation {
+                size: saved.size,
+                allocated_at: self.instant_at_unix_millis(saved.allocated_at_ms),
+                last_accessed: self.instant_at_unix_millis(saved.last_accessed_ms),
+                purpose: saved.purpose,
+                category: MemoryCategory::LongTerm,
+                access_count: saved.access_count,
+                data: saved.data,
+                embedding: saved.embedding,
+                expires_at: saved.expires_at_ms.map(|ms| self.instant_at_unix_millis(ms)),
+            });
+        }
+        self.current_allocation = self.current_allocation - in_memory + total;
+        self.rebuild_index();
+        self.category_usage.insert(MemoryCategory::LongTerm, total);
+        self.store = Some(store);
+        
+        info!("Loaded {} long-term memory allocations totalling {} bytes", loaded, total);
+        Ok(())
+    }
+    
+    /// Write a change of long-term memory to the on-disk store, if there is one
+    ///
+    /// # Arguments
+    ///
+    /// * `record` - The change
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` once the change is on disk, or `MemoryError::StoreFailed`
+    fn persist(&mut self, record: StoreRecord) -> Result<(), MemoryError> {
+        match self.store.as_mut() {
+            Some(store) => store.append(&record).map_err(store_failed),
+            None => Ok(()),
+        }
+    }
+    
+    /// Write the access statistics of the long-term allocations read since they were last written
+    ///
+    /// A read has already succeeded when its statistics are written, so a
+    /// failure is only logged and the statistics are written with the next batch.
+    fn persist_reads(&mut self) {
+        let records: Vec<StoreRecord> = self.unpersisted_reads.iter()
+            .filter_map(|handle| {
+                let allocation = self.allocations.get(handle)
+                    .filter(|allocation| allocation.category == MemoryCategory::LongTerm)?;
+                Some(StoreRecord::Access {
+                    handle: *handle,
+                    last_accessed_ms: self.unix_millis(allocation.last_accessed),
+                    access_count: allocation.access_count,
+                })
+            })
+            .collect();
+        
+        for record in records {
+            if let Err(e) = self.persist(record) {
+                warn!("Failed to write access statistics of long-term memory: {}", e);
+                return;
+            }
+        }
+        self.unpersisted_reads.clear();
+        self.compact_store();
+    }
+    
+    /// Compact the on-disk store once its log holds many more records than needed
+    ///
+    /// A failed compaction leaves the log as it was, so it is only logged.
+    fn compact_store(&mut self) {
+        let live = self.allocations.values().filter(|allocation| allocation.category == MemoryCategory::LongTerm).count();
+        if !self.store.as_ref().is_some_and(|store| store.needs_compaction(live)) {
+            return;
+        }
+        
+        let allocations = self.stored_allocations();
+        if let Some(store) = self.store.as_mut() {
+            match store.compact(&allocations) {
+                // The compacted log holds the latest access statistics
+                Ok(()) => self.unpersisted_reads.clear(),
+                Err(e) => warn!("Failed to compact long-term memory store: {}", e),
+            }
+        }
+    }
+    
+    /// Get the long-term allocations as written to the on-disk store, ordered by handle
+    fn stored_allocations(&self) -> Vec<StoredAllocation> {
+        let mut allocations: Vec<StoredAllocation> = self.allocations.iter()
+            .filter(|(_, allocation)| allocation.category == MemoryCategory::LongTerm)
+            .map(|(handle, allocation)| self.stored_allocation(*handle, allocation))
+            .collect();
+        allocations.sort_by_key(|allocation| allocation.handle);
+        allocations
+    }
+    
+    /// Get an allocation as written to the on-disk store
+    fn stored_allocation(&self, handle: MemoryHandle, allocation: &MemoryAllocation) -> StoredAllocation {
+        StoredAllocation {
+            handle,
+            size: allocation.size,
+            purpose: allocation.purpose.clone(),
+            allocated_at_ms: self.unix_millis(allocation.allocated_at),
+            last_accessed_ms: self.unix_millis(allocation.last_accessed),
+            access_count: allocation.access_count,
+            data: allocation.data.clone(),
+            embedding: allocation.embedding.clone(),
+            expires_at_ms: allocation.expires_at.map(|expires_at| self.unix_millis(expires_at)),
+        }
+    }
+    
+    /// Convert an instant of the clock, past or future, to milliseconds since the Unix epoch
+    fn unix_millis(&self, instant: Instant) -> u64 {
+        let now = u64::try_from(self.clock.utc_now().timestamp_millis()).unwrap_or(0);
+        match instant.checked_duration_since(self.clock.now()) {
+            Some(ahead) => now.saturating_add(u64::try_from(ahead.as_millis()).unwrap_or(u64::MAX)),
+            None => now.saturating_sub(age_millis(&self.clock, instant)),
+        }
+    }
+    
+    /// Convert milliseconds since the Unix epoch, past or future, to an instant of the clock
+    fn instant_at_unix_millis(&self, millis: u64) -> Instant {
+        let now = u64::try_from(self.clock.utc_now().timestamp_millis()).unwrap_or(0);
+        if millis > now {
+            return self.clock.now() + Duration::from_millis(millis - now);
+        }
+        instant_from_age(&self.clock, now - millis)
+    }
+    
+    /// Get the retention strength of an allocation under the decay model of its category
+    fn strength(&self, allocation: &MemoryAllocation, now: Instant) -> f64 {
+        match self.decay.get(&allocation.category) {
+            Some(decay) => decay.strength(now.duration_since(allocation.last_accessed), allocation.access_count),
+            None => 1.0,
+        }
+    }
+    
+    /// Get a memory allocation
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - Handle to the memory allocation
+    ///
+    /// # Returns
+    ///
+    /// The allocation, `MemoryError::NotFound`, or `MemoryError::Expired`
+    fn allocation(&self, handle: MemoryHandle) -> Result<&MemoryAllocation, MemoryError> {
+        let now = self.clock.now();
+        match self.allocations.get(&handle) {
+            Some(allocation) if !allocation.is_expired(now) => Ok(allocation),
+            found => Err(missing(handle, found.is_some())),
+        }
+    }
+    
+    /// Get a memory allocation for modification
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - Handle to the memory allocation
+    ///
+    /// # Returns
+    ///
+    /// The allocation, `MemoryError::NotFound`, or `MemoryError::Expired`
+    fn allocation_mut(&mut self, handle: MemoryHandle) -> Result<&mut MemoryAllocation, MemoryError> {
+        let now = self.clock.now();
+        match self.allocations.get_mut(&handle) {
+            Some(allocation) if !allocation.is_expired(now) => Ok(allocation),
+            found => Err(missing(handle, found.is_some())),
+        }
+    }
+    
+    /// Check that a vector can be used as an embedding or query
+    ///
+    /// # Arguments
+    ///
+    /// * `vector` - The vector
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())`, or `MemoryError::InvalidEmbedding`
+    fn check_embedding(&self, vector: &[f32]) -> Result<(), MemoryError> {
+        self.index.check_vector(vector).map_err(|reason| {
+            let error = MemoryError::InvalidEmbedding(reason);
+            error!("{}", error);
+            error
+        })
+    }
+    
+    /// Add every embedded allocation to a new similarity index, in handle order
+    fn rebuild_index(&mut self) {
+        self.index.clear();
+        let mut embedded: Vec<(&MemoryHandle, &Vec<f32>)> = self.allocations.iter()
+            .filter_map(|(handle, allocation)| Some((handle, allocation.embedding.as_ref()?)))
+            .collect();
+        embedded.sort_by_key(|(handle, _)| **handle);
+        
+        for (handle, embedding) in embedded {
+            match self.index.check_vector(embedding) {
+                Ok(()) => self.index.insert(*handle, embedding),
+                Err(reason) => warn!("Not indexing the embedding of allocation {}: {}", handle, reason),
+            }
+        }
+    }
+    
+    /// Publish the current usage as a memory pressure event
+    fn publish_pressure(&self) {
+        if let Some(bus) = &self.event_bus {
+            bus.publish(KernelEvent::MemoryPressure {
+                current_usage: self.current_allocation,
+                max_allocation: self.max_allocation,
+                usage_percentage: self.usage_percentage(),
+            });
+        }
+    }
+}
+
+/// Report a handle that does not refer to a live allocation
+///
+/// # Arguments
+///
+/// * `handle` - The handle
+/// * `expired` - Whether the allocation exists but has expired
+///
+/// # Returns
+///
+/// `MemoryError::Expired` or `MemoryError::NotFound`
+fn missing(handle: MemoryHandle, expired: bool) -> MemoryError {
+    let error = if expired {
+        MemoryError::Expired(handle)
+    } else {
+        MemoryError::NotFound(handle)
+    };
+    error!("{}", error);
+    error
+}
+
+/// Turn an I/O error of the on-disk store into `MemoryError::StoreFailed`
+fn store_failed(e: std::io::Error) -> MemoryError {
+    let error = MemoryError::StoreFailed(e.to_string());
+    error!("{}", error);
+    error
+}
+
+impl Subsystem for MemoryManager {
+    fn name(&self) -> &str {
+        "memory"
+    }
+    
+    fn initialize(&mut self) -> Result<(), SubsystemError> {
+        self.open_store()?;
+        info!("Memory subsystem ready with {} bytes available", self.max_allocation);
+        Ok(())
+    }
+    
+    fn shutdown(&mut self) -> Result<(), SubsystemError> {
+        // Long-term memory stays on disk, with the access statistics of its latest reads
+        if self.store.is_some() {
+            let allocations = self.stored_allocations();
+            if let Some(store) = self.store.as_mut() {
+                if let Err(e) = store.compact(&allocations) {
+                    warn!("Failed to compact long-term memory store: {}", e);
+                    self.persist_reads();
+                }
+            }
+            self.store = None;
+            self.unpersisted_reads.clear();
+        }
+        
+        if !self.allocations.is_empty() {
+            warn!("Releasing {} outstanding memory allocations on shutdown", self.allocations.len());
+        }
+        
+        self.allocations.clear();
+        self.index.clear();
+        self.current_allocation = 0;
+        for category_size in self.category_usage.values_mut() {
+            *category_size = 0;
+        }
+        
+        info!("Memory subsystem shutdown complete");
+        Ok(())
+    }
+    
+    fn health(&self) -> SubsystemHealth {
+        let usage = self.usage_percentage();
+        if usage >= 95.0 {
+            SubsystemHealth::Degraded(format!("Memory usage at {:.1}%", usage))
+        } else {
+            SubsystemHealth::Healthy
+        }
+    }
+    
+    fn attach(&mut self, context: &KernelContext) {
+        self.event_bus = Some(context.events.clone());
+        self.clock = context.clock.clone();
+        self.handles = context.handles.clone();
+        self.last_optimization = self.clock.now();
+    }
+    
+    fn snapshot(&self) -> Result<Option<serde_json::Value>, SubsystemError> {
+        // Long-term memory kept in the on-disk store is not part of snapshots
+        let allocations = self.allocations.iter()
+            .filter(|(_, allocation)| self.store.is_none() || allocation.category != MemoryCategory::LongTerm)
+            .map(|(handle, allocation)| AllocationState {
+                handle: *handle,
+                size: allocation.size,
+                purpose: allocation.purpose.clone(),
+                category: allocation.category,
+                age_ms: age_millis(&self.clock, allocation.allocated_at),
+                idle_ms: age_millis(&self.clock, allocation.last_accessed),
+                access_count: allocation.access_count,
+                data: allocation.data.clone(),
+                embedding: allocation.embedding.clone(),
+                expires_in_ms: allocation.expires_at
+                    .map(|expires_at| {
+                        let left = expires_at.saturating_duration_since(self.clock.now());
+                        u64::try_from(left.as_millis()).unwrap_or(u64::MAX)
+                    }),
+            })
+            .collect();
+        
+        Ok(Some(save_state(&MemoryState { allocations })?))
+    }
+    
+    fn restore(&mut self, state: serde_json::Value) -> Result<(), SubsystemError> {
+        Ok(self.restore_state(load_state(state)?)?)
+    }
+    
+    fn jobs(&self) -> Vec<JobSpec> {
+        vec![
+            JobSpec::new(OPTIMIZE_JOB, Schedule::Interval(OPTIMIZE_INTERVAL)),
+            JobSpec::new(CONSOLIDATE_JOB, Schedule::Interval(CONSOLIDATE_INTERVAL)),
+        ]
+    }
+    
+    fn run_job(&mut self, job: &str) -> Result<(), SubsystemError> {
+        match job {
+            OPTIMIZE_JOB => Ok(self.optimize()?),
+            CONSOLIDATE_JOB => {
+                self.consolidate()?;
+                Ok(())
+            },
+            _ => Err(SubsystemError::msg(format!("Unknown memory job {}", job))),
+        }
+    }
+    
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs;
+    use std::io::Write;
+    
+    /// Create an empty data directory for a test
+    fn create_data_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("royaos-memory-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+    
+    /// Create and initialize a memory manager keeping long-term memory in a data directory
+    fn open_manager(data_dir: &Path) -> Result<MemoryManager, SubsystemError> {
+        let mut manager = MemoryManager::new(10, "balanced").with_data_dir(data_dir); // 10 MB
+        Subsystem::initialize(&mut manager)?;
+        Ok(manager)
+    }
     
     #[test]
     fn test_memory_allocation() {
@@ -344,13 +1774,91 @@ mod tests {
         assert_eq!(manager.current_usage(), 0);
     }
     
+    #[test]
+    fn test_memory_read_write() {
+        let context = KernelContext::with_clock(EventBus::new(4), Clock::simulated(), HandleGenerator::seeded(1));
+        let mut manager = MemoryManager::new(1, "balanced"); // 1 MB
+        manager.attach(&context);
+        let handle = manager.allocate(16, "Scratch", MemoryCategory::Working).unwrap();
+        
+        // Bytes that were never written read as zero
+        assert_eq!(manager.read(handle, 0..4).unwrap(), vec![0; 4]);
+        
+        context.clock.advance(Duration::from_secs(5));
+        manager.write(handle, 4, b"plan").unwrap();
+        assert_eq!(manager.read(handle, 2..10).unwrap(), b"\0\0plan\0\0");
+        assert_eq!(manager.allocations[&handle].access_count, 3);
+        assert_eq!(manager.allocations[&handle].last_accessed, context.clock.now());
+        
+        // Accesses must stay within the allocation
+        assert_eq!(manager.write(handle, 14, b"too long"), Err(MemoryError::OutOfBounds { handle, start: 14, end: 22, size: 16 }));
+        assert_eq!(manager.read(handle, 8..17).unwrap_err().code(), "MEMORY_OUT_OF_BOUNDS");
+        assert_eq!(manager.write(handle, usize::MAX, b"x").unwrap_err().code(), "MEMORY_OUT_OF_BOUNDS");
+        assert_eq!(manager.allocations[&handle].access_count, 3);
+        
+        let missing = Uuid::new_v4();
+        assert_eq!(manager.read(missing, 0..1), Err(MemoryError::NotFound(missing)));
+    }
+    
+    #[test]
+    fn test_memory_values() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Plan {
+            goal: String,
+            steps: Vec<String>,
+        }
+        
+        let mut manager = MemoryManager::new(1, "balanced"); // 1 MB
+        let handle = manager.allocate(128, "Plan", MemoryCategory::ShortTerm).unwrap();
+        assert_eq!(manager.get_value::<Plan>(handle).unwrap_err().code(), "MEMORY_INVALID_VALUE");
+        
+        let plan = Plan { goal: "tidy".to_string(), steps: vec!["sort".to_string(), "file".to_string()] };
+        manager.put_value(handle, &plan).unwrap();
+        assert_eq!(manager.get_value::<Plan>(handle).unwrap(), plan);
+        assert!(manager.get_value::<u64>(handle).is_err());
+        
+        // A value must fit into the allocation behind its header
+        let long = "x".repeat(128);
+        assert_eq!(manager.put_value(handle, &long).unwrap_err().code(), "MEMORY_OUT_OF_BOUNDS");
+        assert_eq!(manager.get_value::<Plan>(handle).unwrap(), plan);
+        
+        let tiny = manager.allocate(4, "Tiny", MemoryCategory::Working).unwrap();
+        assert_eq!(manager.get_value::<u8>(tiny).unwrap_err().code(), "MEMORY_INVALID_VALUE");
+    }
+    
+    #[test]
+    fn test_memory_category_parsing() {
+        assert_eq!(MemoryCategory::from_str("short_term").unwrap(), MemoryCategory::ShortTerm);
+        assert_eq!(MemoryCategory::from_str("LONG_TERM").unwrap(), MemoryCategory::LongTerm);
+        assert_eq!(MemoryCategory::from_str(MemoryCategory::Background.as_str()).unwrap(), MemoryCategory::Background);
+        
+        assert_eq!(MemoryCategory::from_str("heap"), Err(MemoryError::InvalidCategory("heap".to_string())));
+    }
+    
     #[test]
     fn test_memory_allocation_limit() {
         let mut manager = MemoryManager::new(1, "balanced"); // 1 MB
         
         // Try to allocate more than the limit
         let result = manager.allocate(2 * 1024 * 1024, "Too large allocation", MemoryCategory::Working);
-        assert!(result.is_err());
+        assert_eq!(result, Err(MemoryError::LimitExceeded { requested: 2 * 1024 * 1024, available: 1024 * 1024 }));
+        assert_eq!(result.unwrap_err().code(), "MEMORY_LIMIT_EXCEEDED");
+        
+        let missing = Uuid::new_v4();
+        assert_eq!(manager.deallocate(missing), Err(MemoryError::NotFound(missing)));
+    }
+    
+    #[test]
+    fn test_memory_allocation_overflow() {
+        for strategy in ["balanced", "aggressive"] {
+            let mut manager = MemoryManager::new(1, strategy); // 1 MB
+            manager.allocate(1024, "Small allocation", MemoryCategory::Working).unwrap();
+            
+            // A size that overflows the usage is rejected like any other that does not fit
+            let result = manager.allocate(usize::MAX, "Huge allocation", MemoryCategory::Working);
+            assert_eq!(result, Err(MemoryError::LimitExceeded { requested: usize::MAX, available: 1024 * 1024 - 1024 }));
+            assert_eq!(manager.current_usage(), 1024);
+        }
     }
     
     #[test]
@@ -358,12 +1866,12 @@ mod tests {
         let mut manager = MemoryManager::new(100, "balanced"); // 100 MB
         
         // Allocate memory in different categories
-        let handle1 = manager.allocate(1 * 1024 * 1024, "System allocation", MemoryCategory::System).unwrap();
+        manager.allocate(1024 * 1024, "System allocation", MemoryCategory::System).unwrap();
         let handle2 = manager.allocate(2 * 1024 * 1024, "Working allocation", MemoryCategory::Working).unwrap();
-        let handle3 = manager.allocate(3 * 1024 * 1024, "Long-term allocation", MemoryCategory::LongTerm).unwrap();
+        manager.allocate(3 * 1024 * 1024, "Long-term allocation", MemoryCategory::LongTerm).unwrap();
         
         // Check category usage
-        assert_eq!(manager.category_usage(MemoryCategory::System), 1 * 1024 * 1024);
+        assert_eq!(manager.category_usage(MemoryCategory::System), 1024 * 1024);
         assert_eq!(manager.category_usage(MemoryCategory::Working), 2 * 1024 * 1024);
         assert_eq!(manager.category_usage(MemoryCategory::LongTerm), 3 * 1024 * 1024);
         
@@ -375,25 +1883,527 @@ mod tests {
     
     #[test]
     fn test_memory_optimization() {
+        let context = KernelContext::with_clock(EventBus::new(4), Clock::simulated(), HandleGenerator::seeded(1));
         let mut manager = MemoryManager::new(10, "aggressive"); // 10 MB
+        manager.attach(&context);
         
         // Fill up memory with background allocations
+        let mut handles = Vec::new();
         for i in 0..8 {
-            manager.allocate(1 * 1024 * 1024, &format!("Background {}", i), MemoryCategory::Background).unwrap();
+            handles.push(manager.allocate(1024 * 1024, &format!("Background {}", i), MemoryCategory::Background).unwrap());
         }
         
         // Check usage before optimization
         assert_eq!(manager.current_usage(), 8 * 1024 * 1024);
         
-        // Force last_accessed time to be in the past for some allocations
-        // This is a bit of a hack for testing, in real code we wouldn't manipulate private fields directly
+        // Let all allocations go idle past the aggressive threshold, except the ones accessed since
+        context.clock.advance(Duration::from_secs(61));
+        manager.read(handles[0], 0..16).unwrap();
+        manager.write(handles[1], 0, b"recent").unwrap();
         
         // Optimize memory
         let result = manager.optimize();
         assert!(result.is_ok());
+        assert_eq!(manager.current_usage(), 2 * 1024 * 1024);
+        assert_eq!(manager.category_usage(MemoryCategory::Background), 2 * 1024 * 1024);
+        assert!(manager.deallocate(handles[2]).is_err());
         
         // Try to allocate more memory now that we've optimized
         let result = manager.allocate(3 * 1024 * 1024, "New allocation", MemoryCategory::Working);
         assert!(result.is_ok());
     }
-}
+    
+    #[test]
+    fn test_seeded_allocation_handles() {
+        let allocate = || {
+            let context = KernelContext::with_clock(EventBus::new(4), Clock::simulated(), HandleGenerator::seeded(42));
+            let mut manager = MemoryManager::new(10, "balanced");
+            manager.attach(&context);
+            (0..3).map(|_| manager.allocate(1024, "Seeded", MemoryCategory::Working).unwrap()).collect::<Vec<_>>()
+        };
+        
+        assert_eq!(allocate(), allocate());
+    }
+    
+    #[test]
+    fn test_optimization_strategy_parsing() {
+        for name in OPTIMIZATION_STRATEGIES {
+            assert_eq!(OptimizationStrategy::from_str(name).unwrap().as_str(), name);
+        }
+        assert_eq!(OptimizationStrategy::from_str("Aggressive").unwrap(), OptimizationStrategy::Aggressive);
+        
+        assert!(OptimizationStrategy::from_str("reckless").is_err());
+    }
+    
+    #[test]
+    fn test_optimization_strategy_changes() {
+        let mut manager = MemoryManager::new(100, "balanced");
+        
+        assert!(manager.set_optimization_strategy("aggressive").is_ok());
+        assert_eq!(manager.optimization_strategy(), "aggressive");
+        
+        assert!(manager.set_optimization_strategy("reckless").is_err());
+        assert_eq!(manager.optimization_strategy(), "aggressive");
+    }
+    
+    #[test]
+    fn test_subsystem_shutdown_releases_allocations() {
+        let mut manager = MemoryManager::new(10, "balanced"); // 10 MB
+        manager.allocate(1024 * 1024, "Working allocation", MemoryCategory::Working).unwrap();
+        assert!(Subsystem::health(&manager).is_healthy());
+        
+        Subsystem::shutdown(&mut manager).unwrap();
+        assert_eq!(manager.current_usage(), 0);
+        assert_eq!(manager.category_usage(MemoryCategory::Working), 0);
+    }
+    
+    #[test]
+    fn test_memory_pressure_events() {
+        let context = KernelContext::new(EventBus::new(16));
+        let mut events = context.events.subscribe();
+        let mut manager = MemoryManager::new(1, "balanced"); // 1 MB
+        manager.attach(&context);
+        
+        // Below the threshold nothing is published
+        manager.allocate(512 * 1024, "Half", MemoryCategory::Working).unwrap();
+        assert_eq!(events.try_recv(), Ok(None));
+        
+        // Crossing the threshold publishes once
+        manager.allocate(450 * 1024, "Most of the rest", MemoryCategory::Working).unwrap();
+        match events.try_recv() {
+            Ok(Some(KernelEvent::MemoryPressure { current_usage, .. })) => assert_eq!(current_usage, 962 * 1024),
+            other => panic!("Expected a memory pressure event, got {:?}", other),
+        }
+        manager.allocate(1024, "Still under the limit", MemoryCategory::Working).unwrap();
+        assert_eq!(events.try_recv(), Ok(None));
+        
+        // A refused allocation is reported as well
+        assert!(manager.allocate(1024 * 1024, "Too large", MemoryCategory::Working).is_err());
+        assert!(matches!(events.try_recv(), Ok(Some(KernelEvent::MemoryPressure { .. }))));
+    }
+    
+    #[test]
+    fn test_snapshot_restore() {
+        let mut manager = MemoryManager::new(10, "balanced"); // 10 MB
+        let handle = manager.allocate(1024, "Context", MemoryCategory::LongTerm).unwrap();
+        manager.put_value(handle, "The user prefers metric units").unwrap();
+        let state = Subsystem::snapshot(&manager).unwrap().unwrap();
+        
+        let mut restored = MemoryManager::new(10, "balanced");
+        Subsystem::restore(&mut restored, state.clone()).unwrap();
+        assert_eq!(restored.current_usage(), 1024);
+        assert_eq!(restored.category_usage(MemoryCategory::LongTerm), 1024);
+        assert_eq!(restored.allocations[&handle].access_count, 1);
+        assert_eq!(restored.get_value::<String>(handle).unwrap(), "The user prefers metric units");
+        assert!(restored.deallocate(handle).is_ok());
+        
+        // Allocations that no longer fit leave the manager unchanged
+        let mut smaller = MemoryManager::new(0, "balanced");
+        let error = Subsystem::restore(&mut smaller, state).unwrap_err();
+        assert_eq!(error.downcast_ref::<MemoryError>(), Some(&MemoryError::LimitExceeded { requested: 1024, available: 0 }));
+        assert_eq!(smaller.current_usage(), 0);
+    }
+    
+    #[test]
+    fn test_long_term_store() {
+        let data_dir = create_data_dir();
+        let mut manager = open_manager(&data_dir).unwrap();
+        let kept = manager.allocate(1024, "Preferences", MemoryCategory::LongTerm).unwrap();
+        let freed = manager.allocate(512, "Old fact", MemoryCategory::LongTerm).unwrap();
+        manager.allocate(2048, "Scratch", MemoryCategory::Working).unwrap();
+        manager.put_value(kept, "The user prefers metric units").unwrap();
+        manager.deallocate(freed).unwrap();
+        
+        // Long-term memory is kept out of snapshots, so restoring one leaves it alone
+        let state = Subsystem::snapshot(&manager).unwrap().unwrap();
+        assert_eq!(state["allocations"].as_array().unwrap().len(), 1);
+        Subsystem::restore(&mut manager, state).unwrap();
+        assert_eq!(manager.category_usage(MemoryCategory::LongTerm), 1024);
+        assert_eq!(manager.current_usage(), 3072);
+        
+        // Long-term allocations survive a restart with their handles and contents
+        Subsystem::shutdown(&mut manager).unwrap();
+        Subsystem::initialize(&mut manager).unwrap();
+        assert_eq!(manager.current_usage(), 1024);
+        assert_eq!(manager.get_value::<String>(kept).unwrap(), "The user prefers metric units");
+        Subsystem::shutdown(&mut manager).unwrap();
+        
+        let mut reopened = open_manager(&data_dir).unwrap();
+        assert_eq!(reopened.current_usage(), 1024);
+        assert_eq!(reopened.category_usage(MemoryCategory::LongTerm), 1024);
+        assert_eq!(reopened.allocations[&kept].purpose, "Preferences");
+        assert_eq!(reopened.get_value::<String>(kept).unwrap(), "The user prefers metric units");
+        assert!(!reopened.contains(freed));
+        
+        // Only one memory manager at a time can open the store
+        let error = open_manager(&data_dir).unwrap_err();
+        assert_eq!(error.downcast_ref::<MemoryError>().map(|e| e.code()), Some("MEMORY_STORE_FAILED"));
+        
+        // Consolidated memory is kept like any other long-term memory
+        let rehearsed = reopened.allocate(256, "Rehearsed", MemoryCategory::Working).unwrap();
+        for _ in 0..5 {
+            reopened.read(rehearsed, 0..1).unwrap();
+        }
+        assert_eq!(reopened.consolidate().unwrap(), vec![rehearsed]);
+        drop(reopened);
+        let mut reopened = open_manager(&data_dir).unwrap();
+        assert_eq!(reopened.category_of(rehearsed), Some(MemoryCategory::LongTerm));
+        assert_eq!(reopened.category_usage(MemoryCategory::LongTerm), 1024 + 256);
+        
+        reopened.deallocate(kept).unwrap();
+        reopened.deallocate(rehearsed).unwrap();
+        drop(reopened);
+        assert_eq!(open_manager(&data_dir).unwrap().current_usage(), 0);
+        
+        fs::remove_dir_all(&data_dir).unwrap();
+    }
+    
+    #[test]
+    fn test_long_term_store_damaged_log() {
+        let data_dir = create_data_dir();
+        let mut manager = open_manager(&data_dir).unwrap();
+        let handle = manager.allocate(64, "Fact", MemoryCategory::LongTerm).unwrap();
+        manager.write(handle, 0, b"kept").unwrap();
+        drop(manager);
+        
+        // A record cut short by a crash is dropped
+        let log = data_dir.join(LONG_TERM_DIR).join("long_term.log");
+        fs::OpenOptions::new().append(true).open(&log).unwrap().write_all(br#"{"op":"write","handle":"#).unwrap();
+        let mut reopened = open_manager(&data_dir).unwrap();
+        assert_eq!(reopened.read(handle, 0..4).unwrap(), b"kept");
+        reopened.write(handle, 4, b" too").unwrap();
+        drop(reopened);
+        assert_eq!(open_manager(&data_dir).unwrap().read(handle, 0..8).unwrap(), b"kept too");
+        
+        // A complete but damaged last record was not cut short by a crash
+        let contents = fs::read(&log).unwrap();
+        let mut damaged = contents.clone();
+        damaged.extend_from_slice(b"{\"op\":\"free\"}\n");
+        fs::write(&log, damaged).unwrap();
+        let error = open_manager(&data_dir).unwrap_err();
+        assert_eq!(error.downcast_ref::<MemoryError>().map(|e| e.code()), Some("MEMORY_STORE_FAILED"));
+        
+        // A damaged record followed by others cannot be skipped
+        let mut contents = contents;
+        contents.splice(0..0, b"not a record\n".iter().copied());
+        fs::write(&log, contents).unwrap();
+        let error = open_manager(&data_dir).unwrap_err();
+        assert_eq!(error.downcast_ref::<MemoryError>().map(|e| e.code()), Some("MEMORY_STORE_FAILED"));
+        
+        fs::remove_dir_all(&data_dir).unwrap();
+    }
+    
+    #[test]
+    fn test_long_term_store_compaction() {
+        let data_dir = create_data_dir();
+        let mut manager = open_manager(&data_dir).unwrap();
+        let handle = manager.allocate(64, "Fact", MemoryCategory::LongTerm).unwrap();
+        for _ in 0..600 {
+            let transient = manager.allocate(64, "Passing thought", MemoryCategory::LongTerm).unwrap();
+            manager.deallocate(transient).unwrap();
+        }
+        
+        let log = data_dir.join(LONG_TERM_DIR).join("long_term.log");
+        let records = fs::read_to_string(&log).unwrap().lines().count();
+        assert!(records < 1024, "The log should have been compacted, it holds {} records", records);
+        
+        drop(manager);
+        let reopened = open_manager(&data_dir).unwrap();
+        assert!(reopened.contains(handle));
+        assert_eq!(reopened.current_usage(), 64);
+        
+        fs::remove_dir_all(&data_dir).unwrap();
+    }
+    
+    #[test]
+    fn test_long_term_store_access_statistics() {
+        let data_dir = create_data_dir();
+        let mut manager = open_manager(&data_dir).unwrap();
+        let handles: Vec<MemoryHandle> = (0..READ_BATCH_SIZE)
+            .map(|_| manager.allocate(64, "Fact", MemoryCategory::LongTerm).unwrap())
+            .collect();
+        for handle in &handles[1..] {
+            manager.read(*handle, 0..1).unwrap();
+        }
+        manager.read(handles[0], 0..1).unwrap();
+        manager.read(handles[0], 0..1).unwrap();
+        
+        // Reads of a full batch survive a crash, those after it are lost
+        let last = *handles.last().unwrap();
+        manager.read(last, 0..1).unwrap();
+        drop(manager);
+        let mut reopened = open_manager(&data_dir).unwrap();
+        assert_eq!(reopened.allocations[&handles[0]].access_count, 1);
+        assert_eq!(reopened.allocations[&handles[1]].access_count, 1);
+        assert_eq!(reopened.allocations[&last].access_count, 1);
+        
+        // A shutdown keeps the statistics of every read
+        reopened.read(last, 0..1).unwrap();
+        Subsystem::shutdown(&mut reopened).unwrap();
+        drop(reopened);
+        assert_eq!(open_manager(&data_dir).unwrap().allocations[&last].access_count, 2);
+        
+        fs::remove_dir_all(&data_dir).unwrap();
+    }
+    
+    #[test]
+    fn test_memory_consolidation() {
+        let context = KernelContext::with_clock(EventBus::new(16), Clock::simulated(), HandleGenerator::seeded(5));
+        let mut events = context.events.subscribe();
+        let mut manager = MemoryManager::new(1, "balanced"); // 1 MB
+        manager.attach(&context);
+        
+        let access = |manager: &mut MemoryManager, handle: MemoryHandle, times: usize| {
+            for _ in 0..times {
+                manager.read(handle, 0..1).unwrap();
+            }
+        };
+        
+        // Used often, but too long ago
+        let stale = manager.allocate(64, "Stale", MemoryCategory::Working).unwrap();
+        access(&mut manager, stale, 5);
+        context.clock.advance(Duration::from_secs(901));
+        
+        let rehearsed = manager.allocate(128, "Rehearsed", MemoryCategory::Working).unwrap();
+        access(&mut manager, rehearsed, 5);
+        let glanced = manager.allocate(64, "Glanced", MemoryCategory::ShortTerm).unwrap();
+        access(&mut manager, glanced, 4);
+        let background = manager.allocate(64, "Background", MemoryCategory::Background).unwrap();
+        access(&mut manager, background, 10);
+        
+        assert_eq!(manager.consolidate().unwrap(), vec![rehearsed]);
+        assert_eq!(manager.category_of(rehearsed), Some(MemoryCategory::LongTerm));
+        assert_eq!(manager.category_usage(MemoryCategory::LongTerm), 128);
+        assert_eq!(manager.category_usage(MemoryCategory::Working), 64);
+        assert_eq!(manager.read(rehearsed, 0..1).unwrap(), vec![0]);
+        assert_eq!(events.try_recv(), Ok(Some(KernelEvent::MemoryPromoted {
+            handle: rehearsed,
+            from: "working".to_string(),
+            to: "long_term".to_string(),
+        })));
+        assert!(manager.consolidate().unwrap().is_empty());
+        
+        // The aggressive strategy promotes memory after fewer accesses, unless the filter holds it back
+        manager.set_optimization_strategy("aggressive").unwrap();
+        let mut offered = Vec::new();
+        let promoted = manager.consolidate_where(|handle, size| {
+            offered.push((handle, size));
+            false
+        }).unwrap();
+        assert!(promoted.is_empty());
+        assert_eq!(offered, vec![(glanced, 64)]);
+        assert_eq!(events.try_recv(), Ok(None));
+        assert_eq!(manager.consolidate().unwrap(), vec![glanced]);
+        assert_eq!(manager.category_usage(MemoryCategory::ShortTerm), 0);
+        assert_eq!(manager.category_of(stale), Some(MemoryCategory::Working));
+        assert_eq!(manager.category_of(background), Some(MemoryCategory::Background));
+        assert_eq!(manager.current_usage(), 320);
+    }
+    
+    #[test]
+    fn test_memory_decay() {
+        let context = KernelContext::with_clock(EventBus::new(4), Clock::simulated(), HandleGenerator::seeded(6));
+        let mut manager = MemoryManager::new(1, "balanced"); // 1 MB
+        manager.attach(&context);
+        
+        let glanced = manager.allocate(64, "Glanced", MemoryCategory::ShortTerm).unwrap();
+        let rehearsed = manager.allocate(64, "Rehearsed", MemoryCategory::ShortTerm).unwrap();
+        for _ in 0..3 {
+            manager.read(rehearsed, 0..1).unwrap();
+        }
+        let working = manager.allocate(64, "Working", MemoryCategory::Working).unwrap();
+        assert_eq!(manager.retention_strength(glanced).unwrap(), 1.0);
+        
+        // Short-term memory halves every half-life, more slowly the more it was accessed
+        context.clock.advance(Duration::from_secs(600));
+        assert!((manager.retention_strength(glanced).unwrap() - 0.5).abs() < 1e-9);
+        assert!((manager.retention_strength(rehearsed).unwrap() - 0.5f64.powf(0.25)).abs() < 1e-9);
+        assert_eq!(manager.retention_strength(working).unwrap(), 1.0);
+        
+        // Faded memory is forgotten by the next optimization pass
+        context.clock.advance(Duration::from_secs(2400));
+        manager.optimize().unwrap();
+        assert_eq!(manager.retention_strength(glanced).unwrap_err().code(), "MEMORY_NOT_FOUND");
+        assert!(manager.contains(rehearsed));
+        assert_eq!(manager.current_usage(), 128);
+        assert!(manager.forget().unwrap().is_empty());
+        
+        // Decay can be set per category, and a threshold of 0.0 never forgets
+        let decay = DecayModel { half_life: Duration::from_secs(60), forget_below: 0.0 };
+        manager.set_decay(MemoryCategory::Working, Some(decay)).unwrap();
+        assert!(manager.retention_strength(working).unwrap() < 0.01);
+        assert!(manager.forget().unwrap().is_empty());
+        manager.set_decay(MemoryCategory::ShortTerm, None).unwrap();
+        assert_eq!(manager.retention_strength(rehearsed).unwrap(), 1.0);
+        assert_eq!(manager.decay(MemoryCategory::Working), Some(decay));
+        
+        let no_half_life = DecayModel { half_life: Duration::ZERO, forget_below: 0.0 };
+        let error = manager.set_decay(MemoryCategory::Working, Some(no_half_life)).unwrap_err();
+        assert_eq!(error.code(), "MEMORY_INVALID_DECAY");
+        let forgets_everything = DecayModel { half_life: Duration::from_secs(60), forget_below: 1.0 };
+        assert!(manager.set_decay(MemoryCategory::Working, Some(forgets_everything)).is_err());
+        assert_eq!(manager.decay(MemoryCategory::Working), Some(decay));
+    }
+    
+    #[test]
+    fn test_memory_ttl() {
+        let context = KernelContext::with_clock(EventBus::new(4), Clock::simulated(), HandleGenerator::seeded(7));
+        let mut manager = MemoryManager::new(1, "balanced"); // 1 MB
+        manager.attach(&context);
+        
+        let ephemeral = manager.allocate(64, "Ephemeral", MemoryCategory::Working).unwrap();
+        manager.set_ttl(ephemeral, Some(Duration::from_secs(30))).unwrap();
+        let kept = manager.allocate(64, "Kept", MemoryCategory::Working).unwrap();
+        manager.set_ttl(kept, Some(Duration::from_secs(30))).unwrap();
+        manager.set_ttl(kept, None).unwrap();
+        let freed = manager.allocate(64, "Freed", MemoryCategory::Working).unwrap();
+        manager.set_ttl(freed, Some(Duration::from_secs(30))).unwrap();
+        
+        context.clock.advance(Duration::from_secs(29));
+        manager.read(ephemeral, 0..1).unwrap();
+        
+        // Expired memory cannot be accessed, but holds its bytes until it is released
+        context.clock.advance(Duration::from_secs(1));
+        assert_eq!(manager.read(ephemeral, 0..1).unwrap_err(), MemoryError::Expired(ephemeral));
+        assert_eq!(manager.set_ttl(ephemeral, None).unwrap_err().code(), "MEMORY_EXPIRED");
+        assert_eq!(manager.retention_strength(ephemeral).unwrap_err().code(), "MEMORY_EXPIRED");
+        assert!(!manager.contains(ephemeral));
+        assert_eq!(manager.current_usage(), 192);
+        manager.deallocate(freed).unwrap();
+        
+        assert_eq!(manager.forget().unwrap(), vec![ephemeral]);
+        assert_eq!(manager.current_usage(), 64);
+        assert_eq!(manager.read(kept, 0..1).unwrap(), vec![0]);
+        
+        // Snapshots keep the time left to live
+        let expiring = manager.allocate(64, "Expiring", MemoryCategory::Working).unwrap();
+        manager.set_ttl(expiring, Some(Duration::from_secs(60))).unwrap();
+        let state = Subsystem::snapshot(&manager).unwrap().unwrap();
+        let mut restored = MemoryManager::new(1, "balanced");
+        restored.attach(&context);
+        Subsystem::restore(&mut restored, state).unwrap();
+        context.clock.advance(Duration::from_secs(60));
+        assert!(restored.contains(kept));
+        assert_eq!(restored.forget().unwrap(), vec![expiring]);
+        
+        // So does the long-term store
+        let data_dir = create_data_dir();
+        let mut manager = open_manager(&data_dir).unwrap();
+        let expired = manager.allocate(64, "Expired", MemoryCategory::LongTerm).unwrap();
+        manager.set_ttl(expired, Some(Duration::ZERO)).unwrap();
+        let lasting = manager.allocate(64, "Lasting", MemoryCategory::LongTerm).unwrap();
+        manager.set_ttl(lasting, Some(Duration::from_secs(3600))).unwrap();
+        drop(manager);
+        
+        let mut reopened = open_manager(&data_dir).unwrap();
+        assert_eq!(reopened.read(expired, 0..1).unwrap_err().code(), "MEMORY_EXPIRED");
+        assert!(reopened.contains(lasting));
+        assert_eq!(reopened.forget().unwrap(), vec![expired]);
+        drop(reopened);
+        assert_eq!(open_manager(&data_dir).unwrap().current_usage(), 64);
+        
+        fs::remove_dir_all(&data_dir).unwrap();
+    }
+    
+    #[test]
+    fn test_similarity_search() {
+        let mut manager = MemoryManager::new(10, "balanced"); // 10 MB
+        let weather = manager.allocate_with_embedding(64, "Weather", MemoryCategory::LongTerm, Some(vec![1.0, 0.0, 0.0])).unwrap();
+        let rain = manager.allocate_with_embedding(64, "Rain", MemoryCategory::ShortTerm, Some(vec![0.9, 0.1, 0.0])).unwrap();
+        let taxes = manager.allocate(64, "Taxes", MemoryCategory::LongTerm).unwrap();
+        manager.set_embedding(taxes, Some(vec![0.0, 0.0, 2.0])).unwrap();
+        
+        let found = manager.search_similar(&[1.0, 0.05, 0.0], 2, None).unwrap();
+        assert_eq!(found.iter().map(|found| found.handle).collect::<Vec<_>>(), vec![weather, rain]);
+        assert_eq!(found[1].purpose, "Rain");
+        assert!(found[0].similarity > 0.99);
+        
+        // The category filter applies before the limit
+        let found = manager.search_similar(&[1.0, 0.05, 0.0], 2, Some(MemoryCategory::LongTerm)).unwrap();
+        assert_eq!(found.iter().map(|found| found.handle).collect::<Vec<_>>(), vec![weather, taxes]);
+        assert!(found[1].similarity.abs() < 1e-6);
+        
+        // So does a handle filter
+        let found = manager.search_similar_where(&[1.0, 0.05, 0.0], 2, None, |handle| handle != weather).unwrap();
+        assert_eq!(found.iter().map(|found| found.handle).collect::<Vec<_>>(), vec![rain, taxes]);
+        
+        // Released memory and removed embeddings are no longer found
+        manager.deallocate(weather).unwrap();
+        manager.set_embedding(taxes, None).unwrap();
+        let found = manager.search_similar(&[1.0, 0.0, 0.0], 5, None).unwrap();
+        assert_eq!(found.iter().map(|found| found.handle).collect::<Vec<_>>(), vec![rain]);
+        
+        // Every vector needs the dimension of the embeddings in the index
+        assert_eq!(manager.search_similar(&[1.0, 0.0], 1, None).unwrap_err().code(), "MEMORY_INVALID_EMBEDDING");
+        assert_eq!(manager.set_embedding(taxes, Some(vec![0.0; 3])).unwrap_err().code(), "MEMORY_INVALID_EMBEDDING");
+        let result = manager.allocate_with_embedding(64, "Flat", MemoryCategory::Working, Some(vec![f32::NAN, 1.0, 0.0]));
+        assert_eq!(result.unwrap_err().code(), "MEMORY_INVALID_EMBEDDING");
+        assert_eq!(manager.current_usage(), 128);
+    }
+    
+    #[test]
+    fn test_similarity_search_recall() {
+        let context = KernelContext::with_clock(EventBus::new(4), Clock::simulated(), HandleGenerator::seeded(3));
+        let mut manager = MemoryManager::new(10, "balanced"); // 10 MB
+        manager.attach(&context);
+        
+        // Pseudo-random vectors, so the test repeats
+        let mut state = 42u64;
+        let mut vector = || -> Vec<f32> {
+            (0..16).map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                (state >> 40) as f32 / (1u64 << 24) as f32 - 0.5
+            }).collect()
+        };
+        let mut embedded = Vec::new();
+        for i in 0..300 {
+            let embedding = vector();
+            let handle = manager.allocate_with_embedding(16, &format!("Fact {}", i), MemoryCategory::Working, Some(embedding.clone())).unwrap();
+            embedded.push((handle, embedding));
+        }
+        for (handle, _) in embedded.drain(..50) {
+            manager.deallocate(handle).unwrap();
+        }
+        
+        let cosine = |a: &[f32], b: &[f32]| {
+            let dot: f32 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+            dot / (a.iter().map(|a| a * a).sum::<f32>().sqrt() * b.iter().map(|b| b * b).sum::<f32>().sqrt())
+        };
+        let mut hits = 0;
+        for _ in 0..20 {
+            let query = vector();
+            let mut exact: Vec<(MemoryHandle, f32)> = embedded.iter().map(|(handle, embedding)| (*handle, cosine(&query, embedding))).collect();
+            exact.sort_by(|a, b| b.1.total_cmp(&a.1));
+            
+            let found = manager.search_similar(&query, 10, None).unwrap();
+            assert_eq!(found.len(), 10);
+            hits += found.iter().filter(|found| exact[..10].iter().any(|(handle, _)| *handle == found.handle)).count();
+        }
+        assert!(hits >= 180, "Expected a recall of at least 90%, found {} of 200 nearest neighbours", hits);
+    }
+    
+    #[test]
+    fn test_long_term_embeddings() {
+        let data_dir = create_data_dir();
+        let mut manager = open_manager(&data_dir).unwrap();
+        let kept = manager.allocate_with_embedding(64, "Kept", MemoryCategory::LongTerm, Some(vec![1.0, 0.0])).unwrap();
+        let changed = manager.allocate_with_embedding(64, "Changed", MemoryCategory::LongTerm, Some(vec![1.0, 0.0])).unwrap();
+        manager.set_embedding(changed, Some(vec![0.0, 1.0])).unwrap();
+        manager.allocate_with_embedding(64, "Forgotten", MemoryCategory::Working, Some(vec![1.0, 0.1])).unwrap();
+        
+        // Snapshots keep the embeddings of the other categories
+        let state = Subsystem::snapshot(&manager).unwrap().unwrap();
+        let mut restored = MemoryManager::new(10, "balanced");
+        Subsystem::restore(&mut restored, state).unwrap();
+        assert_eq!(restored.search_similar(&[1.0, 0.1], 5, None).unwrap()[0].purpose, "Forgotten");
+        
+        // The index is rebuilt from the embeddings kept with long-term memory
+        Subsystem::shutdown(&mut manager).unwrap();
+        let reopened = open_manager(&data_dir).unwrap();
+        let found = reopened.search_similar(&[1.0, 0.1], 5, None).unwrap();
+        assert_eq!(found.iter().map(|found| found.handle).collect::<Vec<_>>(), vec![kept, changed]);
+        
+        fs::remove_dir_all(&data_dir).unwrap();
+    }
+
+}
\ No newline at end of file
@@ -0,0 +1,472 @@
+//! On-disk store for long-term memory
+//!
+//! Long-term allocations outlive the process. The memory manager records every change
+//! to them in an append-only log below the data directory and reads the log back when
+//! it starts, so long-term handles and their contents stay valid across restarts.
+//!
+//! The log is JSON Lines with one record per change. A record is written and synced to
+//! disk before the change it describes is applied, so an acknowledged change survives a
+//! crash. A record cut short by a crash lacks its terminating newline and can only be
+//! the last line of the log; it is dropped when the log is read. Any other record that
+//! cannot be read makes the log unreadable. Once the log holds many more records than
+//! there are live allocations, it is compacted: the live allocations are written to a
+//! new file, which then replaces the log in a single rename.
+//!
+//! Reads do not change the contents of an allocation, so their access statistics are
+//! written in batches, and a crash loses the statistics of the reads since the last one.
+//!
+//! A store is locked while it is open, so two memory managers, in one process or
+//! in two, never write to the same log.
+
+use log::{info, warn};
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::MemoryHandle;
+
+/// Directory below the data directory that holds the long-term memory store
+pub const LONG_TERM_DIR: &str = "memory";
+
+/// Name of the log file in the store directory
+const LOG_FILE: &str = "long_term.log";
+
+/// Name of the file locked while the store is open
+const LOCK_FILE: &str = "long_term.lock";
+
+/// Name of the file a compaction writes before it replaces the log
+const COMPACTED_FILE: &str = "long_term.log.compacted";
+
+/// Number of records the log holds at least before it is compacted
+const COMPACT_MIN_RECORDS: usize = 1024;
+
+/// A long-term allocation as written to the store
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct StoredAllocation {
+    /// Handle of the allocation
+    pub handle: MemoryHandle,
+    /// Size of allocation in bytes
+    pub size: usize,
+    /// Memory purpose/description
+    pub purpose: String,
+    /// Time of the allocation in milliseconds since the Unix epoch
+    pub allocated_at_ms: u64,
+    /// Time of the last access in milliseconds since the Unix epoch
+    pub last_accessed_ms: u64,
+    /// Access count for usage statistics
+    pub access_count: usize,
+    /// Bytes written to the allocation, hex encoded
+    #[serde(with = "hex::serde")]
+    pub data: Vec<u8>,
+    /// Embedding of the allocation for similarity search
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding: Option<Vec<f32>>,
+    /// Time the allocation expires in milliseconds since the Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at_ms: Option<u64>,
+}
+
+/// One change recorded in the log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub(crate) enum StoreRecord {
+    /// An allocation was created or replaced
+    Put(StoredAllocation),
+    /// Bytes were written to an allocation
+    Write {
+        /// Handle of the allocation
+        handle: MemoryHandle,
+        /// Position of the first written byte
+        offset: usize,
+        /// The written bytes, hex encoded
+        #[serde(with = "hex::serde")]
+        bytes: Vec<u8>,
+        /// Time of the write in milliseconds since the Unix epoch
+        at_ms: u64,
+    },
+    /// The embedding of an allocation was set or removed
+    Embed {
+        /// Handle of the allocation
+        handle: MemoryHandle,
+        /// The new embedding
+        embedding: Option<Vec<f32>>,
+    },
+    /// The time an allocation expires was set or removed
+    Expire {
+        /// Handle of the allocation
+        handle: MemoryHandle,
+        /// The new expiry time in milliseconds since the Unix epoch
+        expires_at_ms: Option<u64>,
+    },
+    /// Reads changed the access statistics of an allocation
+    Access {
+        /// Handle of the allocation
+        handle: MemoryHandle,
+        /// Time of the last access in milliseconds since the Unix epoch
+        last_accessed_ms: u64,
+        /// Access count for usage statistics
+        access_count: usize,
+    },
+    /// An allocation was released
+    Free {
+        /// Handle of the allocation
+        handle: MemoryHandle,
+    },
+}
+
+/// Append-only log of the long-term allocations
+#[derive(Debug)]
+pub(crate) struct LongTermStore {
+    /// Directory holding the log
+    dir: PathBuf,
+    /// The log, opened for appending
+    log: File,
+    /// Length of the log up to its last complete record
+    len: u64,
+    /// Number of records in the log
+    records: usize,
+    /// The lock file, locked until the store is dropped
+    _lock: File,
+}
+
+impl LongTermStore {
+    /// Open the store in a directory and read the allocations it holds
+    ///
+    /// The directory is created if needed, and the store stays locked until it
+    /// is dropped. A record cut short at the end of the log is removed, and the
+    /// log is compacted if it holds records that no longer describe a live
+    /// allocation.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - Directory of the store
+    ///
+    /// # Returns
+    ///
+    /// The open store and the live allocations ordered by handle, or the I/O
+    /// error that stopped it; a damaged complete record is reported as
+    /// `io::ErrorKind::InvalidData`, and a store that is already open as
+    /// `io::ErrorKind::WouldBlock`
+    pub(crate) fn open(dir: &Path) -> io::Result<(Self, Vec<StoredAllocation>)> {
+        fs::create_dir_all(dir)?;
+        let lock = OpenOptions::new().create(true).truncate(false).write(true).open(dir.join(LOCK_FILE))?;
+        lock.try_lock().map_err(|e| match e {
+            TryLockError::WouldBlock => io::Error::new(
+                io::ErrorKind::WouldBlock,
+                format!("{:?} is in use by another memory manager", dir),
+            ),
+            TryLockError::Error(e) => e,
+        })?;
+        
+        let path = dir.join(LOG_FILE);
+        let (allocations, len, records) = match File::open(&path) {
+            Ok(file) => read_log(&path, file)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (BTreeMap::new(), 0, 0),
+            Err(e) => return Err(e),
+        };
+        
+        let log = OpenOptions::new().create(true).append(true).open(&path)?;
+        if log.metadata()?.len() > len {
+            warn!("Dropping incomplete record at the end of {:?}", path);
+            log.set_len(len)?;
+            log.sync_all()?;
+        }
+        
+        let mut store = Self { dir: dir.to_path_buf(), log, len, records, _lock: lock };
+        let allocations: Vec<StoredAllocation> = allocations.into_values().collect();
+        if store.records > allocations.len() {
+            store.compact(&allocations)?;
+        }
+        
+        info!("Opened long-term memory store {:?} with {} allocations", path, allocations.len());
+        Ok((store, allocations))
+    }
+    
+    /// Append a record to the log and sync it to disk
+    ///
+    /// If the record cannot be written completely, the log is cut back to its
+    /// last complete record.
+    ///
+    /// # Arguments
+    ///
+    /// * `record` - The record to append
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` once the record is on disk, or the I/O error that stopped it
+    pub(crate) fn append(&mut self, record: &StoreRecord) -> io::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        
+        let written = self.log.write_all(&line).and_then(|()| self.log.sync_data());
+        if let Err(e) = written {
+            let truncated = self.log.set_len(self.len).and_then(|()| self.log.seek(SeekFrom::Start(self.len)));
+            if let Err(truncate) = truncated {
+                warn!("Failed to cut back long-term memory log after a failed write: {}", truncate);
+            }
+            return Err(e);
+        }
+        
+        self.len += line.len() as u64;
+        self.records += 1;
+        Ok(())
+    }
+    
+    /// Check whether the log should be compacted
+    ///
+    /// # Arguments
+    ///
+    /// * `live` - Number of live long-term allocations
+    ///
+    /// # Returns
+    ///
+    /// `true` if the log holds many more records than live allocations
+    pub(crate) fn needs_compaction(&self, live: usize) -> bool {
+        self.records >= COMPACT_MIN_RECORDS && self.records > 2 * live
+    }
+    
+    /// Replace the log with one record per live allocation
+    ///
+    /// The new log is written and synced next to the old one before it
+    /// replaces it, so a crash leaves either the old or the new log in place.
+    ///
+    /// # Arguments
+    ///
+    /// * `allocations` - The live long-term allocations
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` once the new log is in place, or the I/O error that stopped it
+    pub(crate) fn compact(&mut self, allocations: &[StoredAllocation]) -> io::Result<()> {
+        let compacted = self.dir.join(COMPACTED_FILE);
+        let path = self.dir.join(LOG_FILE);
+        
+        let mut writer = BufWriter::new(File::create(&compacted)?);
+        for allocation in allocations {
+            serde_json::to_writer(&mut writer, &StoreRecord::Put(allocation.clone()))?;
+            writer.write_all(b"\n")?;
+        }
+        let file = writer.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        let len = file.metadata()?.len();
+        fs::rename(&compacted, &path)?;
+        
+        // Make the rename itself durable where the platform allows syncing a directory
+        if let Ok(dir) = File::open(&self.dir) {
+            let _ = dir.sync_all();
+        }
+        
+        // The new file is the log now, and its handle is at its end
+        let previous = self.records;
+        self.log = file;
+        self.len = len;
+        self.records = allocations.len();
+        
+        info!("Compacted long-term memory log from {} to {} records", previous, self.records);
+        Ok(())
+    }
+}
+
+/// Read the log and apply its records in order
+///
+/// # Arguments
+///
+/// * `path` - Path of the log, for messages
+/// * `file` - The log
+///
+/// # Returns
+///
+/// The live allocations by handle, the length of the log up to its last
+/// complete record and the number of records, or the error that stopped it
+fn read_log(path: &Path, file: File) -> io::Result<(BTreeMap<MemoryHandle, StoredAllocation>, u64, usize)> {
+    let mut allocations = BTreeMap::new();
+    let mut reader = BufReader::new(file);
+    let mut line = Vec::new();
+    let (mut len, mut records, mut line_number) = (0u64, 0usize, 0usize);
+    
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 {
+            break;
+        }
+        line_number += 1;
+        
+        // A record is written with its newline, so only a crash leaves one without it
+        if !line.ends_with(b"\n") {
+            break;
+        }
+        let record = serde_json::from_slice::<StoreRecord>(&line).map_err(|e| io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("damaged record on line {} of {:?}: {}", line_number, path, e),
+        ))?;
+        
+        apply(&mut allocations, record);
+        len += read as u64;
+        records += 1;
+    }
+    
+    Ok((allocations, len, records))
+}
+
+/// Apply one record of the log to the live allocations
+fn apply(allocations: &mut BTreeMap<MemoryHandle, StoredAllocation>, record: StoreRecord) {
+    match record {
+        StoreRecord::Put(allocation) => {
+            allocations.insert(allocation.handle, allocation);
+        },
+        StoreRecord::Write { handle, offset, bytes, at_ms } => {
+            let Some(allocation) = allocations.get_mut(&handle) else {
+                warn!("Long-term memory log writes to unknown allocation {}", handle);
+                return;
+            };
+            let end = offset + bytes.len();
+            if allocation.data.len() < end {
+                allocation.data.resize(end, 0);
+            }
+            allocation.data[offset..end].copy_from_slice(&bytes);
+            allocation.last_accessed_ms = at_ms;
+            allocation.access_count += 1;
+        },
+        StoreRecord::Embed { handle, embedding } => match allocations.get_mut(&handle) {
+            Some(allocation) => allocation.embedding = embedding,
+            None => warn!("Long-term memory log embeds unknown allocation {}", handle),
+        },
+        StoreRecord::Expire { handle, expires_at_ms } => match allocations.get_mut(&handle) {
+            Some(allocation) => allocation.expires_at_ms = expires_at_ms,
+            None => warn!("Long-term memory log expires unknown allocation {}", handle),
+        },
+        StoreRecord::Access { handle, last_accessed_ms, access_count } => match allocations.get_mut(&handle) {
+            Some(allocation) => {
+                allocation.last_accessed_ms = last_accessed_ms;
+                allocation.access_count = access_count;
+            },
+            None => warn!("Long-term memory log accesses unknown allocation {}", handle),
+        },
+        StoreRecord::Free { handle } => {
+            allocations.remove(&handle);
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+    
+    /// Create an empty store directory for a test
+    fn create_store_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("royaos-store-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+    
+    /// Build an allocation to store
+    fn allocation(handle: MemoryHandle, data: &[u8]) -> StoredAllocation {
+        StoredAllocation {
+            handle,
+            size: 64,
+            purpose: "Fact".to_string(),
+            allocated_at_ms: 1_000,
+            last_accessed_ms: 1_000,
+            access_count: 0,
+            data: data.to_vec(),
+            embedding: None,
+            expires_at_ms: None,
+        }
+    }
+    
+    #[test]
+    fn test_torn_tail_is_truncated() {
+        let dir = create_store_dir();
+        let handle = Uuid::new_v4();
+        let (mut store, _) = LongTermStore::open(&dir).unwrap();
+        store.append(&StoreRecord::Put(allocation(handle, b"kept"))).unwrap();
+        drop(store);
+        
+        let log = dir.join(LOG_FILE);
+        let complete = fs::read(&log).unwrap();
+        fs::OpenOptions::new().append(true).open(&log).unwrap()
+            .write_all(br#"{"op":"write","handle":"#).unwrap();
+        
+        let (mut store, allocations) = LongTermStore::open(&dir).unwrap();
+        assert_eq!(allocations, vec![allocation(handle, b"kept")]);
+        assert_eq!(fs::read(&log).unwrap(), complete);
+        
+        // Records appended after the truncation follow the last complete one
+        store.append(&StoreRecord::Write { handle, offset: 4, bytes: b" too".to_vec(), at_ms: 2_000 }).unwrap();
+        drop(store);
+        let (_, allocations) = LongTermStore::open(&dir).unwrap();
+        assert_eq!(allocations[0].data, b"kept too");
+        assert_eq!(allocations[0].access_count, 1);
+        
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    
+    #[test]
+    fn test_damaged_last_record() {
+        let dir = create_store_dir();
+        let (mut store, _) = LongTermStore::open(&dir).unwrap();
+        store.append(&StoreRecord::Put(allocation(Uuid::new_v4(), b""))).unwrap();
+        drop(store);
+        
+        // A complete record that cannot be read was not cut short by a crash
+        let log = dir.join(LOG_FILE);
+        fs::OpenOptions::new().append(true).open(&log).unwrap().write_all(b"not a record\n").unwrap();
+        let error = LongTermStore::open(&dir).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(fs::read_to_string(&log).unwrap().ends_with("not a record\n"));
+        
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    
+    #[test]
+    fn test_compaction() {
+        let dir = create_store_dir();
+        let kept = allocation(Uuid::new_v4(), b"kept");
+        let (mut store, _) = LongTermStore::open(&dir).unwrap();
+        store.append(&StoreRecord::Put(kept.clone())).unwrap();
+        for _ in 0..COMPACT_MIN_RECORDS / 2 {
+            let handle = Uuid::new_v4();
+            store.append(&StoreRecord::Put(allocation(handle, b""))).unwrap();
+            store.append(&StoreRecord::Free { handle }).unwrap();
+        }
+        assert!(store.needs_compaction(1));
+        assert!(!store.needs_compaction(COMPACT_MIN_RECORDS));
+        
+        store.compact(std::slice::from_ref(&kept)).unwrap();
+        assert!(!store.needs_compaction(1));
+        assert!(!dir.join(COMPACTED_FILE).exists());
+        let log = dir.join(LOG_FILE);
+        assert_eq!(fs::read_to_string(&log).unwrap().lines().count(), 1);
+        
+        // The store keeps appending to the compacted log
+        store.append(&StoreRecord::Expire { handle: kept.handle, expires_at_ms: Some(5_000) }).unwrap();
+        assert_eq!(fs::read_to_string(&log).unwrap().lines().count(), 2);
+        drop(store);
+        
+        // Opening a log with records of released allocations compacts it
+        let (_, allocations) = LongTermStore::open(&dir).unwrap();
+        assert_eq!(allocations, vec![StoredAllocation { expires_at_ms: Some(5_000), ..kept }]);
+        assert_eq!(fs::read_to_string(&log).unwrap().lines().count(), 1);
+        
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    
+    #[test]
+    fn test_lock_contention() {
+        let dir = create_store_dir();
+        let (store, _) = LongTermStore::open(&dir).unwrap();
+        
+        let error = LongTermStore::open(&dir).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+        
+        // The lock is released with the store
+        drop(store);
+        assert!(LongTermStore::open(&dir).is_ok());
+        
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -12,13 +12,66 @@
 //! - Audit logging for security events
 //! - Threat detection and prevention
 
-use log::{info, error, debug, warn};
-use std::collections::{HashMap, HashSet};
-use std::sync::{Arc, Mutex};
+use log::{info, debug, warn};
+use royaos_common::{
+    load_state, save_state, Clock, ErrorCode, EventBus, HandleGenerator, JobSpec, KernelContext, KernelEvent, Schedule,
+    Subsystem, SubsystemError, SubsystemHealth,
+};
+use std::any::Any;
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::time::Duration;
 use chrono::{DateTime, Utc};
 use serde::{Serialize, Deserialize};
+use thiserror::Error;
 use uuid::Uuid;
 
+/// Operations that can be listed in the allowed operations of the security manager
+pub const OPERATIONS: [&str; 9] = [
+    "file_read",
+    "file_write",
+    "network_access",
+    "tool_execution",
+    "memory_access",
+    "security_query",
+    "task_management",
+    "config_management",
+    "permission_management",
+];
+
+/// Name of the job that rotates the audit log on the kernel timer service
+pub const ROTATE_AUDIT_LOG_JOB: &str = "rotate_audit_log";
+
+/// Time between two runs of the audit log rotation job
+pub const ROTATE_AUDIT_LOG_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Error returned by the security manager
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SecurityError {
+    /// The security level name is not known
+    #[error("Invalid security level: {0}")]
+    InvalidLevel(String),
+    
+    /// The security policy does not allow the operation
+    #[error("Permission denied: {0}")]
+    Denied(Permission),
+}
+
+impl ErrorCode for SecurityError {
+    fn code(&self) -> &'static str {
+        match self {
+            SecurityError::InvalidLevel(_) => "SECURITY_INVALID_LEVEL",
+            SecurityError::Denied(_) => "SECURITY_DENIED",
+        }
+    }
+}
+
+impl From<SecurityError> for SubsystemError {
+    fn from(error: SecurityError) -> Self {
+        SubsystemError::new(error)
+    }
+}
+
 /// Security level for the system
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SecurityLevel {
@@ -32,18 +85,22 @@ pub enum SecurityLevel {
     Maximum,
 }
 
-impl SecurityLevel {
+impl FromStr for SecurityLevel {
+    type Err = SecurityError;
+    
     /// Convert a string to a SecurityLevel
-    pub fn from_str(s: &str) -> Result<Self, String> {
+    fn from_str(s: &str) -> Result<Self, SecurityError> {
         match s.to_lowercase().as_str() {
             "low" => Ok(SecurityLevel::Low),
             "standard" => Ok(SecurityLevel::Standard),
             "high" => Ok(SecurityLevel::High),
             "maximum" => Ok(SecurityLevel::Maximum),
-            _ => Err(format!("Invalid security level: {}", s)),
+            _ => Err(SecurityError::InvalidLevel(s.to_string())),
         }
     }
-    
+}
+
+impl SecurityLevel {
     /// Convert a SecurityLevel to a string
     pub fn as_str(&self) -> &'static str {
         match self {
@@ -55,6 +112,12 @@ impl SecurityLevel {
     }
 }
 
+impl std::fmt::Display for SecurityLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 /// Permission representing an allowed operation
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Permission {
@@ -66,6 +129,12 @@ pub struct Permission {
     pub resource: String,
 }
 
+impl std::fmt::Display for Permission {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {}", self.resource_type, self.operation, self.resource)
+    }
+}
+
 /// Security event for audit logging
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SecurityEvent {
@@ -83,17 +152,40 @@ pub struct SecurityEvent {
     pub allowed: bool,
 }
 
+/// Saved state of the security manager in a kernel snapshot
+#[derive(Debug, Serialize, Deserialize)]
+struct SecurityState {
+    /// Security level when the snapshot was taken
+    security_level: SecurityLevel,
+    /// Operations the permissions were granted from
+    allowed_operations: Vec<String>,
+    /// Allowed permissions, including those added at runtime
+    allowed_permissions: Vec<Permission>,
+    /// Security event log
+    event_log: Vec<SecurityEvent>,
+}
+
 /// Security manager responsible for security-related functionality
 #[derive(Debug)]
 pub struct SecurityManager {
     /// Current security level
     security_level: SecurityLevel,
+    /// Operations the allowed permissions were granted from
+    allowed_operations: Vec<String>,
     /// Allowed permissions
     allowed_permissions: HashSet<Permission>,
     /// Security event log
     event_log: Vec<SecurityEvent>,
     /// Maximum event log size
     max_log_size: usize,
+    /// Age after which events are dropped when the log is rotated, if they expire
+    audit_retention: Option<Duration>,
+    /// Event bus for denial and level change notifications
+    event_bus: Option<EventBus>,
+    /// Clock for event timestamps
+    clock: Clock,
+    /// Generator of event IDs
+    handles: HandleGenerator,
 }
 
 impl SecurityManager {
@@ -106,56 +198,24 @@ impl SecurityManager {
     ///
     /// # Returns
     ///
-    /// A new SecurityManager instance
-    pub fn new(security_level: &str, allowed_operations: Vec<String>) -> Result<Self, String> {
+    /// A new SecurityManager instance, or `SecurityError::InvalidLevel`
+    pub fn new(security_level: &str, allowed_operations: Vec<String>) -> Result<Self, SecurityError> {
         let security_level = SecurityLevel::from_str(security_level)?;
         
         info!("Initializing security manager with {} security level", security_level.as_str());
         
-        let mut allowed_permissions = HashSet::new();
-        
-        // Convert allowed operations to permissions
-        for operation in allowed_operations {
-            match operation.as_str() {
-                "file_read" => {
-                    allowed_permissions.insert(Permission {
-                        resource_type: "file".to_string(),
-                        operation: "read".to_string(),
-                        resource: "*".to_string(),
-                    });
-                },
-                "file_write" => {
-                    allowed_permissions.insert(Permission {
-                        resource_type: "file".to_string(),
-                        operation: "write".to_string(),
-                        resource: "*".to_string(),
-                    });
-                },
-                "network_access" => {
-                    allowed_permissions.insert(Permission {
-                        resource_type: "network".to_string(),
-                        operation: "connect".to_string(),
-                        resource: "*".to_string(),
-                    });
-                },
-                "tool_execution" => {
-                    allowed_permissions.insert(Permission {
-                        resource_type: "tool".to_string(),
-                        operation: "execute".to_string(),
-                        resource: "*".to_string(),
-                    });
-                },
-                _ => {
-                    warn!("Unknown operation: {}", operation);
-                }
-            }
-        }
+        let allowed_permissions = permissions_for_operations(&allowed_operations);
         
         Ok(Self {
             security_level,
+            allowed_operations,
             allowed_permissions,
             event_log: Vec::new(),
             max_log_size: 1000,
+            audit_retention: None,
+            event_bus: None,
+            clock: Clock::system(),
+            handles: HandleGenerator::random(),
         })
     }
     
@@ -164,7 +224,7 @@ impl SecurityManager {
     /// # Returns
     ///
     /// `Ok(())` if initialization is successful, or an error message
-    pub fn initialize(&mut self) -> Result<(), String> {
+    pub fn initialize(&mut self) -> Result<(), SecurityError> {
         info!("Initializing security manager");
         
         // Log initialization event
@@ -240,9 +300,65 @@ impl SecurityManager {
             allowed,
         );
         
+        if !allowed {
+            self.publish(KernelEvent::PermissionDenied {
+                resource_type: resource_type.to_string(),
+                operation: operation.to_string(),
+                resource: resource.to_string(),
+            });
+        }
+        
         allowed
     }
     
+    /// Require that an operation is allowed
+    ///
+    /// This performs the same check as `check_permission`, for callers that
+    /// propagate a denial as an error.
+    ///
+    /// # Arguments
+    ///
+    /// * `resource_type` - Type of resource being accessed
+    /// * `operation` - Operation being performed
+    /// * `resource` - Resource being accessed
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the operation is allowed, or `SecurityError::Denied`
+    pub fn require_permission(&mut self, resource_type: &str, operation: &str, resource: &str) -> Result<(), SecurityError> {
+        if self.check_permission(resource_type, operation, resource) {
+            Ok(())
+        } else {
+            Err(SecurityError::Denied(Permission {
+                resource_type: resource_type.to_string(),
+                operation: operation.to_string(),
+                resource: resource.to_string(),
+            }))
+        }
+    }
+    
+    /// Check whether a permission is in the allowed permissions
+    ///
+    /// Unlike `check_permission`, this looks up the exact permission without
+    /// applying wildcards or the security level, and is not logged.
+    ///
+    /// # Arguments
+    ///
+    /// * `resource_type` - Type of resource being accessed
+    /// * `operation` - Operation being performed
+    /// * `resource` - Resource being accessed
+    ///
+    /// # Returns
+    ///
+    /// `true` if the permission was granted, `false` otherwise
+    pub fn has_permission(&self, resource_type: &str, operation: &str, resource: &str) -> bool {
+        self.allowed_permissions.contains(&Permission {
+            resource_type: resource_type.to_string(),
+            operation: operation.to_string(),
+            resource: resource.to_string(),
+        })
+    }
+    
     /// Add a permission to the allowed permissions
     ///
     /// # Arguments
@@ -254,7 +370,7 @@ impl SecurityManager {
     /// # Returns
     ///
     /// `Ok(())` if successful, or an error message
-    pub fn add_permission(&mut self, resource_type: &str, operation: &str, resource: &str) -> Result<(), String> {
+    pub fn add_permission(&mut self, resource_type: &str, operation: &str, resource: &str) -> Result<(), SecurityError> {
         info!("Adding permission: {} {} {}", resource_type, operation, resource);
         
         let permission = Permission {
@@ -287,7 +403,7 @@ impl SecurityManager {
     /// # Returns
     ///
     /// `Ok(())` if successful, or an error message
-    pub fn remove_permission(&mut self, resource_type: &str, operation: &str, resource: &str) -> Result<(), String> {
+    pub fn remove_permission(&mut self, resource_type: &str, operation: &str, resource: &str) -> Result<(), SecurityError> {
         info!("Removing permission: {} {} {}", resource_type, operation, resource);
         
         let permission = Permission {
@@ -317,13 +433,14 @@ impl SecurityManager {
     ///
     /// # Returns
     ///
-    /// `Ok(())` if successful, or an error message
-    pub fn set_security_level(&mut self, level: &str) -> Result<(), String> {
+    /// `Ok(())` if successful, or `SecurityError::InvalidLevel` if the level is unknown
+    pub fn set_security_level(&mut self, level: &str) -> Result<(), SecurityError> {
         let new_level = SecurityLevel::from_str(level)?;
         
         info!("Changing security level from {} to {}", 
               self.security_level.as_str(), new_level.as_str());
         
+        let previous = self.security_level;
         self.security_level = new_level;
         
         // Log the security level change
@@ -334,9 +451,37 @@ impl SecurityManager {
             true,
         );
         
+        if previous != new_level {
+            self.publish(KernelEvent::SecurityLevelChanged {
+                previous: previous.as_str().to_string(),
+                level: new_level.as_str().to_string(),
+            });
+        }
+        
         Ok(())
     }
     
+    /// Replace the allowed operations
+    ///
+    /// The permissions granted by the previous operations, including permissions
+    /// added with `add_permission`, are replaced by those of the new operations.
+    ///
+    /// # Arguments
+    ///
+    /// * `allowed_operations` - List of allowed operations
+    pub fn set_allowed_operations(&mut self, allowed_operations: Vec<String>) {
+        info!("Changing allowed operations to {:?}", allowed_operations);
+        self.allowed_permissions = permissions_for_operations(&allowed_operations);
+        
+        self.log_event(
+            "security_management",
+            "change_allowed_operations",
+            &format!("Changed allowed operations to {}", allowed_operations.join(", ")),
+            true,
+        );
+        self.allowed_operations = allowed_operations;
+    }
+    
     /// Get the current security level
     ///
     /// # Returns
@@ -365,6 +510,106 @@ impl SecurityManager {
         self.event_log[start..].to_vec()
     }
     
+    /// Set how long events stay in the audit log before `rotate_event_log` drops them
+    ///
+    /// # Arguments
+    ///
+    /// * `retention` - The retention period, or `None` to keep events until the log is full
+    pub fn set_audit_retention(&mut self, retention: Option<Duration>) {
+        self.audit_retention = retention;
+    }
+    
+    /// Get the retention period of audit events
+    ///
+    /// # Returns
+    ///
+    /// The retention period, or `None` if events are kept until the log is full
+    pub fn audit_retention(&self) -> Option<Duration> {
+        self.audit_retention
+    }
+    
+    /// Drop the events older than the retention period from the audit log
+    ///
+    /// The rotation itself is recorded in the log when events were dropped.
+    ///
+    /// # Returns
+    ///
+    /// Number of dropped events; none if no retention period is set
+    pub fn rotate_event_log(&mut self) -> usize {
+        let Some(cutoff) = self.audit_retention
+            .and_then(|retention| chrono::Duration::from_std(retention).ok())
+            .and_then(|retention| self.clock.utc_now().checked_sub_signed(retention)) else {
+            return 0;
+        };
+        
+        // Events are logged in order, so the expired ones are at the front
+        let expired = self.event_log.partition_point(|event| event.timestamp < cutoff);
+        if expired > 0 {
+            self.event_log.drain(0..expired);
+            info!("Rotated {} audit events older than {}", expired, cutoff);
+            self.log_event(
+                "system",
+                "audit_log_rotated",
+                &format!("Dropped {} audit events older than {}", expired, cutoff),
+                true,
+            );
+        }
+        
+        expired
+    }
+    
+    /// Record a security event raised by another component
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - Event source
+    /// * `event_type` - Event type
+    /// * `details` - Event details
+    /// * `allowed` - Whether the event was allowed
+    pub fn record_event(&mut self, source: &str, event_type: &str, details: &str, allowed: bool) {
+        self.log_event(source, event_type, details, allowed);
+    }
+    
+    /// Apply the security state saved in a kernel snapshot
+    ///
+    /// The saved audit log is put in front of the events logged since startup.
+    /// The saved permissions, including those added at runtime, are restored
+    /// only if the configured security level and allowed operations are the
+    /// ones they were granted under; otherwise the configured policy is kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The saved security manager state
+    fn restore_state(&mut self, state: SecurityState) {
+        let mut event_log = state.event_log;
+        let saved_ids: HashSet<Uuid> = event_log.iter().map(|event| event.id).collect();
+        event_log.extend(self.event_log.drain(..).filter(|event| !saved_ids.contains(&event.id)));
+        if event_log.len() > self.max_log_size {
+            let excess = event_log.len() - self.max_log_size;
+            event_log.drain(0..excess);
+        }
+        self.event_log = event_log;
+        
+        if state.security_level == self.security_level && state.allowed_operations == self.allowed_operations {
+            self.allowed_permissions = state.allowed_permissions.into_iter().collect();
+            info!("Restored {} permissions and {} audit events", self.allowed_permissions.len(), self.event_log.len());
+        } else {
+            warn!("Security policy changed since the snapshot (level {}, operations {:?}), keeping the configured permissions",
+                  state.security_level, state.allowed_operations);
+        }
+    }
+    
+    /// Publish an event on the kernel event bus if one is attached
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The event to publish
+    fn publish(&self, event: KernelEvent) {
+        if let Some(bus) = &self.event_bus {
+            bus.publish(event);
+        }
+    }
+    
     /// Log a security event
     ///
     /// # Arguments
@@ -375,8 +620,8 @@ impl SecurityManager {
     /// * `allowed` - Whether the event was allowed
     fn log_event(&mut self, source: &str, event_type: &str, details: &str, allowed: bool) {
         let event = SecurityEvent {
-            id: Uuid::new_v4(),
-            timestamp: Utc::now(),
+            id: self.handles.next_handle(),
+            timestamp: self.clock.utc_now(),
             event_type: event_type.to_string(),
             source: source.to_string(),
             details: details.to_string(),
@@ -445,6 +690,177 @@ impl SecurityManager {
     }
 }
 
+/// Convert allowed operations from the configuration to permissions
+///
+/// # Arguments
+///
+/// * `operations` - Names of the allowed operations
+///
+/// # Returns
+///
+/// The permissions granted by the operations; unknown operations are skipped
+fn permissions_for_operations(operations: &[String]) -> HashSet<Permission> {
+    let mut allowed_permissions = HashSet::new();
+    
+    for operation in operations {
+        match operation.as_str() {
+            "file_read" => {
+                allowed_permissions.insert(Permission {
+                    resource_type: "file".to_string(),
+                    operation: "read".to_string(),
+                    resource: "*".to_string(),
+                });
+            },
+            "file_write" => {
+                allowed_permissions.insert(Permission {
+                    resource_type: "file".to_string(),
+                    operation: "write".to_string(),
+                    resource: "*".to_string(),
+                });
+            },
+            "network_access" => {
+                allowed_permissions.insert(Permission {
+                    resource_type: "network".to_string(),
+                    operation: "connect".to_string(),
+                    resource: "*".to_string(),
+                });
+            },
+            "tool_execution" => {
+                allowed_permissions.insert(Permission {
+                    resource_type: "tool".to_string(),
+                    operation: "execute".to_string(),
+                    resource: "*".to_string(),
+                });
+            },
+            "memory_access" => {
+                for memory_operation in ["allocate", "free", "write", "read", "consolidate"] {
+                    allowed_permissions.insert(Permission {
+                        resource_type: "memory".to_string(),
+                        operation: memory_operation.to_string(),
+                        resource: "*".to_string(),
+                    });
+                }
+            },
+            "security_query" => {
+                allowed_permissions.insert(Permission {
+                    resource_type: "security".to_string(),
+                    operation: "check".to_string(),
+                    resource: "*".to_string(),
+                });
+            },
+            "task_management" => {
+                allowed_permissions.insert(Permission {
+                    resource_type: "task".to_string(),
+                    operation: "cancel".to_string(),
+                    resource: "*".to_string(),
+                });
+                for job_operation in ["schedule", "list", "cancel"] {
+                    allowed_permissions.insert(Permission {
+                        resource_type: "job".to_string(),
+                        operation: job_operation.to_string(),
+                        resource: "*".to_string(),
+                    });
+                }
+            },
+            "config_management" => {
+                for config_operation in ["reload", "snapshot", "quota"] {
+                    allowed_permissions.insert(Permission {
+                        resource_type: "config".to_string(),
+                        operation: config_operation.to_string(),
+                        resource: "*".to_string(),
+                    });
+                }
+            },
+            "permission_management" => {
+                for security_operation in ["grant", "revoke"] {
+                    allowed_permissions.insert(Permission {
+                        resource_type: "security".to_string(),
+                        operation: security_operation.to_string(),
+                        resource: "*".to_string(),
+                    });
+                }
+            },
+            _ => {
+                warn!("Unknown operation: {}", operation);
+            }
+        }
+    }
+    
+    allowed_permissions
+}
+
+impl Subsystem for SecurityManager {
+    fn name(&self) -> &str {
+        "security"
+    }
+    
+    fn initialize(&mut self) -> Result<(), SubsystemError> {
+        Ok(SecurityManager::initialize(self)?)
+    }
+    
+    fn shutdown(&mut self) -> Result<(), SubsystemError> {
+        info!("Shutting down security manager");
+        
+        self.log_event(
+            "system",
+            "security_shutdown",
+            "Security manager shutting down",
+            true,
+        );
+        
+        Ok(())
+    }
+    
+    fn health(&self) -> SubsystemHealth {
+        SubsystemHealth::Healthy
+    }
+    
+    fn attach(&mut self, context: &KernelContext) {
+        self.event_bus = Some(context.events.clone());
+        self.clock = context.clock.clone();
+        self.handles = context.handles.clone();
+    }
+    
+    fn snapshot(&self) -> Result<Option<serde_json::Value>, SubsystemError> {
+        Ok(Some(save_state(&SecurityState {
+            security_level: self.security_level,
+            allowed_operations: self.allowed_operations.clone(),
+            allowed_permissions: self.allowed_permissions.iter().cloned().collect(),
+            event_log: self.event_log.clone(),
+        })?))
+    }
+    
+    fn restore(&mut self, state: serde_json::Value) -> Result<(), SubsystemError> {
+        self.restore_state(load_state(state)?);
+        Ok(())
+    }
+    
+    fn jobs(&self) -> Vec<JobSpec> {
+        match self.audit_retention {
+            Some(_) => vec![JobSpec::new(ROTATE_AUDIT_LOG_JOB, Schedule::Interval(ROTATE_AUDIT_LOG_INTERVAL))],
+            None => Vec::new(),
+        }
+    }
+    
+    fn run_job(&mut self, job: &str) -> Result<(), SubsystemError> {
+        match job {
+            ROTATE_AUDIT_LOG_JOB => {
+                self.rotate_event_log();
+                Ok(())
+            },
+            _ => Err(SubsystemError::msg(format!("Unknown security job {}", job))),
+        }
+    }
+    
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -456,7 +872,7 @@ mod tests {
         assert_eq!(SecurityLevel::from_str("high").unwrap(), SecurityLevel::High);
         assert_eq!(SecurityLevel::from_str("maximum").unwrap(), SecurityLevel::Maximum);
         
-        assert!(SecurityLevel::from_str("invalid").is_err());
+        assert_eq!(SecurityLevel::from_str("invalid"), Err(SecurityError::InvalidLevel("invalid".to_string())));
     }
     
     #[test]
@@ -475,6 +891,37 @@ mod tests {
         // Check denied permissions
         assert!(!manager.check_permission("file", "write", "test.txt"));
         assert!(!manager.check_permission("tool", "execute", "calculator"));
+        
+        // Denials can be propagated as errors
+        assert_eq!(manager.require_permission("file", "read", "test.txt"), Ok(()));
+        let error = manager.require_permission("file", "write", "test.txt").unwrap_err();
+        assert_eq!(error.code(), "SECURITY_DENIED");
+        assert_eq!(error.to_string(), "Permission denied: file write test.txt");
+    }
+    
+    #[test]
+    fn test_kernel_operations() {
+        let allowed_operations = vec![
+            "memory_access".to_string(),
+            "security_query".to_string(),
+            "task_management".to_string(),
+        ];
+        
+        let mut manager = SecurityManager::new("standard", allowed_operations).unwrap();
+        
+        assert!(manager.check_permission("memory", "allocate", "working"));
+        assert!(manager.check_permission("memory", "free", "working"));
+        assert!(manager.check_permission("memory", "write", "working"));
+        assert!(manager.check_permission("memory", "consolidate", "*"));
+        assert!(manager.check_permission("security", "check", "file"));
+        assert!(manager.check_permission("task", "cancel", "reflection"));
+        assert!(manager.check_permission("job", "schedule", "memory_free"));
+        assert!(!manager.check_permission("tool", "execute", "calculator"));
+        
+        manager.record_event("kernel", "syscall_denied", "Denied tool_execute", false);
+        let events = manager.get_recent_events(1);
+        assert_eq!(events[0].event_type, "syscall_denied");
+        assert!(!events[0].allowed);
     }
     
     #[test]
@@ -490,12 +937,20 @@ mod tests {
         
         // Now allowed
         assert!(manager.check_permission("file", "read", "test.txt"));
+        assert!(manager.has_permission("file", "read", "test.txt"));
         
         // Remove permission
         manager.remove_permission("file", "read", "test.txt").unwrap();
         
         // Now denied again
         assert!(!manager.check_permission("file", "read", "test.txt"));
+        assert!(!manager.has_permission("file", "read", "test.txt"));
+        
+        // Wildcards only count for the exact permission
+        manager.set_allowed_operations(vec!["permission_management".to_string()]);
+        assert!(manager.has_permission("security", "grant", "*"));
+        assert!(!manager.has_permission("security", "grant", "file"));
+        assert!(manager.check_permission("security", "revoke", "file"));
     }
     
     #[test]
@@ -516,4 +971,88 @@ mod tests {
         assert!(manager.check_permission("file", "read", "test.txt"));
         assert!(!manager.check_permission("file", "write", "test.txt"));
     }
+    
+    #[test]
+    fn test_allowed_operations_changes() {
+        let mut manager = SecurityManager::new("standard", vec!["file_read".to_string()]).unwrap();
+        manager.add_permission("file", "write", "/tmp/scratch").unwrap();
+        assert!(manager.check_permission("file", "read", "/etc/hosts"));
+        assert!(!manager.check_permission("config", "reload", "*"));
+        
+        manager.set_allowed_operations(vec!["config_management".to_string()]);
+        
+        assert!(manager.check_permission("config", "reload", "*"));
+        assert!(!manager.check_permission("file", "read", "/etc/hosts"));
+        assert!(!manager.check_permission("file", "write", "/tmp/scratch"));
+        
+        let events = manager.get_recent_events(10);
+        assert!(events.iter().any(|event| event.event_type == "change_allowed_operations"));
+    }
+    
+    #[test]
+    fn test_security_events_published() {
+        let context = KernelContext::new(EventBus::new(8));
+        let mut events = context.events.subscribe();
+        
+        let mut manager = SecurityManager::new("standard", vec!["file_read".to_string()]).unwrap();
+        manager.attach(&context);
+        
+        assert!(manager.check_permission("file", "read", "test.txt"));
+        assert!(!manager.check_permission("file", "write", "test.txt"));
+        manager.set_security_level("high").unwrap();
+        
+        assert_eq!(events.try_recv(), Ok(Some(KernelEvent::PermissionDenied {
+            resource_type: "file".to_string(),
+            operation: "write".to_string(),
+            resource: "test.txt".to_string(),
+        })));
+        assert_eq!(events.try_recv(), Ok(Some(KernelEvent::SecurityLevelChanged {
+            previous: "standard".to_string(),
+            level: "high".to_string(),
+        })));
+        assert_eq!(events.try_recv(), Ok(None));
+    }
+    
+    #[test]
+    fn test_audit_log_rotation() {
+        let context = KernelContext::with_clock(EventBus::new(8), Clock::simulated(), HandleGenerator::seeded(1));
+        let mut manager = SecurityManager::new("standard", vec!["file_read".to_string()]).unwrap();
+        manager.attach(&context);
+        manager.initialize().unwrap();
+        assert!(manager.jobs().is_empty());
+        assert_eq!(manager.rotate_event_log(), 0);
+        
+        manager.set_audit_retention(Some(Duration::from_secs(3600)));
+        assert_eq!(manager.jobs()[0].name, ROTATE_AUDIT_LOG_JOB);
+        context.clock.advance(Duration::from_secs(3601));
+        manager.record_event("kernel", "syscall_denied", "Denied tool_execute", false);
+        
+        assert_eq!(manager.rotate_event_log(), 1);
+        let events: Vec<String> = manager.get_recent_events(10).into_iter().map(|event| event.event_type).collect();
+        assert_eq!(events, vec!["syscall_denied", "audit_log_rotated"]);
+        
+        manager.run_job(ROTATE_AUDIT_LOG_JOB).unwrap();
+        assert_eq!(manager.get_recent_events(10).len(), 2);
+    }
+    
+    #[test]
+    fn test_snapshot_restore() {
+        let operations = vec!["file_read".to_string()];
+        let mut manager = SecurityManager::new("standard", operations.clone()).unwrap();
+        manager.add_permission("network", "connect", "example.com").unwrap();
+        let state = Subsystem::snapshot(&manager).unwrap().unwrap();
+        
+        // Under the same policy the runtime permissions and the audit log come back
+        let mut restored = SecurityManager::new("standard", operations).unwrap();
+        Subsystem::restore(&mut restored, state.clone()).unwrap();
+        assert!(restored.check_permission("network", "connect", "example.com"));
+        assert!(restored.get_recent_events(100).iter().any(|event| event.event_type == "add_permission"));
+        
+        // A changed policy keeps the configured permissions
+        let mut changed = SecurityManager::new("standard", vec!["file_write".to_string()]).unwrap();
+        Subsystem::restore(&mut changed, state).unwrap();
+        assert!(!changed.check_permission("network", "connect", "example.com"));
+        assert!(!changed.check_permission("file", "read", "test.txt"));
+        assert!(changed.check_permission("file", "write", "test.txt"));
+    }
 }
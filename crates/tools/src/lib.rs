@@ -11,15 +11,69 @@
 //! - Tool versioning and compatibility checking
 
 use log::{info, error, debug, warn};
+use royaos_common::{
+    load_state, save_state, Clock, ErrorCode, EventBus, HandleGenerator, KernelContext, KernelEvent, Subsystem,
+    SubsystemError, SubsystemHealth,
+};
+use std::any::Any;
 use std::collections::HashMap;
+use std::fmt;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
+use std::time::Instant;
 use serde::{Serialize, Deserialize};
+use thiserror::Error;
 use uuid::Uuid;
 
 /// Tool handle type used to reference registered tools
 pub type ToolHandle = Uuid;
 
+/// Error returned by the tool manager
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ToolError {
+    /// No tool is registered for the handle
+    #[error("No tool found for handle {0}")]
+    NotFound(ToolHandle),
+    
+    /// No tool is registered with the identifier
+    #[error("No tool found with id {0}")]
+    UnknownTool(String),
+    
+    /// The tool is registered but disabled
+    #[error("Tool {0} is disabled")]
+    Disabled(ToolHandle),
+    
+    /// The tool does not provide the capability
+    #[error("Capability {capability} not found for tool {tool}")]
+    CapabilityNotFound {
+        /// Handle of the tool
+        tool: ToolHandle,
+        /// Name of the requested capability
+        capability: String,
+    },
+    
+    /// The parameters do not match what the capability expects
+    #[error("Invalid tool parameters: {0}")]
+    InvalidParameters(String),
+}
+
+impl ErrorCode for ToolError {
+    fn code(&self) -> &'static str {
+        match self {
+            ToolError::NotFound(_) | ToolError::UnknownTool(_) => "TOOL_NOT_FOUND",
+            ToolError::Disabled(_) => "TOOL_DISABLED",
+            ToolError::CapabilityNotFound { .. } => "TOOL_CAPABILITY_NOT_FOUND",
+            ToolError::InvalidParameters(_) => "TOOL_INVALID_PARAMETERS",
+        }
+    }
+}
+
+impl From<ToolError> for SubsystemError {
+    fn from(error: ToolError) -> Self {
+        SubsystemError::new(error)
+    }
+}
+
 /// Tool capability representing a specific function a tool can perform
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolCapability {
@@ -80,12 +134,126 @@ pub struct ToolResult {
     pub execution_time_ms: u64,
 }
 
+/// Runs the capabilities of a tool implemented inside the RoyaOS process
+///
+/// Tools without an executor run the built-in simulated capabilities.
+pub trait ToolExecutor: fmt::Debug + Send + Sync {
+    /// Run a capability of the tool
+    ///
+    /// # Arguments
+    ///
+    /// * `capability` - Name of the capability to execute
+    /// * `params` - Parameters for the capability
+    ///
+    /// # Returns
+    ///
+    /// The result data, or a `ToolError`
+    fn execute(&self, capability: &str, params: &serde_json::Value) -> Result<String, ToolError>;
+}
+
+/// A checked tool execution that runs without access to the tool manager
+///
+/// Created with [`ToolManager::start_execution`]. Once it has run, its result
+/// is recorded with [`ToolManager::finish_execution`], so executions can run
+/// in parallel while the tool manager is only held to start and finish them.
+#[derive(Debug)]
+pub struct ToolExecution {
+    /// Handle to the tool
+    handle: ToolHandle,
+    /// Name of the capability to execute
+    capability: String,
+    /// Executor of the tool, if it has one
+    executor: Option<Arc<dyn ToolExecutor>>,
+    /// Clock for the execution time
+    clock: Clock,
+    /// When the execution started
+    start_time: Instant,
+}
+
+impl ToolExecution {
+    /// Get the handle of the tool being executed
+    ///
+    /// # Returns
+    ///
+    /// Handle to the tool
+    pub fn handle(&self) -> ToolHandle {
+        self.handle
+    }
+    
+    /// Run the capability
+    ///
+    /// # Arguments
+    ///
+    /// * `params` - Parameters for the capability
+    ///
+    /// # Returns
+    ///
+    /// Result of the tool execution, or a `ToolError`
+    pub fn run(&self, params: &str) -> Result<ToolResult, ToolError> {
+        let params: serde_json::Value = serde_json::from_str(params)
+            .map_err(|e| ToolError::InvalidParameters(e.to_string()))?;
+        
+        let outcome = match &self.executor {
+            Some(executor) => Ok(executor.execute(&self.capability, &params)?),
+            None => self.simulate(&params)?,
+        };
+        
+        let execution_time_ms = self.clock.elapsed(self.start_time).as_millis() as u64;
+        Ok(match outcome {
+            Ok(data) => ToolResult {
+                success: true,
+                data: Some(data),
+                error: None,
+                execution_time_ms,
+            },
+            Err(error_msg) => ToolResult {
+                success: false,
+                data: None,
+                error: Some(error_msg),
+                execution_time_ms,
+            },
+        })
+    }
+    
+    /// Run one of the built-in simulated capabilities
+    ///
+    /// # Arguments
+    ///
+    /// * `params` - The parsed parameters
+    ///
+    /// # Returns
+    ///
+    /// The result data or the reason the capability failed, or `ToolError::InvalidParameters`
+    fn simulate(&self, params: &serde_json::Value) -> Result<Result<String, String>, ToolError> {
+        // In a real implementation, we would actually execute the tool
+        // For this example, we'll just simulate execution
+        match self.capability.as_str() {
+            "add" => {
+                let a = number_parameter(params, "a")?;
+                let b = number_parameter(params, "b")?;
+                Ok(Ok((a + b).to_string()))
+            },
+            "subtract" => {
+                let a = number_parameter(params, "a")?;
+                let b = number_parameter(params, "b")?;
+                Ok(Ok((a - b).to_string()))
+            },
+            capability => {
+                let error_msg = format!("Capability {} not implemented", capability);
+                error!("{}", error_msg);
+                Ok(Err(error_msg))
+            },
+        }
+    }
+}
+
 /// Tool instance representing a registered tool
 #[derive(Debug)]
 struct ToolInstance {
     /// Tool metadata
     metadata: ToolMetadata,
     /// Path to the tool executable or library
+    #[allow(dead_code)]
     path: PathBuf,
     /// Whether the tool is currently enabled
     enabled: bool,
@@ -93,6 +261,30 @@ struct ToolInstance {
     execution_count: usize,
     /// Last execution time
     last_execution: Option<std::time::Instant>,
+    /// Executor running the tool's capabilities, if it is implemented in process
+    executor: Option<Arc<dyn ToolExecutor>>,
+}
+
+/// Saved state of the tool manager in a kernel snapshot
+#[derive(Debug, Serialize, Deserialize)]
+struct ToolsState {
+    /// The tools that were registered when the snapshot was taken
+    tools: Vec<ToolState>,
+}
+
+/// Saved state of one registered tool
+#[derive(Debug, Serialize, Deserialize)]
+struct ToolState {
+    /// Handle of the tool
+    handle: ToolHandle,
+    /// Tool metadata
+    metadata: ToolMetadata,
+    /// Path to the tool executable or library
+    path: PathBuf,
+    /// Whether the tool was enabled
+    enabled: bool,
+    /// Number of times the tool had been executed
+    execution_count: usize,
 }
 
 /// Tool manager responsible for managing tools in RoyaOS
@@ -106,6 +298,12 @@ pub struct ToolManager {
     discovery_enabled: bool,
     /// Tool execution history
     execution_history: Vec<(ToolHandle, std::time::Instant, bool)>,
+    /// Event bus for tool execution notifications
+    event_bus: Option<EventBus>,
+    /// Clock for execution times
+    clock: Clock,
+    /// Generator of tool handles
+    handles: HandleGenerator,
 }
 
 impl ToolManager {
@@ -121,7 +319,7 @@ impl ToolManager {
     /// A new ToolManager instance
     pub fn new(tool_dirs: Vec<String>, discovery_enabled: bool) -> Self {
         let tool_dirs = tool_dirs.iter()
-            .map(|dir| PathBuf::from(dir))
+            .map(PathBuf::from)
             .collect();
         
         info!("Initializing tool manager with discovery {}", 
@@ -132,6 +330,9 @@ impl ToolManager {
             tool_dirs,
             discovery_enabled,
             execution_history: Vec::new(),
+            event_bus: None,
+            clock: Clock::system(),
+            handles: HandleGenerator::random(),
         }
     }
     
@@ -142,7 +343,7 @@ impl ToolManager {
     /// # Returns
     ///
     /// `Ok(())` if initialization is successful, or an error message
-    pub fn initialize(&mut self) -> Result<(), String> {
+    pub fn initialize(&mut self) -> Result<(), ToolError> {
         info!("Initializing tool manager");
         
         if self.discovery_enabled {
@@ -158,81 +359,148 @@ impl ToolManager {
     /// # Returns
     ///
     /// `Ok(())` if discovery is successful, or an error message
-    pub fn discover_tools(&mut self) -> Result<(), String> {
+    pub fn discover_tools(&mut self) -> Result<(), ToolError> {
         info!("Discovering tools in {} directories", self.tool_dirs.len());
         
-        for dir in &self.tool_dirs {
-            debug!("Searching for tools in directory: {:?}", dir);
-            
-            if !dir.exists() {
-                warn!("Tool directory does not exist: {:?}", dir);
-                continue;
+        for dir in self.tool_dirs.clone() {
+            self.discover_tools_in(&dir)?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Get the configured tool directories
+    ///
+    /// # Returns
+    ///
+    /// The directories searched for tools
+    pub fn tool_dirs(&self) -> &[PathBuf] {
+        &self.tool_dirs
+    }
+    
+    /// Replace the tool directories
+    ///
+    /// Tools discovered in directories that are no longer configured are
+    /// unregistered, and newly configured directories are searched for tools if
+    /// discovery is enabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `tool_dirs` - The new tool directories
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if successful, or an error message
+    pub fn set_tool_dirs(&mut self, tool_dirs: Vec<String>) -> Result<(), ToolError> {
+        let tool_dirs: Vec<PathBuf> = tool_dirs.iter().map(PathBuf::from).collect();
+        info!("Changing tool directories from {:?} to {:?}", self.tool_dirs, tool_dirs);
+        
+        let removed: Vec<PathBuf> = self.tool_dirs.iter()
+            .filter(|dir| !tool_dirs.contains(dir))
+            .cloned()
+            .collect();
+        let added: Vec<PathBuf> = tool_dirs.iter()
+            .filter(|dir| !self.tool_dirs.contains(dir))
+            .cloned()
+            .collect();
+        
+        let before = self.tools.len();
+        self.tools.retain(|_, tool| !removed.iter().any(|dir| tool.path.starts_with(dir)));
+        if self.tools.len() < before {
+            info!("Unregistered {} tools from removed directories", before - self.tools.len());
+        }
+        
+        self.tool_dirs = tool_dirs;
+        
+        if self.discovery_enabled {
+            for dir in added {
+                self.discover_tools_in(&dir)?;
             }
-            
-            // In a real implementation, we would scan the directory for tool manifests
-            // and load them. For this example, we'll just simulate finding tools.
-            
-            // Simulate finding a calculator tool
-            let calculator_metadata = ToolMetadata {
-                id: "calculator".to_string(),
-                name: "Calculator".to_string(),
-                description: "Performs mathematical calculations".to_string(),
-                version: "1.0.0".to_string(),
-                author: "RoyaOS Team".to_string(),
-                categories: vec!["math".to_string(), "utility".to_string()],
-                capabilities: vec![
-                    ToolCapability {
-                        name: "add".to_string(),
-                        description: "Add two numbers".to_string(),
-                        parameters: vec![
-                            ToolParameter {
-                                name: "a".to_string(),
-                                description: "First number".to_string(),
-                                param_type: "number".to_string(),
-                                required: true,
-                                default_value: None,
-                            },
-                            ToolParameter {
-                                name: "b".to_string(),
-                                description: "Second number".to_string(),
-                                param_type: "number".to_string(),
-                                required: true,
-                                default_value: None,
-                            },
-                        ],
-                        return_type: "number".to_string(),
-                    },
-                    ToolCapability {
-                        name: "subtract".to_string(),
-                        description: "Subtract two numbers".to_string(),
-                        parameters: vec![
-                            ToolParameter {
-                                name: "a".to_string(),
-                                description: "First number".to_string(),
-                                param_type: "number".to_string(),
-                                required: true,
-                                default_value: None,
-                            },
-                            ToolParameter {
-                                name: "b".to_string(),
-                                description: "Second number".to_string(),
-                                param_type: "number".to_string(),
-                                required: true,
-                                default_value: None,
-                            },
-                        ],
-                        return_type: "number".to_string(),
-                    },
-                ],
-            };
-            
-            let calculator_path = dir.join("calculator");
-            self.register_tool(calculator_metadata, calculator_path)?;
         }
         
         Ok(())
     }
     
+    /// Discover tools in one tool directory
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory to search
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if discovery is successful, or an error message
+    fn discover_tools_in(&mut self, dir: &Path) -> Result<(), ToolError> {
+        debug!("Searching for tools in directory: {:?}", dir);
+        
+        if !dir.exists() {
+            warn!("Tool directory does not exist: {:?}", dir);
+            return Ok(());
+        }
+        
+        // In a real implementation, we would scan the directory for tool manifests
+        // and load them. For this example, we'll just simulate finding tools.
+        
+        // Simulate finding a calculator tool
+        let calculator_metadata = ToolMetadata {
+            id: "calculator".to_string(),
+            name: "Calculator".to_string(),
+            description: "Performs mathematical calculations".to_string(),
+            version: "1.0.0".to_string(),
+            author: "RoyaOS Team".to_string(),
+            categories: vec!["math".to_string(), "utility".to_string()],
+            capabilities: vec![
+                ToolCapability {
+                    name: "add".to_string(),
+                    description: "Add two numbers".to_string(),
+                    parameters: vec![
+                        ToolParameter {
+                            name: "a".to_string(),
+                            description: "First number".to_string(),
+                            param_type: "number".to_string(),
+                            required: true,
+                            default_value: None,
+                        },
+                        ToolParameter {
+                            name: "b".to_string(),
+                            description: "Second number".to_string(),
+                            param_type: "number".to_string(),
+                            required: true,
+                            default_value: None,
+                        },
+                    ],
+                    return_type: "number".to_string(),
+                },
+                ToolCapability {
+                    name: "subtract".to_string(),
+                    description: "Subtract two numbers".to_string(),
+                    parameters: vec![
+                        ToolParameter {
+                            name: "a".to_string(),
+                            description: "First number".to_string(),
+                            param_type: "number".to_string(),
+                            required: true,
+                            default_value: None,
+                        },
+                        ToolParameter {
+                            name: "b".to_string(),
+                            description: "Second number".to_string(),
+                            param_type: "number".to_string(),
+                            required: true,
+                            default_value: None,
+                        },
+                    ],
+                    return_type: "number".to_string(),
+                },
+            ],
+        };
+        
+        let calculator_path = dir.join("calculator");
+        self.register_tool(calculator_metadata, calculator_path)?;
+        
+        Ok(())
+    }
+    
     /// Register a tool with the tool manager
     ///
     /// # Arguments
@@ -242,17 +510,18 @@ impl ToolManager {
     ///
     /// # Returns
     ///
-    /// Handle to the registered tool, or an error message
-    pub fn register_tool(&mut self, metadata: ToolMetadata, path: PathBuf) -> Result<ToolHandle, String> {
+    /// Handle to the registered tool, or a `ToolError`
+    pub fn register_tool(&mut self, metadata: ToolMetadata, path: PathBuf) -> Result<ToolHandle, ToolError> {
         info!("Registering tool: {} ({})", metadata.name, metadata.id);
         
-        let handle = Uuid::new_v4();
+        let handle = self.handles.next_handle();
         let tool = ToolInstance {
             metadata,
             path,
             enabled: true,
             execution_count: 0,
             last_execution: None,
+            executor: None,
         };
         
         self.tools.insert(handle, tool);
@@ -261,6 +530,27 @@ impl ToolManager {
         Ok(handle)
     }
     
+    /// Run a tool's capabilities with an in-process executor
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - Handle to the tool
+    /// * `executor` - The executor running the tool's capabilities
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if successful, or `ToolError::NotFound`
+    pub fn set_executor(&mut self, handle: ToolHandle, executor: Arc<dyn ToolExecutor>) -> Result<(), ToolError> {
+        let tool = self.tools.get_mut(&handle).ok_or_else(|| {
+            let error = ToolError::NotFound(handle);
+            error!("{}", error);
+            error
+        })?;
+        
+        tool.executor = Some(executor);
+        Ok(())
+    }
+    
     /// Execute a tool capability
     ///
     /// # Arguments
@@ -271,93 +561,82 @@ impl ToolManager {
     ///
     /// # Returns
     ///
-    /// Result of the tool execution, or an error message
-    pub fn execute_tool(&mut self, handle: ToolHandle, capability: &str, params: &str) -> Result<ToolResult, String> {
-        debug!("Executing tool {} capability {} with params {}", handle, capability, params);
+    /// Result of the tool execution, or a `ToolError`
+    pub fn execute_tool(&mut self, handle: ToolHandle, capability: &str, params: &str) -> Result<ToolResult, ToolError> {
+        let execution = self.start_execution(handle, capability)?;
+        let result = execution.run(params)?;
+        self.finish_execution(&execution, &result);
         
-        let tool = self.tools.get_mut(&handle).ok_or_else(|| {
-            let error_msg = format!("No tool found for handle {}", handle);
-            error!("{}", error_msg);
-            error_msg
+        Ok(result)
+    }
+    
+    /// Check that a tool capability can be executed and start executing it
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - Handle to the tool
+    /// * `capability` - Name of the capability to execute
+    ///
+    /// # Returns
+    ///
+    /// The execution, to be run and then finished, or a `ToolError`
+    pub fn start_execution(&self, handle: ToolHandle, capability: &str) -> Result<ToolExecution, ToolError> {
+        debug!("Executing tool {} capability {}", handle, capability);
+        
+        let tool = self.tools.get(&handle).ok_or_else(|| {
+            let error = ToolError::NotFound(handle);
+            error!("{}", error);
+            error
         })?;
         
         if !tool.enabled {
-            let error_msg = format!("Tool {} is disabled", handle);
-            error!("{}", error_msg);
-            return Err(error_msg);
+            let error = ToolError::Disabled(handle);
+            error!("{}", error);
+            return Err(error);
         }
         
-        // Find the capability
-        let capability_info = tool.metadata.capabilities.iter()
+        // Make sure the capability exists
+        tool.metadata.capabilities.iter()
             .find(|cap| cap.name == capability)
             .ok_or_else(|| {
-                let error_msg = format!("Capability {} not found for tool {}", capability, handle);
-                error!("{}", error_msg);
-                error_msg
+                let error = ToolError::CapabilityNotFound { tool: handle, capability: capability.to_string() };
+                error!("{}", error);
+                error
             })?;
         
-        // In a real implementation, we would actually execute the tool
-        // For this example, we'll just simulate execution
-        
-        let start_time = std::time::Instant::now();
-        
-        // Simulate execution
-        let result = match capability {
-            "add" => {
-                // Parse parameters
-                let params: serde_json::Value = serde_json::from_str(params)
-                    .map_err(|e| format!("Failed to parse parameters: {}", e))?;
-                
-                let a = params["a"].as_f64().ok_or("Parameter 'a' must be a number")?;
-                let b = params["b"].as_f64().ok_or("Parameter 'b' must be a number")?;
-                
-                let sum = a + b;
-                
-                ToolResult {
-                    success: true,
-                    data: Some(sum.to_string()),
-                    error: None,
-                    execution_time_ms: start_time.elapsed().as_millis() as u64,
-                }
-            },
-            "subtract" => {
-                // Parse parameters
-                let params: serde_json::Value = serde_json::from_str(params)
-                    .map_err(|e| format!("Failed to parse parameters: {}", e))?;
-                
-                let a = params["a"].as_f64().ok_or("Parameter 'a' must be a number")?;
-                let b = params["b"].as_f64().ok_or("Parameter 'b' must be a number")?;
-                
-                let difference = a - b;
-                
-                ToolResult {
-                    success: true,
-                    data: Some(difference.to_string()),
-                    error: None,
-                    execution_time_ms: start_time.elapsed().as_millis() as u64,
-                }
-            },
-            _ => {
-                let error_msg = format!("Capability {} not implemented", capability);
-                error!("{}", error_msg);
-                
-                ToolResult {
-                    success: false,
-                    data: None,
-                    error: Some(error_msg),
-                    execution_time_ms: start_time.elapsed().as_millis() as u64,
-                }
-            }
-        };
-        
-        // Update tool statistics
-        tool.execution_count += 1;
-        tool.last_execution = Some(start_time);
+        Ok(ToolExecution {
+            handle,
+            capability: capability.to_string(),
+            executor: tool.executor.clone(),
+            clock: self.clock.clone(),
+            start_time: self.clock.now(),
+        })
+    }
+    
+    /// Record the result of a finished execution
+    ///
+    /// # Arguments
+    ///
+    /// * `execution` - The execution that ran
+    /// * `result` - Its result
+    pub fn finish_execution(&mut self, execution: &ToolExecution, result: &ToolResult) {
+        // The tool may have been unregistered while it ran
+        if let Some(tool) = self.tools.get_mut(&execution.handle) {
+            tool.execution_count += 1;
+            tool.last_execution = Some(execution.start_time);
+        }
         
         // Record in execution history
-        self.execution_history.push((handle, start_time, result.success));
+        self.execution_history.push((execution.handle, execution.start_time, result.success));
         
-        Ok(result)
+        if let Some(bus) = &self.event_bus {
+            bus.publish(KernelEvent::ToolExecuted {
+                tool: execution.handle,
+                capability: execution.capability.clone(),
+                success: result.success,
+                execution_time_ms: result.execution_time_ms,
+            });
+        }
     }
     
     /// Get a list of all registered tools
@@ -371,6 +650,21 @@ impl ToolManager {
             .collect()
     }
     
+    /// Find a registered tool by its identifier
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Identifier of the tool as declared in its metadata
+    ///
+    /// # Returns
+    ///
+    /// Handle to the first tool with a matching identifier, if any
+    pub fn find_tool(&self, id: &str) -> Option<ToolHandle> {
+        self.tools.iter()
+            .find(|(_, tool)| tool.metadata.id == id)
+            .map(|(handle, _)| *handle)
+    }
+    
     /// Get information about a specific tool
     ///
     /// # Arguments
@@ -379,12 +673,12 @@ impl ToolManager {
     ///
     /// # Returns
     ///
-    /// Tool metadata, or an error message
-    pub fn get_tool_info(&self, handle: ToolHandle) -> Result<ToolMetadata, String> {
+    /// Tool metadata, or a `ToolError`
+    pub fn get_tool_info(&self, handle: ToolHandle) -> Result<ToolMetadata, ToolError> {
         let tool = self.tools.get(&handle).ok_or_else(|| {
-            let error_msg = format!("No tool found for handle {}", handle);
-            error!("{}", error_msg);
-            error_msg
+            let error = ToolError::NotFound(handle);
+            error!("{}", error);
+            error
         })?;
         
         Ok(tool.metadata.clone())
@@ -400,11 +694,11 @@ impl ToolManager {
     /// # Returns
     ///
     /// `Ok(())` if successful, or an error message
-    pub fn set_tool_enabled(&mut self, handle: ToolHandle, enabled: bool) -> Result<(), String> {
+    pub fn set_tool_enabled(&mut self, handle: ToolHandle, enabled: bool) -> Result<(), ToolError> {
         let tool = self.tools.get_mut(&handle).ok_or_else(|| {
-            let error_msg = format!("No tool found for handle {}", handle);
-            error!("{}", error_msg);
-            error_msg
+            let error = ToolError::NotFound(handle);
+            error!("{}", error);
+            error
         })?;
         
         tool.enabled = enabled;
@@ -412,6 +706,114 @@ impl ToolManager {
         
         Ok(())
     }
+    
+    /// Apply the tool state saved in a kernel snapshot
+    ///
+    /// A saved tool is matched to the registered tool with the same identifier
+    /// and path, which takes over the saved handle, enabled flag and execution
+    /// count. Saved tools that are no longer registered are dropped, and tools
+    /// registered since the snapshot keep their current state.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The saved tool manager state
+    fn restore_state(&mut self, state: ToolsState) {
+        let mut restored = 0;
+        
+        for saved in state.tools {
+            let current = self.tools.iter()
+                .find(|(_, tool)| tool.metadata.id == saved.metadata.id && tool.path == saved.path)
+                .map(|(handle, _)| *handle);
+            
+            match current.and_then(|handle| self.tools.remove(&handle)) {
+                Some(mut tool) => {
+                    tool.enabled = saved.enabled;
+                    tool.execution_count = saved.execution_count;
+                    self.tools.insert(saved.handle, tool);
+                    restored += 1;
+                },
+                None => warn!("Tool {} at {:?} is no longer registered, dropping its saved state", saved.metadata.id, saved.path),
+            }
+        }
+        
+        info!("Restored the state of {} tools", restored);
+    }
+}
+
+/// Read a numeric capability parameter
+///
+/// # Arguments
+///
+/// * `params` - The parsed parameters
+/// * `name` - Name of the parameter
+///
+/// # Returns
+///
+/// The parameter value, or `ToolError::InvalidParameters` if it is missing or not a number
+fn number_parameter(params: &serde_json::Value, name: &str) -> Result<f64, ToolError> {
+    params[name]
+        .as_f64()
+        .ok_or_else(|| ToolError::InvalidParameters(format!("Parameter '{}' must be a number", name)))
+}
+
+impl Subsystem for ToolManager {
+    fn name(&self) -> &str {
+        "tools"
+    }
+    
+    fn dependencies(&self) -> Vec<String> {
+        vec!["security".to_string()]
+    }
+    
+    fn initialize(&mut self) -> Result<(), SubsystemError> {
+        Ok(ToolManager::initialize(self)?)
+    }
+    
+    fn shutdown(&mut self) -> Result<(), SubsystemError> {
+        info!("Shutting down tool manager, {} executions recorded", self.execution_history.len());
+        Ok(())
+    }
+    
+    fn health(&self) -> SubsystemHealth {
+        if self.discovery_enabled && !self.tool_dirs.iter().any(|dir| dir.exists()) {
+            SubsystemHealth::Degraded("No configured tool directory exists".to_string())
+        } else {
+            SubsystemHealth::Healthy
+        }
+    }
+    
+    fn attach(&mut self, context: &KernelContext) {
+        self.event_bus = Some(context.events.clone());
+        self.clock = context.clock.clone();
+        self.handles = context.handles.clone();
+    }
+    
+    fn snapshot(&self) -> Result<Option<serde_json::Value>, SubsystemError> {
+        let tools = self.tools.iter()
+            .map(|(handle, tool)| ToolState {
+                handle: *handle,
+                metadata: tool.metadata.clone(),
+                path: tool.path.clone(),
+                enabled: tool.enabled,
+                execution_count: tool.execution_count,
+            })
+            .collect();
+        
+        Ok(Some(save_state(&ToolsState { tools })?))
+    }
+    
+    fn restore(&mut self, state: serde_json::Value) -> Result<(), SubsystemError> {
+        self.restore_state(load_state(state)?);
+        Ok(())
+    }
+    
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
 }
 
 #[cfg(test)]
@@ -433,11 +835,14 @@ mod tests {
         };
         
         let path = PathBuf::from("./tools/test-tool");
-        let handle = manager.register_tool(metadata.clone(), path).unwrap();
+        manager.register_tool(metadata.clone(), path).unwrap();
         
         let tools = manager.list_tools();
         assert_eq!(tools.len(), 1);
         assert_eq!(tools[0].1.id, "test-tool");
+        
+        assert_eq!(manager.find_tool("test-tool"), Some(tools[0].0));
+        assert_eq!(manager.find_tool("missing-tool"), None);
     }
     
     #[test]
@@ -480,11 +885,75 @@ mod tests {
         let path = PathBuf::from("./tools/calculator");
         let handle = manager.register_tool(metadata, path).unwrap();
         
+        let context = KernelContext::new(EventBus::new(4));
+        let mut events = context.events.subscribe();
+        manager.attach(&context);
+        
         // Execute the add capability
         let params = r#"{"a": 2, "b": 3}"#;
         let result = manager.execute_tool(handle, "add", params).unwrap();
         
         assert!(result.success);
         assert_eq!(result.data, Some("5".to_string()));
+        
+        // The execution is announced on the event bus
+        match events.try_recv() {
+            Ok(Some(KernelEvent::ToolExecuted { tool, capability, success, .. })) => {
+                assert_eq!(tool, handle);
+                assert_eq!(capability, "add");
+                assert!(success);
+            },
+            other => panic!("Expected a tool execution event, got {:?}", other),
+        }
+        
+        // Failures are reported as typed errors
+        let error = manager.execute_tool(handle, "multiply", params).unwrap_err();
+        assert_eq!(error, ToolError::CapabilityNotFound { tool: handle, capability: "multiply".to_string() });
+        assert_eq!(error.code(), "TOOL_CAPABILITY_NOT_FOUND");
+        assert!(matches!(
+            manager.execute_tool(handle, "add", r#"{"a": "two"}"#),
+            Err(ToolError::InvalidParameters(_))
+        ));
+        
+        // An execution started from the manager runs without it
+        #[derive(Debug)]
+        struct Echo;
+        impl ToolExecutor for Echo {
+            fn execute(&self, capability: &str, params: &serde_json::Value) -> Result<String, ToolError> {
+                Ok(format!("{} {}", capability, params["a"]))
+            }
+        }
+        manager.set_executor(handle, Arc::new(Echo)).unwrap();
+        let execution = manager.start_execution(handle, "add").unwrap();
+        let result = execution.run(params).unwrap();
+        assert_eq!(result.data, Some("add 2".to_string()));
+        manager.finish_execution(&execution, &result);
+        assert_eq!(manager.tools[&handle].execution_count, 2);
+        
+        manager.set_tool_enabled(handle, false).unwrap();
+        assert_eq!(manager.execute_tool(handle, "add", params).unwrap_err(), ToolError::Disabled(handle));
+    }
+    
+    #[test]
+    fn test_tool_dirs_changes() {
+        let old_dir = std::env::temp_dir().join(format!("royaos-tools-{}", Uuid::new_v4()));
+        let new_dir = std::env::temp_dir().join(format!("royaos-tools-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&old_dir).unwrap();
+        std::fs::create_dir_all(&new_dir).unwrap();
+        
+        let mut manager = ToolManager::new(vec![old_dir.to_string_lossy().to_string()], true);
+        manager.initialize().unwrap();
+        assert_eq!(manager.list_tools().len(), 1);
+        
+        manager.set_tool_dirs(vec![new_dir.to_string_lossy().to_string()]).unwrap();
+        
+        let tools = manager.list_tools();
+        assert_eq!(manager.tool_dirs(), std::slice::from_ref(&new_dir));
+        assert_eq!(tools.len(), 1);
+        let handle = manager.find_tool("calculator").unwrap();
+        assert_eq!(manager.tools[&handle].path, new_dir.join("calculator"));
+        
+        std::fs::remove_dir_all(&old_dir).unwrap();
+        std::fs::remove_dir_all(&new_dir).unwrap();
     }
 }
@@ -0,0 +1,111 @@
+//! Command line interface for RoyaOS
+//!
+//! This module parses the command line flags and turns them into the top layer of
+//! the configuration.
+
+use clap::Parser;
+use std::path::PathBuf;
+
+use crate::config::ConfigLoader;
+
+/// Command line flags for RoyaOS
+#[derive(Debug, Parser)]
+#[command(name = "royaos", version, about = "An operating system for Roya AGI")]
+pub struct Cli {
+    /// Configuration file to use instead of config/config.yaml
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    /// Directory for persistent data, overriding system.data_dir
+    #[arg(long, value_name = "DIR")]
+    pub data_dir: Option<String>,
+    /// Log level (error, warn, info, debug or trace), overriding system.log_level
+    #[arg(long, value_name = "LEVEL")]
+    pub log_level: Option<String>,
+    /// Record every system call to a trace file, overriding system.trace_syscalls
+    #[arg(long)]
+    pub trace_syscalls: bool,
+    /// Seed for the handles the kernel assigns, overriding system.seed
+    #[arg(long, value_name = "SEED")]
+    pub seed: Option<u64>,
+    /// Replay a syscall trace into a fresh kernel, report divergences and exit
+    #[arg(long, value_name = "TRACE", conflicts_with = "print_config")]
+    pub replay: Option<PathBuf>,
+    /// Print the effective configuration with the source of every value and exit
+    #[arg(long)]
+    pub print_config: bool,
+}
+
+impl Cli {
+    /// Build the configuration loader for these flags
+    ///
+    /// # Returns
+    ///
+    /// A loader that reads the selected configuration file and applies the flag overrides
+    pub fn config_loader(&self) -> ConfigLoader {
+        let mut loader = ConfigLoader::new();
+        if let Some(path) = &self.config {
+            loader = loader.with_config_file(path);
+        }
+        if let Some(data_dir) = &self.data_dir {
+            loader = loader.with_flag("system.data_dir", "--data-dir", data_dir);
+        }
+        if let Some(log_level) = &self.log_level {
+            loader = loader.with_flag("system.log_level", "--log-level", log_level);
+        }
+        if self.trace_syscalls {
+            loader = loader.with_flag("system.trace_syscalls", "--trace-syscalls", "true");
+        }
+        if let Some(seed) = self.seed {
+            loader = loader.with_flag("system.seed", "--seed", &seed.to_string());
+        }
+        loader
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ConfigSource;
+    use std::fs;
+    use uuid::Uuid;
+    
+    #[test]
+    fn test_flags_override_configuration() {
+        let dir = std::env::temp_dir().join(format!("royaos-cli-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.yaml");
+        fs::write(&path, "system:\n  log_level: debug\n  data_dir: /var/lib/royaos\n").unwrap();
+        
+        let cli = Cli::try_parse_from([
+            "royaos", "--config", path.to_str().unwrap(), "--log-level", "error", "--data-dir", "/srv/royaos", "--trace-syscalls",
+        ]).unwrap();
+        let env = [("ROYAOS_SYSTEM_DATA_DIR".to_string(), "/opt/royaos".to_string())];
+        let loaded = cli.config_loader().load_with_env(env).unwrap();
+        
+        assert_eq!(loaded.config.system.log_level, "error");
+        assert_eq!(loaded.sources["system.log_level"], ConfigSource::Flag("--log-level"));
+        assert_eq!(loaded.config.system.data_dir, "/srv/royaos");
+        assert!(loaded.describe().contains("data_dir: \"/srv/royaos\"  # flag --data-dir"));
+        assert!(loaded.config.system.trace_syscalls);
+        assert!(loaded.describe().contains("trace_syscalls: true  # flag --trace-syscalls"));
+        
+        // Flags that are not given leave the lower layers in place
+        let loaded = Cli::try_parse_from(["royaos", "--config", path.to_str().unwrap()]).unwrap()
+            .config_loader()
+            .load_with_env([])
+            .unwrap();
+        assert_eq!(loaded.config.system.log_level, "debug");
+        assert_eq!(loaded.sources["system.log_level"], ConfigSource::File(path.clone()));
+        
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    
+    #[test]
+    fn test_missing_config_file() {
+        let path = std::env::temp_dir().join(format!("royaos-cli-{}.yaml", Uuid::new_v4()));
+        let cli = Cli::try_parse_from(["royaos", "--config", path.to_str().unwrap()]).unwrap();
+        assert!(matches!(cli.config_loader().load_with_env([]), Err(crate::error::RoyaOsError::ConfigNotFound(missing)) if missing == path));
+        
+        assert!(Cli::try_parse_from(["royaos", "--print-config", "--replay", "trace.jsonl"]).is_err());
+    }
+}
@@ -1,16 +1,29 @@
 //! Configuration module for RoyaOS
 //!
 //! This module handles loading and managing system configuration.
+//!
+//! The configuration is assembled from layers, each overriding the ones before it:
+//! built-in defaults, the system file, the user file, the configuration file,
+//! `ROYAOS_*` environment variables and finally command line flags. Every value
+//! remembers the layer it came from so the effective configuration can be explained.
 
-use serde::{Deserialize, Serialize};
+use royaos_kernel::Quota;
+use royaos_memory::{DecayModel, MemoryCategory, OptimizationStrategy, SHORT_TERM_DECAY};
+use royaos_security::SecurityLevel;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_yaml::{Mapping, Value};
+use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use crate::error::RoyaOsError;
+use crate::validate;
 
 /// Main configuration structure for RoyaOS
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Config {
     /// System configuration
     pub system: SystemConfig,
@@ -20,10 +33,16 @@ pub struct Config {
     pub tools: ToolsConfig,
     /// Security configuration
     pub security: SecurityConfig,
+    /// Interface configuration
+    #[serde(default)]
+    pub interface: InterfaceConfig,
+    /// Default resource quotas of agents
+    #[serde(default)]
+    pub quotas: QuotasConfig,
 }
 
 /// System configuration
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemConfig {
     /// System name
     pub name: String,
@@ -33,19 +52,100 @@ pub struct SystemConfig {
     pub log_level: String,
     /// Data directory
     pub data_dir: String,
+    /// Restart policy the watchdog applies to failed subsystems
+    #[serde(default = "default_restart_policy")]
+    pub restart_policy: String,
+    /// Time allowed for in-flight work to finish on shutdown (in seconds)
+    #[serde(default = "default_shutdown_timeout")]
+    pub shutdown_timeout: u64,
+    /// Save the kernel state to the data directory on shutdown and restore it on startup,
+    /// and keep long-term memory there
+    #[serde(default = "default_persist_state")]
+    pub persist_state: bool,
+    /// Record every system call to a trace file in the data directory
+    #[serde(default)]
+    pub trace_syscalls: bool,
+    /// Seed for the handles the kernel assigns, so runs repeat them (0 for random handles)
+    #[serde(default)]
+    pub seed: u64,
 }
 
 /// Memory configuration
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MemoryConfig {
     /// Maximum memory allocation (in MB)
     pub max_allocation: usize,
     /// Memory optimization strategy
-    pub optimization_strategy: String,
+    #[serde(with = "text")]
+    pub optimization_strategy: OptimizationStrategy,
+    /// How memory of each category fades
+    #[serde(default)]
+    pub decay: CategoryDecayConfig,
+}
+
+/// Decay of one memory category; a half-life of 0 keeps its memory from fading
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DecayConfig {
+    /// Time until the strength of an allocation that was never accessed halves (in seconds)
+    pub half_life: u64,
+    /// Strength below which an allocation is forgotten (in percent, 0 keeps it)
+    pub forget_below: u64,
+}
+
+/// Decay per memory category
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryDecayConfig {
+    /// System memory
+    pub system: DecayConfig,
+    /// Short-term memory
+    pub short_term: DecayConfig,
+    /// Working memory
+    pub working: DecayConfig,
+    /// Long-term memory
+    pub long_term: DecayConfig,
+    /// Background memory
+    pub background: DecayConfig,
+}
+
+impl CategoryDecayConfig {
+    /// Convert the configured decay to the models the memory manager applies
+    ///
+    /// # Returns
+    ///
+    /// The decay model of every category, or `None` for categories whose memory does not fade
+    pub fn to_models(&self) -> Vec<(MemoryCategory, Option<DecayModel>)> {
+        let model = |decay: &DecayConfig| (decay.half_life > 0).then(|| DecayModel {
+            half_life: Duration::from_secs(decay.half_life),
+            forget_below: decay.forget_below as f64 / 100.0,
+        });
+        
+        vec![
+            (MemoryCategory::System, model(&self.system)),
+            (MemoryCategory::ShortTerm, model(&self.short_term)),
+            (MemoryCategory::Working, model(&self.working)),
+            (MemoryCategory::LongTerm, model(&self.long_term)),
+            (MemoryCategory::Background, model(&self.background)),
+        ]
+    }
+}
+
+impl Default for CategoryDecayConfig {
+    fn default() -> Self {
+        Self {
+            system: DecayConfig::default(),
+            short_term: DecayConfig {
+                half_life: SHORT_TERM_DECAY.half_life.as_secs(),
+                forget_below: (SHORT_TERM_DECAY.forget_below * 100.0).round() as u64,
+            },
+            working: DecayConfig::default(),
+            long_term: DecayConfig::default(),
+            background: DecayConfig::default(),
+        }
+    }
 }
 
 /// Tools configuration
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolsConfig {
     /// Tool discovery enabled
     pub discovery_enabled: bool,
@@ -54,30 +154,721 @@ pub struct ToolsConfig {
 }
 
 /// Security configuration
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SecurityConfig {
     /// Security level
-    pub security_level: String,
+    #[serde(with = "text")]
+    pub security_level: SecurityLevel,
     /// Allowed operations
     pub allowed_operations: Vec<String>,
+    /// Age after which audit events are dropped from the audit log (in seconds, 0 keeps them)
+    #[serde(default = "default_audit_retention")]
+    pub audit_retention: u64,
+}
+
+/// Interface configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InterfaceConfig {
+    /// Address the interface listener accepts AGI connections on
+    pub listen_addr: String,
+    /// Time without requests after which a session is closed (in seconds, 0 keeps sessions open)
+    #[serde(default = "default_idle_timeout")]
+    pub idle_timeout: u64,
+}
+
+/// Default resource quotas of agents; a limit of 0 means unlimited
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QuotasConfig {
+    /// Reject system calls from connections that have not identified as an agent
+    pub require_agent: bool,
+    /// Memory per category (in MB)
+    pub memory: CategoryQuotasConfig,
+    /// Concurrent tool executions
+    pub concurrent_tool_executions: usize,
+    /// System calls per second
+    pub syscalls_per_second: u32,
+    /// Audit-log entries per minute
+    pub audit_events_per_minute: u32,
+}
+
+/// Memory quota per category (in MB); a limit of 0 means unlimited
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CategoryQuotasConfig {
+    /// System memory
+    pub system: usize,
+    /// Short-term memory
+    pub short_term: usize,
+    /// Working memory
+    pub working: usize,
+    /// Long-term memory
+    pub long_term: usize,
+    /// Background memory
+    pub background: usize,
+}
+
+impl QuotasConfig {
+    /// Convert the configured limits to the quota the kernel enforces
+    ///
+    /// # Returns
+    ///
+    /// The quota, with limits of 0 left unset
+    pub fn to_quota(&self) -> Quota {
+        let limit = |value: usize| (value > 0).then_some(value);
+        let memory = [
+            (MemoryCategory::System, self.memory.system),
+            (MemoryCategory::ShortTerm, self.memory.short_term),
+            (MemoryCategory::Working, self.memory.working),
+            (MemoryCategory::LongTerm, self.memory.long_term),
+            (MemoryCategory::Background, self.memory.background),
+        ];
+        
+        Quota {
+            memory_bytes: memory.into_iter()
+                .filter_map(|(category, mb)| limit(mb).map(|mb| (category, mb * 1024 * 1024)))
+                .collect(),
+            concurrent_tool_executions: limit(self.concurrent_tool_executions),
+            syscalls_per_second: (self.syscalls_per_second > 0).then_some(self.syscalls_per_second),
+            audit_events_per_minute: (self.audit_events_per_minute > 0).then_some(self.audit_events_per_minute),
+        }
+    }
+}
+
+impl Default for SystemConfig {
+    fn default() -> Self {
+        Self {
+            name: "RoyaOS".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            log_level: "info".to_string(),
+            data_dir: "./data".to_string(),
+            restart_policy: default_restart_policy(),
+            shutdown_timeout: default_shutdown_timeout(),
+            persist_state: default_persist_state(),
+            trace_syscalls: false,
+            seed: 0,
+        }
+    }
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            max_allocation: 1024,
+            optimization_strategy: OptimizationStrategy::Balanced,
+            decay: CategoryDecayConfig::default(),
+        }
+    }
+}
+
+impl Default for ToolsConfig {
+    fn default() -> Self {
+        Self {
+            discovery_enabled: true,
+            tool_dirs: vec!["./tools".to_string()],
+        }
+    }
+}
+
+impl Default for SecurityConfig {
+    fn default() -> Self {
+        Self {
+            security_level: SecurityLevel::Standard,
+            allowed_operations: vec![
+                "tool_execution".to_string(),
+                "memory_access".to_string(),
+                "security_query".to_string(),
+                "task_management".to_string(),
+            ],
+            audit_retention: default_audit_retention(),
+        }
+    }
+}
+
+impl Default for InterfaceConfig {
+    fn default() -> Self {
+        Self {
+            listen_addr: "127.0.0.1:8000".to_string(),
+            idle_timeout: default_idle_timeout(),
+        }
+    }
+}
+
+fn default_restart_policy() -> String {
+    "on-failure".to_string()
+}
+
+fn default_shutdown_timeout() -> u64 {
+    10
+}
+
+fn default_persist_state() -> bool {
+    true
+}
+
+fn default_audit_retention() -> u64 {
+    7 * 24 * 3600
+}
+
+fn default_idle_timeout() -> u64 {
+    1800
 }
 
-/// Load configuration from file
-pub fn load_config() -> Result<Config, RoyaOsError> {
-    let config_path = Path::new("config/config.yaml");
+/// Convert a number of seconds where 0 turns the feature off to a duration
+///
+/// # Arguments
+///
+/// * `secs` - The number of seconds
+///
+/// # Returns
+///
+/// The duration, or `None` for 0
+pub fn optional_secs(secs: u64) -> Option<Duration> {
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+/// Serialization of typed settings as the names used in configuration files
+mod text {
+    use super::*;
     
-    // Check if config file exists
-    if !config_path.exists() {
-        return Err(RoyaOsError::ConfigNotFound);
+    pub fn serialize<T: fmt::Display, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
     }
     
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: std::str::FromStr,
+        T::Err: fmt::Display,
+        D: Deserializer<'de>,
+    {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Default location of the configuration file, relative to the working directory
+pub const DEFAULT_CONFIG_PATH: &str = "config/config.yaml";
+
+/// Location of the system-wide configuration file
+pub const SYSTEM_CONFIG_PATH: &str = "/etc/royaos/config.yaml";
+
+/// Prefix of the environment variables that override configuration values
+pub const ENV_PREFIX: &str = "ROYAOS_";
+
+/// Layer a configuration value was taken from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// Built-in default
+    Default,
+    /// A configuration file
+    File(PathBuf),
+    /// An environment variable
+    Env(String),
+    /// A command line flag
+    Flag(&'static str),
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::File(path) => write!(f, "file {}", path.display()),
+            ConfigSource::Env(name) => write!(f, "env {}", name),
+            ConfigSource::Flag(flag) => write!(f, "flag {}", flag),
+        }
+    }
+}
+
+/// Effective configuration together with the source of every value
+#[derive(Debug, Clone)]
+pub struct LoadedConfig {
+    /// The merged configuration
+    pub config: Config,
+    /// The merged configuration as a YAML tree, in file order
+    pub values: Value,
+    /// Layer each setting was taken from, by dotted path
+    pub sources: HashMap<String, ConfigSource>,
+    /// Problems that did not stop loading, such as unknown environment variables
+    pub warnings: Vec<String>,
+}
+
+impl LoadedConfig {
+    /// Render the effective configuration as YAML, annotating every value with its source
+    ///
+    /// # Returns
+    ///
+    /// The annotated configuration
+    pub fn describe(&self) -> String {
+        let mut output = String::new();
+        self.describe_value("", &self.values, 0, &mut output);
+        output
+    }
+    
+    /// Append the annotated lines for one configuration subtree
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - Dotted path of the subtree
+    /// * `value` - The subtree
+    /// * `depth` - Indentation depth of the subtree
+    /// * `output` - The text the lines are appended to
+    fn describe_value(&self, prefix: &str, value: &Value, depth: usize, output: &mut String) {
+        let Value::Mapping(fields) = value else {
+            return;
+        };
+        
+        for (key, value) in fields {
+            let key = key_name(key);
+            let setting = join_path(prefix, &key);
+            let indent = "  ".repeat(depth);
+            
+            if let Value::Mapping(_) = value {
+                output.push_str(&format!("{}{}:\n", indent, key));
+                self.describe_value(&setting, value, depth + 1, output);
+            } else {
+                // JSON is valid YAML flow style and keeps lists on one line
+                let rendered = serde_json::to_string(value).unwrap_or_default();
+                let source = self.sources.get(&setting).cloned().unwrap_or(ConfigSource::Default);
+                output.push_str(&format!("{}{}: {}  # {}\n", indent, key, rendered, source));
+            }
+        }
+    }
+}
+
+/// Loads the configuration from all layers
+///
+/// Files that do not exist are skipped, except a configuration file named
+/// explicitly with [`ConfigLoader::with_config_file`].
+#[derive(Debug, Clone)]
+pub struct ConfigLoader {
+    /// System-wide configuration file
+    system_file: PathBuf,
+    /// Configuration file of the current user
+    user_file: Option<PathBuf>,
+    /// Configuration file applied after the system and user files
+    config_file: PathBuf,
+    /// Whether the configuration file was named explicitly and must exist
+    config_file_required: bool,
+    /// Values set on the command line, by dotted path
+    flags: Vec<(&'static str, &'static str, String)>,
+}
+
+impl Default for ConfigLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConfigLoader {
+    /// Create a loader using the standard file locations
+    ///
+    /// # Returns
+    ///
+    /// A new ConfigLoader instance
+    pub fn new() -> Self {
+        Self {
+            system_file: PathBuf::from(SYSTEM_CONFIG_PATH),
+            user_file: user_config_path(),
+            config_file: PathBuf::from(DEFAULT_CONFIG_PATH),
+            config_file_required: false,
+            flags: Vec::new(),
+        }
+    }
+    
+    /// Use the given configuration file instead of `config/config.yaml`
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the configuration file, which must exist
+    ///
+    /// # Returns
+    ///
+    /// The updated loader
+    pub fn with_config_file(mut self, path: &Path) -> Self {
+        self.config_file = path.to_path_buf();
+        self.config_file_required = true;
+        self
+    }
+    
+    /// Override a setting from a command line flag
+    ///
+    /// # Arguments
+    ///
+    /// * `setting` - Dotted path of the setting
+    /// * `flag` - Name of the flag, for example `--data-dir`
+    /// * `value` - The value given on the command line
+    ///
+    /// # Returns
+    ///
+    /// The updated loader
+    pub fn with_flag(mut self, setting: &'static str, flag: &'static str, value: &str) -> Self {
+        self.flags.push((setting, flag, value.to_string()));
+        self
+    }
+    
+    /// Load the configuration from all layers
+    ///
+    /// # Returns
+    ///
+    /// The effective configuration, or an error if a file cannot be read or parsed,
+    /// the named configuration file is missing, an override has an invalid value, or
+    /// the merged configuration fails validation
+    pub fn load(&self) -> Result<LoadedConfig, RoyaOsError> {
+        self.load_with_env(std::env::vars())
+    }
+    
+    /// Load the configuration from all layers with the given environment
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The environment variables to read overrides from
+    ///
+    /// # Returns
+    ///
+    /// The effective configuration, or an error as for [`ConfigLoader::load`]
+    pub fn load_with_env(&self, env: impl IntoIterator<Item = (String, String)>) -> Result<LoadedConfig, RoyaOsError> {
+        let mut values = serde_yaml::to_value(Config::default())?;
+        let mut sources = HashMap::new();
+        let mut warnings = Vec::new();
+        
+        // File layers
+        let mut files = vec![self.system_file.clone()];
+        files.extend(self.user_file.clone());
+        files.push(self.config_file.clone());
+        for path in files {
+            if !path.exists() {
+                if self.config_file_required && path == self.config_file {
+                    return Err(RoyaOsError::ConfigNotFound(path));
+                }
+                continue;
+            }
+            
+            let layer = read_layer(&path)?;
+            merge("", &mut values, layer, &ConfigSource::File(path), &mut sources);
+        }
+        
+        // Environment layer, in a stable order
+        let mut env: Vec<(String, String)> = env.into_iter().filter(|(name, _)| name.starts_with(ENV_PREFIX)).collect();
+        env.sort();
+        let settings = env_settings(&values);
+        for (name, raw) in env {
+            match settings.get(&name) {
+                Some(setting) => {
+                    set_setting(&mut values, setting, &raw, ConfigSource::Env(name.clone()), &mut sources)?;
+                },
+                None => warnings.push(format!("Ignoring unknown configuration variable {}", name)),
+            }
+        }
+        
+        // Flag layer
+        for (setting, flag, raw) in &self.flags {
+            set_setting(&mut values, setting, raw, ConfigSource::Flag(flag), &mut sources)?;
+        }
+        
+        match validate::validate(&values, &sources) {
+            Ok(validation_warnings) => warnings.extend(validation_warnings),
+            Err(problems) => return Err(RoyaOsError::InvalidConfig(problems)),
+        }
+        
+        let config: Config = serde_yaml::from_value(values.clone())?;
+        
+        Ok(LoadedConfig { config, values, sources, warnings })
+    }
+}
+
+#[cfg(test)]
+impl ConfigLoader {
+    /// Create a loader that reads only the given configuration file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the configuration file, which must exist
+    ///
+    /// # Returns
+    ///
+    /// A new ConfigLoader instance that skips the system and user files
+    pub fn isolated(path: &Path) -> Self {
+        Self {
+            system_file: PathBuf::new(),
+            user_file: None,
+            config_file: path.to_path_buf(),
+            config_file_required: true,
+            flags: Vec::new(),
+        }
+    }
+}
+
+/// Get the configuration file of the current user
+///
+/// # Returns
+///
+/// `$XDG_CONFIG_HOME/royaos/config.yaml`, falling back to `~/.config`, or `None`
+/// if neither variable is set
+fn user_config_path() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME").filter(|dir| !dir.is_empty())?).join(".config"),
+    };
+    Some(base.join("royaos").join("config.yaml"))
+}
+
+/// Read one configuration file layer
+///
+/// # Arguments
+///
+/// * `config_path` - Path of the configuration file
+///
+/// # Returns
+///
+/// The parsed YAML tree, or an error if the file cannot be read or parsed
+fn read_layer(config_path: &Path) -> Result<Value, RoyaOsError> {
     // Open and read config file
     let mut file = File::open(config_path)?;
     let mut contents = String::new();
     file.read_to_string(&mut contents)?;
     
-    // Parse YAML
-    let config: Config = serde_yaml::from_str(&contents)?;
+    // Parse YAML; an empty file overrides nothing
+    let layer: Value = serde_yaml::from_str(&contents)?;
+    Ok(if layer.is_null() { Value::Mapping(Mapping::new()) } else { layer })
+}
+
+/// Merge a layer into the configuration tree
+///
+/// Sections are merged field by field; any other value, including lists, replaces
+/// the value below it as a whole.
+///
+/// # Arguments
+///
+/// * `prefix` - Dotted path of the values
+/// * `base` - The tree the layer is merged into
+/// * `layer` - The overriding values
+/// * `source` - Where the layer comes from
+/// * `sources` - Source of every setting, updated for the overridden ones
+fn merge(prefix: &str, base: &mut Value, layer: Value, source: &ConfigSource, sources: &mut HashMap<String, ConfigSource>) {
+    match (base, layer) {
+        (Value::Mapping(base_fields), Value::Mapping(layer_fields)) => {
+            for (key, value) in layer_fields {
+                let setting = join_path(prefix, &key_name(&key));
+                match base_fields.get_mut(&key) {
+                    Some(existing) => merge(&setting, existing, value, source, sources),
+                    None => {
+                        mark_sources(&setting, &value, source, sources);
+                        base_fields.insert(key, value);
+                    },
+                }
+            }
+        },
+        (base, layer) => {
+            mark_sources(prefix, &layer, source, sources);
+            *base = layer;
+        },
+    }
+}
+
+/// Record the source of every setting in a subtree
+fn mark_sources(prefix: &str, value: &Value, source: &ConfigSource, sources: &mut HashMap<String, ConfigSource>) {
+    match value {
+        Value::Mapping(fields) => {
+            for (key, value) in fields {
+                mark_sources(&join_path(prefix, &key_name(key)), value, source, sources);
+            }
+        },
+        _ => {
+            sources.insert(prefix.to_string(), source.clone());
+        },
+    }
+}
+
+/// Map the environment variable of every setting to its dotted path
+///
+/// `memory.max_allocation` is overridden by `ROYAOS_MEMORY_MAX_ALLOCATION`.
+fn env_settings(values: &Value) -> HashMap<String, String> {
+    let mut settings = HashMap::new();
+    let mut sources = HashMap::new();
+    mark_sources("", values, &ConfigSource::Default, &mut sources);
+    for setting in sources.into_keys() {
+        let name = format!("{}{}", ENV_PREFIX, setting.replace('.', "_").to_uppercase());
+        settings.insert(name, setting);
+    }
+    settings
+}
+
+/// Override one setting with a value given as text
+///
+/// The text is converted to the type of the value it replaces. Lists are given
+/// as comma separated items.
+///
+/// # Arguments
+///
+/// * `values` - The configuration tree
+/// * `setting` - Dotted path of the setting
+/// * `raw` - The new value as text
+/// * `source` - Where the value comes from
+/// * `sources` - Source of every setting
+///
+/// # Returns
+///
+/// `Ok(())` if the value was set, or an error if it does not fit the setting
+fn set_setting(
+    values: &mut Value,
+    setting: &str,
+    raw: &str,
+    source: ConfigSource,
+    sources: &mut HashMap<String, ConfigSource>,
+) -> Result<(), RoyaOsError> {
+    let invalid = |expected: &str| {
+        RoyaOsError::ConfigOverride(format!("{} for {} is not {}: {}", source, setting, expected, raw))
+    };
     
-    Ok(config)
+    let mut target = values;
+    for key in setting.split('.') {
+        target = target
+            .get_mut(key)
+            .ok_or_else(|| RoyaOsError::ConfigOverride(format!("Unknown setting {} from {}", setting, source)))?;
+    }
+    
+    *target = match target {
+        Value::Bool(_) => Value::Bool(raw.trim().parse().map_err(|_| invalid("true or false"))?),
+        Value::Number(_) => {
+            let number: serde_yaml::Number = raw.trim().parse().map_err(|_| invalid("a number"))?;
+            Value::Number(number)
+        },
+        Value::Sequence(_) => Value::Sequence(
+            raw.split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(|item| Value::String(item.to_string()))
+                .collect(),
+        ),
+        _ => Value::String(raw.to_string()),
+    };
+    sources.insert(setting.to_string(), source);
+    
+    Ok(())
+}
+
+/// Get the name of a configuration key
+fn key_name(key: &Value) -> String {
+    match key {
+        Value::String(key) => key.clone(),
+        other => serde_json::to_string(other).unwrap_or_default(),
+    }
+}
+
+/// Append a key to a dotted path
+fn join_path(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() { key.to_string() } else { format!("{}.{}", prefix, key) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use uuid::Uuid;
+    
+    /// Write a configuration file into a new temporary directory
+    fn write_config(contents: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("royaos-config-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.yaml");
+        fs::write(&path, contents).unwrap();
+        path
+    }
+    
+    /// Build environment variables from name and value pairs
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+    
+    #[test]
+    fn test_layer_precedence() {
+        let path = write_config("system:\n  data_dir: /var/lib/royaos\n  log_level: debug\n  shutdown_timeout: 5\n");
+        let file = ConfigSource::File(path.clone());
+        let loader = ConfigLoader::isolated(&path).with_flag("system.shutdown_timeout", "--shutdown-timeout", "30");
+        
+        let loaded = loader.load_with_env(env(&[
+            ("ROYAOS_SYSTEM_LOG_LEVEL", "warn"),
+            ("ROYAOS_SYSTEM_SHUTDOWN_TIMEOUT", "20"),
+        ])).unwrap();
+        
+        // Defaults < file < env < flags
+        assert_eq!(loaded.config.system.name, "RoyaOS");
+        assert_eq!(loaded.sources.get("system.name"), None);
+        assert_eq!(loaded.config.system.data_dir, "/var/lib/royaos");
+        assert_eq!(loaded.sources["system.data_dir"], file);
+        assert_eq!(loaded.config.system.log_level, "warn");
+        assert_eq!(loaded.sources["system.log_level"], ConfigSource::Env("ROYAOS_SYSTEM_LOG_LEVEL".to_string()));
+        assert_eq!(loaded.config.system.shutdown_timeout, 30);
+        assert_eq!(loaded.sources["system.shutdown_timeout"], ConfigSource::Flag("--shutdown-timeout"));
+        
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+    
+    #[test]
+    fn test_env_overrides_nested_settings() {
+        let path = write_config("quotas:\n  memory:\n    working: 16\n");
+        let loaded = ConfigLoader::isolated(&path).load_with_env(env(&[
+            ("ROYAOS_MEMORY_DECAY_SHORT_TERM_HALF_LIFE", "900"),
+            ("ROYAOS_MEMORY_MAX_ALLOCATION", "64"),
+            ("ROYAOS_QUOTAS_MEMORY_WORKING", "64"),
+            ("ROYAOS_SECURITY_ALLOWED_OPERATIONS", "memory_access, security_query"),
+            ("HOME", "/home/roya"),
+        ])).unwrap();
+        
+        assert_eq!(loaded.config.memory.decay.short_term.half_life, 900);
+        assert_eq!(loaded.config.memory.max_allocation, 64);
+        assert_eq!(loaded.config.quotas.memory.working, 64);
+        assert_eq!(loaded.config.security.allowed_operations, vec!["memory_access", "security_query"]);
+        assert_eq!(
+            loaded.sources["quotas.memory.working"],
+            ConfigSource::Env("ROYAOS_QUOTAS_MEMORY_WORKING".to_string()),
+        );
+        
+        // Values that do not fit the setting are rejected
+        let error = ConfigLoader::isolated(&path).load_with_env(env(&[("ROYAOS_QUOTAS_MEMORY_WORKING", "lots")])).unwrap_err();
+        assert!(matches!(error, RoyaOsError::ConfigOverride(_)));
+        
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+    
+    #[test]
+    fn test_unknown_variables_warn() {
+        let path = write_config("");
+        let loaded = ConfigLoader::isolated(&path).load_with_env(env(&[
+            ("ROYAOS_MEMORY_MAX_ALLOCATON", "2048"),
+            ("ROYAOS_SYSTEM", "on"),
+        ])).unwrap();
+        
+        assert_eq!(loaded.config.memory.max_allocation, 1024);
+        let ignored: Vec<&String> = loaded.warnings.iter().filter(|warning| warning.starts_with("Ignoring")).collect();
+        assert_eq!(ignored, vec![
+            "Ignoring unknown configuration variable ROYAOS_MEMORY_MAX_ALLOCATON",
+            "Ignoring unknown configuration variable ROYAOS_SYSTEM",
+        ]);
+        
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+    
+    #[test]
+    fn test_describe_names_sources() {
+        let path = write_config("memory:\n  max_allocation: 2048\n");
+        let loaded = ConfigLoader::isolated(&path)
+            .with_flag("system.data_dir", "--data-dir", "/srv/royaos")
+            .load_with_env(env(&[("ROYAOS_MEMORY_OPTIMIZATION_STRATEGY", "aggressive")]))
+            .unwrap();
+        
+        let described = loaded.describe();
+        let lines: Vec<&str> = described.lines().collect();
+        assert!(lines.contains(&"system:"));
+        assert!(lines.contains(&"  name: \"RoyaOS\"  # default"));
+        assert!(lines.contains(&"  data_dir: \"/srv/royaos\"  # flag --data-dir"));
+        assert!(lines.contains(&format!("  max_allocation: 2048  # file {}", path.display()).as_str()));
+        assert!(lines.contains(&"  optimization_strategy: \"aggressive\"  # env ROYAOS_MEMORY_OPTIMIZATION_STRATEGY"));
+        assert!(lines.contains(&"  tool_dirs: [\"./tools\"]  # default"));
+        
+        // The described configuration reads back as the effective one
+        let reread: Config = serde_yaml::from_str(&described).unwrap();
+        assert_eq!(reread.memory.max_allocation, 2048);
+        assert_eq!(reread.system.data_dir, "/srv/royaos");
+        
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
 }
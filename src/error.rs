@@ -1,15 +1,35 @@
 //! Error types for RoyaOS
 //!
-//! This module defines the error types used throughout the system.
+//! This module defines the error types used throughout the system. The errors of the
+//! kernel and its subsystems are wrapped unchanged, so callers can match on them and
+//! every error keeps the stable code reported to clients.
 
+use royaos_interface::InterfaceError;
+use royaos_kernel::{ErrorCode, KernelError, SyscallError};
+use royaos_memory::MemoryError;
+use royaos_security::SecurityError;
+use royaos_tools::ToolError;
 use thiserror::Error;
 
 /// Main error type for RoyaOS
 #[derive(Error, Debug)]
+#[allow(dead_code)]
 pub enum RoyaOsError {
     /// Configuration not found
-    #[error("Configuration file not found")]
-    ConfigNotFound,
+    #[error("Configuration file not found: {}", .0.display())]
+    ConfigNotFound(std::path::PathBuf),
+    
+    /// Invalid configuration override from the environment or command line
+    #[error("Invalid configuration override: {0}")]
+    ConfigOverride(String),
+    
+    /// Configuration failed validation; lists every problem found
+    #[error("Invalid configuration:{}", crate::validate::report(.0))]
+    InvalidConfig(Vec<crate::validate::ConfigProblem>),
+    
+    /// Configuration reload rejected
+    #[error("Configuration reload rejected: {0}")]
+    ConfigReload(String),
     
     /// IO error
     #[error("IO error: {0}")]
@@ -19,23 +39,56 @@ pub enum RoyaOsError {
     #[error("YAML parsing error: {0}")]
     YamlParsing(#[from] serde_yaml::Error),
     
-    /// Memory allocation error
-    #[error("Memory allocation error: {0}")]
-    MemoryAllocation(String),
+    /// Memory error
+    #[error("Memory error: {0}")]
+    Memory(#[from] MemoryError),
     
     /// Tool error
     #[error("Tool error: {0}")]
-    Tool(String),
+    Tool(#[from] ToolError),
     
     /// Security error
     #[error("Security error: {0}")]
-    Security(String),
+    Security(#[from] SecurityError),
+    
+    /// Kernel error
+    #[error("Kernel error: {0}")]
+    Kernel(#[from] KernelError),
+    
+    /// System call error
+    #[error("Syscall error: {0}")]
+    Syscall(#[from] SyscallError),
     
     /// Interface error
     #[error("Interface error: {0}")]
-    Interface(String),
+    Interface(#[from] InterfaceError),
+    
+    /// A system call was made before the connection identified as an agent
+    #[error("Identify as an agent before making system calls")]
+    AgentRequired,
     
     /// Unknown error
     #[error("Unknown error: {0}")]
     Unknown(String),
 }
+
+impl ErrorCode for RoyaOsError {
+    fn code(&self) -> &'static str {
+        match self {
+            RoyaOsError::ConfigNotFound(_) => "CONFIG_NOT_FOUND",
+            RoyaOsError::ConfigOverride(_) => "CONFIG_INVALID_OVERRIDE",
+            RoyaOsError::InvalidConfig(_) => "CONFIG_INVALID",
+            RoyaOsError::ConfigReload(_) => "CONFIG_RELOAD_REJECTED",
+            RoyaOsError::Io(_) => "IO_ERROR",
+            RoyaOsError::YamlParsing(_) => "CONFIG_PARSE_ERROR",
+            RoyaOsError::Memory(error) => error.code(),
+            RoyaOsError::Tool(error) => error.code(),
+            RoyaOsError::Security(error) => error.code(),
+            RoyaOsError::Kernel(error) => error.code(),
+            RoyaOsError::Syscall(error) => error.code(),
+            RoyaOsError::Interface(error) => error.code(),
+            RoyaOsError::AgentRequired => "QUOTA_AGENT_REQUIRED",
+            RoyaOsError::Unknown(_) => "UNKNOWN",
+        }
+    }
+}
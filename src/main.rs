@@ -2,52 +2,367 @@
 //!
 //! This is the main entry point for the RoyaOS system.
 
-use log::{info, error};
+use clap::Parser;
+use log::{info, error, warn};
+use royaos_interface::InterfaceManager;
+use royaos_kernel::{Clock, HandleGenerator, Kernel, RestartPolicy, Trace, DEFAULT_WATCHDOG_INTERVAL, LOAD_SAMPLE_INTERVAL, SCHEDULER_SUBSYSTEM, TIMER_TICK};
+use royaos_memory::MemoryManager;
+use royaos_security::SecurityManager;
+use royaos_tools::ToolManager;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+use tokio::signal::unix::{signal, Signal, SignalKind};
+use tokio::sync::watch;
 
+mod cli;
 mod config;
 mod error;
+mod reload;
+mod server;
+mod validate;
+
+use cli::Cli;
+use config::Config;
+use error::RoyaOsError;
+use reload::ConfigReloader;
+
+/// Interface API version served to Roya AGI
+const API_VERSION: &str = "1.0";
 
 /// Main entry point for RoyaOS
-fn main() {
-    // Initialize logging
-    env_logger::init();
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    
+    // Load configuration; logging is not set up yet, so failures go to stderr
+    let loader = cli.config_loader();
+    let loaded = match loader.load() {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("Failed to load configuration: {}", e);
+            process::exit(1);
+        }
+    };
+    
+    if cli.print_config {
+        for warning in &loaded.warnings {
+            eprintln!("{}", warning);
+        }
+        print!("{}", loaded.describe());
+        return;
+    }
+    
+    // Initialize logging; RUST_LOG overrides the configured level unless --log-level is given
+    let log_level = &loaded.config.system.log_level;
+    let mut logger = env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(log_level));
+    if cli.log_level.is_some() {
+        logger.parse_filters(log_level);
+    }
+    logger.init();
     
     info!("Starting RoyaOS...");
+    for warning in &loaded.warnings {
+        warn!("{}", warning);
+    }
+    let config = loaded.config;
+    
+    info!("Configuration loaded successfully for {} {}", config.system.name, config.system.version);
+    
+    if let Some(trace) = &cli.replay {
+        let clean = replay(&config, trace).await;
+        process::exit(if clean { 0 } else { 1 });
+    }
     
-    // Load configuration
-    let config = match config::load_config() {
-        Ok(cfg) => cfg,
+    let mut signals = match Signals::new() {
+        Ok(signals) => signals,
         Err(e) => {
-            error!("Failed to load configuration: {}", e);
+            error!("Failed to install signal handlers: {}", e);
             process::exit(1);
         }
     };
     
-    info!("Configuration loaded successfully");
-    
     // Initialize kernel
     info!("Initializing kernel...");
     
-    // TODO: Initialize kernel components
+    let snapshot_dir = config.system.persist_state.then(|| PathBuf::from(&config.system.data_dir));
+    let kernel = match build_kernel(&config, snapshot_dir.as_deref()) {
+        Ok(kernel) => Arc::new(kernel),
+        Err(e) => {
+            error!("Failed to initialize kernel: {}", e);
+            process::exit(1);
+        }
+    };
     
     info!("Kernel initialized");
     
+    // Pick up the state saved when RoyaOS last stopped
+    if let Some(data_dir) = &snapshot_dir {
+        match kernel.restore(data_dir) {
+            Ok(Some(path)) => info!("Restored system state from {:?}", path),
+            Ok(None) => info!("No saved system state, starting fresh"),
+            Err(e) => error!("Failed to restore system state, starting fresh: {}", e),
+        }
+    }
+    
+    if let Err(e) = reload::set_tracing(&kernel, &config) {
+        warn!("Failed to start syscall tracing: {}", e);
+    }
+    
     // Start system services
     info!("Starting system services...");
     
-    // TODO: Start system services
+    if let Err(e) = kernel.start_watchdog(DEFAULT_WATCHDOG_INTERVAL) {
+        warn!("Failed to start watchdog: {}", e);
+    }
+    if let Err(e) = kernel.start_load_sampler(LOAD_SAMPLE_INTERVAL) {
+        warn!("Failed to start load sampler: {}", e);
+    }
+    if let Err(e) = kernel.start_timers(TIMER_TICK) {
+        warn!("Failed to start timers: {}", e);
+    }
+    
+    let listener = match TcpListener::bind(&config.interface.listen_addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Failed to bind interface listener to {}: {}", config.interface.listen_addr, e);
+            shutdown(kernel, Duration::ZERO, snapshot_dir.as_deref()).await;
+            process::exit(1);
+        }
+    };
+    
+    let deadline = Duration::from_secs(config.system.shutdown_timeout);
+    let (stop, stopped) = watch::channel(false);
+    let reloader = Arc::new(ConfigReloader::new(loader, config.clone()));
+    let mut server = tokio::spawn(server::run(listener, kernel.clone(), reloader.clone(), stopped, deadline));
     
     info!("System services started");
     
     // Main system loop
     info!("RoyaOS is now running");
     
-    // TODO: Implement main system loop
+    let started = Instant::now();
+    let exit_code = loop {
+        tokio::select! {
+            signal = signals.recv() => match signal {
+                ControlSignal::Reload => {
+                    info!("Received SIGHUP, reloading configuration");
+                    if let Err(e) = reloader.reload(&kernel) {
+                        warn!("Configuration reload failed: {}", e);
+                    }
+                },
+                ControlSignal::Shutdown(name) => {
+                    info!("Received {}, shutting down RoyaOS", name);
+                    break 0;
+                },
+            },
+            result = &mut server => {
+                error!("Interface listener stopped unexpectedly: {:?}", result);
+                break 1;
+            },
+        }
+    };
+    
+    // Stop accepting requests, then let in-flight work finish within the deadline
+    let _ = stop.send(true);
+    let shutdown_started = Instant::now();
+    if !server.is_finished() {
+        if let Err(e) = server.await {
+            error!("Interface listener failed: {}", e);
+        }
+    }
+    let remaining = deadline.saturating_sub(shutdown_started.elapsed());
+    
+    let clean = shutdown(kernel, remaining, snapshot_dir.as_deref()).await;
+    info!("RoyaOS stopped after running for {:?}", started.elapsed());
+    
+    if exit_code != 0 || !clean {
+        process::exit(1);
+    }
+}
+
+/// Construct the kernel and its subsystems from the configuration and start them
+///
+/// # Arguments
+///
+/// * `config` - The system configuration
+/// * `data_dir` - Data directory to keep long-term memory in, or `None` to keep it in memory only
+///
+/// # Returns
+///
+/// The running kernel, or an error if a subsystem could not be created or started
+fn build_kernel(config: &Config, data_dir: Option<&Path>) -> Result<Kernel, RoyaOsError> {
+    let restart_policy: RestartPolicy = config.system.restart_policy.parse()?;
+    
+    let mut security = SecurityManager::new(config.security.security_level.as_str(), config.security.allowed_operations.clone())?;
+    security.set_audit_retention(config::optional_secs(config.security.audit_retention));
     
-    // This is a placeholder - in a real implementation, we would have a proper event loop
-    loop {
-        // Process system events
-        std::thread::sleep(std::time::Duration::from_secs(1));
+    let mut interface = InterfaceManager::new(API_VERSION);
+    interface.set_idle_timeout(config::optional_secs(config.interface.idle_timeout));
+    
+    let handles = match config.system.seed {
+        0 => HandleGenerator::random(),
+        seed => HandleGenerator::seeded(seed),
+    };
+    
+    let mut kernel = Kernel::with_clock(&config.system.version, Clock::system(), handles);
+    let mut memory = MemoryManager::new(config.memory.max_allocation, config.memory.optimization_strategy.as_str());
+    if let Some(data_dir) = data_dir {
+        memory = memory.with_data_dir(data_dir);
+    }
+    for (category, decay) in config.memory.decay.to_models() {
+        memory.set_decay(category, decay)?;
+    }
+    kernel.register_subsystem(Box::new(memory))?;
+    kernel.register_subsystem(Box::new(ToolManager::new(
+        config.tools.tool_dirs.clone(),
+        config.tools.discovery_enabled,
+    )))?;
+    kernel.register_subsystem(Box::new(security))?;
+    kernel.register_subsystem(Box::new(interface))?;
+    kernel.set_default_quota(config.quotas.to_quota());
+    
+    // The watchdog, load sampler and timers run on the scheduler, so it is not restarted
+    for name in kernel.subsystem_names() {
+        if name != SCHEDULER_SUBSYSTEM {
+            kernel.set_restart_policy(&name, restart_policy)?;
+        }
+    }
+    
+    kernel.initialize()?;
+    
+    Ok(kernel)
+}
+
+/// Replay a syscall trace into a freshly started kernel and print the divergences
+///
+/// The kernel is built from the configuration without restoring saved state
+/// or long-term memory, so the trace should have been recorded on a system started the same way.
+///
+/// # Arguments
+///
+/// * `config` - The system configuration
+/// * `path` - Path of the trace file
+///
+/// # Returns
+///
+/// `true` if every system call in the trace produced the traced outcome, `false` otherwise
+async fn replay(config: &Config, path: &Path) -> bool {
+    info!("Replaying syscall trace {:?}", path);
+    
+    let trace = match Trace::read(path) {
+        Ok(trace) => trace,
+        Err(e) => {
+            error!("Failed to read syscall trace: {}", e);
+            return false;
+        }
+    };
+    let kernel = match build_kernel(config, None) {
+        Ok(kernel) => Arc::new(kernel),
+        Err(e) => {
+            error!("Failed to initialize kernel: {}", e);
+            return false;
+        }
+    };
+    
+    let clean = match kernel.replay_trace(&trace) {
+        Ok(report) => {
+            for divergence in &report.divergences {
+                println!("{}", divergence);
+            }
+            println!("Replayed {} system calls, {} diverged", report.replayed, report.divergences.len());
+            report.is_clean()
+        },
+        Err(e) => {
+            error!("Failed to replay syscall trace: {}", e);
+            false
+        }
+    };
+    
+    shutdown(kernel, Duration::ZERO, None).await && clean
+}
+
+/// Process signal RoyaOS reacts to
+enum ControlSignal {
+    /// Shut the system down; carries the name of the signal
+    Shutdown(&'static str),
+    /// Reload the configuration file
+    Reload,
+}
+
+/// Listeners for the process signals RoyaOS reacts to
+struct Signals {
+    interrupt: Signal,
+    terminate: Signal,
+    hangup: Signal,
+}
+
+impl Signals {
+    /// Install the signal listeners
+    ///
+    /// # Returns
+    ///
+    /// The installed listeners, or an error if a listener cannot be installed
+    fn new() -> Result<Self, RoyaOsError> {
+        Ok(Self {
+            interrupt: signal(SignalKind::interrupt())?,
+            terminate: signal(SignalKind::terminate())?,
+            hangup: signal(SignalKind::hangup())?,
+        })
+    }
+    
+    /// Wait for the next signal
+    ///
+    /// # Returns
+    ///
+    /// What the received signal asks RoyaOS to do
+    async fn recv(&mut self) -> ControlSignal {
+        tokio::select! {
+            _ = self.interrupt.recv() => ControlSignal::Shutdown("SIGINT"),
+            _ = self.terminate.recv() => ControlSignal::Shutdown("SIGTERM"),
+            _ = self.hangup.recv() => ControlSignal::Reload,
+        }
+    }
+}
+
+/// Drain in-flight kernel work, save the kernel state and shut the kernel down
+///
+/// # Arguments
+///
+/// * `kernel` - The running kernel
+/// * `deadline` - Longest time to wait for in-flight work
+/// * `data_dir` - Data directory to save the kernel state to, or `None` to not save it
+///
+/// # Returns
+///
+/// `true` if the kernel state was saved and the kernel shut down cleanly, `false` otherwise
+async fn shutdown(kernel: Arc<Kernel>, deadline: Duration, data_dir: Option<&Path>) -> bool {
+    info!("Shutting down kernel with {:?} deadline", deadline);
+    let mut clean = kernel.drain(deadline).await;
+    
+    // Subsystems release their state when they shut down, so save it first
+    if let Some(data_dir) = data_dir {
+        if let Err(e) = kernel.snapshot(data_dir) {
+            error!("Failed to save system state: {}", e);
+            clean = false;
+        }
+    }
+    
+    let mut kernel = match Arc::try_unwrap(kernel) {
+        Ok(kernel) => kernel,
+        Err(kernel) => {
+            error!("Kernel is still in use by {} handles, skipping subsystem shutdown", Arc::strong_count(&kernel) - 1);
+            return false;
+        }
+    };
+    
+    match kernel.shutdown() {
+        Ok(()) => clean,
+        Err(e) => {
+            error!("Kernel shutdown failed: {}", e);
+            false
+        }
     }
 }
@@ -0,0 +1,372 @@
+//! Configuration reload for RoyaOS
+//!
+//! This module re-reads the configuration layers while the system is running and applies
+//! the settings that can change live to the running subsystems. A reload is all or
+//! nothing: if the new configuration changes a setting that needs a restart, or a subsystem
+//! rejects a new value, the running configuration is kept unchanged.
+
+use log::{info, error, warn};
+use royaos_kernel::{Kernel, MEMORY_SUBSYSTEM, SECURITY_SUBSYSTEM, TOOLS_SUBSYSTEM};
+use royaos_memory::MemoryManager;
+use royaos_security::SecurityManager;
+use royaos_tools::ToolManager;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::config::{Config, ConfigLoader};
+use crate::error::RoyaOsError;
+
+/// Settings that can be changed without restarting RoyaOS; a section covers all of its settings
+pub const LIVE_SETTINGS: [&str; 7] = [
+    "system.trace_syscalls",
+    "memory.optimization_strategy",
+    "memory.decay",
+    "security.security_level",
+    "security.allowed_operations",
+    "tools.tool_dirs",
+    "quotas",
+];
+
+/// A setting whose value differs between two configurations
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConfigChange {
+    /// Dotted path of the setting, for example `memory.max_allocation`
+    pub setting: String,
+    /// Value in the running configuration
+    pub old: Value,
+    /// Value in the new configuration
+    pub new: Value,
+    /// Whether the change can be applied without a restart
+    pub live: bool,
+}
+
+impl fmt::Display for ConfigChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} -> {}", self.setting, self.old, self.new)?;
+        if !self.live {
+            write!(f, " (requires restart)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare two configurations setting by setting
+///
+/// # Arguments
+///
+/// * `old` - The running configuration
+/// * `new` - The new configuration
+///
+/// # Returns
+///
+/// The changed settings in file order
+pub fn diff(old: &Config, new: &Config) -> Vec<ConfigChange> {
+    let mut changes = Vec::new();
+    let old = serde_json::to_value(old).unwrap_or(Value::Null);
+    let new = serde_json::to_value(new).unwrap_or(Value::Null);
+    diff_values("", &old, &new, &mut changes);
+    changes
+}
+
+/// Collect the differences between two configuration values
+///
+/// Sections are compared field by field; any other value, including lists, is
+/// compared as a whole.
+///
+/// # Arguments
+///
+/// * `prefix` - Dotted path of the values
+/// * `old` - The running value
+/// * `new` - The new value
+/// * `changes` - The list the differences are added to
+fn diff_values(prefix: &str, old: &Value, new: &Value, changes: &mut Vec<ConfigChange>) {
+    if let (Value::Object(old_fields), Value::Object(new_fields)) = (old, new) {
+        let mut keys: Vec<&String> = old_fields.keys().collect();
+        keys.extend(new_fields.keys().filter(|key| !old_fields.contains_key(*key)));
+        
+        for key in keys {
+            let setting = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+            let old_value = old_fields.get(key).unwrap_or(&Value::Null);
+            let new_value = new_fields.get(key).unwrap_or(&Value::Null);
+            diff_values(&setting, old_value, new_value, changes);
+        }
+    } else if old != new {
+        changes.push(ConfigChange {
+            setting: prefix.to_string(),
+            old: old.clone(),
+            new: new.clone(),
+            live: is_live(prefix),
+        });
+    }
+}
+
+/// Check whether a setting can be changed without a restart
+fn is_live(setting: &str) -> bool {
+    LIVE_SETTINGS.iter().any(|live| {
+        setting.strip_prefix(live).is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+    })
+}
+
+/// Render changes as an indented report, one change per line
+fn report(changes: &[ConfigChange]) -> String {
+    changes.iter().map(|change| format!("\n  {}", change)).collect()
+}
+
+/// Reloads the configuration into the running system
+#[derive(Debug)]
+pub struct ConfigReloader {
+    /// Loader for the configuration layers the system was started with
+    loader: ConfigLoader,
+    /// The configuration the system is running with
+    current: Mutex<Config>,
+}
+
+impl ConfigReloader {
+    /// Create a new reloader
+    ///
+    /// # Arguments
+    ///
+    /// * `loader` - Loader for the configuration layers, including the command line overrides
+    /// * `config` - The configuration the system was started with
+    ///
+    /// # Returns
+    ///
+    /// A new ConfigReloader instance
+    pub fn new(loader: ConfigLoader, config: Config) -> Self {
+        Self {
+            loader,
+            current: Mutex::new(config),
+        }
+    }
+    
+    /// Re-read the configuration layers and apply the changed settings
+    ///
+    /// # Arguments
+    ///
+    /// * `kernel` - The running kernel
+    ///
+    /// # Returns
+    ///
+    /// The applied changes, or an error if the configuration could not be loaded,
+    /// changes a setting that needs a restart, or has a value a subsystem rejects; the
+    /// running configuration is kept in all error cases
+    pub fn reload(&self, kernel: &Kernel) -> Result<Vec<ConfigChange>, RoyaOsError> {
+        info!("Reloading configuration");
+        
+        let loaded = self.loader.load().inspect_err(|e| {
+            error!("Failed to load configuration, keeping the running configuration: {}", e);
+        })?;
+        for warning in &loaded.warnings {
+            warn!("{}", warning);
+        }
+        let new = loaded.config;
+        
+        let mut current = self.lock();
+        let changes = diff(&current, &new);
+        if changes.is_empty() {
+            info!("Configuration unchanged");
+            return Ok(changes);
+        }
+        
+        if changes.iter().any(|change| !change.live) {
+            let error_msg = format!("Settings that require a restart were changed:{}", report(&changes));
+            error!("{}", error_msg);
+            return Err(RoyaOsError::ConfigReload(error_msg));
+        }
+        
+        // Apply the changes in order and restore the applied ones if a later one fails
+        for (index, change) in changes.iter().enumerate() {
+            if let Err(e) = apply_setting(kernel, &change.setting, &new) {
+                for applied in changes[..index].iter().rev() {
+                    if let Err(restore_error) = apply_setting(kernel, &applied.setting, &current) {
+                        warn!("Failed to restore {}: {}", applied.setting, restore_error);
+                    }
+                }
+                
+                let error_msg = format!("Invalid value for {}: {}", change.setting, e);
+                error!("{}", error_msg);
+                return Err(RoyaOsError::ConfigReload(error_msg));
+            }
+        }
+        
+        info!("Applied configuration changes:{}", report(&changes));
+        *current = new;
+        
+        Ok(changes)
+    }
+    
+    /// Get the configuration the system is running with
+    ///
+    /// # Returns
+    ///
+    /// A copy of the running configuration
+    pub fn config(&self) -> Config {
+        self.lock().clone()
+    }
+    
+    /// Lock the running configuration
+    fn lock(&self) -> std::sync::MutexGuard<'_, Config> {
+        self.current.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Apply one live setting from a configuration to the running subsystem
+///
+/// # Arguments
+///
+/// * `kernel` - The running kernel
+/// * `setting` - Dotted path of the setting
+/// * `config` - The configuration holding the value to apply
+///
+/// # Returns
+///
+/// `Ok(())` if the subsystem accepted the value, or the error it was rejected with
+fn apply_setting(kernel: &Kernel, setting: &str, config: &Config) -> Result<(), RoyaOsError> {
+    match setting {
+        "system.trace_syscalls" => set_tracing(kernel, config)?,
+        "memory.optimization_strategy" => kernel.with_subsystem(MEMORY_SUBSYSTEM, |memory: &mut MemoryManager| {
+            memory.set_optimization_strategy(config.memory.optimization_strategy.as_str())
+        })??,
+        decay if decay.starts_with("memory.decay.") => kernel.with_subsystem(MEMORY_SUBSYSTEM, |memory: &mut MemoryManager| {
+            config.memory.decay.to_models().into_iter().try_for_each(|(category, decay)| memory.set_decay(category, decay))
+        })??,
+        "security.security_level" => kernel.with_subsystem(SECURITY_SUBSYSTEM, |security: &mut SecurityManager| {
+            security.set_security_level(config.security.security_level.as_str())
+        })??,
+        "security.allowed_operations" => kernel.with_subsystem(SECURITY_SUBSYSTEM, |security: &mut SecurityManager| {
+            security.set_allowed_operations(config.security.allowed_operations.clone())
+        })?,
+        "tools.tool_dirs" => kernel.with_subsystem(TOOLS_SUBSYSTEM, |tools: &mut ToolManager| {
+            tools.set_tool_dirs(config.tools.tool_dirs.clone())
+        })??,
+        // Whether agents must identify is checked on every system call
+        "quotas.require_agent" => {},
+        quota if quota.starts_with("quotas.") => kernel.set_default_quota(config.quotas.to_quota()),
+        _ => return Err(RoyaOsError::ConfigReload(format!("Setting {} cannot be changed while RoyaOS is running", setting))),
+    }
+    
+    Ok(())
+}
+
+/// Start or stop the syscall tracer as a configuration asks
+///
+/// # Arguments
+///
+/// * `kernel` - The running kernel
+/// * `config` - The configuration holding `system.trace_syscalls`
+///
+/// # Returns
+///
+/// `Ok(())` once tracing matches the configuration, or the error the trace file could not be created with
+pub fn set_tracing(kernel: &Kernel, config: &Config) -> Result<(), RoyaOsError> {
+    if config.system.trace_syscalls {
+        kernel.start_trace(Path::new(&config.system.data_dir))?;
+    } else {
+        kernel.stop_trace();
+    }
+    
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use royaos_memory::OptimizationStrategy;
+    use royaos_security::SecurityLevel;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use uuid::Uuid;
+    
+    /// Write a configuration file into a new directory that also serves as the data directory
+    fn write_config(dir: &Path, extra: &str) -> PathBuf {
+        let path = dir.join("config.yaml");
+        fs::write(&path, format!("system:\n  data_dir: {:?}\n{}", dir, extra)).unwrap();
+        path
+    }
+    
+    /// Start a kernel and a reloader from the configuration file
+    fn start(path: &Path) -> (Kernel, ConfigReloader) {
+        let loader = ConfigLoader::isolated(path);
+        let config = loader.load().unwrap().config;
+        let kernel = crate::build_kernel(&config, None).unwrap();
+        (kernel, ConfigReloader::new(loader, config))
+    }
+    
+    #[test]
+    fn test_reload_applies_live_settings() {
+        let dir = std::env::temp_dir().join(format!("royaos-reload-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = write_config(&dir, "");
+        let (kernel, reloader) = start(&path);
+        assert_eq!(kernel.default_quota().syscalls_per_second, None);
+        assert_eq!(kernel.trace_path(), None);
+        
+        write_config(&dir, concat!(
+            "  trace_syscalls: true\n",
+            "memory:\n  optimization_strategy: aggressive\n",
+            "security:\n  security_level: high\n",
+            "quotas:\n  syscalls_per_second: 5\n",
+        ));
+        let changes = reloader.reload(&kernel).unwrap();
+        let settings: Vec<&str> = changes.iter().map(|change| change.setting.as_str()).collect();
+        assert_eq!(settings, vec![
+            "memory.optimization_strategy",
+            "quotas.syscalls_per_second",
+            "security.security_level",
+            "system.trace_syscalls",
+        ]);
+        assert!(changes.iter().all(|change| change.live));
+        
+        // The running subsystems use the new values
+        assert!(kernel.trace_path().is_some_and(|trace| trace.starts_with(&dir)));
+        assert_eq!(kernel.default_quota().syscalls_per_second, Some(5));
+        let strategy = kernel.with_subsystem(MEMORY_SUBSYSTEM, |memory: &mut MemoryManager| {
+            memory.optimization_strategy().to_string()
+        }).unwrap();
+        assert_eq!(strategy, "aggressive");
+        assert_eq!(reloader.config().security.security_level, SecurityLevel::High);
+        assert!(reloader.config().system.trace_syscalls);
+        
+        // Reloading the same file again changes nothing
+        assert!(reloader.reload(&kernel).unwrap().is_empty());
+        
+        kernel.stop_trace();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    
+    #[test]
+    fn test_reload_rejects_restart_settings() {
+        let dir = std::env::temp_dir().join(format!("royaos-reload-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = write_config(&dir, "");
+        let (kernel, reloader) = start(&path);
+        
+        // A restart-only change is rejected along with the live changes next to it
+        let moved = dir.join("moved");
+        fs::write(&path, format!(
+            "system:\n  data_dir: {:?}\ninterface:\n  listen_addr: 127.0.0.1:9000\nmemory:\n  optimization_strategy: aggressive\nquotas:\n  syscalls_per_second: 5\n",
+            moved,
+        )).unwrap();
+        let error = reloader.reload(&kernel).unwrap_err();
+        assert!(matches!(error, RoyaOsError::ConfigReload(_)));
+        let message = error.to_string();
+        assert!(message.contains("system.data_dir"), "{}", message);
+        assert!(message.contains("interface.listen_addr: \"127.0.0.1:8000\" -> \"127.0.0.1:9000\" (requires restart)"), "{}", message);
+        
+        let running = reloader.config();
+        assert_eq!(running.system.data_dir, dir.to_str().unwrap());
+        assert_eq!(running.interface.listen_addr, "127.0.0.1:8000");
+        assert_eq!(running.memory.optimization_strategy, OptimizationStrategy::Balanced);
+        let strategy = kernel.with_subsystem(MEMORY_SUBSYSTEM, |memory: &mut MemoryManager| {
+            memory.optimization_strategy().to_string()
+        }).unwrap();
+        assert_eq!(strategy, "balanced");
+        assert_eq!(running.quotas.syscalls_per_second, 0);
+        assert_eq!(kernel.default_quota().syscalls_per_second, None);
+        
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
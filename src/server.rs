@@ -0,0 +1,574 @@
+//! Interface listener for RoyaOS
+//!
+//! This module accepts connections from Roya AGI and carries requests between them and
+//! the kernel. Every connection is an interface session exchanging newline-delimited
+//! JSON: each line the client sends is a `Request` and each line sent back is the
+//! matching `Response`.
+//!
+//! Requests of type `syscall` are executed by the kernel, `batch` requests run several
+//! system calls as a unit that is rolled back if one fails, `reload_config` requests
+//! reload the configuration file and `save_snapshot` requests save the kernel state to
+//! the data directory; all other requests are handled by the interface subsystem.
+//! Failed responses carry the stable code of the error next to its message.
+//!
+//! A connection acting for one of several agents sends an `identify` request first.
+//! Its system calls are then charged to the agent's quota, and `quota_usage` requests
+//! report the agent's quota and usage.
+//!
+//! Every request counts as activity on the session. A session the interface subsystem
+//! closes for being idle ends its connection.
+
+use log::{info, error, debug, warn};
+use royaos_interface::{InterfaceError, InterfaceManager, Request, Response, SessionHandle};
+use royaos_kernel::{BatchStep, EventError, EventKind, EventSubscriber, Kernel, KernelEvent, INTERFACE_SUBSYSTEM, SECURITY_SUBSYSTEM};
+use royaos_security::SecurityManager;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+
+use crate::error::RoyaOsError;
+use crate::reload::ConfigReloader;
+
+/// Request type executed as a kernel system call
+const SYSCALL_REQUEST: &str = "syscall";
+
+/// Request type executing system calls as a batch
+const BATCH_REQUEST: &str = "batch";
+
+/// Request type reloading the configuration file
+const RELOAD_CONFIG_REQUEST: &str = "reload_config";
+
+/// Request type saving a snapshot of the kernel state
+const SAVE_SNAPSHOT_REQUEST: &str = "save_snapshot";
+
+/// Request type binding the connection to an agent
+const IDENTIFY_REQUEST: &str = "identify";
+
+/// Request type reporting the quota and usage of an agent
+const QUOTA_USAGE_REQUEST: &str = "quota_usage";
+
+/// Accept AGI connections until shutdown is signalled
+///
+/// After shutdown is signalled no new connections are accepted. Open sessions
+/// finish the request they are processing and are closed; sessions still busy
+/// when the deadline passes are aborted.
+///
+/// # Arguments
+///
+/// * `listener` - The bound listener to accept connections on
+/// * `kernel` - The running kernel
+/// * `reloader` - Reloader for the configuration file
+/// * `shutdown` - Receiver that turns `true` when the system shuts down
+/// * `deadline` - Longest time to wait for open sessions after shutdown
+///
+/// # Returns
+///
+/// `Ok(())` once all sessions are closed, or an error if accepting fails
+pub async fn run(
+    listener: TcpListener,
+    kernel: Arc<Kernel>,
+    reloader: Arc<ConfigReloader>,
+    shutdown: watch::Receiver<bool>,
+    deadline: Duration,
+) -> Result<(), RoyaOsError> {
+    info!("Interface listener accepting connections on {}", listener.local_addr()?);
+    let mut sessions = JoinSet::new();
+    let mut stop = shutdown.clone();
+    
+    let result = loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, peer)) => {
+                    debug!("Accepted connection from {}", peer);
+                    sessions.spawn(serve_connection(stream, kernel.clone(), reloader.clone(), shutdown.clone()));
+                },
+                Err(e) => {
+                    error!("Failed to accept connection: {}", e);
+                    break Err(RoyaOsError::Io(e));
+                },
+            },
+            _ = stop.wait_for(|stop| *stop) => break Ok(()),
+            // Reap finished sessions so the set does not grow without bound
+            Some(_) = sessions.join_next(), if !sessions.is_empty() => {},
+        }
+    };
+    
+    drop(listener);
+    info!("Interface listener stopped, closing {} sessions", sessions.len());
+    
+    if tokio::time::timeout(deadline, async { while sessions.join_next().await.is_some() {} }).await.is_err() {
+        warn!("Aborting {} sessions still busy after {:?}", sessions.len(), deadline);
+        sessions.abort_all();
+        while sessions.join_next().await.is_some() {}
+    }
+    
+    result
+}
+
+/// Serve requests on one connection until the client disconnects or shutdown is signalled
+///
+/// # Arguments
+///
+/// * `stream` - The client connection
+/// * `kernel` - The running kernel
+/// * `reloader` - Reloader for the configuration file
+/// * `shutdown` - Receiver that turns `true` when the system shuts down
+async fn serve_connection(
+    stream: TcpStream,
+    kernel: Arc<Kernel>,
+    reloader: Arc<ConfigReloader>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let peer = stream.peer_addr().map(|addr| addr.to_string()).unwrap_or_default();
+    let metadata = HashMap::from([("peer".to_string(), peer.clone())]);
+    
+    let session = match kernel.with_subsystem(INTERFACE_SUBSYSTEM, |interface: &mut InterfaceManager| {
+        interface.create_session(metadata)
+    }) {
+        Ok(session) => session,
+        Err(e) => {
+            error!("Failed to open session for {}: {}", peer, e);
+            return;
+        },
+    };
+    
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let mut agent = None;
+    let mut closed_sessions = kernel.subscribe(&[EventKind::SessionClosed]);
+    let mut reaped = false;
+    
+    loop {
+        let line = tokio::select! {
+            line = lines.next_line() => line,
+            _ = shutdown.wait_for(|stop| *stop) => break,
+            _ = session_closed(&mut closed_sessions, session) => {
+                info!("Closing idle connection from {}", peer);
+                reaped = true;
+                break;
+            },
+        };
+        
+        let line = match line {
+            Ok(Some(line)) if line.trim().is_empty() => continue,
+            Ok(Some(line)) => line,
+            Ok(None) => break,
+            Err(e) => {
+                warn!("Connection from {} failed: {}", peer, e);
+                break;
+            },
+        };
+        
+        // Kernel calls block, so they run off the async worker threads
+        let (kernel_call, reloader_call, mut agent_call) = (kernel.clone(), reloader.clone(), agent.clone());
+        let handled = tokio::task::spawn_blocking(move || {
+            let response = handle_line(&kernel_call, &reloader_call, session, &mut agent_call, &line);
+            (response, agent_call)
+        }).await;
+        let response = match handled {
+            Ok((response, identified)) => {
+                agent = identified;
+                response
+            },
+            Err(e) => {
+                error!("Request from {} failed: {}", peer, e);
+                break;
+            },
+        };
+        let mut encoded = match serde_json::to_string(&response) {
+            Ok(encoded) => encoded,
+            Err(e) => {
+                error!("Failed to encode response {}: {}", response.id, e);
+                break;
+            },
+        };
+        encoded.push('\n');
+        
+        if let Err(e) = writer.write_all(encoded.as_bytes()).await {
+            warn!("Failed to send response to {}: {}", peer, e);
+            break;
+        }
+    }
+    
+    if reaped {
+        return;
+    }
+    if let Err(e) = kernel.with_subsystem(INTERFACE_SUBSYSTEM, |interface: &mut InterfaceManager| {
+        interface.close_session(session)
+    }) {
+        error!("Failed to close session {}: {}", session, e);
+    }
+}
+
+/// Wait until a session is closed by the interface subsystem
+///
+/// # Arguments
+///
+/// * `events` - Subscriber to `SessionClosed` events
+/// * `session` - The session to wait for
+async fn session_closed(events: &mut EventSubscriber, session: SessionHandle) {
+    loop {
+        match events.recv().await {
+            Ok(KernelEvent::SessionClosed { session: closed }) if closed == session => return,
+            Ok(_) | Err(EventError::Lagged(_)) => continue,
+            Err(EventError::Closed) => std::future::pending().await,
+        }
+    }
+}
+
+/// Process one request line from a session
+///
+/// # Arguments
+///
+/// * `kernel` - The running kernel
+/// * `reloader` - Reloader for the configuration file
+/// * `session` - The session the request was received on
+/// * `agent` - Agent ID the connection identified as, if any
+/// * `line` - The JSON encoded request
+///
+/// # Returns
+///
+/// The response to send back to the client
+fn handle_line(
+    kernel: &Kernel,
+    reloader: &ConfigReloader,
+    session: SessionHandle,
+    agent: &mut Option<String>,
+    line: &str,
+) -> Response {
+    let request: Request = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => return Response::failure(String::new(), &InterfaceError::InvalidRequest(e.to_string())),
+    };
+    
+    let touched = kernel.with_subsystem(INTERFACE_SUBSYSTEM, |interface: &mut InterfaceManager| {
+        interface.touch_session(session)
+    })
+    .map_err(RoyaOsError::from)
+    .and_then(|result| result.map_err(RoyaOsError::from));
+    if let Err(e) = touched {
+        return Response::failure(request.id, &e);
+    }
+    
+    match request.request_type.as_str() {
+        SYSCALL_REQUEST => return handle_syscall(kernel, reloader, session, agent.as_deref(), request),
+        BATCH_REQUEST => return handle_batch(kernel, reloader, session, agent.as_deref(), request),
+        RELOAD_CONFIG_REQUEST => return handle_reload(kernel, reloader, request),
+        SAVE_SNAPSHOT_REQUEST => return handle_save_snapshot(kernel, reloader, request),
+        IDENTIFY_REQUEST => return handle_identify(kernel, agent, request),
+        QUOTA_USAGE_REQUEST => return handle_quota_usage(kernel, agent.as_deref(), request),
+        _ => {},
+    }
+    
+    let id = request.id.clone();
+    kernel.with_subsystem(INTERFACE_SUBSYSTEM, |interface: &mut InterfaceManager| {
+        interface.process_request(session, request)
+    })
+    .map_err(RoyaOsError::from)
+    .and_then(|result| result.map_err(RoyaOsError::from))
+    .unwrap_or_else(|e| Response::failure(id, &e))
+}
+
+/// Execute a `syscall` request on the kernel
+///
+/// The request parameters name the system call and its arguments, for example
+/// `{"name": "memory_alloc", "args": ["1024", "scratch"]}`. Calls on a connection
+/// that identified as an agent are charged to the agent's quota.
+///
+/// # Arguments
+///
+/// * `kernel` - The running kernel
+/// * `reloader` - Reloader holding the running configuration
+/// * `session` - The session the request was received on
+/// * `agent` - Agent ID the connection identified as, if any
+/// * `request` - The syscall request
+///
+/// # Returns
+///
+/// The response carrying the system call result
+fn handle_syscall(
+    kernel: &Kernel,
+    reloader: &ConfigReloader,
+    session: SessionHandle,
+    agent: Option<&str>,
+    request: Request,
+) -> Response {
+    let name = match request.parameters.get("name").and_then(|name| name.as_str()) {
+        Some(name) => name,
+        None => {
+            let error = InterfaceError::InvalidRequest("Syscall request is missing a name".to_string());
+            return Response::failure(request.id, &error);
+        },
+    };
+    
+    let args: Vec<String> = match request.parameters.get("args") {
+        None => Vec::new(),
+        Some(args) => match serde_json::from_value(args.clone()) {
+            Ok(args) => args,
+            Err(e) => {
+                let error = InterfaceError::InvalidRequest(format!("Invalid syscall arguments: {}", e));
+                return Response::failure(request.id, &error);
+            },
+        },
+    };
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    
+    let result = match agent {
+        Some(agent) => kernel.process_principal_syscall(agent, Some(session), name, &args),
+        None if reloader.config().quotas.require_agent => {
+            return Response::failure(request.id, &RoyaOsError::AgentRequired);
+        },
+        None => kernel.process_session_syscall(session, name, &args),
+    };
+    
+    match result {
+        Ok(result) => Response::success(request.id, serde_json::Value::String(result)),
+        Err(e) => Response::failure(request.id, &e),
+    }
+}
+
+/// Execute a `batch` request on the kernel
+///
+/// The request parameters list the system calls in order, for example
+/// `{"steps": [{"syscall": "memory_alloc", "args": ["1024"]}, {"syscall": "memory_free", "args": ["$0"]}]}`.
+/// The response carries the outcome of every step, including the steps rolled
+/// back after a failure.
+///
+/// # Arguments
+///
+/// * `kernel` - The running kernel
+/// * `reloader` - Reloader holding the running configuration
+/// * `session` - The session the request was received on
+/// * `agent` - Agent ID the connection identified as, if any
+/// * `request` - The batch request
+///
+/// # Returns
+///
+/// The response carrying the batch result
+fn handle_batch(
+    kernel: &Kernel,
+    reloader: &ConfigReloader,
+    session: SessionHandle,
+    agent: Option<&str>,
+    request: Request,
+) -> Response {
+    let steps: Vec<BatchStep> = match request.parameters.get("steps").map(|steps| serde_json::from_value(steps.clone())) {
+        Some(Ok(steps)) => steps,
+        Some(Err(e)) => {
+            let error = InterfaceError::InvalidRequest(format!("Invalid batch steps: {}", e));
+            return Response::failure(request.id, &error);
+        },
+        None => {
+            let error = InterfaceError::InvalidRequest("Batch request is missing its steps".to_string());
+            return Response::failure(request.id, &error);
+        },
+    };
+    
+    let result = match agent {
+        Some(agent) => kernel.process_principal_batch(agent, Some(session), &steps),
+        None if reloader.config().quotas.require_agent => {
+            return Response::failure(request.id, &RoyaOsError::AgentRequired);
+        },
+        None => kernel.process_session_batch(session, &steps),
+    };
+    
+    match result {
+        Ok(result) => Response::success(request.id, serde_json::json!(result)),
+        Err(e) => Response::failure(request.id, &e),
+    }
+}
+
+/// Execute an `identify` request
+///
+/// The request parameters name the agent the connection acts for, for example
+/// `{"agent_id": "planner"}`. A new agent is registered under the default quota.
+/// A connection can identify as one agent only.
+///
+/// # Arguments
+///
+/// * `kernel` - The running kernel
+/// * `agent` - Agent ID the connection identified as, if any
+/// * `request` - The identify request
+///
+/// # Returns
+///
+/// The response carrying the agent ID and the quota that applies to it
+fn handle_identify(kernel: &Kernel, agent: &mut Option<String>, request: Request) -> Response {
+    let agent_id = match request.parameters.get("agent_id").and_then(|agent_id| agent_id.as_str()) {
+        Some(agent_id) if !agent_id.is_empty() => agent_id,
+        _ => {
+            let error = InterfaceError::InvalidRequest("Identify request is missing an agent_id".to_string());
+            return Response::failure(request.id, &error);
+        },
+    };
+    if let Some(current) = agent.as_deref().filter(|current| *current != agent_id) {
+        let error = InterfaceError::InvalidRequest(format!("Connection already identified as agent {}", current));
+        return Response::failure(request.id, &error);
+    }
+    
+    kernel.register_principal(agent_id);
+    *agent = Some(agent_id.to_string());
+    
+    match kernel.principal_quota(agent_id) {
+        Ok(quota) => Response::success(request.id, serde_json::json!({ "agent_id": agent_id, "quota": quota })),
+        Err(e) => Response::failure(request.id, &e),
+    }
+}
+
+/// Execute a `quota_usage` request
+///
+/// Without parameters the request reports on the agent the connection identified
+/// as. Reporting on another agent, named with `{"agent_id": "planner"}`, needs the
+/// `config_management` operation.
+///
+/// # Arguments
+///
+/// * `kernel` - The running kernel
+/// * `agent` - Agent ID the connection identified as, if any
+/// * `request` - The usage request
+///
+/// # Returns
+///
+/// The response carrying the agent's quota and usage
+fn handle_quota_usage(kernel: &Kernel, agent: Option<&str>, request: Request) -> Response {
+    let requested = request.parameters.get("agent_id").and_then(|agent_id| agent_id.as_str());
+    let agent_id = match (requested, agent) {
+        (Some(requested), Some(own)) if requested == own => own,
+        (Some(requested), _) => {
+            if let Err(e) = require_config_permission(kernel, "quota") {
+                return Response::failure(request.id, &e);
+            }
+            requested
+        },
+        (None, Some(own)) => own,
+        (None, None) => {
+            let error = InterfaceError::InvalidRequest("Quota usage request is missing an agent_id".to_string());
+            return Response::failure(request.id, &error);
+        },
+    };
+    
+    let report = kernel.principal_quota(agent_id)
+        .and_then(|quota| Ok((quota, kernel.principal_usage(agent_id)?)));
+    match report {
+        Ok((quota, usage)) => Response::success(request.id, serde_json::json!({
+            "agent_id": agent_id,
+            "quota": quota,
+            "usage": usage,
+        })),
+        Err(e) => Response::failure(request.id, &e),
+    }
+}
+
+/// Execute a `reload_config` request
+///
+/// The caller needs the `config_management` operation.
+///
+/// # Arguments
+///
+/// * `kernel` - The running kernel
+/// * `reloader` - Reloader for the configuration file
+/// * `request` - The reload request
+///
+/// # Returns
+///
+/// The response listing the applied changes
+fn handle_reload(kernel: &Kernel, reloader: &ConfigReloader, request: Request) -> Response {
+    if let Err(e) = require_config_permission(kernel, "reload") {
+        return Response::failure(request.id, &e);
+    }
+    
+    match reloader.reload(kernel) {
+        Ok(changes) => Response::success(request.id, serde_json::json!({ "changes": changes })),
+        Err(e) => Response::failure(request.id, &e),
+    }
+}
+
+/// Execute a `save_snapshot` request
+///
+/// The snapshot is written to the `snapshots` directory below `system.data_dir`.
+/// The caller needs the `config_management` operation.
+///
+/// # Arguments
+///
+/// * `kernel` - The running kernel
+/// * `reloader` - Reloader holding the running configuration
+/// * `request` - The snapshot request
+///
+/// # Returns
+///
+/// The response carrying the path of the written snapshot
+fn handle_save_snapshot(kernel: &Kernel, reloader: &ConfigReloader, request: Request) -> Response {
+    if let Err(e) = require_config_permission(kernel, "snapshot") {
+        return Response::failure(request.id, &e);
+    }
+    
+    let data_dir = reloader.config().system.data_dir;
+    match kernel.snapshot(Path::new(&data_dir)) {
+        Ok(path) => Response::success(request.id, serde_json::json!({ "path": path })),
+        Err(e) => Response::failure(request.id, &e),
+    }
+}
+
+/// Check that configuration management operations are allowed
+///
+/// # Arguments
+///
+/// * `kernel` - The running kernel
+/// * `operation` - The configuration operation, for example `reload`
+///
+/// # Returns
+///
+/// `Ok(())` if the operation is allowed, or the reason it was rejected
+fn require_config_permission(kernel: &Kernel, operation: &str) -> Result<(), RoyaOsError> {
+    kernel.with_subsystem(SECURITY_SUBSYSTEM, |security: &mut SecurityManager| {
+        security.require_permission("config", operation, "*")
+    })??;
+    
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, ConfigLoader};
+    use std::time::Instant;
+    use tokio::io::AsyncReadExt;
+    
+    #[tokio::test]
+    async fn test_serve_request_and_shut_down() {
+        let kernel = Arc::new(crate::build_kernel(&Config::default(), None).unwrap());
+        let reloader = Arc::new(ConfigReloader::new(ConfigLoader::new(), Config::default()));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let deadline = Duration::from_secs(2);
+        let (stop, stopped) = watch::channel(false);
+        let server = tokio::spawn(run(listener, kernel.clone(), reloader, stopped, deadline));
+        
+        // One request line gets one response line
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let request = r#"{"id": "alloc-1", "request_type": "syscall", "parameters": {"name": "memory_alloc", "args": ["64", "scratch"]}, "timestamp": 0}"#;
+        client.write_all(format!("{}\n", request).as_bytes()).await.unwrap();
+        let (reader, _writer) = client.split();
+        let mut reader = BufReader::new(reader);
+        let mut line = String::new();
+        reader.read_line(&mut line).await.unwrap();
+        let response: Response = serde_json::from_str(&line).unwrap();
+        assert_eq!(response.id, "alloc-1");
+        assert!(response.success, "Request failed: {:?}", response.error);
+        assert!(response.data.is_some());
+        assert_eq!(response.error_code, None);
+        
+        // Shutdown closes the session that is still open well within the deadline
+        let started = Instant::now();
+        stop.send(true).unwrap();
+        tokio::time::timeout(deadline, server).await.unwrap().unwrap().unwrap();
+        assert!(started.elapsed() < deadline);
+        let mut rest = Vec::new();
+        assert_eq!(reader.read_to_end(&mut rest).await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,669 @@
+//! Configuration validation for RoyaOS
+//!
+//! This module checks the merged configuration before it is turned into a `Config`.
+//! Every setting is checked against the type of its built-in default and against the
+//! values and ranges the subsystems accept, and all problems are reported together.
+//! Problems with a value read from a file point at the line and column of the value.
+
+use log::LevelFilter;
+use royaos_kernel::RestartPolicy;
+use royaos_memory::OptimizationStrategy;
+use royaos_security::{SecurityLevel, OPERATIONS};
+use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde_yaml::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::config::{Config, ConfigSource};
+
+/// Largest accepted memory allocation (in MB)
+pub const MAX_ALLOCATION_LIMIT: u64 = 1024 * 1024;
+
+/// Longest accepted shutdown timeout (in seconds)
+pub const MAX_SHUTDOWN_TIMEOUT: u64 = 3600;
+
+/// Position of a value in a configuration file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    /// Line number, starting at 1
+    pub line: usize,
+    /// Column number, starting at 1
+    pub column: usize,
+}
+
+/// A configuration value that failed validation
+#[derive(Debug, Clone)]
+pub struct ConfigProblem {
+    /// Dotted path of the setting, with an index for list items
+    pub setting: String,
+    /// What is wrong with the value
+    pub message: String,
+    /// Layer the value was taken from
+    pub source: ConfigSource,
+    /// Position of the value if it was read from a file
+    pub location: Option<Location>,
+}
+
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.source, self.location) {
+            (ConfigSource::File(path), Some(location)) => {
+                write!(f, "{}:{}:{}: ", path.display(), location.line, location.column)?
+            },
+            (source, _) => write!(f, "{}: ", source)?,
+        }
+        write!(f, "{}: {}", self.setting, self.message)
+    }
+}
+
+/// Render problems as an indented report, one problem per line
+pub fn report(problems: &[ConfigProblem]) -> String {
+    problems.iter().map(|problem| format!("\n  {}", problem)).collect()
+}
+
+/// Step on the path to a configuration value
+#[derive(Debug, Clone)]
+enum Segment {
+    /// Field of a section
+    Key(String),
+    /// Item of a list
+    Index(usize),
+}
+
+/// Validate a merged configuration tree
+///
+/// # Arguments
+///
+/// * `values` - The merged configuration tree
+/// * `sources` - Layer each setting was taken from
+///
+/// # Returns
+///
+/// Warnings about values that are usable but suspicious, or every problem found
+pub fn validate(values: &Value, sources: &HashMap<String, ConfigSource>) -> Result<Vec<String>, Vec<ConfigProblem>> {
+    let mut validator = Validator {
+        sources,
+        problems: Vec::new(),
+        warnings: Vec::new(),
+    };
+    
+    let defaults = serde_yaml::to_value(Config::default()).unwrap_or(Value::Null);
+    validator.check_types(&mut Vec::new(), &defaults, values);
+    validator.check_values(values);
+    
+    if validator.problems.is_empty() {
+        Ok(validator.warnings)
+    } else {
+        Err(validator.problems)
+    }
+}
+
+/// Collects the problems found in a configuration tree
+struct Validator<'a> {
+    /// Layer each setting was taken from
+    sources: &'a HashMap<String, ConfigSource>,
+    /// Problems found so far
+    problems: Vec<ConfigProblem>,
+    /// Warnings found so far
+    warnings: Vec<String>,
+}
+
+impl Validator<'_> {
+    /// Check that every value has the type of its default and that no unknown settings are present
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the values
+    /// * `default` - The built-in default
+    /// * `value` - The merged value
+    fn check_types(&mut self, path: &mut Vec<Segment>, default: &Value, value: &Value) {
+        match (default, value) {
+            (Value::Mapping(default_fields), Value::Mapping(fields)) => {
+                for (key, value) in fields {
+                    let Some(name) = key.as_str() else {
+                        path.push(Segment::Key(format!("{:?}", key)));
+                        self.problem(path, "setting names must be strings".to_string());
+                        path.pop();
+                        continue;
+                    };
+                    
+                    path.push(Segment::Key(name.to_string()));
+                    match default_fields.get(key) {
+                        Some(default) => self.check_types(path, default, value),
+                        None => self.problem(path, "unknown setting".to_string()),
+                    }
+                    path.pop();
+                }
+            },
+            (Value::Mapping(_), _) => self.problem(path, format!("expected a section, found {}", kind(value))),
+            (Value::Sequence(_), Value::Sequence(items)) => {
+                for (index, item) in items.iter().enumerate() {
+                    if !item.is_string() {
+                        path.push(Segment::Index(index));
+                        self.problem(path, format!("expected a string, found {}", kind(item)));
+                        path.pop();
+                    }
+                }
+            },
+            (Value::Sequence(_), _) => self.problem(path, format!("expected a list, found {}", kind(value))),
+            (Value::Number(_), Value::Number(number)) if number.is_u64() => {},
+            (Value::Number(_), Value::Number(number)) => {
+                self.problem(path, format!("expected a whole number of at least 0, found {}", number))
+            },
+            (Value::Number(_), _) => self.problem(path, format!("expected a whole number, found {}", kind(value))),
+            (Value::Bool(_), Value::Bool(_)) => {},
+            (Value::Bool(_), _) => self.problem(path, format!("expected true or false, found {}", kind(value))),
+            (_, Value::String(_)) => {},
+            (_, _) => self.problem(path, format!("expected a string, found {}", kind(value))),
+        }
+    }
+    
+    /// Check the values and ranges of settings; values of the wrong type are skipped
+    fn check_values(&mut self, values: &Value) {
+        let text = |setting: &str| values.get(section(setting)).and_then(|s| s.get(field(setting))).and_then(Value::as_str);
+        let number = |setting: &str| values.get(section(setting)).and_then(|s| s.get(field(setting))).and_then(Value::as_u64);
+        let list = |setting: &str| {
+            values.get(section(setting))
+                .and_then(|s| s.get(field(setting)))
+                .and_then(Value::as_sequence)
+                .map(|items| items.iter().filter_map(Value::as_str).collect::<Vec<_>>())
+                .unwrap_or_default()
+        };
+        
+        if let Some(level) = text("system.log_level") {
+            if LevelFilter::from_str(level).is_err() {
+                self.setting_problem("system.log_level", format!(
+                    "invalid log level {:?}, expected one of off, error, warn, info, debug, trace", level
+                ));
+            }
+        }
+        if let Some(policy) = text("system.restart_policy") {
+            if RestartPolicy::from_str(policy).is_err() {
+                self.setting_problem("system.restart_policy", format!(
+                    "invalid restart policy {:?}, expected one of never, on-failure, always", policy
+                ));
+            }
+        }
+        if let Some(timeout) = number("system.shutdown_timeout") {
+            if timeout > MAX_SHUTDOWN_TIMEOUT {
+                self.setting_problem("system.shutdown_timeout", format!(
+                    "{} seconds is out of range, expected at most {}", timeout, MAX_SHUTDOWN_TIMEOUT
+                ));
+            }
+        }
+        if let Some(data_dir) = text("system.data_dir") {
+            if let Err(message) = check_data_dir(data_dir) {
+                self.setting_problem("system.data_dir", message);
+            }
+        }
+        
+        if let Some(max_allocation) = number("memory.max_allocation") {
+            if max_allocation == 0 || max_allocation > MAX_ALLOCATION_LIMIT {
+                self.setting_problem("memory.max_allocation", format!(
+                    "{} MB is out of range, expected 1 to {}", max_allocation, MAX_ALLOCATION_LIMIT
+                ));
+            }
+        }
+        if let Some(strategy) = text("memory.optimization_strategy") {
+            if OptimizationStrategy::from_str(strategy).is_err() {
+                self.setting_problem("memory.optimization_strategy", format!(
+                    "invalid optimization strategy {:?}, expected one of aggressive, balanced, conservative", strategy
+                ));
+            }
+        }
+        let decay = values.get("memory").and_then(|memory| memory.get("decay"));
+        for category in ["system", "short_term", "working", "long_term", "background"] {
+            let forget_below = decay.and_then(|decay| decay.get(category))
+                .and_then(|category| category.get("forget_below"))
+                .and_then(Value::as_u64);
+            if let Some(forget_below) = forget_below.filter(|forget_below| *forget_below >= 100) {
+                self.setting_problem(&format!("memory.decay.{}.forget_below", category), format!(
+                    "{}% is out of range, expected 0 to 99", forget_below
+                ));
+            }
+        }
+        
+        for (index, dir) in list("tools.tool_dirs").into_iter().enumerate() {
+            match check_tool_dir(dir) {
+                Ok(None) => {},
+                Ok(Some(warning)) => self.warnings.push(format!("tools.tool_dirs[{}]: {}", index, warning)),
+                Err(message) => self.item_problem("tools.tool_dirs", index, message),
+            }
+        }
+        
+        if let Some(level) = text("security.security_level") {
+            if SecurityLevel::from_str(level).is_err() {
+                self.setting_problem("security.security_level", format!(
+                    "invalid security level {:?}, expected one of low, standard, high, maximum", level
+                ));
+            }
+        }
+        let mut seen = HashSet::new();
+        for (index, operation) in list("security.allowed_operations").into_iter().enumerate() {
+            if !OPERATIONS.contains(&operation) {
+                self.item_problem("security.allowed_operations", index, format!(
+                    "unknown operation {:?}, expected one of {}", operation, OPERATIONS.join(", ")
+                ));
+            } else if !seen.insert(operation) {
+                self.warnings.push(format!("security.allowed_operations[{}]: {} is listed more than once", index, operation));
+            }
+        }
+        
+        if let Some(listen_addr) = text("interface.listen_addr") {
+            let port = listen_addr.rsplit_once(':').map(|(host, port)| (host, port.parse::<u16>()));
+            if !matches!(port, Some((host, Ok(_))) if !host.is_empty()) {
+                self.setting_problem("interface.listen_addr", format!(
+                    "invalid address {:?}, expected host:port", listen_addr
+                ));
+            }
+        }
+    }
+    
+    /// Record a problem with a whole setting
+    fn setting_problem(&mut self, setting: &str, message: String) {
+        let path: Vec<Segment> = setting.split('.').map(|key| Segment::Key(key.to_string())).collect();
+        self.problem(&path, message);
+    }
+    
+    /// Record a problem with one item of a list setting
+    fn item_problem(&mut self, setting: &str, index: usize, message: String) {
+        let mut path: Vec<Segment> = setting.split('.').map(|key| Segment::Key(key.to_string())).collect();
+        path.push(Segment::Index(index));
+        self.problem(&path, message);
+    }
+    
+    /// Record a problem, locating the value in the file it was read from
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the value
+    /// * `message` - What is wrong with the value
+    fn problem(&mut self, path: &[Segment], message: String) {
+        // Sources are tracked per setting; list items share the source of their list
+        let mut setting = String::new();
+        let mut source = ConfigSource::Default;
+        for segment in path {
+            match segment {
+                Segment::Key(key) if setting.is_empty() => setting.push_str(key),
+                Segment::Key(key) => {
+                    setting.push('.');
+                    setting.push_str(key);
+                },
+                Segment::Index(index) => setting.push_str(&format!("[{}]", index)),
+            }
+            if let Some(found) = self.sources.get(&setting) {
+                source = found.clone();
+            }
+        }
+        
+        let location = match &source {
+            ConfigSource::File(file) => locate(file, path),
+            _ => None,
+        };
+        
+        self.problems.push(ConfigProblem { setting, message, source, location });
+    }
+}
+
+/// Get the section of a dotted setting
+fn section(setting: &str) -> &str {
+    setting.split_once('.').map_or(setting, |(section, _)| section)
+}
+
+/// Get the field of a dotted setting
+fn field(setting: &str) -> &str {
+    setting.split_once('.').map_or(setting, |(_, field)| field)
+}
+
+/// Describe the kind of a YAML value for error messages
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "nothing",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Sequence(_) => "a list",
+        Value::Mapping(_) => "a section",
+        Value::Tagged(_) => "a tagged value",
+    }
+}
+
+/// Check that the data directory exists and is writable, or can be created
+fn check_data_dir(data_dir: &str) -> Result<(), String> {
+    if data_dir.is_empty() {
+        return Err("must not be empty".to_string());
+    }
+    
+    // A missing directory is created on first use inside its nearest existing ancestor
+    let dir = Path::new(data_dir);
+    let existing = dir
+        .ancestors()
+        .map(|ancestor| if ancestor.as_os_str().is_empty() { Path::new(".") } else { ancestor })
+        .find(|ancestor| ancestor.exists())
+        .unwrap_or(Path::new("."));
+    
+    let metadata = fs::metadata(existing).map_err(|e| format!("cannot access {}: {}", existing.display(), e))?;
+    if !metadata.is_dir() {
+        return Err(format!("{} is not a directory", existing.display()));
+    }
+    if metadata.permissions().readonly() {
+        return Err(if existing == dir {
+            format!("{} is not writable", existing.display())
+        } else {
+            format!("{} cannot be created because {} is not writable", dir.display(), existing.display())
+        });
+    }
+    
+    Ok(())
+}
+
+/// Check that a tool directory can be scanned
+///
+/// # Returns
+///
+/// A warning if the directory does not exist, or an error if it cannot be scanned
+fn check_tool_dir(dir: &str) -> Result<Option<String>, String> {
+    if dir.is_empty() {
+        return Err("must not be empty".to_string());
+    }
+    
+    let path = Path::new(dir);
+    if !path.exists() {
+        return Ok(Some(format!("tool directory {} does not exist", dir)));
+    }
+    if !path.is_dir() {
+        return Err(format!("{} is not a directory", dir));
+    }
+    fs::read_dir(path).map_err(|e| format!("cannot read {}: {}", dir, e))?;
+    
+    Ok(None)
+}
+
+/// Find the position of a value in a configuration file
+///
+/// The file is parsed again and the value at `path` is rejected on purpose, so the
+/// parser reports where it found it.
+///
+/// # Arguments
+///
+/// * `file` - The configuration file
+/// * `path` - Path of the value
+///
+/// # Returns
+///
+/// The position of the value, or `None` if the file no longer contains it
+fn locate(file: &Path, path: &[Segment]) -> Option<Location> {
+    let contents = fs::read_to_string(file).ok()?;
+    let deserializer = serde_yaml::Deserializer::from_str(&contents);
+    
+    match Locator(path).deserialize(deserializer) {
+        Ok(()) => None,
+        Err(e) => e.location().map(|location| Location {
+            line: location.line(),
+            column: location.column(),
+        }),
+    }
+}
+
+/// Walks a YAML document to the value at a path and fails there
+struct Locator<'a>(&'a [Segment]);
+
+impl<'de> DeserializeSeed<'de> for Locator<'_> {
+    type Value = ();
+    
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Locator<'_> {
+    type Value = ();
+    
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a configuration value")
+    }
+    
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let Some((Segment::Key(name), rest)) = self.0.split_first() else {
+            return Err(de::Error::custom("found"));
+        };
+        
+        while let Some(key) = map.next_key::<Value>()? {
+            if key.as_str() == Some(name.as_str()) {
+                map.next_value_seed(Locator(rest))?;
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(())
+    }
+    
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let Some((Segment::Index(target), rest)) = self.0.split_first() else {
+            return Err(de::Error::custom("found"));
+        };
+        
+        let mut index = 0;
+        loop {
+            let found = if index == *target {
+                seq.next_element_seed(Locator(rest))?.is_some()
+            } else {
+                seq.next_element::<IgnoredAny>()?.is_some()
+            };
+            if !found {
+                return Ok(());
+            }
+            index += 1;
+        }
+    }
+    
+    // Any other value is either the target or a dead end
+    fn visit_bool<E: de::Error>(self, _: bool) -> Result<(), E> {
+        self.scalar()
+    }
+    
+    fn visit_i64<E: de::Error>(self, _: i64) -> Result<(), E> {
+        self.scalar()
+    }
+    
+    fn visit_u64<E: de::Error>(self, _: u64) -> Result<(), E> {
+        self.scalar()
+    }
+    
+    fn visit_f64<E: de::Error>(self, _: f64) -> Result<(), E> {
+        self.scalar()
+    }
+    
+    fn visit_str<E: de::Error>(self, _: &str) -> Result<(), E> {
+        self.scalar()
+    }
+    
+    fn visit_unit<E: de::Error>(self) -> Result<(), E> {
+        self.scalar()
+    }
+}
+
+impl Locator<'_> {
+    /// Fail at a scalar if it is the target value
+    fn scalar<E: de::Error>(&self) -> Result<(), E> {
+        if self.0.is_empty() {
+            Err(E::custom("found"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ConfigLoader;
+    use crate::error::RoyaOsError;
+    use std::path::PathBuf;
+    use uuid::Uuid;
+    
+    /// Write a configuration file whose tool directory exists, so it loads without warnings
+    fn write_config(contents: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("royaos-validate-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.yaml");
+        fs::write(&path, format!("{}tools:\n  tool_dirs: [{:?}]\n", contents, dir)).unwrap();
+        path
+    }
+    
+    /// Load a configuration file that is expected to fail validation
+    fn problems(path: &Path) -> Vec<ConfigProblem> {
+        match ConfigLoader::isolated(path).load_with_env([]) {
+            Err(RoyaOsError::InvalidConfig(problems)) => problems,
+            other => panic!("Expected validation problems, got {:?}", other.map(|loaded| loaded.config)),
+        }
+    }
+    
+    #[test]
+    fn test_every_problem_is_reported() {
+        let path = write_config(concat!(
+            "system:\n",
+            "  log_level: loud\n",
+            "  shutdown_timeout: 7200\n",
+            "memory:\n",
+            "  max_allocation: 0\n",
+            "  optimization_strategy: greedy\n",
+            "security:\n",
+            "  allowed_operations:\n",
+            "    - memory_access\n",
+            "    - time_travel\n",
+            "interface:\n",
+            "  listen_addr: nowhere\n",
+            "  idle_timout: 60\n",
+        ));
+        
+        let found: Vec<(String, Option<Location>)> = problems(&path).into_iter()
+            .map(|problem| {
+                assert_eq!(problem.source, ConfigSource::File(path.clone()));
+                (problem.setting, problem.location)
+            })
+            .collect();
+        let at = |line, column| Some(Location { line, column });
+        assert_eq!(found, vec![
+            ("interface.idle_timout".to_string(), at(13, 16)),
+            ("system.log_level".to_string(), at(2, 14)),
+            ("system.shutdown_timeout".to_string(), at(3, 21)),
+            ("memory.max_allocation".to_string(), at(5, 19)),
+            ("memory.optimization_strategy".to_string(), at(6, 26)),
+            ("security.allowed_operations[1]".to_string(), at(10, 7)),
+            ("interface.listen_addr".to_string(), at(12, 16)),
+        ]);
+        
+        // The report names the file and position of every problem
+        let report = report(&problems(&path));
+        assert_eq!(report.lines().count(), 8);
+        assert!(report.contains(&format!(
+            "{}:2:14: system.log_level: invalid log level \"loud\", expected one of off, error, warn, info, debug, trace",
+            path.display(),
+        )));
+        
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+    
+    #[test]
+    fn test_type_problems_are_located() {
+        let path = write_config(concat!(
+            "system:\n",
+            "  shutdown_timeout: 1.5\n",
+            "memory:\n",
+            "  max_allocation: 0\n",
+            "security:\n",
+            "  allowed_operations: memory_access\n",
+            "quotas:\n",
+            "  require_agent: sometimes\n",
+        ));
+        
+        let found: Vec<(String, String, Option<Location>)> = problems(&path).into_iter()
+            .map(|problem| (problem.setting, problem.message, problem.location))
+            .collect();
+        assert_eq!(found, vec![
+            (
+                "system.shutdown_timeout".to_string(),
+                "expected a whole number of at least 0, found 1.5".to_string(),
+                Some(Location { line: 2, column: 21 }),
+            ),
+            (
+                "security.allowed_operations".to_string(),
+                "expected a list, found a string".to_string(),
+                Some(Location { line: 6, column: 23 }),
+            ),
+            (
+                "quotas.require_agent".to_string(),
+                "expected true or false, found a string".to_string(),
+                Some(Location { line: 8, column: 18 }),
+            ),
+            (
+                "memory.max_allocation".to_string(),
+                "0 MB is out of range, expected 1 to 1048576".to_string(),
+                Some(Location { line: 4, column: 19 }),
+            ),
+        ]);
+        
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+    
+    #[test]
+    fn test_decay_problems_are_located() {
+        let path = write_config(concat!(
+            "memory:\n",
+            "  decay:\n",
+            "    working:\n",
+            "      half_life: 1.5\n",
+            "      forget_below: 120\n",
+        ));
+        
+        let found: Vec<(String, String, Option<Location>)> = problems(&path).into_iter()
+            .map(|problem| (problem.setting, problem.message, problem.location))
+            .collect();
+        assert_eq!(found, vec![
+            (
+                "memory.decay.working.half_life".to_string(),
+                "expected a whole number of at least 0, found 1.5".to_string(),
+                Some(Location { line: 4, column: 18 }),
+            ),
+            (
+                "memory.decay.working.forget_below".to_string(),
+                "120% is out of range, expected 0 to 99".to_string(),
+                Some(Location { line: 5, column: 21 }),
+            ),
+        ]);
+        
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+    
+    #[test]
+    fn test_valid_configuration() {
+        let path = write_config(concat!(
+            "system:\n",
+            "  log_level: debug\n",
+            "  shutdown_timeout: 30\n",
+            "memory:\n",
+            "  max_allocation: 2048\n",
+            "  optimization_strategy: conservative\n",
+            "security:\n",
+            "  security_level: high\n",
+            "  allowed_operations: [memory_access, security_query]\n",
+            "interface:\n",
+            "  listen_addr: 0.0.0.0:8100\n",
+        ));
+        
+        let loaded = ConfigLoader::isolated(&path).load_with_env([]).unwrap();
+        assert_eq!(validate(&loaded.values, &loaded.sources).unwrap(), Vec::<String>::new());
+        assert!(loaded.warnings.is_empty(), "{:?}", loaded.warnings);
+        
+        // The built-in defaults are valid too, apart from the tool directory that may be missing
+        let defaults = serde_yaml::to_value(Config::default()).unwrap();
+        let warnings = validate(&defaults, &HashMap::new()).unwrap();
+        assert!(warnings.iter().all(|warning| warning.starts_with("tools.tool_dirs[0]")), "{:?}", warnings);
+        
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}